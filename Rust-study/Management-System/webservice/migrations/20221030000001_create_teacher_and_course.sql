@@ -0,0 +1,24 @@
+-- 老师和课程
+CREATE TABLE teacher (
+    id          SERIAL PRIMARY KEY,
+    name        VARCHAR(100) NOT NULL,
+    picture_url VARCHAR(200) NOT NULL,
+    profile     VARCHAR(2000) NOT NULL
+);
+
+-- 删除老师时一并删除他的课程
+CREATE TABLE course (
+    id          SERIAL PRIMARY KEY,
+    teacher_id  INT NOT NULL REFERENCES teacher (id) ON DELETE CASCADE,
+    name        VARCHAR(140) NOT NULL,
+    time        TIMESTAMP DEFAULT now(),
+    description VARCHAR(2000),
+    format      VARCHAR(30),
+    structure   VARCHAR(200),
+    duration    VARCHAR(30),
+    price       INT,
+    language    VARCHAR(30),
+    level       VARCHAR(30)
+);
+
+CREATE INDEX course_teacher_id_idx ON course (teacher_id);
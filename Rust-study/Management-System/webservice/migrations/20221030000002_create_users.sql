@@ -0,0 +1,9 @@
+-- 登录账号 teacher角色的账号关联一个老师 删除老师时账号一起删除
+CREATE TABLE users (
+    id            SERIAL PRIMARY KEY,
+    username      VARCHAR(50) NOT NULL UNIQUE,
+    password_hash VARCHAR(200) NOT NULL,
+    role          VARCHAR(10) NOT NULL CHECK (role IN ('teacher', 'admin')),
+    teacher_id    INT UNIQUE REFERENCES teacher (id) ON DELETE CASCADE,
+    CHECK (role = 'admin' OR teacher_id IS NOT NULL)
+);
@@ -0,0 +1,27 @@
+-- 学生和选课
+CREATE TABLE student (
+    id    SERIAL PRIMARY KEY,
+    name  VARCHAR(100) NOT NULL,
+    email VARCHAR(200) NOT NULL UNIQUE
+);
+
+-- 课程容量 为空表示不限人数
+ALTER TABLE course ADD COLUMN capacity INT CHECK (capacity >= 0);
+
+-- 删除课程或学生时一并删除选课记录
+CREATE TABLE enrollment (
+    course_id   INT NOT NULL REFERENCES course (id) ON DELETE CASCADE,
+    student_id  INT NOT NULL REFERENCES student (id) ON DELETE CASCADE,
+    enrolled_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (course_id, student_id)
+);
+
+CREATE INDEX enrollment_student_id_idx ON enrollment (student_id);
+
+-- student角色的账号关联一个学生
+ALTER TABLE users ADD COLUMN student_id INT UNIQUE REFERENCES student (id) ON DELETE CASCADE;
+ALTER TABLE users DROP CONSTRAINT users_role_check;
+ALTER TABLE users DROP CONSTRAINT users_check;
+ALTER TABLE users ADD CONSTRAINT users_role_check CHECK (role IN ('teacher', 'admin', 'student'));
+ALTER TABLE users ADD CONSTRAINT users_teacher_check CHECK (role <> 'teacher' OR teacher_id IS NOT NULL);
+ALTER TABLE users ADD CONSTRAINT users_student_check CHECK (role <> 'student' OR student_id IS NOT NULL);
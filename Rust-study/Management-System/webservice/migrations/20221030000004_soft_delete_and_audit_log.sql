@@ -0,0 +1,20 @@
+-- 软删除 deleted_at不为空表示已删除 查询时过滤掉 可以恢复
+-- 删除老师时他的课程使用同一个deleted_at 恢复老师时一起恢复
+ALTER TABLE teacher ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE course ADD COLUMN deleted_at TIMESTAMP;
+
+-- 老师和课程的修改记录 old_value和new_value是修改前后的数据
+-- 创建和恢复时old_value为空 删除时new_value为空
+-- actor_id是操作者的账号id 不加外键 账号删除后记录仍然保留
+CREATE TABLE audit_log (
+    id          SERIAL PRIMARY KEY,
+    entity_type VARCHAR(20) NOT NULL CHECK (entity_type IN ('teacher', 'course')),
+    entity_id   INT NOT NULL,
+    action      VARCHAR(20) NOT NULL CHECK (action IN ('create', 'update', 'delete', 'restore')),
+    actor_id    INT,
+    old_value   JSONB,
+    new_value   JSONB,
+    created_at  TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_log_entity_idx ON audit_log (entity_type, entity_id);
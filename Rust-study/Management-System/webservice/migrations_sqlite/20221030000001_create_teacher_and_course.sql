@@ -0,0 +1,23 @@
+-- 和migrations目录下Postgres的表结构相同
+CREATE TABLE teacher (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    name        TEXT NOT NULL,
+    picture_url TEXT NOT NULL,
+    profile     TEXT NOT NULL
+);
+
+CREATE TABLE course (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    teacher_id  INTEGER NOT NULL REFERENCES teacher (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    time        TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    description TEXT,
+    format      TEXT,
+    structure   TEXT,
+    duration    TEXT,
+    price       INTEGER,
+    language    TEXT,
+    level       TEXT
+);
+
+CREATE INDEX course_teacher_id_idx ON course (teacher_id);
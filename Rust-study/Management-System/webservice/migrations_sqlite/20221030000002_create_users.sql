@@ -0,0 +1,8 @@
+CREATE TABLE users (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    username      TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    role          TEXT NOT NULL CHECK (role IN ('teacher', 'admin')),
+    teacher_id    INTEGER UNIQUE REFERENCES teacher (id) ON DELETE CASCADE,
+    CHECK (role = 'admin' OR teacher_id IS NOT NULL)
+);
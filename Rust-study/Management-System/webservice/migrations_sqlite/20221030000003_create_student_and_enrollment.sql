@@ -0,0 +1,34 @@
+-- 和migrations目录下Postgres的表结构相同
+CREATE TABLE student (
+    id    INTEGER PRIMARY KEY AUTOINCREMENT,
+    name  TEXT NOT NULL,
+    email TEXT NOT NULL UNIQUE
+);
+
+ALTER TABLE course ADD COLUMN capacity INTEGER CHECK (capacity >= 0);
+
+CREATE TABLE enrollment (
+    course_id   INTEGER NOT NULL REFERENCES course (id) ON DELETE CASCADE,
+    student_id  INTEGER NOT NULL REFERENCES student (id) ON DELETE CASCADE,
+    enrolled_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (course_id, student_id)
+);
+
+CREATE INDEX enrollment_student_id_idx ON enrollment (student_id);
+
+-- SQLite不能修改已有的约束 重建users表
+CREATE TABLE users_new (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    username      TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    role          TEXT NOT NULL CHECK (role IN ('teacher', 'admin', 'student')),
+    teacher_id    INTEGER UNIQUE REFERENCES teacher (id) ON DELETE CASCADE,
+    student_id    INTEGER UNIQUE REFERENCES student (id) ON DELETE CASCADE,
+    CHECK (role <> 'teacher' OR teacher_id IS NOT NULL),
+    CHECK (role <> 'student' OR student_id IS NOT NULL)
+);
+
+INSERT INTO users_new (id, username, password_hash, role, teacher_id)
+    SELECT id, username, password_hash, role, teacher_id FROM users;
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
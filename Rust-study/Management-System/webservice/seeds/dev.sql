@@ -0,0 +1,33 @@
+-- 开发环境数据 可以重复执行 已存在的记录不会被覆盖
+-- 账号: admin/admin-password alice/teacher-password bob/teacher-password dave/teacher-password(学生)
+INSERT INTO teacher (id, name, picture_url, profile) VALUES
+    (1, 'Alice Chen', 'https://example.com/alice.png', 'Rust and systems programming'),
+    (2, 'Bob Li', 'https://example.com/bob.png', 'Web development with actix')
+ON CONFLICT (id) DO NOTHING;
+
+INSERT INTO course (id, teacher_id, name, description, format, structure, duration, price, language, level, capacity) VALUES
+    (1, 1, 'Rust basics', 'Ownership, borrowing and lifetimes', 'video', '10 chapters', '10 hours', 100, 'English', 'beginner', NULL),
+    (2, 1, 'Async Rust', 'Futures, tokio and async IO', 'video', '8 chapters', '12 hours', 200, 'English', 'advanced', NULL),
+    (3, 2, 'Actix web services', 'Building a REST API with actix-web and sqlx', 'live', '6 sessions', '9 hours', 150, 'Chinese', 'intermediate', 20)
+ON CONFLICT (id) DO NOTHING;
+
+INSERT INTO student (id, name, email) VALUES
+    (1, 'Dave Zhang', 'dave@example.com')
+ON CONFLICT (id) DO NOTHING;
+
+INSERT INTO users (id, username, password_hash, role, teacher_id, student_id) VALUES
+    (1, 'admin', '$argon2id$v=19$m=4096,t=3,p=1$SUKeq0v4HIScK6GhhCRMIA$7S+b5DcbSiLHDG0ujUOPJI2PXf5OEWk4t4hOyNWDRB8', 'admin', NULL, NULL),
+    (2, 'alice', '$argon2id$v=19$m=4096,t=3,p=1$xX2B5zn3ChZ7bGN5EaO2dg$fWJ4kVEHexS8xQ9hzjy80FGmRxZUpqVCWH2RcaHnkRI', 'teacher', 1, NULL),
+    (3, 'bob', '$argon2id$v=19$m=4096,t=3,p=1$xX2B5zn3ChZ7bGN5EaO2dg$fWJ4kVEHexS8xQ9hzjy80FGmRxZUpqVCWH2RcaHnkRI', 'teacher', 2, NULL),
+    (4, 'dave', '$argon2id$v=19$m=4096,t=3,p=1$xX2B5zn3ChZ7bGN5EaO2dg$fWJ4kVEHexS8xQ9hzjy80FGmRxZUpqVCWH2RcaHnkRI', 'student', NULL, 1)
+ON CONFLICT (id) DO NOTHING;
+
+INSERT INTO enrollment (course_id, student_id) VALUES
+    (1, 1)
+ON CONFLICT DO NOTHING;
+
+-- 手动指定了id 需要把序列移到最大id之后
+SELECT setval('teacher_id_seq', (SELECT MAX(id) FROM teacher));
+SELECT setval('course_id_seq', (SELECT MAX(id) FROM course));
+SELECT setval('users_id_seq', (SELECT MAX(id) FROM users));
+SELECT setval('student_id_seq', (SELECT MAX(id) FROM student));
@@ -0,0 +1,319 @@
+use crate::error::MyError;
+use crate::models::user::{Role, TokenResponse, User};
+use crate::state::AppState;
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::future::{ready, Ready};
+
+//access token有效期15分钟 refresh token有效期7天 可以用环境变量覆盖(单位秒)
+const DEFAULT_ACCESS_TTL: i64 = 15 * 60;
+const DEFAULT_REFRESH_TTL: i64 = 7 * 24 * 60 * 60;
+//verify_dummy_password使用的哈希 参数要和hash_password生成的一样 耗时才相同
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=4096,t=3,p=1$H7NKyDA77laGcT6dwTZBjA$1RlusOfLRnoXccSyBOriClXEkW7pEyh93De/G7XRL6c";
+//HS256密钥至少32字节 .env.example里的示例值是公开的 不能用来签发token
+const MIN_SECRET_LEN: usize = 32;
+const EXAMPLE_SECRET: &str = "change-me-in-production";
+
+//两种token用kind区分 refresh token不能用来访问接口 access token也不能用来刷新
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
+
+//JWT里保存的内容 sub是用户id
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Claims {
+    pub sub: String,
+    pub role: Role,
+    pub teacher_id: Option<i32>,
+    //增加学生之前签发的token没有这个字段
+    #[serde(default)]
+    pub student_id: Option<i32>,
+    pub kind: TokenKind,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+//签发和校验JWT 使用HS256
+pub struct AuthConfig {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    access_ttl: i64,
+    refresh_ttl: i64,
+}
+
+impl AuthConfig {
+    pub fn new(secret: &[u8]) -> Self {
+        AuthConfig {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            access_ttl: DEFAULT_ACCESS_TTL,
+            refresh_ttl: DEFAULT_REFRESH_TTL,
+        }
+    }
+
+    //读取JWT_SECRET JWT_ACCESS_TTL JWT_REFRESH_TTL 密钥不合格或者有效期不是数字时返回错误
+    pub fn from_env() -> Result<Self, String> {
+        let secret = env::var("JWT_SECRET").map_err(|_| "JWT_SECRET is not set".to_string())?;
+        check_secret(&secret)?;
+        let ttl = |name: &str, default: i64| match env::var(name) {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| format!("{} must be a number of seconds", name)),
+            Err(_) => Ok(default),
+        };
+        Ok(AuthConfig {
+            access_ttl: ttl("JWT_ACCESS_TTL", DEFAULT_ACCESS_TTL)?,
+            refresh_ttl: ttl("JWT_REFRESH_TTL", DEFAULT_REFRESH_TTL)?,
+            ..AuthConfig::new(secret.as_bytes())
+        })
+    }
+
+    //给用户签发一对新的token
+    pub fn issue(&self, user: &User) -> Result<TokenResponse, MyError> {
+        let role = Role::parse(&user.role)
+            .ok_or_else(|| MyError::DBError(format!("Unknown role {} for user {}", user.role, user.id)))?;
+        let now = Utc::now().timestamp();
+        let token = |kind: TokenKind, ttl: i64| {
+            let claims = Claims {
+                sub: user.id.to_string(),
+                role,
+                teacher_id: user.teacher_id,
+                student_id: user.student_id,
+                kind,
+                iat: now,
+                exp: now + ttl,
+            };
+            encode(&Header::default(), &claims, &self.encoding).map_err(|e| MyError::ActixError(e.to_string()))
+        };
+        Ok(TokenResponse {
+            access_token: token(TokenKind::Access, self.access_ttl)?,
+            refresh_token: token(TokenKind::Refresh, self.refresh_ttl)?,
+            token_type: "Bearer".into(),
+            expires_in: self.access_ttl,
+        })
+    }
+
+    //校验签名、过期时间和token种类
+    pub fn verify(&self, token: &str, kind: TokenKind) -> Result<Claims, MyError> {
+        let claims = decode::<Claims>(token, &self.decoding, &Validation::default())
+            .map_err(|_err| MyError::Unauthorized("Invalid or expired token".into()))?
+            .claims;
+        if claims.kind != kind {
+            return Err(MyError::Unauthorized("Wrong token type".into()));
+        }
+        Ok(claims)
+    }
+}
+
+impl Claims {
+    pub fn user_id(&self) -> Result<i32, MyError> {
+        self.sub
+            .parse()
+            .map_err(|_err| MyError::Unauthorized("Invalid or expired token".into()))
+    }
+}
+
+//密码加盐后用argon2计算哈希 结果是包含参数和盐的PHC字符串
+pub fn hash_password(password: &str) -> Result<String, MyError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| MyError::ActixError(e.to_string()))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
+//用户名不存在时也校验一次密码 两种情况耗时相同 不能通过响应时间判断用户名是否存在
+pub fn verify_dummy_password(password: &str) {
+    verify_password(password, DUMMY_PASSWORD_HASH);
+}
+
+//已登录的调用者 处理函数的参数里加上AuthUser就要求请求带有效的access token
+//Authorization: Bearer <access_token>
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: i32,
+    pub role: Role,
+    pub teacher_id: Option<i32>,
+    pub student_id: Option<i32>,
+}
+
+impl AuthUser {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    //只有老师本人或管理员可以修改老师的资料和课程
+    pub fn require_teacher(&self, teacher_id: i32) -> Result<(), MyError> {
+        if self.is_admin() || self.teacher_id == Some(teacher_id) {
+            Ok(())
+        } else {
+            Err(MyError::Forbidden("Not allowed to modify another teacher's data".into()))
+        }
+    }
+
+    //学生本人或管理员可以查看学生的选课
+    pub fn require_student(&self, student_id: i32) -> Result<(), MyError> {
+        if self.is_admin() || self.student_id == Some(student_id) {
+            Ok(())
+        } else {
+            Err(MyError::Forbidden("Not allowed to access another student's data".into()))
+        }
+    }
+
+    //选课和退课只能由学生自己操作 返回调用者的学生id
+    pub fn own_student_id(&self) -> Result<i32, MyError> {
+        self.student_id
+            .ok_or_else(|| MyError::Forbidden("Student role required".into()))
+    }
+
+    pub fn require_admin(&self) -> Result<(), MyError> {
+        if self.is_admin() {
+            Ok(())
+        } else {
+            Err(MyError::Forbidden("Admin role required".into()))
+        }
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = MyError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authenticate(req))
+    }
+}
+
+fn authenticate(req: &HttpRequest) -> Result<AuthUser, MyError> {
+    let app_state = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| MyError::ActixError("AppState is not configured".into()))?;
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| MyError::Unauthorized("Missing bearer token".into()))?;
+    let claims = app_state.auth.verify(token.trim(), TokenKind::Access)?;
+    Ok(AuthUser {
+        user_id: claims.user_id()?,
+        role: claims.role,
+        teacher_id: claims.teacher_id,
+        student_id: claims.student_id,
+    })
+}
+
+//拒绝示例密钥和太短的密钥 否则任何人都可以伪造token
+fn check_secret(secret: &str) -> Result<(), String> {
+    if secret == EXAMPLE_SECRET {
+        return Err("JWT_SECRET is still the example value, set a random secret".into());
+    }
+    if secret.len() < MIN_SECRET_LEN {
+        return Err(format!("JWT_SECRET must be at least {} bytes", MIN_SECRET_LEN));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn teacher_user() -> User {
+        User {
+            id: 7,
+            username: "alice".into(),
+            password_hash: String::new(),
+            role: "teacher".into(),
+            teacher_id: Some(3),
+            student_id: None,
+        }
+    }
+
+    #[test]
+    fn password_hash_test() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+
+        //假哈希的算法和参数与新生成的哈希相同
+        let params = |hash: &str| hash.rsplitn(3, '$').nth(2).unwrap().to_string();
+        assert_eq!(params(DUMMY_PASSWORD_HASH), params(&hash));
+    }
+
+    #[test]
+    fn token_round_trip_test() {
+        let auth = AuthConfig::new(b"test-secret");
+        let tokens = auth.issue(&teacher_user()).unwrap();
+
+        let claims = auth.verify(&tokens.access_token, TokenKind::Access).unwrap();
+        assert_eq!(claims.user_id().unwrap(), 7);
+        assert_eq!(claims.role, Role::Teacher);
+        assert_eq!(claims.teacher_id, Some(3));
+        assert!(auth.verify(&tokens.refresh_token, TokenKind::Refresh).is_ok());
+
+        //两种token不能混用 其他密钥签发的token无效
+        assert!(auth.verify(&tokens.refresh_token, TokenKind::Access).is_err());
+        assert!(auth.verify(&tokens.access_token, TokenKind::Refresh).is_err());
+        assert!(AuthConfig::new(b"other").verify(&tokens.access_token, TokenKind::Access).is_err());
+    }
+
+    #[test]
+    fn secret_check_test() {
+        assert!(check_secret(EXAMPLE_SECRET).is_err());
+        assert!(check_secret("short").is_err());
+        assert!(check_secret(&"x".repeat(MIN_SECRET_LEN - 1)).is_err());
+        assert!(check_secret(&"x".repeat(MIN_SECRET_LEN)).is_ok());
+    }
+
+    #[test]
+    fn owner_or_admin_test() {
+        let teacher = AuthUser {
+            user_id: 7,
+            role: Role::Teacher,
+            teacher_id: Some(3),
+            student_id: None,
+        };
+        assert!(teacher.require_teacher(3).is_ok());
+        assert!(matches!(teacher.require_teacher(4), Err(MyError::Forbidden(_))));
+        assert!(teacher.require_admin().is_err());
+
+        let admin = AuthUser {
+            user_id: 1,
+            role: Role::Admin,
+            teacher_id: None,
+            student_id: None,
+        };
+        assert!(admin.require_teacher(4).is_ok());
+        assert!(admin.require_admin().is_ok());
+        assert!(admin.require_student(5).is_ok());
+        assert!(admin.own_student_id().is_err());
+
+        let student = AuthUser {
+            user_id: 9,
+            role: Role::Student,
+            teacher_id: None,
+            student_id: Some(5),
+        };
+        assert_eq!(student.own_student_id().unwrap(), 5);
+        assert!(student.require_student(5).is_ok());
+        assert!(matches!(student.require_student(6), Err(MyError::Forbidden(_))));
+        assert!(student.require_teacher(3).is_err());
+    }
+}
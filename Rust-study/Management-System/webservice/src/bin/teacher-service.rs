@@ -1,45 +1,128 @@
 use actix_cors::Cors;
 use actix_web::{web, http, App, HttpServer};
 use sqlx::postgres::PgPoolOptions;
-use std::sync::Mutex;
-use std::{io, env};
+use sqlx::sqlite::SqlitePoolOptions;
+use std::{io, env, process};
 use dotenv::dotenv;
+use tracing_subscriber::EnvFilter;
 
+#[path = "../auth.rs"]
+mod auth;
+#[path = "../db.rs"]
+mod db;
 #[path = "../dbaccess/mod.rs"]
 mod dbaccess;
 #[path = "../error.rs"]
 mod error;
 #[path = "../handlers/mod.rs"]
 mod handlers;
+#[path = "../metrics.rs"]
+mod metrics;
+#[path = "../middleware.rs"]
+mod middleware;
 #[path = "../models/mod.rs"]
 mod models;
+#[path = "../openapi.rs"]
+mod openapi;
+#[path = "../repository/mod.rs"]
+mod repository;
 #[path = "../routers.rs"]
 mod routers;
 #[path = "../state.rs"]
 mod state;
 
+use auth::AuthConfig;
+use middleware::{RequestMetrics, RequestTracing};
+use repository::{MemoryStore, PgStore, SqliteStore};
 use routers::*;
 use state::AppState;
 
+const USAGE: &str = "Usage: teacher-service [serve|migrate|seed]
+  serve    run pending migrations, then start the service (default)
+  migrate  run pending migrations and exit
+  seed     run pending migrations and load development data";
+
+//JWT配置不合格时不启动服务
+fn auth_config() -> AuthConfig {
+    AuthConfig::from_env().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    })
+}
+
 #[actix_rt::main]
 async fn main() -> io::Result<()> {
     //添加读取环境变量相关代码
     dotenv().ok();
+    //日志级别用RUST_LOG设置 例如RUST_LOG=debug 默认info
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
 
-    //创建数据库连接池
+    let command = env::args().nth(1).unwrap_or_else(|| "serve".to_string());
+    if !matches!(command.as_str(), "serve" | "migrate" | "seed") {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    //创建数据库连接池 DATABASE_URL以sqlite:开头时使用SQLite 为memory:时使用内存存储 否则使用Postgres
+    //每次启动都先执行迁移 已经执行过的版本会跳过
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
 
-    let db_pool = PgPoolOptions::new()
-        .connect(&database_url)
-        .await
-        .unwrap();
+    let app_state = if database_url == "memory:" {
+        if command != "serve" {
+            eprintln!("The in-memory store has no migrations or development data");
+            process::exit(2);
+        }
+        AppState::new(MemoryStore::default(), auth_config())
+    } else if database_url.starts_with("sqlite:") {
+        let db_pool = SqlitePoolOptions::new()
+            .connect(&database_url)
+            .await
+            .unwrap();
+        if let Err(e) = db::run_sqlite_migrations(&db_pool).await {
+            eprintln!("Migration failed: {}", e);
+            process::exit(1);
+        }
+        match command.as_str() {
+            "migrate" => {
+                println!("Migrations are up to date");
+                return Ok(());
+            }
+            "seed" => {
+                eprintln!("Development data is only available for Postgres");
+                process::exit(2);
+            }
+            _ => {}
+        }
+        AppState::new(SqliteStore::new(db_pool), auth_config())
+    } else {
+        let db_pool = PgPoolOptions::new()
+            .connect(&database_url)
+            .await
+            .unwrap();
+        if let Err(e) = db::run_migrations(&db_pool).await {
+            eprintln!("Migration failed: {}", e);
+            process::exit(1);
+        }
+        match command.as_str() {
+            "migrate" => {
+                println!("Migrations are up to date");
+                return Ok(());
+            }
+            "seed" => {
+                db::seed_dev_data(&db_pool)
+                    .await
+                    .map_err(io::Error::other)?;
+                println!("Development data loaded");
+                return Ok(());
+            }
+            _ => {}
+        }
+        AppState::new(PgStore::new(db_pool), auth_config())
+    };
 
-    let shared_data = web::Data::new(AppState{
-        health_check_response:"I'm ok".to_string(),
-        visit_count:Mutex::new(0),
-        // courses:Mutex::new(vec![]),
-        db: db_pool,
-    });
+    let shared_data = web::Data::new(app_state);
     //添加路由注册
     let app = move || {
         let cors = Cors::default()
@@ -54,16 +137,13 @@ async fn main() -> io::Result<()> {
 
         App::new()
             .app_data(shared_data.clone())
-            .app_data(web::JsonConfig::default().error_handler(|_err, _req| {
-                MyError::InvalidInput("Player provide valid Json input".to_string()).into()
-            }))
             .wrap(cors)
-            .configure(general_routes)
-            .configure(course_routes)
-            .configure(teacher_routes)
+            .wrap(RequestMetrics)
+            .wrap(RequestTracing)
+            .configure(app_config)
     };
     
-    println!("Service is running");
+    tracing::info!("Service is running on 127.0.0.1:3001");
     HttpServer::new(app).bind("127.0.0.1:3001")?.run().await
 }
 
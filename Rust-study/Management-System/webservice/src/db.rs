@@ -0,0 +1,30 @@
+use sqlx::migrate::Migrator;
+use sqlx::postgres::PgPool;
+use sqlx::sqlite::SqlitePool;
+use sqlx::Executor;
+
+//migrations目录下的版本化迁移在编译时嵌入二进制文件 数据库里用_sqlx_migrations表记录已执行的版本
+//新增迁移: 在migrations目录下添加 <时间戳>_<描述>.sql 已发布的迁移文件不要再修改
+//只执行迁移不启动服务:
+//  cargo run -- migrate
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+//SQLite使用单独的迁移目录 表结构和Postgres保持一致
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations_sqlite");
+
+//开发环境数据 可以重复执行
+const DEV_SEED: &str = include_str!("../seeds/dev.sql");
+
+//执行还没有执行过的迁移
+pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+pub async fn run_sqlite_migrations(pool: &SqlitePool) -> Result<(), sqlx::migrate::MigrateError> {
+    SQLITE_MIGRATOR.run(pool).await
+}
+
+//seed文件包含多条语句 直接传字符串执行时不使用预处理语句 可以一次执行多条
+pub async fn seed_dev_data(pool: &PgPool) -> Result<(), sqlx::Error> {
+    pool.execute(DEV_SEED).await?;
+    Ok(())
+}
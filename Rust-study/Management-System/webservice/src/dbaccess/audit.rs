@@ -0,0 +1,39 @@
+use crate::error::MyError;
+use crate::models::audit::{AuditEntry, AuditRecord, EntityType};
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+//audit_log表的结构见migrations/20221030000004_soft_delete_and_audit_log.sql
+
+//写入修改记录 和修改放在同一个事务里 修改失败时不会留下记录
+pub async fn insert_audit_db(tx: &mut Transaction<'_, Postgres>, record: AuditRecord) -> Result<(), MyError> {
+    sqlx::query(
+        "INSERT INTO audit_log (entity_type, entity_id, action, actor_id, old_value, new_value)
+        VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(record.entity_type.as_str())
+    .bind(record.entity_id)
+    .bind(record.action.as_str())
+    .bind(record.actor_id)
+    .bind(record.old_value)
+    .bind(record.new_value)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+//某个老师或课程的修改记录 按时间顺序
+pub async fn get_audit_log_db(pool: &PgPool, entity_type: EntityType, entity_id: i32) -> Result<Vec<AuditEntry>, MyError> {
+    let rows = sqlx::query_as::<_, AuditEntry>(
+        "SELECT id, entity_type, entity_id, action, actor_id, old_value, new_value, created_at
+        FROM audit_log WHERE entity_type = $1 AND entity_id = $2
+        ORDER BY id",
+    )
+    .bind(entity_type.as_str())
+    .bind(entity_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
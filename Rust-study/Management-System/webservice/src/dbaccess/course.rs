@@ -1,41 +1,59 @@
+use crate::dbaccess::audit::insert_audit_db;
 use crate::error::MyError;
+use crate::models::audit::{AuditAction, AuditRecord};
 use crate::models::course::*;
+use crate::models::page::Page;
+use crate::repository::sql::{course_list_sql, Param, POSTGRES};
 use chrono::NaiveDateTime;
 use sqlx::postgres::PgPool;
 
+//获得所有老师的课程
+pub async fn get_courses_db(pool: &PgPool, query: &CourseQuery) -> Result<Page<Course>, MyError> {
+    list_courses_db(pool, None, query).await
+}
+
 //获得老师对应课程
-pub async fn get_course_for_teacher_db(pool: &PgPool, teacher_id: i32) -> Result<Vec<Course>, MyError> {
-    let rows:Vec<Course> = sqlx::query_as!(
-        Course,
-        r#"SELECT * FROM course WHERE teacher_id=$1"#,
-        teacher_id
-    ).fetch_all(pool).await?;
-
-    // let courses: Vec<Course> = rows.iter().map(|r| Course {
-    //     id: Some(r.id),
-    //     teacher_id: r.teacher_id,
-    //     name: r.name.clone(),
-    //     time: Some(NaiveDateTime::from(r.time.unwrap())),
-    // }).collect();
-
-    // match courses.len() {
-    //     0 => Err(MyError::NotFound("Course not found".into())),
-    //     _ => Ok(courses),
-    // }
-    Ok(rows)
+pub async fn get_course_for_teacher_db(pool: &PgPool, teacher_id: i32, query: &CourseQuery) -> Result<Page<Course>, MyError> {
+    list_courses_db(pool, Some(teacher_id), query).await
 }
 
-//获得课程明细
+//筛选条件是动态的 SQL由repository::sql拼接 这里绑定参数并执行
+async fn list_courses_db(pool: &PgPool, teacher_id: Option<i32>, query: &CourseQuery) -> Result<Page<Course>, MyError> {
+    let limit = query.validate()?;
+    let list = course_list_sql(&POSTGRES, teacher_id, query, limit);
+
+    let mut rows_query = sqlx::query_as::<_, Course>(&list.sql);
+    let mut count_query = sqlx::query_scalar::<_, i64>(&list.count_sql);
+    for (i, param) in list.params.iter().enumerate() {
+        rows_query = match param {
+            Param::Int(value) => rows_query.bind(*value),
+            Param::Text(value) => rows_query.bind(value.clone()),
+        };
+        if i < list.count_params {
+            count_query = match param {
+                Param::Int(value) => count_query.bind(*value),
+                Param::Text(value) => count_query.bind(value.clone()),
+            };
+        }
+    }
+
+    let rows = rows_query.fetch_all(pool).await?;
+    let total = count_query.fetch_one(pool).await?;
+    Ok(Page::new(rows, limit, total, |course| course.id))
+}
+
+//获得课程明细 已删除的课程查不到
+//course表多了deleted_at列 Course没有这个字段 这里列出具体的列
 pub async fn get_course_details_db(pool: &PgPool, teacher_id: i32, course_id:i32) -> Result<Course, MyError>{
-    let row = sqlx::query_as!(
-        Course,
-        r#"SELECT * FROM course WHERE teacher_id=$1 and id = $2"#,
-        teacher_id,
-        course_id
-    ).fetch_optional(pool).await?;
+    let row = sqlx::query_as::<_, Course>(
+        r#"SELECT teacher_id, id, name, time, description, format, structure, duration, price, language, level, capacity
+        FROM course WHERE teacher_id=$1 and id = $2 AND deleted_at IS NULL"#,
+    )
+    .bind(teacher_id)
+    .bind(course_id)
+    .fetch_optional(pool).await?;
 
     if let Some(course) = row {
-        Ok(
         //     Course{
         //         id: Some(row.id),
         //         teacher_id: row.teacher_id,
@@ -43,30 +61,40 @@ pub async fn get_course_details_db(pool: &PgPool, teacher_id: i32, course_id:i32
         //         time: Some(NaiveDateTime::from(row.time.unwrap())),
         // })
         Ok(course)
-        )
     } else {
         Err(MyError::NotFound("Course ID not found".into()))
     }
     
 }
 
-//新增课程
-pub async fn post_new_course_db(pool: &PgPool, new_course:Course) -> Result<Course, MyError>{
-    let row = sqlx::query_as!(
-        Course,
-        r#"INSERT INTO course (teacher_id, name, description, format, structure, duration, price, language, level)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-        RETURNING *"#,
-        new_course.teacher_id,
-        new_course.name,
-        new_course.description,
-        new_course.format,
-        new_course.structure,
-        new_course.duration,
-        new_course.price,
-        new_course.language,
-        new_course.level,
-    ).fetch_one(pool).await?;
+//新增课程 老师不存在或已删除时返回字段错误
+pub async fn post_new_course_db(pool: &PgPool, new_course:CreateCourse, actor_id: i32) -> Result<Course, MyError>{
+    let mut tx = pool.begin().await?;
+    let teacher = sqlx::query_scalar::<_, i32>(
+        "SELECT id FROM teacher WHERE id = $1 AND deleted_at IS NULL FOR SHARE",
+    )
+    .bind(new_course.teacher_id)
+    .fetch_optional(&mut tx).await?;
+    if teacher.is_none() {
+        return Err(MyError::invalid_field("teacher_id", "does not reference an existing record"));
+    }
+
+    let row = sqlx::query_as::<_, Course>(
+        r#"INSERT INTO course (teacher_id, name, description, format, structure, duration, price, language, level, capacity)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING teacher_id, id, name, time, description, format, structure, duration, price, language, level, capacity"#,
+    )
+    .bind(new_course.teacher_id)
+    .bind(new_course.name)
+    .bind(new_course.description)
+    .bind(new_course.format.map(|format| format.as_str()))
+    .bind(new_course.structure)
+    .bind(new_course.duration)
+    .bind(new_course.price)
+    .bind(new_course.language)
+    .bind(new_course.level.map(|level| level.as_str()))
+    .bind(new_course.capacity)
+    .fetch_one(&mut tx).await?;
 
     // Ok(Course{
     //         id: Some(row.id),
@@ -74,101 +102,108 @@ pub async fn post_new_course_db(pool: &PgPool, new_course:Course) -> Result<Cour
     //         name: row.name.clone(),
     //         time: Some(NaiveDateTime::from(row.time.unwrap())),
     // })
+    insert_audit_db(&mut tx, AuditRecord::course(AuditAction::Create, actor_id, None, Some(&row))).await?;
+    tx.commit().await?;
     Ok(row)
 }
 
-pub async fn delete_course_db(pool: &PgPool, teacher_id: i32,id:i32) {
-    let course_row = sqlx::query_as!(
-        Course,
-        "DELETE FROM course where teacher_id = $1 and id=$2",
-         teacher_id,
-         id,
-    ).execute(pool).await?;
+//删除课程 软删除 选课记录保留 恢复课程后仍然有效
+pub async fn delete_course_db(pool: &PgPool, teacher_id: i32, id:i32, actor_id: i32) -> Result<String, MyError> {
+    let mut tx = pool.begin().await?;
+    let course = sqlx::query_as::<_, Course>(
+        "UPDATE course SET deleted_at = now() WHERE teacher_id = $1 and id = $2 AND deleted_at IS NULL
+        RETURNING teacher_id, id, name, time, description, format, structure, duration, price, language, level, capacity",
+    )
+    .bind(teacher_id)
+    .bind(id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| MyError::NotFound("Course id not found".into()))?;
+
+    insert_audit_db(&mut tx, AuditRecord::course(AuditAction::Delete, actor_id, Some(&course), None)).await?;
+    tx.commit().await?;
+    Ok("Deleted 1 record".to_string())
+}
+
+//恢复单独删除的课程 老师已删除时要先恢复老师
+pub async fn restore_course_db(pool: &PgPool, teacher_id: i32, id: i32, actor_id: i32) -> Result<Course, MyError> {
+    let mut tx = pool.begin().await?;
+    let deleted_at = sqlx::query_scalar::<_, Option<NaiveDateTime>>("SELECT deleted_at FROM teacher WHERE id = $1 FOR SHARE")
+        .bind(teacher_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| MyError::NotFound("NO teacher found".into()))?;
+    if deleted_at.is_some() {
+        return Err(MyError::Conflict("Teacher is deleted, restore the teacher first".into()));
+    }
+
+    let course = sqlx::query_as::<_, Course>(
+        "UPDATE course SET deleted_at = NULL WHERE teacher_id = $1 AND id = $2 AND deleted_at IS NOT NULL
+        RETURNING teacher_id, id, name, time, description, format, structure, duration, price, language, level, capacity",
+    )
+    .bind(teacher_id)
+    .bind(id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| MyError::NotFound("No deleted course found".into()))?;
 
-    Ok(format!("Deleted {:?} record", course_row))
+    insert_audit_db(&mut tx, AuditRecord::course(AuditAction::Restore, actor_id, None, Some(&course))).await?;
+    tx.commit().await?;
+    Ok(course)
 }
 
 pub async fn update_course_details_db(
     pool: &PgPool, 
     teacher_id: i32,
     id:i32,
-    update_course:UpdateCourse) 
+    update_course:UpdateCourse,
+    actor_id: i32)
     -> Result<Course, MyError> {
-        let current_course_row = sqlx::query_as!(
-            Course,
-            r#"SELECT * FROM course where teacher_id = $1 and id = $2"#,
-            teacher_id,
-            id,
+        let mut tx = pool.begin().await?;
+        let current_course_row = sqlx::query_as::<_, Course>(
+            r#"SELECT teacher_id, id, name, time, description, format, structure, duration, price, language, level, capacity
+            FROM course where teacher_id = $1 and id = $2 AND deleted_at IS NULL FOR UPDATE"#,
         )
-            .fetch_one(pool)
-            .await
-            .map_err(|_err| MyError::NotFound("Course Id not found".into()))?;
+            .bind(teacher_id)
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| MyError::NotFound("Course Id not found".into()))?;
     
-        let name: String = if let Some(name) = update_course.name {
-            name
-        } else {
-            current_course_row.name
-        };
-        let description: String = if let Some(description) = update_course.description {
-            description
-        } else {
-            current_course_row.description.unwrap_or_default()
-        };
-        let format: String = if let Some(format) = update_course.format {
-            format
-        } else {
-            current_course_row.format.unwrap_or_default()
-        };
-        let structure: String = if let Some(structure) = update_course.structure {
-            structure
-        } else {
-            current_course_row.structure.unwrap_or_default()
-        };
-        let duration: String = if let Some(duration) = update_course.duration {
-            duration
-        } else {
-            current_course_row.duration.unwrap_or_default()
-        };
-        let language: String = if let Some(language) = update_course.language {
-            language
-        } else {
-            current_course_row.language.unwrap_or_default()
-        };
-        let level: String = if let Some(level) = update_course.level {
-            level
-        } else {
-            current_course_row.level.unwrap_or_default()
-        };
-        let price: i32 = if let Some(price) = update_course.price {
-            price
-        } else {
-            current_course_row.price.unwrap_or_default()
-        };
+        //没有提供的字段保持原来的值 原来为空的仍然为空
+        let current = current_course_row.clone();
+        let name = update_course.name.unwrap_or(current.name);
+        let description = update_course.description.or(current.description);
+        let format = update_course.format.map(|format| format.to_string()).or(current.format);
+        let structure = update_course.structure.or(current.structure);
+        let duration = update_course.duration.or(current.duration);
+        let language = update_course.language.or(current.language);
+        let level = update_course.level.map(|level| level.to_string()).or(current.level);
+        let price = update_course.price.or(current.price);
+        let capacity = update_course.capacity.or(current.capacity);
     
-        let course_row = sqlx::query_as!(
-            Course,
+        let course = sqlx::query_as::<_, Course>(
             "UPDATE course SET name = $1, description = $2, format = $3,
             structure = $4, duration = $5, price = $6, language = $7,
-            level = $8 where teacher_id = $9 and id = $10
+            level = $8, capacity = $9 where teacher_id = $10 and id = $11
             RETURNING id, teacher_id, name, time,
-            description, format, structure, duration, price, language, level",
-            name,
-            description,
-            format,
-            structure,
-            duration,
-            price,
-            language,
-            level,
-            teacher_id,
-            id
+            description, format, structure, duration, price, language, level, capacity",
         )
-            .fetch_one(pool)
-            .await;
-    
-        if let Ok(course) = course_row {
-            Ok(course)
-        } else {
-            Err(MyError::NotFound("Course id not found".into()))
-        }
-}
\ No newline at end of file
+            .bind(name)
+            .bind(description)
+            .bind(format)
+            .bind(structure)
+            .bind(duration)
+            .bind(price)
+            .bind(language)
+            .bind(level)
+            .bind(capacity)
+            .bind(teacher_id)
+            .bind(id)
+            .fetch_one(&mut tx)
+            .await?;
+
+        insert_audit_db(&mut tx, AuditRecord::course(AuditAction::Update, actor_id, Some(&current_course_row), Some(&course))).await?;
+        tx.commit().await?;
+        Ok(course)
+}
@@ -1,2 +1,5 @@
+pub mod audit;
 pub mod course;
-pub mod teacher;
\ No newline at end of file
+pub mod student;
+pub mod teacher;
+pub mod user;
\ No newline at end of file
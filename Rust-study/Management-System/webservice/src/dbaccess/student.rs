@@ -0,0 +1,102 @@
+use crate::dbaccess::course::get_course_details_db;
+use crate::error::MyError;
+use crate::models::course::Course;
+use crate::models::student::{Enrollment, Student};
+use sqlx::postgres::PgPool;
+
+//student和enrollment表的结构见migrations/20221030000003_create_student_and_enrollment.sql
+
+//选课 在事务里锁住课程行 同一门课的选课请求依次执行 人数不会超过容量
+pub async fn enroll_db(pool: &PgPool, teacher_id: i32, course_id: i32, student_id: i32) -> Result<Enrollment, MyError> {
+    let mut tx = pool.begin().await?;
+
+    let capacity = sqlx::query_scalar::<_, Option<i32>>(
+        "SELECT capacity FROM course WHERE teacher_id = $1 AND id = $2 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(teacher_id)
+    .bind(course_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| MyError::NotFound("Course ID not found".into()))?;
+
+    let enrolled = sqlx::query_scalar::<_, i32>(
+        "SELECT student_id FROM enrollment WHERE course_id = $1 AND student_id = $2",
+    )
+    .bind(course_id)
+    .bind(student_id)
+    .fetch_optional(&mut tx)
+    .await?;
+    if enrolled.is_some() {
+        return Err(MyError::AlreadyEnrolled("Already enrolled in this course".into()));
+    }
+
+    if let Some(capacity) = capacity {
+        let enrolled = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM enrollment WHERE course_id = $1")
+            .bind(course_id)
+            .fetch_one(&mut tx)
+            .await?;
+        if enrolled >= capacity as i64 {
+            return Err(MyError::CourseFull("Course is full".into()));
+        }
+    }
+
+    let enrollment = sqlx::query_as::<_, Enrollment>(
+        "INSERT INTO enrollment (course_id, student_id) VALUES ($1, $2)
+        RETURNING course_id, student_id, enrolled_at",
+    )
+    .bind(course_id)
+    .bind(student_id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(enrollment)
+}
+
+//退课
+pub async fn withdraw_db(pool: &PgPool, teacher_id: i32, course_id: i32, student_id: i32) -> Result<String, MyError> {
+    get_course_details_db(pool, teacher_id, course_id).await?;
+    let result = sqlx::query("DELETE FROM enrollment WHERE course_id = $1 AND student_id = $2")
+    .bind(course_id)
+    .bind(student_id)
+    .execute(pool)
+    .await?;
+
+    match result.rows_affected() {
+        0 => Err(MyError::NotEnrolled("Not enrolled in this course".into())),
+        n => Ok(format!("Deleted {} record", n)),
+    }
+}
+
+//课程的学生名单 按选课时间排序 已删除的课程查不到
+pub async fn get_course_roster_db(pool: &PgPool, teacher_id: i32, course_id: i32) -> Result<Vec<Student>, MyError> {
+    get_course_details_db(pool, teacher_id, course_id).await?;
+    let rows = sqlx::query_as::<_, Student>(
+        "SELECT s.id, s.name, s.email FROM student s
+        JOIN enrollment e ON e.student_id = s.id
+        WHERE e.course_id = $1
+        ORDER BY e.enrolled_at, s.id",
+    )
+    .bind(course_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+//学生选的课程 按选课时间排序 不包括已删除的课程
+pub async fn get_student_courses_db(pool: &PgPool, student_id: i32) -> Result<Vec<Course>, MyError> {
+    let rows = sqlx::query_as::<_, Course>(
+        "SELECT c.teacher_id, c.id, c.name, c.time, c.description, c.format, c.structure,
+        c.duration, c.price, c.language, c.level, c.capacity
+        FROM course c
+        JOIN enrollment e ON e.course_id = c.id
+        WHERE e.student_id = $1 AND c.deleted_at IS NULL
+        ORDER BY e.enrolled_at, c.id",
+    )
+    .bind(student_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
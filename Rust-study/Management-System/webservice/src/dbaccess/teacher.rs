@@ -1,119 +1,147 @@
+use crate::dbaccess::audit::insert_audit_db;
 use crate::error::MyError;
-use crate::models::teacher::{CreateTeacher, Teacher, UpdateTeacher};
+use crate::models::audit::{AuditAction, AuditRecord};
+use crate::models::course::Course;
+use crate::models::page::{page_size, Page};
+use crate::models::teacher::{CreateTeacher, Teacher, TeacherQuery, UpdateTeacher};
+use chrono::NaiveDateTime;
 use sqlx::postgres::PgPool;
 
-//查找老师
-pub async fn get_all_teachers_db(pool: &PgPool) -> Result<Vec<Teacher>,MyError> {
-    let rows = sqlx::query!("SELECT * FROM teacher")
+//已删除的老师deleted_at不为空 除了恢复以外的查询都要过滤掉
+
+//查找老师 按id分页
+pub async fn get_all_teachers_db(pool: &PgPool, query: &TeacherQuery) -> Result<Page<Teacher>,MyError> {
+    let limit = page_size(query.limit)?;
+    let teachers = sqlx::query_as::<_, Teacher>(
+        "SELECT id, name, picture_url, profile FROM teacher WHERE deleted_at IS NULL AND id > $1 ORDER BY id LIMIT $2",
+    )
+        .bind(query.cursor.unwrap_or(0))
+        .bind(limit + 1)
         .fetch_all(pool)
         .await?;
+    let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM teacher WHERE deleted_at IS NULL")
+        .fetch_one(pool)
+        .await?;
 
-    let teachers: Vec<Teacher> = rows
-    .iter()
-    .map(|r| Teacher {
-        id: r.id,
-        name: r.name.clone(),
-        picture_url: r.picture_url.clone(),
-        profile: r.profile.clone(),
-    }).collect();
-
-    match teachers.len() {
-        0 => Err(MyError::NotFound("NO teacher found".into())),
-        _ => Ok(teachers)
-    }
+    Ok(Page::new(teachers, limit, total, |teacher| teacher.id))
 }
 
 //获得老师具体信息
-pub async fn get_teacher_details_db(pool: &PgPool, teacher_id: i32) -> Result<Vec<Teacher>,MyError> {
-    let row = sqlx::query!(
-        "SELECT * FROM teacher where id = $1",teacher_id)
-        .fetch_one(pool)
-        .await
-        .map(|r| Teacher {
-            id: r.id,
-            name: r.name.clone(),
-            picture_url: r.picture_url.clone(),
-            profile: r.profile.clone(),
-        })
-        .map_err(|_err| MyError::NotFound("NO teacher found".into()))?;
+pub async fn get_teacher_details_db(pool: &PgPool, teacher_id: i32) -> Result<Teacher,MyError> {
+    let row = sqlx::query_as::<_, Teacher>(
+        "SELECT id, name, picture_url, profile FROM teacher where id = $1 AND deleted_at IS NULL")
+        .bind(teacher_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| MyError::NotFound("NO teacher found".into()))?;
 
     Ok(row)
 }
 
 //新增老师信息
-pub async fn post_new_teacher_db(pool: &PgPool, new_teacher:CreateTeachers) -> Result<Teacher,MyError> {
-    let row = sqlx::query!(
+pub async fn post_new_teacher_db(pool: &PgPool, new_teacher:CreateTeacher, actor_id: i32) -> Result<Teacher,MyError> {
+    let mut tx = pool.begin().await?;
+    let teacher = sqlx::query_as::<_, Teacher>(
         "INSERT INTO teacher (name, picture_url, profile) VALUES ($1, $2,$3)
-        RETURNING name, picture_url, profile",
-        new_teacher.name,
-        new_teacher.picture_url,
-        new_teacher.profile)
-        .fetch_one(pool)
+        RETURNING id, name, picture_url, profile")
+        .bind(new_teacher.name)
+        .bind(new_teacher.picture_url)
+        .bind(new_teacher.profile)
+        .fetch_one(&mut tx)
         .await?;
 
-    Ok(Teacher { 
-        id: row.id, 
-        name: row.name, 
-        picture_url: row.picture_url, 
-        profile: row.profile
-    })
+    insert_audit_db(&mut tx, AuditRecord::teacher(AuditAction::Create, actor_id, None, Some(&teacher))).await?;
+    tx.commit().await?;
+    Ok(teacher)
 }
 
-//修改老师信息
-pub async fn update_teacher_details_db(pool: &PgPool, teacher_id:i32, update_teacher:UpdateTeachers) -> Result<Teacher,MyError> {
-    let row = sqlx::query!(
-        "SELECT * FROM teacher where id = $1", teacher_id)
-        .fetch_one(pool)
-        .await
-        .map_err(|_err| MyError::NotFound("NO teacher found".into()))?;
-
-    let temp = Teacher { 
-        id: row.id, 
-        name: if let Some(name) = update_teacher.name {
-            name
-        } else {
-            row.name
-        }, 
-        picture_url: if let Some(pic) = update_teacher.picture_url {
-            picture_url
-        } else {
-            row.picture_url
-        }, 
-        profile: if let Some(profile) = update_teacher.profile {
-            profile
-        } else {
-            row.profile
-        },
-    };
-
-    let update_row = sqlx::query!(
+//修改老师信息 锁住这一行 记录的修改前数据就是被覆盖的数据
+pub async fn update_teacher_details_db(pool: &PgPool, teacher_id:i32, update_teacher:UpdateTeacher, actor_id: i32) -> Result<Teacher,MyError> {
+    let mut tx = pool.begin().await?;
+    let current = sqlx::query_as::<_, Teacher>(
+        "SELECT id, name, picture_url, profile FROM teacher where id = $1 AND deleted_at IS NULL FOR UPDATE",
+    )
+        .bind(teacher_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| MyError::NotFound("Teacher id not found".into()))?;
+
+    let updated = sqlx::query_as::<_, Teacher>(
         "UPDATE teacher SET name = $1, picture_url = $2, profile = $3 WHERE  id = $4 RETURNING id, name, picture_url, profile",
-        temp.name,
-        temp.picture_url,
-        temp.profile,
-        teacher_id
     )
-        .fetch_one(pool)
-        .await
-        .map(|r| Teacher {
-            id: r.id,
-            name: r.name,
-            picture_url: r.picture_url,
-            profile: r.profile,
-        })
-        .map_err(|_err| MyError::NotFound("Teacher id not found".into()))?;
-    
-    Ok(())
+        .bind(update_teacher.name.unwrap_or_else(|| current.name.clone()))
+        .bind(update_teacher.picture_url.unwrap_or_else(|| current.picture_url.clone()))
+        .bind(update_teacher.profile.unwrap_or_else(|| current.profile.clone()))
+        .bind(teacher_id)
+        .fetch_one(&mut tx)
+        .await?;
+
+    insert_audit_db(&mut tx, AuditRecord::teacher(AuditAction::Update, actor_id, Some(&current), Some(&updated))).await?;
+    tx.commit().await?;
+    Ok(updated)
 }
 
-//删除老师
-pub async fn delete_teacher_db(pool: &PgPool, teacher_id: i32) -> Result<String, MyError> {
-    let row = sqlx::query(&format!("DELETE FROM teacher WHERE id = {}",teacher_id))
-    .execute(pool)
-    .await
-    .map_err(|_err| MyError::DBError("NO teacher found".into()))?;
-    
-    Ok(format!("Deleted {:?} record", row))
+//删除老师 软删除 他的课程使用同一个删除时间 恢复时根据这个时间找到一起删除的课程
+pub async fn delete_teacher_db(pool: &PgPool, teacher_id: i32, actor_id: i32) -> Result<String, MyError> {
+    let mut tx = pool.begin().await?;
+    let teacher = sqlx::query_as::<_, Teacher>(
+        "UPDATE teacher SET deleted_at = now() WHERE id = $1 AND deleted_at IS NULL
+        RETURNING id, name, picture_url, profile",
+    )
+    .bind(teacher_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| MyError::NotFound("NO teacher found".into()))?;
+
+    let courses = sqlx::query_as::<_, Course>(
+        "UPDATE course SET deleted_at = (SELECT deleted_at FROM teacher WHERE id = $1)
+        WHERE teacher_id = $1 AND deleted_at IS NULL
+        RETURNING teacher_id, id, name, time, description, format, structure,
+        duration, price, language, level, capacity",
+    )
+    .bind(teacher_id)
+    .fetch_all(&mut tx)
+    .await?;
+    for course in &courses {
+        insert_audit_db(&mut tx, AuditRecord::course(AuditAction::Delete, actor_id, Some(course), None)).await?;
+    }
+
+    insert_audit_db(&mut tx, AuditRecord::teacher(AuditAction::Delete, actor_id, Some(&teacher), None)).await?;
+    tx.commit().await?;
+    Ok(format!("Deleted teacher and {} courses", courses.len()))
 }
 
+//恢复老师和跟他一起删除的课程 之前单独删除的课程不恢复
+pub async fn restore_teacher_db(pool: &PgPool, teacher_id: i32, actor_id: i32) -> Result<Teacher, MyError> {
+    let mut tx = pool.begin().await?;
+    let deleted_at = sqlx::query_scalar::<_, NaiveDateTime>(
+        "SELECT deleted_at FROM teacher WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE",
+    )
+    .bind(teacher_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| MyError::NotFound("No deleted teacher found".into()))?;
+
+    let teacher = sqlx::query_as::<_, Teacher>(
+        "UPDATE teacher SET deleted_at = NULL WHERE id = $1 RETURNING id, name, picture_url, profile",
+    )
+    .bind(teacher_id)
+    .fetch_one(&mut tx)
+    .await?;
+    let courses = sqlx::query_as::<_, Course>(
+        "UPDATE course SET deleted_at = NULL WHERE teacher_id = $1 AND deleted_at = $2
+        RETURNING teacher_id, id, name, time, description, format, structure,
+        duration, price, language, level, capacity",
+    )
+    .bind(teacher_id)
+    .bind(deleted_at)
+    .fetch_all(&mut tx)
+    .await?;
 
+    insert_audit_db(&mut tx, AuditRecord::teacher(AuditAction::Restore, actor_id, None, Some(&teacher))).await?;
+    for course in &courses {
+        insert_audit_db(&mut tx, AuditRecord::course(AuditAction::Restore, actor_id, None, Some(course))).await?;
+    }
+    tx.commit().await?;
+    Ok(teacher)
+}
@@ -0,0 +1,118 @@
+use crate::dbaccess::audit::insert_audit_db;
+use crate::error::MyError;
+use crate::models::audit::{AuditAction, AuditRecord};
+use crate::models::teacher::Teacher;
+use crate::models::user::{RegisterStudent, RegisterTeacher, User};
+use sqlx::postgres::PgPool;
+
+//users表的结构见migrations/20221030000002_create_users.sql
+//老师被删除后他的账号查不到 不能登录也不能刷新token 恢复老师后可以继续使用
+
+//按用户名查找账号 登录时使用
+pub async fn get_user_by_username_db(pool: &PgPool, username: &str) -> Result<Option<User>, MyError> {
+    let row = sqlx::query_as::<_, User>(
+        "SELECT id, username, password_hash, role, teacher_id, student_id FROM users
+        WHERE username = $1 AND NOT EXISTS (SELECT 1 FROM teacher t WHERE t.id = users.teacher_id AND t.deleted_at IS NOT NULL)",
+    )
+    .bind(username)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+//按id查找账号 刷新token时使用
+pub async fn get_user_db(pool: &PgPool, user_id: i32) -> Result<Option<User>, MyError> {
+    let row = sqlx::query_as::<_, User>(
+        "SELECT id, username, password_hash, role, teacher_id, student_id FROM users
+        WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM teacher t WHERE t.id = users.teacher_id AND t.deleted_at IS NOT NULL)",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+//注册老师 老师资料和账号在同一个事务里创建
+pub async fn post_new_teacher_user_db(
+    pool: &PgPool,
+    new_user: RegisterTeacher,
+    password_hash: String,
+) -> Result<User, MyError> {
+    let mut tx = pool.begin().await?;
+
+    let taken = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE username = $1")
+        .bind(&new_user.username)
+        .fetch_optional(&mut tx)
+        .await?;
+    if taken.is_some() {
+        return Err(MyError::Conflict("Username is already taken".into()));
+    }
+
+    let teacher = sqlx::query_as::<_, Teacher>(
+        "INSERT INTO teacher (name, picture_url, profile) VALUES ($1, $2, $3) RETURNING id, name, picture_url, profile",
+    )
+    .bind(&new_user.name)
+    .bind(&new_user.picture_url)
+    .bind(&new_user.profile)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let user = sqlx::query_as::<_, User>(
+        "INSERT INTO users (username, password_hash, role, teacher_id) VALUES ($1, $2, 'teacher', $3)
+        RETURNING id, username, password_hash, role, teacher_id, student_id",
+    )
+    .bind(&new_user.username)
+    .bind(&password_hash)
+    .bind(teacher.id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    insert_audit_db(&mut tx, AuditRecord::teacher(AuditAction::Create, user.id, None, Some(&teacher))).await?;
+    tx.commit().await?;
+    Ok(user)
+}
+
+//注册学生 学生和账号在同一个事务里创建
+pub async fn post_new_student_user_db(
+    pool: &PgPool,
+    new_user: RegisterStudent,
+    password_hash: String,
+) -> Result<User, MyError> {
+    let mut tx = pool.begin().await?;
+
+    let taken = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE username = $1")
+        .bind(&new_user.username)
+        .fetch_optional(&mut tx)
+        .await?;
+    if taken.is_some() {
+        return Err(MyError::Conflict("Username is already taken".into()));
+    }
+    let registered = sqlx::query_scalar::<_, i32>("SELECT id FROM student WHERE email = $1")
+        .bind(&new_user.email)
+        .fetch_optional(&mut tx)
+        .await?;
+    if registered.is_some() {
+        return Err(MyError::Conflict("Email is already registered".into()));
+    }
+
+    let student_id = sqlx::query_scalar::<_, i32>("INSERT INTO student (name, email) VALUES ($1, $2) RETURNING id")
+        .bind(&new_user.name)
+        .bind(&new_user.email)
+        .fetch_one(&mut tx)
+        .await?;
+
+    let user = sqlx::query_as::<_, User>(
+        "INSERT INTO users (username, password_hash, role, student_id) VALUES ($1, $2, 'student', $3)
+        RETURNING id, username, password_hash, role, teacher_id, student_id",
+    )
+    .bind(&new_user.username)
+    .bind(&password_hash)
+    .bind(student_id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(user)
+}
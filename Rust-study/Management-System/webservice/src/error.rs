@@ -1,7 +1,14 @@
-use actix_web::{error, http::StatusCode, HttpResponse, Result};
+use actix_web::error::{self, JsonPayloadError, PathError, ResponseError};
+use actix_web::{http::{header, StatusCode}, HttpResponse, Result};
 use serde::Serialize;
-use sqlx::error::Error as SQLxError;
+use serde_json::error::Category;
+use sqlx::error::{DatabaseError, Error as SQLxError};
+use sqlx::postgres::PgDatabaseError;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
+use utoipa::ToSchema;
+use validator::ValidationErrors;
 
 //自定义错误类型枚举
 #[derive(Debug, Serialize)]
@@ -9,37 +16,163 @@ pub enum MyError {
     DBError(String),
     ActixError(String),
     NotFound(String),
-    InvalidInput(String),
+    InvalidInput(InputError),
+    //没有登录或token无效
+    Unauthorized(String),
+    //已登录但没有权限
+    Forbidden(String),
+    //选课 课程人数已满
+    CourseFull(String),
+    //选课 已经选过这门课
+    AlreadyEnrolled(String),
+    //退课 没有选过这门课
+    NotEnrolled(String),
+    //和已有的数据冲突 例如用户名重复 或者删除仍被引用的数据
+    Conflict(String),
+    //请求体超过大小限制
+    PayloadTooLarge(String),
+    //请求体不是JSON
+    UnsupportedMediaType(String),
+    //数据库连接失败或连接池没有空闲连接
+    Unavailable(String),
 }
 
-// 传递给用户的错误类型
-#[derive(Debug, Serialize)]
+//字段名对应的错误信息
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
+
+//请求数据不合法 fields为空表示请求本身有问题(400) 不为空表示字段校验没有通过(422)
+#[derive(Debug, Serialize, Default)]
+pub struct InputError {
+    pub message: String,
+    pub fields: FieldErrors,
+}
+
+impl From<String> for InputError {
+    fn from(message: String) -> Self {
+        InputError {
+            message,
+            fields: FieldErrors::new(),
+        }
+    }
+}
+
+impl From<&str> for InputError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+//返回给用户的错误 code是机器可读的错误类型 message是给人看的错误信息
+//服务器内部错误的具体原因只写日志 不返回给用户
+#[derive(Debug, Serialize, ToSchema)]
 pub struct MyErrorResponse {
-    error_message: String,
+    #[schema(example = "not_found")]
+    code: String,
+    message: String,
+    //没有通过校验的字段 以及每个字段的错误信息
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fields: FieldErrors,
+    //和响应头x-request-id相同 用来在日志里找到这个请求
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
-//将具体的错误类型转换成字符串消息
-impl MyError{
-    fn error_response(&self) -> String {
+impl MyError {
+    //字段错误 例如外键指向的数据不存在
+    pub fn invalid_field(field: &str, message: &str) -> Self {
+        let mut fields = FieldErrors::new();
+        fields.insert(field.to_string(), vec![message.to_string()]);
+        MyError::InvalidInput(InputError {
+            message: "Validation failed".into(),
+            fields,
+        })
+    }
+
+    pub fn code(&self) -> &'static str {
         match self {
-            MyError::DBError(msg) => {
-                println!("Database error occured:{:?}", msg);
-                "Database errror".into()
-            },
-            MyError::ActixError(msg) => {
-                println!("Server error occured:{:?}", msg);
-                "Internal server errror".into()
-            },
-            MyError::NotFound(msg) => {
-                println!("Not found error occured:{:?}", msg);
-                msg.into()
-            },
-            MyError::InvalidInput(msg) => {
-                println!("Invalid paramters received: {:?}", msg);
-                msg.into()
-            }
+            MyError::DBError(_) => "database_error",
+            MyError::ActixError(_) => "internal_error",
+            MyError::NotFound(_) => "not_found",
+            MyError::InvalidInput(err) if err.fields.is_empty() => "invalid_input",
+            MyError::InvalidInput(_) => "validation_failed",
+            MyError::Unauthorized(_) => "unauthorized",
+            MyError::Forbidden(_) => "forbidden",
+            MyError::Conflict(_) => "conflict",
+            MyError::PayloadTooLarge(_) => "payload_too_large",
+            MyError::UnsupportedMediaType(_) => "unsupported_media_type",
+            MyError::Unavailable(_) => "service_unavailable",
+            MyError::CourseFull(_) => "course_full",
+            MyError::AlreadyEnrolled(_) => "already_enrolled",
+            MyError::NotEnrolled(_) => "not_enrolled",
+        }
+    }
+
+    //枚举成员的名字 用作/metrics里错误计数的标签
+    pub fn variant(&self) -> &'static str {
+        match self {
+            MyError::DBError(_) => "DBError",
+            MyError::ActixError(_) => "ActixError",
+            MyError::NotFound(_) => "NotFound",
+            MyError::InvalidInput(_) => "InvalidInput",
+            MyError::Unauthorized(_) => "Unauthorized",
+            MyError::Forbidden(_) => "Forbidden",
+            MyError::Conflict(_) => "Conflict",
+            MyError::PayloadTooLarge(_) => "PayloadTooLarge",
+            MyError::UnsupportedMediaType(_) => "UnsupportedMediaType",
+            MyError::Unavailable(_) => "Unavailable",
+            MyError::CourseFull(_) => "CourseFull",
+            MyError::AlreadyEnrolled(_) => "AlreadyEnrolled",
+            MyError::NotEnrolled(_) => "NotEnrolled",
+        }
+    }
+
+    //错误的具体原因 只用于日志
+    fn detail(&self) -> &str {
+        match self {
+            MyError::InvalidInput(err) => &err.message,
+            MyError::DBError(msg)
+            | MyError::ActixError(msg)
+            | MyError::NotFound(msg)
+            | MyError::Unauthorized(msg)
+            | MyError::Forbidden(msg)
+            | MyError::Conflict(msg)
+            | MyError::PayloadTooLarge(msg)
+            | MyError::UnsupportedMediaType(msg)
+            | MyError::Unavailable(msg)
+            | MyError::CourseFull(msg)
+            | MyError::AlreadyEnrolled(msg)
+            | MyError::NotEnrolled(msg) => msg,
         }
     }
+
+    //返回给用户的错误信息
+    fn message(&self) -> String {
+        match self {
+            MyError::DBError(_) => "Database error".into(),
+            MyError::ActixError(_) => "Internal server error".into(),
+            MyError::Unavailable(_) => "Service is temporarily unavailable".into(),
+            _ => self.detail().into(),
+        }
+    }
+
+    //中间件知道请求ID 会用这个方法生成带request_id的响应body
+    pub fn to_response(&self, request_id: Option<&str>) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        // 401需要告诉客户端使用哪种认证方式
+        if let MyError::Unauthorized(_) = self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        let fields = match self {
+            MyError::InvalidInput(err) => err.fields.clone(),
+            _ => FieldErrors::new(),
+        };
+        response.json(MyErrorResponse {
+            code: self.code().into(),
+            message: self.message(),
+            fields,
+            request_id: request_id.map(String::from),
+        })
+    }
 }
 
 // 将错误类型转换成具体的http响应
@@ -47,35 +180,220 @@ impl MyError{
 impl error::ResponseError for MyError {
     fn status_code(&self) -> StatusCode {
         match self {
-            MyError::DBError(msg) | MyError::ActixError(msg) => StatusCode:: INTERNAL_SERVER_ERROR,
-            MyError::NotFound(msg) => StatusCode:: NOT_FOUND,
-            MyError::InvalidInput(_msg) => StatusCode::BAD_REQUEST,
+            MyError::DBError(_) | MyError::ActixError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            MyError::NotFound(_) | MyError::NotEnrolled(_) => StatusCode::NOT_FOUND,
+            MyError::InvalidInput(err) if err.fields.is_empty() => StatusCode::BAD_REQUEST,
+            MyError::InvalidInput(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            MyError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            MyError::Forbidden(_) => StatusCode::FORBIDDEN,
+            MyError::Conflict(_) | MyError::CourseFull(_) | MyError::AlreadyEnrolled(_) => StatusCode::CONFLICT,
+            MyError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            MyError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            MyError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
+    //日志记在当前请求的span里 服务器错误用error级别 用户的错误请求用info级别
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).json(MyErrorResponse {
-            error_message: self.error_response(),
-        })
+        match self {
+            _ if self.status_code().is_server_error() => {
+                tracing::error!(code = self.code(), detail = self.detail(), "request failed")
+            }
+            MyError::InvalidInput(err) if !err.fields.is_empty() => {
+                tracing::info!(code = self.code(), detail = self.detail(), fields = ?err.fields, "request rejected")
+            }
+            _ => tracing::info!(code = self.code(), detail = self.detail(), "request rejected"),
+        }
+        self.to_response(None)
     }
 }
 
 //当MyError实现error::ResponseError这个trait后，需要两个trait（debug display）
 impl fmt::Display for MyError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self)
+        write!(f, "{}: {}", self.code(), self.detail())
     }
 }
 
-//将以下两种错误类型转换成MyError错误类型
+//将以下错误类型转换成MyError错误类型
 impl From<actix_web::error::Error> for MyError {
     fn from(err: actix_web::error::Error) -> Self {
         MyError::ActixError(err.to_string())
     }
 }
 
+//连接错误返回503 约束错误按约束类型区分 其他数据库错误返回500
 impl From<SQLxError> for MyError {
     fn from(err: SQLxError) -> Self {
-        MyError::DBError(err.to_string())
+        match &err {
+            SQLxError::RowNotFound => MyError::NotFound("Record not found".into()),
+            SQLxError::PoolTimedOut | SQLxError::PoolClosed | SQLxError::Io(_) | SQLxError::Tls(_) => {
+                MyError::Unavailable(err.to_string())
+            }
+            SQLxError::Database(db_err) => {
+                constraint_error(db_err.as_ref()).unwrap_or_else(|| MyError::DBError(err.to_string()))
+            }
+            _ => MyError::DBError(err.to_string()),
+        }
+    }
+}
+
+//违反约束的数据库错误 错误码见
+//PostgreSQL: https://www.postgresql.org/docs/current/errcodes-appendix.html
+//SQLite(扩展错误码): https://www.sqlite.org/rescode.html
+fn constraint_error(err: &dyn DatabaseError) -> Option<MyError> {
+    let code = sqlite_constraint_code(err.message()).map(Cow::from).or_else(|| err.code())?;
+    let field = constraint_field(err);
+    let error = match code.as_ref() {
+        //唯一约束
+        "23505" | "2067" | "1555" => MyError::Conflict(match field {
+            Some(field) => format!("{} is already taken", field),
+            None => "Record already exists".into(),
+        }),
+        //外键约束 删除仍被引用的数据是冲突 写入的外键指向不存在的数据是字段错误
+        "23503" | "787" if err.message().starts_with("update or delete") => {
+            MyError::Conflict("Record is still referenced by other records".into())
+        }
+        "23503" | "787" => MyError::invalid_field(
+            field.as_deref().unwrap_or("reference"),
+            "does not reference an existing record",
+        ),
+        //检查约束
+        "23514" | "275" => MyError::invalid_field(field.as_deref().unwrap_or("value"), "value is not allowed"),
+        //非空约束
+        "23502" | "1299" => MyError::invalid_field(field.as_deref().unwrap_or("value"), "must not be null"),
+        _ => return None,
+    };
+    Some(error)
+}
+
+//带RETURNING的语句违反约束时SQLite只返回通用错误码1 这时根据错误信息判断约束类型
+fn sqlite_constraint_code(message: &str) -> Option<&'static str> {
+    [
+        ("UNIQUE constraint failed", "2067"),
+        ("FOREIGN KEY constraint failed", "787"),
+        ("CHECK constraint failed", "275"),
+        ("NOT NULL constraint failed", "1299"),
+    ]
+    .iter()
+    .find(|(prefix, _)| message.starts_with(prefix))
+    .map(|(_, code)| *code)
+}
+
+//从约束里找出字段名
+//PostgreSQL的约束名是 表名_字段名_后缀 例如course_teacher_id_fkey
+//SQLite只有错误信息 例如 UNIQUE constraint failed: users.username
+fn constraint_field(err: &dyn DatabaseError) -> Option<String> {
+    if let Some(pg_err) = err.try_downcast_ref::<PgDatabaseError>() {
+        if let Some(column) = pg_err.column() {
+            return Some(column.to_string());
+        }
+        let constraint = pg_err.constraint()?;
+        let name = pg_err
+            .table()
+            .and_then(|table| constraint.strip_prefix(table))
+            .and_then(|name| name.strip_prefix('_'))
+            .unwrap_or(constraint);
+        let name = ["_fkey", "_key", "_check"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+            .unwrap_or(name);
+        return Some(name.to_string()).filter(|name| !name.is_empty());
+    }
+    let (_, failed) = err.message().split_once("failed: ")?;
+    let column = failed.split(|c: char| c == ',' || c.is_whitespace()).next()?;
+    column.rsplit('.').next().map(String::from).filter(|name| !name.is_empty())
+}
+
+//请求体JSON解析失败 区分请求头 请求体大小 JSON格式和数据类型错误
+impl From<JsonPayloadError> for MyError {
+    fn from(err: JsonPayloadError) -> Self {
+        match err {
+            JsonPayloadError::ContentType => {
+                MyError::UnsupportedMediaType("Content-Type must be application/json".into())
+            }
+            JsonPayloadError::Overflow { limit } | JsonPayloadError::OverflowKnownLength { limit, .. } => {
+                MyError::PayloadTooLarge(format!("Request body is larger than {} bytes", limit))
+            }
+            JsonPayloadError::Deserialize(err) => match err.classify() {
+                Category::Data => {
+                    let message = err.to_string();
+                    //缺少字段时能确定是哪个字段 其他数据错误serde_json不会给出字段名
+                    match message
+                        .strip_prefix("missing field `")
+                        .and_then(|rest| rest.split('`').next())
+                    {
+                        Some(field) => MyError::invalid_field(field, "field is required"),
+                        None => MyError::InvalidInput(format!("Invalid JSON data: {}", message).into()),
+                    }
+                }
+                Category::Eof => MyError::InvalidInput(format!("Incomplete JSON: {}", err).into()),
+                Category::Syntax | Category::Io => MyError::InvalidInput(format!("Malformed JSON: {}", err).into()),
+            },
+            err => MyError::InvalidInput(format!("Invalid JSON body: {}", err).into()),
+        }
+    }
+}
+
+//路径参数解析失败 例如 /courses/abc
+impl From<PathError> for MyError {
+    fn from(err: PathError) -> Self {
+        MyError::InvalidInput(format!("Invalid path parameters: {}", err).into())
+    }
+}
+
+//validator的校验结果 每个字段保留所有没有通过的规则的错误信息
+impl From<ValidationErrors> for MyError {
+    fn from(errors: ValidationErrors) -> Self {
+        let fields = errors
+            .field_errors()
+            .into_iter()
+            .map(|(field, errors)| {
+                let messages = errors
+                    .iter()
+                    .map(|error| error.message.as_ref().unwrap_or(&error.code).to_string())
+                    .collect();
+                (field.to_string(), messages)
+            })
+            .collect();
+        MyError::InvalidInput(InputError {
+            message: "Validation failed".into(),
+            fields,
+        })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Body {
+        name: String,
+        price: i32,
+    }
+
+    fn json_error(input: &str) -> MyError {
+        let err = serde_json::from_str::<Body>(input).unwrap_err();
+        JsonPayloadError::Deserialize(err).into()
+    }
+
+    #[test]
+    fn json_error_test() {
+        assert_eq!(json_error(r#"{"name": "Rust", "price": }"#).code(), "invalid_input");
+        assert!(json_error(r#"{"name": "Rust""#).detail().starts_with("Incomplete JSON"));
+        assert!(json_error(r#"{"name": "Rust", "price": "free"}"#).detail().starts_with("Invalid JSON data"));
+        match json_error(r#"{"name": "Rust"}"#) {
+            MyError::InvalidInput(err) => assert_eq!(err.fields["price"], ["field is required"]),
+            other => panic!("expected invalid input, got {:?}", other),
+        }
+
+        let err = MyError::from(JsonPayloadError::ContentType);
+        assert_eq!(err.status_code(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let err = MyError::from(JsonPayloadError::Overflow { limit: 10 });
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(err.to_string(), "payload_too_large: Request body is larger than 10 bytes");
+    }
+}
@@ -0,0 +1,95 @@
+use crate::auth::AuthUser;
+use crate::error::{MyError, MyErrorResponse};
+use crate::models::audit::{AuditEntry, EntityType};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+
+//某个老师或课程的修改历史 按时间顺序返回 只有管理员可以查看
+#[utoipa::path(
+    get,
+    path = "/audit/{entity_type}/{entity_id}",
+    tag = "audit",
+    params(
+        ("entity_type" = EntityType, Path, description = "teacher or course"),
+        ("entity_id" = i32, Path, description = "Teacher or course id")
+    ),
+    responses(
+        (status = 200, description = "Changes to the entity, oldest first", body = [AuditEntry]),
+        (status = 400, description = "Unknown entity type", body = MyErrorResponse),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_audit_log(
+    app_state: web::Data<AppState>,
+    params: web::Path<(EntityType, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, MyError> {
+    user.require_admin()?;
+    let (entity_type, entity_id) = params.into_inner();
+    app_state.audits.list_audit(entity_type, entity_id)
+        .await
+        .map(|entries| HttpResponse::Ok().json(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::testing;
+    use crate::routers::app_config;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    #[actix_rt::test]
+    async fn audit_log_test() {
+        let state = testing::state();
+        let app = test::init_service(App::new().app_data(state.clone()).configure(app_config)).await;
+
+        let req = test::TestRequest::put()
+            .uri("/courses/2/3")
+            .insert_header(testing::bearer(&state, "bob"))
+            .set_json(json!({"price": 250}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::delete()
+            .uri("/teacher/2")
+            .insert_header(testing::bearer(&state, "admin"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        //修改前后的数据和操作者都有记录
+        let req = test::TestRequest::get()
+            .uri("/audit/course/3")
+            .insert_header(testing::bearer(&state, "admin"))
+            .to_request();
+        let entries: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(entries.as_array().unwrap().len(), 2);
+        assert_eq!(entries[0]["action"], "update");
+        assert_eq!(entries[0]["actor_id"], 3);
+        assert_eq!(entries[0]["old_value"]["price"], 200);
+        assert_eq!(entries[0]["new_value"]["price"], 250);
+        assert_eq!(entries[1]["action"], "delete");
+        assert_eq!(entries[1]["actor_id"], 1);
+        assert_eq!(entries[1]["new_value"], Value::Null);
+
+        let req = test::TestRequest::get()
+            .uri("/audit/teacher/2")
+            .insert_header(testing::bearer(&state, "admin"))
+            .to_request();
+        let entries: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(entries[0]["action"], "delete");
+        assert_eq!(entries[0]["old_value"]["name"], "Bob Li");
+
+        let req = test::TestRequest::get()
+            .uri("/audit/course/3")
+            .insert_header(testing::bearer(&state, "alice"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::get()
+            .uri("/audit/student/1")
+            .insert_header(testing::bearer(&state, "admin"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+}
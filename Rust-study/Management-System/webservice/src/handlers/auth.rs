@@ -0,0 +1,190 @@
+use crate::auth::{hash_password, verify_dummy_password, verify_password, TokenKind};
+use crate::error::{MyError, MyErrorResponse};
+use crate::models::user::{LoginRequest, RefreshRequest, RegisterStudent, RegisterTeacher, TokenResponse};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+use validator::Validate;
+
+//注册老师账号 成功后直接返回token
+#[utoipa::path(
+    post,
+    path = "/auth/register",
+    tag = "auth",
+    request_body = RegisterTeacher,
+    responses(
+        (status = 201, description = "Teacher account and profile created", body = TokenResponse),
+        (status = 409, description = "Username is already taken", body = MyErrorResponse),
+        (status = 422, description = "Validation failed, see fields", body = MyErrorResponse)
+    )
+)]
+pub async fn register(
+    app_state: web::Data<AppState>,
+    new_user: web::Json<RegisterTeacher>,
+) -> Result<HttpResponse, MyError> {
+    new_user.validate()?;
+    let new_user = new_user.into_inner();
+    let password_hash = hash_password(&new_user.password)?;
+    let user = app_state.users.register_teacher(new_user, password_hash).await?;
+    app_state
+        .auth
+        .issue(&user)
+        .map(|tokens| HttpResponse::Created().json(tokens))
+}
+
+//注册学生账号 成功后直接返回token
+#[utoipa::path(
+    post,
+    path = "/auth/register/student",
+    tag = "auth",
+    request_body = RegisterStudent,
+    responses(
+        (status = 201, description = "Student account created", body = TokenResponse),
+        (status = 409, description = "Username or email is already registered", body = MyErrorResponse),
+        (status = 422, description = "Validation failed, see fields", body = MyErrorResponse)
+    )
+)]
+pub async fn register_student(
+    app_state: web::Data<AppState>,
+    new_user: web::Json<RegisterStudent>,
+) -> Result<HttpResponse, MyError> {
+    new_user.validate()?;
+    let new_user = new_user.into_inner();
+    let password_hash = hash_password(&new_user.password)?;
+    let user = app_state.users.register_student(new_user, password_hash).await?;
+    app_state
+        .auth
+        .issue(&user)
+        .map(|tokens| HttpResponse::Created().json(tokens))
+}
+
+//登录 用户不存在和密码错误返回同样的信息 不暴露用户名是否存在
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Logged in", body = TokenResponse),
+        (status = 401, description = "Invalid username or password", body = MyErrorResponse)
+    )
+)]
+pub async fn login(
+    app_state: web::Data<AppState>,
+    credentials: web::Json<LoginRequest>,
+) -> Result<HttpResponse, MyError> {
+    let user = app_state.users.find_user_by_username(&credentials.username).await?;
+    match user {
+        Some(user) if verify_password(&credentials.password, &user.password_hash) => app_state
+            .auth
+            .issue(&user)
+            .map(|tokens| HttpResponse::Ok().json(tokens)),
+        Some(_) => Err(MyError::Unauthorized("Invalid username or password".into())),
+        None => {
+            verify_dummy_password(&credentials.password);
+            Err(MyError::Unauthorized("Invalid username or password".into()))
+        }
+    }
+}
+
+//用refresh token换新的token 重新读取账号 角色变化和删除的账号立即生效
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New token pair", body = TokenResponse),
+        (status = 401, description = "Invalid refresh token or deleted account", body = MyErrorResponse)
+    )
+)]
+pub async fn refresh(
+    app_state: web::Data<AppState>,
+    request: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, MyError> {
+    let claims = app_state.auth.verify(&request.refresh_token, TokenKind::Refresh)?;
+    let user = app_state.users.get_user(claims.user_id()?)
+        .await?
+        .ok_or_else(|| MyError::Unauthorized("Account no longer exists".into()))?;
+    app_state
+        .auth
+        .issue(&user)
+        .map(|tokens| HttpResponse::Ok().json(tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::testing;
+    use crate::routers::app_config;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    #[actix_rt::test]
+    async fn register_login_refresh_test() {
+        let app = test::init_service(App::new().app_data(testing::state()).configure(app_config)).await;
+        let register = |username: &str, password: &str| {
+            test::TestRequest::post()
+                .uri("/auth/register")
+                .set_json(json!({
+                    "username": username,
+                    "password": password,
+                    "name": "Carol",
+                    "picture_url": "https://example.com/carol.png",
+                    "profile": "Databases"
+                }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, register("carol", "short")).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["fields"]["password"][0], "password must be at least 8 characters");
+        let resp = test::call_service(&app, register("carol", "carol-password")).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = test::call_service(&app, register("carol", "carol-password")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "conflict");
+
+        let login = |password: &str| {
+            test::TestRequest::post()
+                .uri("/auth/login")
+                .set_json(json!({"username": "carol", "password": password}))
+                .to_request()
+        };
+        let resp = test::call_service(&app, login("wrong-password")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers().get(header::WWW_AUTHENTICATE).unwrap(), "Bearer");
+        let wrong_password: Value = test::read_body_json(resp).await;
+        //用户名不存在时也校验一次密码 响应和密码错误时相同
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({"username": "nobody", "password": "carol-password"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let unknown_user: Value = test::read_body_json(resp).await;
+        assert_eq!(unknown_user["message"], wrong_password["message"]);
+        let tokens: Value = test::read_body_json(test::call_service(&app, login("carol-password")).await).await;
+        assert_eq!(tokens["token_type"], "Bearer");
+
+        //新注册的老师id是3 可以给自己增加课程
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", tokens["access_token"].as_str().unwrap())))
+            .set_json(json!({"teacher_id": 3, "name": "SQL"}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let refresh = |token: &Value| {
+            test::TestRequest::post()
+                .uri("/auth/refresh")
+                .set_json(json!({"refresh_token": token}))
+                .to_request()
+        };
+        let resp = test::call_service(&app, refresh(&tokens["refresh_token"])).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = test::call_service(&app, refresh(&tokens["access_token"])).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,15 +1,30 @@
-use crate::models::course::{CreateCourse, UpdateCourse};
+use crate::auth::AuthUser;
+use crate::models::course::{Course, CourseQuery, CreateCourse, UpdateCourse};
+use crate::models::page::Page;
 
 use crate::state::AppState;
-use crate::dbaccess::course::*;
-use crate::error::MyError;
+use crate::error::{MyError, MyErrorResponse};
 use actix_web::{web, HttpResponse};
 
 //注入new_course app_state
-// 增加新课程
-pub async fn new_course(
+// 增加新课程 只能给自己增加课程 管理员可以给任何老师增加
+#[utoipa::path(
+    post,
+    path = "/courses/",
+    tag = "courses",
+    request_body = CreateCourse,
+    responses(
+        (status = 200, description = "Course created", body = Course),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 422, description = "Validation failed, see fields", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn post_new_course(
     new_course: web::Json<CreateCourse>,
     app_state: web::Data<AppState>,
+    user: AuthUser,
 ) -> Result<HttpResponse,MyError> {
     //此处修改原因：改用数据库进行增删改查
     // println!("Received new course");
@@ -32,15 +47,46 @@ pub async fn new_course(
 
     // app_state.courses.lock().unwrap().push(new_course);
 
-    post_new_course_db(&app_state.db, new_course.try_into()?)
+    user.require_teacher(new_course.teacher_id)?;
+    app_state.courses.create_course(new_course.try_into()?, user.user_id)
     .await.map(|course| HttpResponse::Ok().json(course))
     
 }
 
-//获得某个老师对应的课程
+//获得所有课程 支持筛选、搜索、排序和分页 参数见CourseQuery
+#[utoipa::path(
+    get,
+    path = "/courses/",
+    tag = "courses",
+    params(CourseQuery),
+    responses(
+        (status = 200, description = "One page of courses", body = Page<Course>),
+        (status = 400, description = "Invalid query parameters", body = MyErrorResponse)
+    )
+)]
+pub async fn get_all_courses(
+    app_state: web::Data<AppState>,
+    query: web::Query<CourseQuery>,
+) -> Result<HttpResponse,MyError> {
+    app_state.courses.list_courses(None, &query)
+    .await.map(|page| HttpResponse::Ok().json(page))
+}
+
+//获得某个老师对应的课程 查询参数和get_all_courses相同
+#[utoipa::path(
+    get,
+    path = "/courses/{teacher_id}",
+    tag = "courses",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), CourseQuery),
+    responses(
+        (status = 200, description = "One page of the teacher's courses", body = Page<Course>),
+        (status = 400, description = "Invalid query parameters", body = MyErrorResponse)
+    )
+)]
 pub async fn get_courses_for_teacher(
     app_state: web::Data<AppState>,
     params:web::Path<(i32,)>, // xxxx/{teacher_id}
+    query: web::Query<CourseQuery>,
 ) -> Result<HttpResponse,MyError> {
     //此处修改原因：改用数据库进行增删改查
     // let teacher_id: usize = params.0;
@@ -63,13 +109,23 @@ pub async fn get_courses_for_teacher(
     //查出teacher_id
     // let teacher_id = i32::try_from(params.0).unwrap();
     let (teacher_id,) = params.into_inner();
-    get_courses_for_teacher_db(&app_state.db, teacher_id)
-    .await.map(|courses| HttpResponse::Ok().json(courses))
+    app_state.courses.list_courses(Some(teacher_id), &query)
+    .await.map(|page| HttpResponse::Ok().json(page))
     
 
 }
 
 //获得课程的详细情况
+#[utoipa::path(
+    get,
+    path = "/courses/{teacher_id}/{course_id}",
+    tag = "courses",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), ("course_id" = i32, Path, description = "Course id")),
+    responses(
+        (status = 200, description = "Course details", body = Course),
+        (status = 404, description = "Course not found", body = MyErrorResponse)
+    )
+)]
 pub async fn get_course_detail(
     app_state: web::Data<AppState>,
     params:web::Path<(i32, i32)>,
@@ -96,30 +152,89 @@ pub async fn get_course_detail(
     // let teacher_id = i32::try_from(params.0).unwrap();
     // let course_id = i32::try_from(params.1).unwrap();
     let (teacher_id, course_id) = params.into_inner();
-    get_courses_details_db(&app_state.db, teacher_id, course_id)
+    app_state.courses.get_course(teacher_id, course_id)
     .await.map(|course| HttpResponse::Ok().json(course))
     
 }
 
-// 删除课程
+// 删除课程 只标记deleted_at 可以恢复
+#[utoipa::path(
+    delete,
+    path = "/courses/{teacher_id}/{course_id}",
+    tag = "courses",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), ("course_id" = i32, Path, description = "Course id")),
+    responses(
+        (status = 200, description = "Course soft deleted", body = String),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_course(
     app_state: web::Data<AppState>,
     params: web::Path<(i32, i32)>,
+    user: AuthUser,
 ) -> Result<HttpResponse, MyError> {
     let (teacher_id, course_id) = params.into_inner();
-    delete_course_db(&app_state.db, teacher_id, course_id)
+    user.require_teacher(teacher_id)?;
+    app_state.courses.delete_course(teacher_id, course_id, user.user_id)
         .await
         .map(|resp| HttpResponse::Ok().json(resp))
 }
 
+// 恢复已删除的课程 老师被删除时要先恢复老师
+#[utoipa::path(
+    post,
+    path = "/courses/{teacher_id}/{course_id}/restore",
+    tag = "courses",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), ("course_id" = i32, Path, description = "Course id")),
+    responses(
+        (status = 200, description = "Restored course", body = Course),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "No deleted course with this id", body = MyErrorResponse),
+        (status = 409, description = "The teacher is deleted", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_course(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, MyError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.require_teacher(teacher_id)?;
+    app_state.courses.restore_course(teacher_id, course_id, user.user_id)
+        .await
+        .map(|course| HttpResponse::Ok().json(course))
+}
+
 // 修改课程细节
+#[utoipa::path(
+    put,
+    path = "/courses/{teacher_id}/{course_id}",
+    tag = "courses",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), ("course_id" = i32, Path, description = "Course id")),
+    request_body = UpdateCourse,
+    responses(
+        (status = 200, description = "Updated course", body = Course),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+        (status = 422, description = "Validation failed, see fields", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_course_details(
     app_state: web::Data<AppState>,
     update_course: web::Json<UpdateCourse>,
     params: web::Path<(i32, i32)>,
+    user: AuthUser,
 ) -> Result<HttpResponse, MyError> {
     let (teacher_id, course_id) = params.into_inner();
-    update_course_details_db(&app_state.db, teacher_id, course_id, update_course.try_into()?)
+    user.require_teacher(teacher_id)?;
+    app_state.courses.update_course(teacher_id, course_id, update_course.try_into()?, user.user_id)
         .await
         .map(|resp| HttpResponse::Ok().json(resp))
 }
@@ -127,45 +242,131 @@ pub async fn update_course_details(
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use crate::handlers::testing;
+    use crate::routers::app_config;
     use actix_web::http::StatusCode;
-    use std::sync::Mutex;
-    use dotenv::dotenv;
-    use std::env;
-    use sqlx::postgres::PgPoolOptions;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    #[actix_rt::test]
+    async fn list_courses_test() {
+        let app = test::init_service(App::new().app_data(testing::state()).configure(app_config)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/courses/?language=English&sort=-price&limit=1")
+            .to_request();
+        let page: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(page["total"], 2);
+        assert_eq!(page["items"][0]["name"], "Async Rust");
+        assert_eq!(page["next_cursor"], 2);
+
+        let req = test::TestRequest::get()
+            .uri("/courses/?language=English&sort=-price&limit=1&cursor=2")
+            .to_request();
+        let page: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(page["items"][0]["name"], "Rust basics");
+        assert_eq!(page["next_cursor"], Value::Null);
+
+        let req = test::TestRequest::get().uri("/courses/2?q=ACTIX").to_request();
+        let page: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(page["total"], 1);
+
+        let req = test::TestRequest::get().uri("/courses/?sort=cheapest").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
 
     #[actix_rt::test]
     async fn post_course_test() {
-        //添加读取环境变量相关代码
-        dotenv().ok();
-        //创建数据库连接池
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-        let db_pool = PgPoolOptions::new()
-            .connect(&database_url)
-            .await
-            .unwrap();
-        //初始化app_state
-        let app_state = web::Data::new(AppState{
-            health_check_response:"I'm ok".to_string(),
-            visit_count:Mutex::new(0),
-            // courses:Mutex::new(vec![]),
-            db: db_pool,
-        });
-
-        let course = web::Json(Course {
-            teacher_id: 1,
-            name: "Test course".into(),
-            id: Some(3), //数据库中类型为：serial
-            time:None,
-        });
-
-        // let app_state = web::Data::new(AppState{
-        //     health_check_response:"".to_string(),
-        //     visit_count: Mutex::new(0),
-        //     courses:Mutex::new(vec![]), 
-        // });
-
-        let resp = new_course(course,app_state).await.unwrap();
+        let state = testing::state();
+        let app = test::init_service(App::new().app_data(state.clone()).configure(app_config)).await;
+        let course = |teacher_id: i32| json!({"teacher_id": teacher_id, "name": "Test course"});
+
+        let req = test::TestRequest::post().uri("/courses/").set_json(course(1)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .insert_header(testing::bearer(&state, "alice"))
+            .set_json(course(1))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
         assert_eq!(resp.status(), StatusCode::OK);
+        let created: Value = test::read_body_json(resp).await;
+        assert_eq!(created["id"], 4);
+
+        //只能给自己增加课程 管理员不受限制
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .insert_header(testing::bearer(&state, "alice"))
+            .set_json(course(2))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::post()
+            .uri("/courses/")
+            .insert_header(testing::bearer(&state, "admin"))
+            .set_json(course(2))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
     }
-}
\ No newline at end of file
+
+    #[actix_rt::test]
+    async fn update_and_delete_course_test() {
+        let state = testing::state();
+        let app = test::init_service(App::new().app_data(state.clone()).configure(app_config)).await;
+
+        let req = test::TestRequest::put()
+            .uri("/courses/2/3")
+            .insert_header(testing::bearer(&state, "bob"))
+            .set_json(json!({"price": 250}))
+            .to_request();
+        let course: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(course["price"], 250);
+        assert_eq!(course["language"], "Chinese");
+
+        //没有通过校验 返回422和每个字段的错误信息 课程没有被修改
+        let req = test::TestRequest::put()
+            .uri("/courses/2/3")
+            .insert_header(testing::bearer(&state, "bob"))
+            .set_json(json!({"name": "", "price": -1, "level": "advanced"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["message"], "Validation failed");
+        assert_eq!(body["fields"], json!({
+            "name": ["name must not be empty"],
+            "price": ["price must not be negative"]
+        }));
+        let req = test::TestRequest::get().uri("/courses/2/3").to_request();
+        let course: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(course["level"], "intermediate");
+
+        let req = test::TestRequest::delete()
+            .uri("/courses/2/3")
+            .insert_header(testing::bearer(&state, "alice"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete()
+            .uri("/courses/2/3")
+            .insert_header(testing::bearer(&state, "bob"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/courses/2/3").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        //删除的课程可以恢复 修改过的价格还在
+        let req = test::TestRequest::post()
+            .uri("/courses/2/3/restore")
+            .insert_header(testing::bearer(&state, "bob"))
+            .to_request();
+        let course: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(course["price"], 250);
+        let req = test::TestRequest::post()
+            .uri("/courses/2/3/restore")
+            .insert_header(testing::bearer(&state, "bob"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,10 +1,29 @@
 use actix_web::{web, HttpResponse};
 use crate::state::AppState;
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "general",
+    responses(
+        (status = 200, description = "Health message", body = String)
+    )
+)]
 pub async fn health_check_handler(app_state: web::Data<AppState>) -> HttpResponse {
-    let health_check_response = &app_state.health_check_response;
-    let mut visit_count = app_state.visit_count.lock().unwrap();
-    let response = format!("{} {} times", health_check_response, visit_count);
-    *visit_count += 1;
-    HttpResponse::Ok().json(&response)
+    HttpResponse::Ok().json(&app_state.health_check_response)
+}
+
+//Prometheus文本格式的指标 请求数和耗时按路由和状态码统计 错误数按MyError统计
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "general",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text exposition format", body = String, content_type = "text/plain")
+    )
+)]
+pub async fn metrics_handler(app_state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(app_state.metrics.render(app_state.pool.as_ref()))
 }
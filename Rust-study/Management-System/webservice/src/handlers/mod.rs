@@ -1,3 +1,8 @@
+pub mod audit;
+pub mod auth;
 pub mod general;
 pub mod course;
-pub mod teacher;
\ No newline at end of file
+pub mod student;
+pub mod teacher;
+#[cfg(test)]
+pub mod testing;
\ No newline at end of file
@@ -0,0 +1,172 @@
+use crate::auth::AuthUser;
+use crate::error::{MyError, MyErrorResponse};
+use crate::models::course::Course;
+use crate::models::student::{Enrollment, Student};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+
+//选课 只能由学生本人操作
+#[utoipa::path(
+    post,
+    path = "/courses/{teacher_id}/{course_id}/enrollment",
+    tag = "students",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), ("course_id" = i32, Path, description = "Course id")),
+    responses(
+        (status = 201, description = "Enrolled", body = Enrollment),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse),
+        (status = 409, description = "Course is full or already enrolled", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn enroll(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, MyError> {
+    let (teacher_id, course_id) = params.into_inner();
+    let student_id = user.own_student_id()?;
+    app_state.enrollments.enroll(teacher_id, course_id, student_id)
+        .await
+        .map(|enrollment| HttpResponse::Created().json(enrollment))
+}
+
+//退课
+#[utoipa::path(
+    delete,
+    path = "/courses/{teacher_id}/{course_id}/enrollment",
+    tag = "students",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), ("course_id" = i32, Path, description = "Course id")),
+    responses(
+        (status = 200, description = "Withdrawn", body = String),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "Course not found or not enrolled", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn withdraw(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, MyError> {
+    let (teacher_id, course_id) = params.into_inner();
+    let student_id = user.own_student_id()?;
+    app_state.enrollments.withdraw(teacher_id, course_id, student_id)
+        .await
+        .map(|resp| HttpResponse::Ok().json(resp))
+}
+
+//课程的学生名单 只有任课老师和管理员可以查看
+#[utoipa::path(
+    get,
+    path = "/courses/{teacher_id}/{course_id}/students",
+    tag = "students",
+    params(("teacher_id" = i32, Path, description = "Teacher id"), ("course_id" = i32, Path, description = "Course id")),
+    responses(
+        (status = 200, description = "Students enrolled in the course", body = Vec<Student>),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "Course not found", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_course_roster(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32, i32)>,
+    user: AuthUser,
+) -> Result<HttpResponse, MyError> {
+    let (teacher_id, course_id) = params.into_inner();
+    user.require_teacher(teacher_id)?;
+    app_state.enrollments.list_roster(teacher_id, course_id)
+        .await
+        .map(|students| HttpResponse::Ok().json(students))
+}
+
+//学生选的课程 只有学生本人和管理员可以查看
+#[utoipa::path(
+    get,
+    path = "/students/{student_id}/courses",
+    tag = "students",
+    params(("student_id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Courses the student is enrolled in", body = Vec<Course>),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn get_student_courses(
+    app_state: web::Data<AppState>,
+    params: web::Path<(i32,)>,
+    user: AuthUser,
+) -> Result<HttpResponse, MyError> {
+    let (student_id,) = params.into_inner();
+    user.require_student(student_id)?;
+    app_state.enrollments.list_student_courses(student_id)
+        .await
+        .map(|courses| HttpResponse::Ok().json(courses))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handlers::testing;
+    use crate::routers::app_config;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    #[actix_rt::test]
+    async fn enrollment_test() {
+        let state = testing::state();
+        let app = test::init_service(App::new().app_data(state.clone()).configure(app_config)).await;
+        let call = |req: test::TestRequest, username: &str| req.insert_header(testing::bearer(&state, username)).to_request();
+
+        //课程3只能选一个学生
+        let enroll = || test::TestRequest::post().uri("/courses/2/3/enrollment");
+        let resp = test::call_service(&app, call(enroll(), "dave")).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let enrollment: Value = test::read_body_json(resp).await;
+        assert_eq!(enrollment["student_id"], 1);
+        let resp = test::call_service(&app, call(enroll(), "dave")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        assert_eq!(test::call_service(&app, call(enroll(), "bob")).await.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::post()
+            .uri("/auth/register/student")
+            .set_json(json!({"username": "erin", "password": "erin-password", "name": "Erin", "email": "erin@example.com"}))
+            .to_request();
+        let tokens: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        let erin = (
+            actix_web::http::header::AUTHORIZATION,
+            format!("Bearer {}", tokens["access_token"].as_str().unwrap()),
+        );
+        let req = enroll().insert_header(erin.clone()).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["message"], "Course is full");
+        let req = test::TestRequest::post().uri("/courses/2/9/enrollment").insert_header(erin).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        //名单只有任课老师和管理员可以查看
+        let roster = || test::TestRequest::get().uri("/courses/2/3/students");
+        let students: Value = test::read_body_json(test::call_service(&app, call(roster(), "bob")).await).await;
+        assert_eq!(students[0]["name"], "Dave Zhang");
+        assert_eq!(test::call_service(&app, call(roster(), "alice")).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, call(roster(), "dave")).await.status(), StatusCode::FORBIDDEN);
+
+        let courses = || test::TestRequest::get().uri("/students/1/courses");
+        let list: Value = test::read_body_json(test::call_service(&app, call(courses(), "dave")).await).await;
+        assert_eq!(list[0]["name"], "Actix web services");
+        assert_eq!(test::call_service(&app, call(courses(), "admin")).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, call(courses(), "alice")).await.status(), StatusCode::FORBIDDEN);
+
+        let withdraw = || test::TestRequest::delete().uri("/courses/2/3/enrollment");
+        assert_eq!(test::call_service(&app, call(withdraw(), "dave")).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, call(withdraw(), "dave")).await.status(), StatusCode::NOT_FOUND);
+        let list: Value = test::read_body_json(test::call_service(&app, call(courses(), "dave")).await).await;
+        assert_eq!(list, json!([]));
+    }
+}
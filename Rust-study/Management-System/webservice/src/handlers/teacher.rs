@@ -1,143 +1,276 @@
-use crate::models::teacher::{CreateTeacher, UpdateTeacher};
-use crate::dbaccess::teacher::*;
-use crate::errors::MyError;
+use crate::auth::AuthUser;
+use crate::models::page::Page;
+use crate::models::teacher::{CreateTeacher, Teacher, TeacherQuery, UpdateTeacher};
+use crate::error::{MyError, MyErrorResponse};
 use crate::state::AppState;
 
 use actix_web::{web, HttpResponse};
 
 //整体逻辑类似于课程
 
-//获得所有老师
-pub async fn get_all_teachers(app_state: web::Data<AppState>
+//获得所有老师 分页返回
+#[utoipa::path(
+    get,
+    path = "/teacher/",
+    tag = "teachers",
+    params(TeacherQuery),
+    responses(
+        (status = 200, description = "One page of teachers", body = Page<Teacher>),
+        (status = 400, description = "Invalid query parameters", body = MyErrorResponse)
+    )
+)]
+pub async fn get_all_teachers(app_state: web::Data<AppState>,
+    query: web::Query<TeacherQuery>,
 ) -> Result<HttpResponse, MyError> {
-    get_all_teacher_db(&app_state.db)
+    app_state.teachers.list_teachers(&query)
         .await
         .map(|teachers| HttpResponse::Ok().json(teachers))
 }
 
 //获得老师的细节信息
+#[utoipa::path(
+    get,
+    path = "/teacher/{teacher_id}",
+    tag = "teachers",
+    params(("teacher_id" = i32, Path, description = "Teacher id")),
+    responses(
+        (status = 200, description = "Teacher details", body = Teacher),
+        (status = 404, description = "Teacher not found", body = MyErrorResponse)
+    )
+)]
 pub async fn get_teacher_details(
     app_state: web::Data<AppState>,
     params: web::Path<i32>,
 ) -> Result<HttpResponse, MyError> {
     let teacher_id = params.into_inner();
-    get_teacher_details_db(&app_state.db, teacher_id)
+    app_state.teachers.get_teacher(teacher_id)
         .await
         .map(|teacher| HttpResponse::Ok().json(teacher))
 }
 
-//增加新老师
+//增加新老师 只有管理员可以直接创建老师 老师自己通过/auth/register注册
+#[utoipa::path(
+    post,
+    path = "/teacher/",
+    tag = "teachers",
+    request_body = CreateTeacher,
+    responses(
+        (status = 200, description = "Teacher created", body = Teacher),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 422, description = "Validation failed, see fields", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
 pub async fn post_new_teacher(
     app_state: web::Data<AppState>,
-    new_teacher: web::Json<CreateTeacher>
+    new_teacher: web::Json<CreateTeacher>,
+    user: AuthUser,
 ) -> Result<HttpResponse, MyError> {
-    post_new_teacher_db(&app_state.db,new_teacher.try_into()?)
+    user.require_admin()?;
+    app_state.teachers.create_teacher(new_teacher.try_into()?, user.user_id)
         .await
         .map(|teacher| HttpResponse::Ok().json(teacher))
 }
 
 //修改老师细节
+#[utoipa::path(
+    put,
+    path = "/teacher/{teacher_id}",
+    tag = "teachers",
+    params(("teacher_id" = i32, Path, description = "Teacher id")),
+    request_body = UpdateTeacher,
+    responses(
+        (status = 200, description = "Updated teacher", body = Teacher),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "Teacher not found", body = MyErrorResponse),
+        (status = 422, description = "Validation failed, see fields", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
 pub async fn update_teacher_details(
     app_state: web::Data<AppState>,
     teacher_id: web::Path<i32>,
-    update_teacher: web::Json<UpdateTeacher>
+    update_teacher: web::Json<UpdateTeacher>,
+    user: AuthUser,
 ) -> Result<HttpResponse, MyError> {
     let teacher_id = teacher_id.into_inner();
-    update_teacher_details_db(&app_state.db, teacher_id, update_teacher.try_into()?)
+    user.require_teacher(teacher_id)?;
+    app_state.teachers.update_teacher(teacher_id, update_teacher.try_into()?, user.user_id)
         .await
         .map(|teacher| HttpResponse::Ok().json(teacher))
 }
 
-//删除某个老师
+//删除某个老师 只标记deleted_at 老师的课程一起删除 账号不能再登录
+#[utoipa::path(
+    delete,
+    path = "/teacher/{teacher_id}",
+    tag = "teachers",
+    params(("teacher_id" = i32, Path, description = "Teacher id")),
+    responses(
+        (status = 200, description = "Teacher and courses soft deleted, the account is disabled", body = String),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "Teacher not found", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
 pub async fn delete_teacher(
     app_state: web::Data<AppState>,
     teacher_id: web::Path<i32>,
+    user: AuthUser,
 ) -> Result<HttpResponse, MyError> {
     let teacher_id = teacher_id.into_inner();
-    delete_teacher_db(&app_state.db, teacher_id)
+    user.require_teacher(teacher_id)?;
+    app_state.teachers.delete_teacher(teacher_id, user.user_id)
         .await
         .map(|msg| HttpResponse::Ok().json(msg))
 }
 
+//恢复已删除的老师 和老师一起删除的课程也恢复 只有管理员可以恢复
+#[utoipa::path(
+    post,
+    path = "/teacher/{teacher_id}/restore",
+    tag = "teachers",
+    params(("teacher_id" = i32, Path, description = "Teacher id")),
+    responses(
+        (status = 200, description = "Restored teacher", body = Teacher),
+        (status = 401, description = "Missing or invalid access token", body = MyErrorResponse),
+        (status = 403, description = "Not allowed for the caller's role", body = MyErrorResponse),
+        (status = 404, description = "No deleted teacher with this id", body = MyErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn restore_teacher(
+    app_state: web::Data<AppState>,
+    teacher_id: web::Path<i32>,
+    user: AuthUser,
+) -> Result<HttpResponse, MyError> {
+    user.require_admin()?;
+    app_state.teachers.restore_teacher(teacher_id.into_inner(), user.user_id)
+        .await
+        .map(|teacher| HttpResponse::Ok().json(teacher))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use crate::handlers::testing;
+    use crate::routers::app_config;
     use actix_web::http::StatusCode;
-    use dotenv::dotenv;
-    use sqlx::postgres::PgPoolOptions;
-    use std::{env, sync::Mutex};
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
 
     #[actix_rt::test]
-    async fn get_all_teacher_success_test() {
-        dotenv().ok();
-
-        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-        let db_pool = PgPoolOptions::new().connect(&db_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    async fn get_teachers_test() {
+        let app = test::init_service(App::new().app_data(testing::state()).configure(app_config)).await;
 
-        let resp = get_all_teachers(app_state).await.unwrap();
-        assert_eq!(resp.status(),StatusCode::OK)
-    }
+        let req = test::TestRequest::get().uri("/teacher/?limit=1").to_request();
+        let page: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(page["total"], 2);
+        assert_eq!(page["items"][0]["name"], "Alice Chen");
+        assert_eq!(page["next_cursor"], 1);
 
-    #[actix_rt::test]
-    async fn get_tutor_detail_success_test() {
-        dotenv().ok();
-
-        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-        let db_pool = PgPoolOptions::new().connect(&db_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+        let req = test::TestRequest::get().uri("/teacher/?cursor=1").to_request();
+        let page: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(page["items"][0]["name"], "Bob Li");
+        assert_eq!(page["next_cursor"], Value::Null);
 
-        let params: web::Path<i32> = web::Path::from(1);
-        let resp = get_teacher_details(app_state, params).await.unwrap();
-        assert_eq!(resp.status(),StatusCode::OK)
+        let req = test::TestRequest::get().uri("/teacher/2").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::get().uri("/teacher/99").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
     }
 
-    #[ignore]
     #[actix_rt::test]
-    async fn post_teacher_success_test() {
-        dotenv().ok();
-
-        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-        let db_pool = PgPoolOptions::new().connect(&db_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
+    async fn post_and_update_teacher_test() {
+        let state = testing::state();
+        let app = test::init_service(App::new().app_data(state.clone()).configure(app_config)).await;
+        let new_teacher = json!({
+            "name": "Third Teacher",
+            "picture_url": "https://example.com/third.png",
+            "profile": "A teacher in Machine Learning"
         });
 
-        let new_teacher = CreateTeacher {
-            name: "Third Teacher".into(),
-            picture_url: "http://yangxu.pro".into(),
-            profile: "A teacher in Machine Learning".into(),
-        };
-        let teacher_params = web::Json(new_teacher);
-        let resp = post_new_teacher(app_state,teacher_params).await.unwrap();
-        assert_eq!(resp.status(),StatusCode::OK)
+        //老师只能通过注册创建 直接创建需要管理员
+        let req = test::TestRequest::post()
+            .uri("/teacher/")
+            .insert_header(testing::bearer(&state, "alice"))
+            .set_json(&new_teacher)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::post()
+            .uri("/teacher/")
+            .insert_header(testing::bearer(&state, "admin"))
+            .set_json(&new_teacher)
+            .to_request();
+        let teacher: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(teacher["id"], 3);
+
+        let req = test::TestRequest::put()
+            .uri("/teacher/1")
+            .insert_header(testing::bearer(&state, "alice"))
+            .set_json(json!({"profile": "Rust and embedded"}))
+            .to_request();
+        let teacher: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(teacher["profile"], "Rust and embedded");
+        assert_eq!(teacher["name"], "Alice Chen");
+
+        let req = test::TestRequest::put()
+            .uri("/teacher/2")
+            .insert_header(testing::bearer(&state, "alice"))
+            .set_json(json!({"profile": "hacked"}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
     }
 
-    #[ignore]
     #[actix_rt::test]
-    async fn delete_teacher_success_test() {
-        dotenv().ok();
-
-        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
-        let db_pool = PgPoolOptions::new().connect(&db_url).await.unwrap();
-        let app_state: web::Data<AppState> = web::Data::new(AppState {
-            health_check_response: "".to_string(),
-            visit_count: Mutex::new(0),
-            db: db_pool,
-        });
+    async fn delete_teacher_test() {
+        let state = testing::state();
+        let app = test::init_service(App::new().app_data(state.clone()).configure(app_config)).await;
+
+        let req = test::TestRequest::delete()
+            .uri("/teacher/2")
+            .insert_header(testing::bearer(&state, "alice"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::delete()
+            .uri("/teacher/2")
+            .insert_header(testing::bearer(&state, "admin"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
 
-        let params: web::Path<i32> = web::Path::from(1);
-        let resp = delete_teacher(app_state, params).await.unwrap();
-        assert_eq!(resp.status(),StatusCode::OK)
+        //老师的课程一起删除 账号不能再登录
+        let req = test::TestRequest::get().uri("/teacher/2").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+        let req = test::TestRequest::get().uri("/courses/2").to_request();
+        let page: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(page["total"], 0);
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({"username": "bob", "password": "teacher-password"}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        //只有管理员可以恢复 课程一起恢复
+        let req = test::TestRequest::post()
+            .uri("/teacher/2/restore")
+            .insert_header(testing::bearer(&state, "alice"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        let req = test::TestRequest::post()
+            .uri("/teacher/2/restore")
+            .insert_header(testing::bearer(&state, "admin"))
+            .to_request();
+        let teacher: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(teacher["name"], "Bob Li");
+        let req = test::TestRequest::get().uri("/courses/2").to_request();
+        let page: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(page["total"], 1);
+        let req = test::TestRequest::post()
+            .uri("/teacher/2/restore")
+            .insert_header(testing::bearer(&state, "admin"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
     }
-}
\ No newline at end of file
+}
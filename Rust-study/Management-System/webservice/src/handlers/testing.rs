@@ -0,0 +1,89 @@
+//处理函数测试使用的内存存储 数据和seeds/dev.sql相同 不需要数据库
+use crate::auth::AuthConfig;
+use crate::models::course::Course;
+use crate::models::student::Student;
+use crate::models::teacher::Teacher;
+use crate::models::user::User;
+use crate::repository::MemoryStore;
+use crate::state::AppState;
+use actix_web::http::header;
+use actix_web::web;
+
+//所有账号的密码都是teacher-password
+const PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=4096,t=3,p=1$xX2B5zn3ChZ7bGN5EaO2dg$fWJ4kVEHexS8xQ9hzjy80FGmRxZUpqVCWH2RcaHnkRI";
+
+fn teacher(id: i32, name: &str, profile: &str) -> Teacher {
+    Teacher {
+        id,
+        name: name.into(),
+        picture_url: format!("https://example.com/{}.png", id),
+        profile: profile.into(),
+    }
+}
+
+fn course(id: i32, teacher_id: i32, name: &str, price: i32, language: &str, level: &str) -> Course {
+    Course {
+        teacher_id,
+        id,
+        name: name.into(),
+        time: None,
+        description: Some(format!("Learn {}", name)),
+        format: Some("video".into()),
+        structure: None,
+        duration: None,
+        price: Some(price),
+        language: Some(language.into()),
+        level: Some(level.into()),
+        capacity: None,
+    }
+}
+
+//admin是管理员 alice是老师1 bob是老师2 dave是学生1
+fn users() -> Vec<User> {
+    [
+        ("admin", "admin", None, None),
+        ("alice", "teacher", Some(1), None),
+        ("bob", "teacher", Some(2), None),
+        ("dave", "student", None, Some(1)),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (username, role, teacher_id, student_id))| User {
+        id: i as i32 + 1,
+        username: username.to_string(),
+        password_hash: PASSWORD_HASH.into(),
+        role: role.to_string(),
+        teacher_id: *teacher_id,
+        student_id: *student_id,
+    })
+    .collect()
+}
+
+pub fn state() -> web::Data<AppState> {
+    let teachers = vec![
+        teacher(1, "Alice Chen", "Rust and systems programming"),
+        teacher(2, "Bob Li", "Web development with actix"),
+    ];
+    let mut courses = vec![
+        course(1, 1, "Rust basics", 100, "English", "beginner"),
+        course(2, 1, "Async Rust", 300, "English", "advanced"),
+        course(3, 2, "Actix web services", 200, "Chinese", "intermediate"),
+    ];
+    //课程3只能选一个学生
+    courses[2].capacity = Some(1);
+    let students = vec![Student {
+        id: 1,
+        name: "Dave Zhang".into(),
+        email: "dave@example.com".into(),
+    }];
+    let store = MemoryStore::new(teachers, courses, users(), students);
+    web::Data::new(AppState::new(store, AuthConfig::new(b"test-secret")))
+}
+
+//账号的Authorization请求头
+pub fn bearer(state: &AppState, username: &str) -> (header::HeaderName, String) {
+    let user = users().into_iter().find(|user| user.username == username).unwrap();
+    let tokens = state.auth.issue(&user).unwrap();
+    (header::AUTHORIZATION, format!("Bearer {}", tokens.access_token))
+}
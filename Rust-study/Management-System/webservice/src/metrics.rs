@@ -0,0 +1,141 @@
+use crate::error::MyError;
+use crate::repository::PoolStatus;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::time::Duration;
+
+//Prometheus指标 由middleware::RequestMetrics记录 GET /metrics以文本格式导出
+//route用注册时的模式 例如/courses/{teacher_id} 不用实际路径 避免标签值无限增长
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    errors: IntCounterVec,
+    db_connections: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency in seconds"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let errors = IntCounterVec::new(
+            Opts::new("http_errors_total", "Number of error responses by MyError variant"),
+            &["variant"],
+        )
+        .unwrap();
+        let db_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(errors.clone())).unwrap();
+        registry.register(Box::new(db_connections.clone())).unwrap();
+        Metrics {
+            registry,
+            requests,
+            latency,
+            errors,
+            db_connections,
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, latency: Duration) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+        self.requests.with_label_values(&labels).inc();
+        self.latency.with_label_values(&labels).observe(latency.as_secs_f64());
+    }
+
+    pub fn observe_error(&self, err: &MyError) {
+        self.errors.with_label_values(&[err.variant()]).inc();
+    }
+
+    //连接池的数字在导出时读取 内存存储没有连接池 不导出这一项
+    pub fn render(&self, pool: &dyn PoolStatus) -> String {
+        if let Some(stats) = pool.pool_status() {
+            self.db_connections.with_label_values(&["in_use"]).set(stats.in_use as i64);
+            self.db_connections.with_label_values(&["idle"]).set(stats.idle as i64);
+        }
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::testing;
+    use crate::middleware::RequestMetrics;
+    use crate::repository::PoolStats;
+    use crate::routers::app_config;
+    use actix_web::http::StatusCode;
+
+    struct FixedPool;
+
+    impl PoolStatus for FixedPool {
+        fn pool_status(&self) -> Option<PoolStats> {
+            Some(PoolStats { in_use: 2, idle: 3 })
+        }
+    }
+
+    #[test]
+    fn render_test() {
+        let metrics = Metrics::new();
+        metrics.observe_request("GET", "/health", 200, Duration::from_millis(3));
+        metrics.observe_error(&MyError::CourseFull("Course is full".into()));
+
+        let text = metrics.render(&FixedPool);
+        assert!(text.contains(r#"http_requests_total{method="GET",route="/health",status="200"} 1"#));
+        assert!(text.contains(r#"http_request_duration_seconds_bucket{method="GET",route="/health",status="200",le="0.005"} 1"#));
+        assert!(text.contains(r#"http_errors_total{variant="CourseFull"} 1"#));
+        assert!(text.contains(r#"db_pool_connections{state="in_use"} 2"#));
+        assert!(text.contains(r#"db_pool_connections{state="idle"} 3"#));
+    }
+
+    #[actix_rt::test]
+    async fn metrics_endpoint_test() {
+        use actix_web::{test, App};
+
+        let app = test::init_service(
+            App::new()
+                .app_data(testing::state())
+                .configure(app_config)
+                .wrap(RequestMetrics),
+        )
+        .await;
+        for uri in ["/health", "/health", "/courses/1/99", "/no-such-route"] {
+            test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        }
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let text = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(text.contains(r#"http_requests_total{method="GET",route="/health",status="200"} 2"#));
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/courses/{teacher_id}/{course_id}",status="404"} 1"#
+        ));
+        assert!(text.contains(r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#));
+        assert!(text.contains(r#"http_errors_total{variant="NotFound"} 1"#));
+        //内存存储没有连接池
+        assert!(!text.contains("db_pool_connections"));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::error::MyError;
+use crate::state::AppState;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{web, Error};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::time::Instant;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+//每个请求一个tracing span 记录请求ID 方法 路由 状态码和耗时
+//请求ID优先使用客户端或网关传来的x-request-id 没有时生成一个 并写回响应头
+//MyError的错误响应换成带request_id的body 状态码和内层中间件设置的响应头(例如CORS)保持不变
+pub struct RequestTracing;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware { service }))
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = incoming_request_id(&req).unwrap_or_else(|| Uuid::new_v4().to_string());
+        //路由用注册时的模式 例如/courses/{teacher_id} 没有匹配的路由时为空
+        let span = tracing::info_span!(
+            "request",
+            request_id = %request_id,
+            method = %req.method(),
+            route = req.match_pattern().unwrap_or_default().as_str(),
+            path = req.path(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        let started = Instant::now();
+        let fut = span.in_scope(|| self.service.call(req));
+
+        Box::pin(
+            async move {
+                let res = fut.await?;
+                let status = res.status();
+                let span = tracing::Span::current();
+                span.record("status", status.as_u16());
+                span.record("latency_ms", started.elapsed().as_secs_f64() * 1000.0);
+                if status.is_server_error() {
+                    tracing::error!("request finished");
+                } else {
+                    tracing::info!("request finished");
+                }
+
+                let body = res
+                    .response()
+                    .error()
+                    .and_then(|err| err.as_error::<MyError>())
+                    .map(|err| err.to_response(Some(&request_id)).into_body());
+                let mut res = match body {
+                    Some(body) => res.map_body(|_, _| EitherBody::right(body)),
+                    None => res.map_into_left_body(),
+                };
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                }
+                Ok(res)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+//记录每个请求的次数和耗时 以及MyError错误响应的次数 指标保存在AppState.metrics里
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let app_state = req.app_data::<web::Data<AppState>>().cloned();
+        let method = req.method().to_string();
+        //没有匹配的路由统一记为unmatched
+        let route = req.match_pattern().unwrap_or_else(|| "unmatched".into());
+        let started = Instant::now();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            if let Some(app_state) = app_state {
+                let metrics = &app_state.metrics;
+                metrics.observe_request(&method, &route, res.status().as_u16(), started.elapsed());
+                if let Some(err) = res.response().error().and_then(|err| err.as_error::<MyError>()) {
+                    metrics.observe_error(err);
+                }
+            }
+            Ok(res)
+        })
+    }
+}
+
+//只接受较短的 由字母数字和-_.组成的请求ID 避免日志里出现任意内容
+fn incoming_request_id(req: &ServiceRequest) -> Option<String> {
+    let value = req.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?;
+    let valid = !value.is_empty()
+        && value.len() <= 64
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid.then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::testing;
+    use crate::routers::app_config;
+    use actix_web::http::StatusCode;
+    use serde_json::Value;
+
+    #[actix_rt::test]
+    async fn request_id_test() {
+        use actix_web::{test, App};
+
+        let app = test::init_service(
+            App::new()
+                .app_data(testing::state())
+                .configure(app_config)
+                .wrap(RequestTracing),
+        )
+        .await;
+
+        //没有传请求ID时生成一个
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let generated = resp.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+        assert!(Uuid::parse_str(generated).is_ok());
+
+        //错误响应的body里也有请求ID
+        let req = test::TestRequest::get()
+            .uri("/courses/1/99")
+            .insert_header((REQUEST_ID_HEADER, "req-42"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "req-42");
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["request_id"], "req-42");
+
+        //不合法的请求ID会被替换
+        let req = test::TestRequest::get()
+            .uri("/health")
+            .insert_header((REQUEST_ID_HEADER, "bad id\twith spaces"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_ne!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "bad id\twith spaces");
+    }
+
+    //和teacher-service一样的中间件顺序 错误响应也要带上CORS头部 否则浏览器读不到错误信息
+    #[actix_rt::test]
+    async fn error_response_keeps_cors_headers_test() {
+        use actix_cors::Cors;
+        use actix_web::http::header;
+        use actix_web::{test, App};
+
+        let app = test::init_service(
+            App::new()
+                .app_data(testing::state())
+                .wrap(Cors::default().allowed_origin("http://localhost:8080"))
+                .wrap(RequestMetrics)
+                .wrap(RequestTracing)
+                .configure(app_config),
+        )
+        .await;
+
+        for (uri, status) in [("/health", StatusCode::OK), ("/courses/1/99", StatusCode::NOT_FOUND)] {
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::ORIGIN, "http://localhost:8080"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status, "{}", uri);
+            assert_eq!(
+                resp.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+                "http://localhost:8080",
+                "{}",
+                uri
+            );
+            assert!(resp.headers().contains_key(REQUEST_ID_HEADER));
+        }
+        let req = test::TestRequest::get()
+            .uri("/courses/1/99")
+            .insert_header((header::ORIGIN, "http://localhost:8080"))
+            .insert_header((REQUEST_ID_HEADER, "req-7"))
+            .to_request();
+        let body: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(body["request_id"], "req-7");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::models::course::Course;
+use crate::models::teacher::Teacher;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use utoipa::ToSchema;
+
+//记录修改历史的数据类型 路径参数里使用小写 例如/audit/course/1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityType {
+    Teacher,
+    Course,
+}
+
+impl EntityType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntityType::Teacher => "teacher",
+            EntityType::Course => "course",
+        }
+    }
+}
+
+impl fmt::Display for EntityType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+        }
+    }
+}
+
+//audit_log表的一行 和course一样 数据库里的类型和动作是字符串
+#[derive(Debug, Serialize, Clone, sqlx::FromRow, ToSchema)]
+pub struct AuditEntry {
+    pub id: i32,
+    #[schema(example = "course")]
+    pub entity_type: String,
+    pub entity_id: i32,
+    #[schema(example = "update")]
+    pub action: String,
+    //操作者的账号id
+    pub actor_id: Option<i32>,
+    //修改前后的数据 创建和恢复时old_value为空 删除时new_value为空
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
+
+//要写入audit_log的一条记录 和修改在同一个事务里写入
+pub struct AuditRecord {
+    pub entity_type: EntityType,
+    pub entity_id: i32,
+    pub action: AuditAction,
+    pub actor_id: i32,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+impl AuditRecord {
+    pub fn teacher(action: AuditAction, actor_id: i32, old: Option<&Teacher>, new: Option<&Teacher>) -> Self {
+        let entity_id = old.or(new).map_or(0, |teacher| teacher.id);
+        AuditRecord::new(EntityType::Teacher, entity_id, action, actor_id, old, new)
+    }
+
+    pub fn course(action: AuditAction, actor_id: i32, old: Option<&Course>, new: Option<&Course>) -> Self {
+        let entity_id = old.or(new).map_or(0, |course| course.id);
+        AuditRecord::new(EntityType::Course, entity_id, action, actor_id, old, new)
+    }
+
+    fn new<T: Serialize>(
+        entity_type: EntityType,
+        entity_id: i32,
+        action: AuditAction,
+        actor_id: i32,
+        old: Option<&T>,
+        new: Option<&T>,
+    ) -> Self {
+        let to_json = |value: Option<&T>| value.and_then(|value| serde_json::to_value(value).ok());
+        AuditRecord {
+            entity_type,
+            entity_id,
+            action,
+            actor_id,
+            old_value: to_json(old),
+            new_value: to_json(new),
+        }
+    }
+}
@@ -1,11 +1,66 @@
 use crate::error::MyError;
+use crate::models::page::page_size;
 
-use actix_web::web::{self, Json};
+use actix_web::web;
 use chrono::NaiveDateTime;
 use serde::{Serialize, Deserialize};
 use std::convert::TryFrom;
+use std::fmt;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
-#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow)]
+//课程难度 数据库里保存小写字符串
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CourseLevel {
+    Beginner,
+    Intermediate,
+    Advanced,
+}
+
+//授课方式 数据库里保存小写字符串
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CourseFormat {
+    Video,
+    Live,
+    Text,
+}
+
+impl CourseLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CourseLevel::Beginner => "beginner",
+            CourseLevel::Intermediate => "intermediate",
+            CourseLevel::Advanced => "advanced",
+        }
+    }
+}
+
+impl CourseFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CourseFormat::Video => "video",
+            CourseFormat::Live => "live",
+            CourseFormat::Text => "text",
+        }
+    }
+}
+
+impl fmt::Display for CourseLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for CourseFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+//数据库里的课程 format和level按字符串读取 加校验之前写入的旧数据也能正常返回
+#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow, ToSchema)]
 pub struct Course{
     pub teacher_id: i32,
     pub id: i32,
@@ -19,33 +74,116 @@ pub struct Course{
     pub price: Option<i32>,
     pub language: Option<String>,
     pub level: Option<String>,
+    //最多可以选课的学生人数 为空表示不限
+    pub capacity: Option<i32>,
 }
 
-//新增课程
-#[derive(Debug, Deserialize, Clone)]
+//新增课程 字段长度和数据库的列一致
+#[derive(Debug, Deserialize, Clone, Validate, ToSchema)]
 pub struct CreateCourse {
     pub teacher_id: i32,
+    #[validate(
+        length(max = 140, message = "name must be at most 140 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "name must not be empty")
+    )]
     pub name: String,
+    #[validate(length(max = 2000, message = "description must be at most 2000 characters"))]
     pub description: Option<String>,
-    pub format: Option<String>,
+    pub format: Option<CourseFormat>,
+    #[validate(length(max = 200, message = "structure must be at most 200 characters"))]
     pub structure: Option<String>,
+    #[validate(length(max = 30, message = "duration must be at most 30 characters"))]
     pub duration: Option<String>,
+    #[validate(range(min = 0, message = "price must not be negative"))]
     pub price: Option<i32>,
+    #[validate(
+        length(max = 30, message = "language must be at most 30 characters"),
+        custom(function = "crate::models::rules::language_name", message = "language must contain only letters and spaces")
+    )]
     pub language: Option<String>,
-    pub level: Option<String>,
+    pub level: Option<CourseLevel>,
+    #[validate(range(min = 0, message = "capacity must not be negative"))]
+    pub capacity: Option<i32>,
 }
 
-//修改课程
-#[derive(Debug, Deserialize, Clone)]
+//修改课程 只校验提供了的字段
+#[derive(Debug, Deserialize, Clone, Validate, ToSchema)]
 pub struct UpdateCourse {
+    #[validate(
+        length(max = 140, message = "name must be at most 140 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "name must not be empty")
+    )]
     pub name: Option<String>,
+    #[validate(length(max = 2000, message = "description must be at most 2000 characters"))]
     pub description: Option<String>,
-    pub format: Option<String>,
+    pub format: Option<CourseFormat>,
+    #[validate(length(max = 200, message = "structure must be at most 200 characters"))]
     pub structure: Option<String>,
+    #[validate(length(max = 30, message = "duration must be at most 30 characters"))]
     pub duration: Option<String>,
+    #[validate(range(min = 0, message = "price must not be negative"))]
     pub price: Option<i32>,
+    #[validate(
+        length(max = 30, message = "language must be at most 30 characters"),
+        custom(function = "crate::models::rules::language_name", message = "language must contain only letters and spaces")
+    )]
     pub language: Option<String>,
-    pub level: Option<String>,
+    pub level: Option<CourseLevel>,
+    //容量改小不影响已经选课的学生 只是不能再选
+    #[validate(range(min = 0, message = "capacity must not be negative"))]
+    pub capacity: Option<i32>,
+}
+
+//课程列表的排序方式 -表示倒序 默认按id即创建顺序
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default, ToSchema)]
+pub enum CourseSort {
+    #[default]
+    #[serde(rename = "id")]
+    IdAsc,
+    #[serde(rename = "-id")]
+    IdDesc,
+    #[serde(rename = "price")]
+    PriceAsc,
+    #[serde(rename = "-price")]
+    PriceDesc,
+    #[serde(rename = "name")]
+    NameAsc,
+    #[serde(rename = "-name")]
+    NameDesc,
+}
+
+//课程列表的查询参数 例如 /courses/1?language=English&min_price=100&q=rust&sort=-price&limit=10
+//cursor是上一页返回的next_cursor q在名称和描述里搜索 不区分大小写
+#[derive(Debug, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CourseQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<i32>,
+    pub language: Option<String>,
+    pub level: Option<CourseLevel>,
+    pub format: Option<CourseFormat>,
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: CourseSort,
+}
+
+impl CourseQuery {
+    //返回本页的记录数
+    pub fn validate(&self) -> Result<i64, MyError> {
+        if let (Some(min), Some(max)) = (self.min_price, self.max_price) {
+            if min > max {
+                return Err(MyError::InvalidInput("min_price must not be greater than max_price".into()));
+            }
+        }
+        page_size(self.limit)
+    }
+
+    //去掉首尾空白后的搜索词 空字符串表示不搜索
+    pub fn search(&self) -> Option<&str> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
+    }
 }
 
 // //将请求json转化为createcourse类型数据
@@ -65,37 +203,89 @@ pub struct UpdateCourse {
 //     }
 // }
 
-//另一种方式将请求json转化为createcourse类型数据
+//另一种方式将请求json转化为createcourse类型数据 没有通过校验时返回每个字段的错误信息
 impl TryFrom<web::Json<CreateCourse>> for CreateCourse {
     type Error = MyError;
 
     fn try_from(course: web::Json<CreateCourse>) -> Result<Self,Self::Error> {
-        Ok(CreateCourse{
-            teacher_id: course.teacher_id,
-            name: course.name.clone(),
-            description: course.description.clone(),
-            format: course.format.clone(),
-            structure: course.structure.clone(),
-            duration: course.duration.clone(),
-            price: course.price,
-            language: course.language.clone(),
-            level: course.level.clone(),
-        })
+        course.validate()?;
+        Ok(course.into_inner())
+    }
+}
+
+impl TryFrom<web::Json<UpdateCourse>> for UpdateCourse {
+    type Error = MyError;
+
+    fn try_from(course: web::Json<UpdateCourse>) -> Result<Self, Self::Error> {
+        course.validate()?;
+        Ok(course.into_inner())
     }
 }
 
-impl From<web::Json<UpdateCourse>> for UpdateCourse {
-
-    fn from(course: web::Json<UpdateCourse>) -> Result<Self, Self::Error> {
-        Ok(UpdateCourse{
-            name: course.name.clone(),
-            description: course.description.clone(),
-            format: course.format.clone(),
-            structure: course.structure.clone(),
-            duration: course.duration.clone(),
-            price: course.price,
-            language: course.language.clone(),
-            level: course.level.clone(),
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn course_query_test() {
+        let query = web::Query::<CourseQuery>::from_query(
+            "language=English&min_price=10&max_price=50&q=rust&sort=-price&limit=5&cursor=3",
+        )
+        .unwrap();
+        assert_eq!(query.language.as_deref(), Some("English"));
+        assert_eq!(query.sort, CourseSort::PriceDesc);
+        assert_eq!(query.cursor, Some(3));
+        assert_eq!(query.validate().unwrap(), 5);
+
+        let query = web::Query::<CourseQuery>::from_query("").unwrap();
+        assert_eq!(query.sort, CourseSort::IdAsc);
+
+        assert!(web::Query::<CourseQuery>::from_query("sort=cheapest").is_err());
+        let query = web::Query::<CourseQuery>::from_query("min_price=50&max_price=10").unwrap();
+        assert!(query.validate().is_err());
+
+        let query = web::Query::<CourseQuery>::from_query("level=advanced&format=live").unwrap();
+        assert_eq!(query.level, Some(CourseLevel::Advanced));
+        assert_eq!(query.format, Some(CourseFormat::Live));
+        assert!(web::Query::<CourseQuery>::from_query("level=expert").is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn create_course_validation_test() {
+        let course: CreateCourse = serde_json::from_value(serde_json::json!({
+            "teacher_id": 1,
+            "name": "Rust basics",
+            "price": 100,
+            "language": "English",
+            "level": "beginner",
+            "format": "video",
+            "capacity": 30
+        }))
+        .unwrap();
+        assert!(CreateCourse::try_from(web::Json(course.clone())).is_ok());
+
+        let invalid = CreateCourse {
+            name: "  ".into(),
+            price: Some(-1),
+            language: Some("en-US".into()),
+            capacity: Some(-5),
+            ..course
+        };
+        match CreateCourse::try_from(web::Json(invalid)) {
+            Err(MyError::InvalidInput(err)) => {
+                assert_eq!(err.fields["name"], ["name must not be empty"]);
+                assert_eq!(err.fields["price"], ["price must not be negative"]);
+                assert_eq!(err.fields["language"], ["language must contain only letters and spaces"]);
+                assert_eq!(err.fields["capacity"], ["capacity must not be negative"]);
+                assert_eq!(err.fields.len(), 4);
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        //级别和方式只能是枚举里的值
+        assert!(serde_json::from_value::<CreateCourse>(serde_json::json!({
+            "teacher_id": 1, "name": "Rust", "level": "expert"
+        }))
+        .is_err());
+    }
+}
@@ -1,2 +1,7 @@
+pub mod audit;
 pub mod course;
-pub mod teacher;
\ No newline at end of file
+pub mod page;
+pub mod rules;
+pub mod student;
+pub mod teacher;
+pub mod user;
\ No newline at end of file
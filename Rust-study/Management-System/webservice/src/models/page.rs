@@ -0,0 +1,84 @@
+use crate::error::MyError;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+//列表接口的返回格式 total是符合条件的记录总数
+//next_cursor是本页最后一条记录的id 作为cursor参数获取下一页 为空表示没有下一页
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub next_cursor: Option<i32>,
+}
+
+impl<T> Page<T> {
+    //rows比limit多查一条 用来判断是否还有下一页
+    pub fn new(mut rows: Vec<T>, limit: i64, total: i64, id: impl Fn(&T) -> i32) -> Self {
+        let next_cursor = if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(id)
+        } else {
+            None
+        };
+        Page {
+            items: rows,
+            total,
+            next_cursor,
+        }
+    }
+}
+
+//没有指定limit时使用默认值 超过上限时按上限返回
+pub fn page_size(limit: Option<i64>) -> Result<i64, MyError> {
+    match limit {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(limit) if limit < 1 => Err(MyError::InvalidInput("limit must be at least 1".into())),
+        Some(limit) => Ok(limit.min(MAX_PAGE_SIZE)),
+    }
+}
+
+//LIKE/ILIKE的模式里 % _ \ 有特殊含义 搜索词里的这些字符需要转义
+pub fn like_pattern(text: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_test() {
+        let page = Page::new(vec![1, 2, 3], 2, 10, |n| *n);
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.total, 10);
+        assert_eq!(page.next_cursor, Some(2));
+
+        let page = Page::new(vec![1, 2], 2, 2, |n| *n);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn page_size_test() {
+        assert_eq!(page_size(None).unwrap(), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(5)).unwrap(), 5);
+        assert_eq!(page_size(Some(1000)).unwrap(), MAX_PAGE_SIZE);
+        assert!(page_size(Some(0)).is_err());
+    }
+
+    #[test]
+    fn like_pattern_test() {
+        assert_eq!(like_pattern("rust"), "%rust%");
+        assert_eq!(like_pattern("100%_a\\b"), "%100\\%\\_a\\\\b%");
+    }
+}
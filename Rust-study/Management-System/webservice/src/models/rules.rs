@@ -0,0 +1,58 @@
+use validator::ValidationError;
+
+//validator没有提供的校验规则 在模型上用#[validate(custom(function = "..."))]引用
+//错误信息写在模型的属性里 这里只返回规则名
+
+//不能为空或只有空白
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("not_blank"));
+    }
+    Ok(())
+}
+
+//语言名称只能包含字母和空格 例如English、Chinese、Brazilian Portuguese
+pub fn language_name(value: &str) -> Result<(), ValidationError> {
+    let value = value.trim();
+    if value.is_empty() || !value.chars().all(|c| c.is_alphabetic() || c == ' ') {
+        return Err(ValidationError::new("language"));
+    }
+    Ok(())
+}
+
+//只接受http和https的图片地址 validator的url规则也会接受ftp:和mailto:这样的地址
+pub fn http_url(value: &str) -> Result<(), ValidationError> {
+    let lower = value.to_ascii_lowercase();
+    let rest = lower
+        .strip_prefix("https://")
+        .or_else(|| lower.strip_prefix("http://"))
+        .ok_or_else(|| ValidationError::new("http_url"))?;
+    if rest.is_empty() || rest.starts_with('/') || value.chars().any(char::is_whitespace) {
+        return Err(ValidationError::new("http_url"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_test() {
+        assert!(not_blank("Rust").is_ok());
+        assert!(not_blank(" \t").is_err());
+
+        assert!(language_name("English").is_ok());
+        assert!(language_name("Brazilian Portuguese").is_ok());
+        assert!(language_name("中文").is_ok());
+        assert!(language_name("en-US").is_err());
+        assert!(language_name("<script>").is_err());
+
+        assert!(http_url("https://example.com/a.png").is_ok());
+        assert!(http_url("HTTP://example.com").is_ok());
+        assert!(http_url("ftp://example.com/a.png").is_err());
+        assert!(http_url("https://").is_err());
+        assert!(http_url("https://exa mple.com").is_err());
+        assert!(http_url("javascript:alert(1)").is_err());
+    }
+}
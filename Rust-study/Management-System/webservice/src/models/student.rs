@@ -0,0 +1,19 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+//学生 通过student角色的账号登录后选课
+#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow, ToSchema)]
+pub struct Student {
+    pub id: i32, //serial 主键
+    pub name: String,
+    pub email: String,
+}
+
+//选课记录 同一个学生在一门课程里只能有一条
+#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow, ToSchema)]
+pub struct Enrollment {
+    pub course_id: i32,
+    pub student_id: i32,
+    pub enrolled_at: NaiveDateTime,
+}
@@ -1,9 +1,11 @@
 use actix_web::web;
-use actix_web::web::Json;
 use serde::{Serialize, Deserialize};
-use crate::errors::MyError;
+use crate::error::MyError;
+use std::convert::TryFrom;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow, ToSchema)]
 pub struct Teacher{
     pub id: i32, //serial 主键
     pub name: String,
@@ -11,42 +13,63 @@ pub struct Teacher{
     pub profile:String,
 }
 
-//新增老师
-#[derive(Debug, Deserialize, Clone)]
+//老师列表的查询参数 按id排序 cursor是上一页返回的next_cursor
+#[derive(Debug, Deserialize, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TeacherQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<i32>,
+}
+
+//新增老师 字段长度和数据库的列一致
+#[derive(Debug, Deserialize, Clone, Validate, ToSchema)]
 pub struct CreateTeacher {
+    #[validate(
+        length(max = 100, message = "name must be at most 100 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "name must not be empty")
+    )]
     pub name: String,
+    #[validate(
+        length(max = 200, message = "picture_url must be at most 200 characters"),
+        custom(function = "crate::models::rules::http_url", message = "picture_url must be an http or https URL")
+    )]
     pub picture_url: String,
+    #[validate(length(max = 2000, message = "profile must be at most 2000 characters"))]
     pub profile:String,
 }
 
-//修改老师信息
-#[derive(Debug, Deserialize, Clone)]
+//修改老师信息 只校验提供了的字段
+#[derive(Debug, Deserialize, Clone, Validate, ToSchema)]
 pub struct UpdateTeacher {
+    #[validate(
+        length(max = 100, message = "name must be at most 100 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "name must not be empty")
+    )]
     pub name: Option<String>,
+    #[validate(
+        length(max = 200, message = "picture_url must be at most 200 characters"),
+        custom(function = "crate::models::rules::http_url", message = "picture_url must be an http or https URL")
+    )]
     pub picture_url: Option<String>,
+    #[validate(length(max = 2000, message = "profile must be at most 2000 characters"))]
     pub profile:Option<String>,
 }
 
-impl From<web::Json<CreateTeacher>> for CreateTeacher {
+//没有通过校验时返回每个字段的错误信息
+impl TryFrom<web::Json<CreateTeacher>> for CreateTeacher {
     type Error = MyError;
 
-    fn from(new_teacher: web::Json<CreateTeacher>) -> Result<Self, Self::Error> {
-        Ok(CreateTeacher{
-            name: new_teacher.name.clone(),
-            picture_url: new_teacher.picture_url.clone(),
-            profile: new_teacher.profile.clone(),
-        })
+    fn try_from(new_teacher: web::Json<CreateTeacher>) -> Result<Self, Self::Error> {
+        new_teacher.validate()?;
+        Ok(new_teacher.into_inner())
     }
 }
 
-impl From<web::Json<UpdateTeacher>> for UpdateTeacher {
+impl TryFrom<web::Json<UpdateTeacher>> for UpdateTeacher {
     type Error = MyError;
 
-    fn from(update_teacher: web::Json<UpdateTeacher>) -> Result<Self, Self::Error> {
-        Ok(UpdateTeacher{
-            name: update_teacher.name.clone(),
-            picture_url: update_teacher.picture_url.clone(),
-            profile: update_teacher.profile.clone(),
-        })
+    fn try_from(update_teacher: web::Json<UpdateTeacher>) -> Result<Self, Self::Error> {
+        update_teacher.validate()?;
+        Ok(update_teacher.into_inner())
     }
 }
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+//用户角色 teacher只能管理自己的资料和课程 admin可以管理所有老师和课程 student可以选课和退课
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Teacher,
+    Admin,
+    Student,
+}
+
+impl Role {
+    //数据库users表的role列保存小写字符串
+    pub fn parse(role: &str) -> Option<Role> {
+        match role {
+            "teacher" => Some(Role::Teacher),
+            "admin" => Some(Role::Admin),
+            "student" => Some(Role::Student),
+            _ => None,
+        }
+    }
+}
+
+//登录账号 password_hash是argon2生成的PHC格式字符串 不会返回给客户端
+//teacher角色的账号通过teacher_id关联到自己的老师资料 student角色的账号通过student_id关联到学生
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct User {
+    pub id: i32, //serial 主键
+    pub username: String,
+    pub password_hash: String,
+    pub role: String,
+    pub teacher_id: Option<i32>,
+    pub student_id: Option<i32>,
+}
+
+//注册老师账号 同时创建老师资料 老师资料的规则和CreateTeacher相同
+#[derive(Debug, Deserialize, Clone, Validate, ToSchema)]
+pub struct RegisterTeacher {
+    #[validate(
+        length(max = 50, message = "username must be at most 50 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "username must not be empty")
+    )]
+    pub username: String,
+    #[validate(length(min = 8, message = "password must be at least 8 characters"))]
+    pub password: String,
+    #[validate(
+        length(max = 100, message = "name must be at most 100 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "name must not be empty")
+    )]
+    pub name: String,
+    #[validate(
+        length(max = 200, message = "picture_url must be at most 200 characters"),
+        custom(function = "crate::models::rules::http_url", message = "picture_url must be an http or https URL")
+    )]
+    pub picture_url: String,
+    #[validate(length(max = 2000, message = "profile must be at most 2000 characters"))]
+    pub profile: String,
+}
+
+//注册学生账号 同时创建学生
+#[derive(Debug, Deserialize, Clone, Validate, ToSchema)]
+pub struct RegisterStudent {
+    #[validate(
+        length(max = 50, message = "username must be at most 50 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "username must not be empty")
+    )]
+    pub username: String,
+    #[validate(length(min = 8, message = "password must be at least 8 characters"))]
+    pub password: String,
+    #[validate(
+        length(max = 100, message = "name must be at most 100 characters"),
+        custom(function = "crate::models::rules::not_blank", message = "name must not be empty")
+    )]
+    pub name: String,
+    #[validate(
+        length(max = 200, message = "email must be at most 200 characters"),
+        email(message = "email is not a valid address")
+    )]
+    pub email: String,
+}
+
+//登录
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+//用refresh token换一对新的token
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+//登录、注册、刷新成功后返回 expires_in是access token的有效秒数
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
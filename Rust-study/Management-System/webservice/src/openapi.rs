@@ -0,0 +1,144 @@
+use crate::error::MyErrorResponse;
+use crate::models::audit::{AuditEntry, EntityType};
+use crate::handlers::{audit, auth, course, general, student, teacher};
+use crate::models::course::{Course, CourseFormat, CourseLevel, CourseSort, CreateCourse, UpdateCourse};
+use crate::models::student::{Enrollment, Student};
+use crate::models::teacher::{CreateTeacher, Teacher, UpdateTeacher};
+use crate::models::user::{LoginRequest, RefreshRequest, RegisterStudent, RegisterTeacher, TokenResponse};
+use actix_web::HttpResponse;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+//OpenAPI 3文档 由处理函数上的#[utoipa::path]和模型上的ToSchema生成
+//新增路由时在处理函数上加#[utoipa::path] 再加到下面的paths里 tests里会检查路由表里的路由都有文档
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Teacher service API"),
+    paths(
+        general::health_check_handler,
+        general::metrics_handler,
+        auth::register,
+        auth::register_student,
+        auth::login,
+        auth::refresh,
+        course::get_all_courses,
+        course::post_new_course,
+        course::get_courses_for_teacher,
+        course::get_course_detail,
+        course::delete_course,
+        course::update_course_details,
+        course::restore_course,
+        student::enroll,
+        student::withdraw,
+        student::get_course_roster,
+        student::get_student_courses,
+        teacher::get_all_teachers,
+        teacher::post_new_teacher,
+        teacher::get_teacher_details,
+        teacher::update_teacher_details,
+        teacher::delete_teacher,
+        teacher::restore_teacher,
+        audit::get_audit_log,
+    ),
+    components(schemas(
+        Course, CourseFormat, CourseLevel, CourseSort, CreateCourse, UpdateCourse,
+        Teacher, CreateTeacher, UpdateTeacher,
+        Student, Enrollment,
+        AuditEntry, EntityType,
+        RegisterTeacher, RegisterStudent, LoginRequest, RefreshRequest, TokenResponse,
+        MyErrorResponse,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "general", description = "Health check and metrics"),
+        (name = "auth", description = "Registration, login and token refresh"),
+        (name = "courses", description = "Courses, filtered and paginated"),
+        (name = "teachers", description = "Teacher profiles"),
+        (name = "students", description = "Enrollment and rosters"),
+        (name = "audit", description = "Change history, admin only"),
+    )
+)]
+pub struct ApiDoc;
+
+//需要登录的接口使用 Authorization: Bearer <access_token>
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+            );
+        }
+    }
+}
+
+//GET /openapi.json
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::testing;
+    use crate::routers::{app_config, route_table};
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    //文档本身的路由不需要写进文档
+    const UNDOCUMENTED: &[&str] = &["GET /openapi.json"];
+
+    //app_config注册的路由 和文档里的格式一样 例如 GET /courses/{teacher_id}
+    fn registered_routes() -> BTreeSet<String> {
+        route_table().iter().flat_map(|group| group.paths()).collect()
+    }
+
+    fn documented_routes() -> BTreeSet<String> {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut routes = BTreeSet::new();
+        for (path, operations) in doc["paths"].as_object().unwrap() {
+            for method in operations.as_object().unwrap().keys() {
+                routes.insert(format!("{} {}", method.to_uppercase(), path));
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented_test() {
+        let registered = registered_routes();
+        assert!(registered.contains("GET /courses/{teacher_id}/{course_id}"));
+        assert!(registered.contains("GET /health"));
+
+        let documented = documented_routes();
+        let missing: Vec<_> = registered
+            .iter()
+            .filter(|route| !documented.contains(*route) && !UNDOCUMENTED.contains(&route.as_str()))
+            .collect();
+        assert!(missing.is_empty(), "routes without #[utoipa::path] in ApiDoc: {:?}", missing);
+        let stale: Vec<_> = documented.difference(&registered).collect();
+        assert!(stale.is_empty(), "documented routes that are not registered: {:?}", stale);
+    }
+
+    #[actix_rt::test]
+    async fn openapi_json_test() {
+        use actix_web::{test, App};
+
+        let app = test::init_service(App::new().app_data(testing::state()).configure(app_config)).await;
+        let req = test::TestRequest::get().uri("/openapi.json").to_request();
+        let doc: Value = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(doc["info"]["title"], "Teacher service API");
+        assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+
+        let post_course = &doc["paths"]["/courses/"]["post"];
+        assert_eq!(post_course["security"][0]["bearer"], serde_json::json!([]));
+        assert_eq!(
+            post_course["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/CreateCourse"
+        );
+        let level = &doc["components"]["schemas"]["CourseLevel"]["enum"];
+        assert_eq!(level, &serde_json::json!(["beginner", "intermediate", "advanced"]));
+    }
+}
@@ -0,0 +1,496 @@
+use super::{AuditRepository, CourseRepository, EnrollmentRepository, PoolStats, PoolStatus, TeacherRepository, UserRepository};
+use crate::error::MyError;
+use crate::models::audit::{AuditAction, AuditEntry, AuditRecord, EntityType};
+use crate::models::course::{Course, CourseQuery, CourseSort, CreateCourse, UpdateCourse};
+use crate::models::page::{page_size, Page};
+use crate::models::student::{Enrollment, Student};
+use crate::models::teacher::{CreateTeacher, Teacher, TeacherQuery, UpdateTeacher};
+use crate::models::user::{RegisterStudent, RegisterTeacher, User};
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use std::cmp::Ordering;
+use std::sync::Mutex;
+
+//内存存储 最早的版本就是用Mutex<Vec<Course>>保存课程 现在用于测试和本地演示 重启后数据丢失
+//新记录的id是当前最大id加1 已删除的记录放在deleted_*里 带着删除时间 恢复时移回来
+//需要同时锁多个列表时按字段顺序加锁
+#[derive(Default)]
+pub struct MemoryStore {
+    teachers: Mutex<Vec<Teacher>>,
+    deleted_teachers: Mutex<Vec<(Teacher, NaiveDateTime)>>,
+    courses: Mutex<Vec<Course>>,
+    deleted_courses: Mutex<Vec<(Course, NaiveDateTime)>>,
+    users: Mutex<Vec<User>>,
+    students: Mutex<Vec<Student>>,
+    enrollments: Mutex<Vec<Enrollment>>,
+    audit: Mutex<Vec<AuditEntry>>,
+}
+
+impl MemoryStore {
+    //使用给定的初始数据 没有选课记录
+    #[cfg(test)]
+    pub fn new(teachers: Vec<Teacher>, courses: Vec<Course>, users: Vec<User>, students: Vec<Student>) -> Self {
+        MemoryStore {
+            teachers: Mutex::new(teachers),
+            courses: Mutex::new(courses),
+            users: Mutex::new(users),
+            students: Mutex::new(students),
+            ..Default::default()
+        }
+    }
+
+    fn record_audit(&self, record: AuditRecord) {
+        let mut audit = self.audit.lock().unwrap();
+        let entry = AuditEntry {
+            id: next_id(&audit, |entry| entry.id),
+            entity_type: record.entity_type.to_string(),
+            entity_id: record.entity_id,
+            action: record.action.as_str().to_string(),
+            actor_id: Some(record.actor_id),
+            old_value: record.old_value,
+            new_value: record.new_value,
+            created_at: Utc::now().naive_utc(),
+        };
+        audit.push(entry);
+    }
+
+    //已删除老师的账号也占用用户名 和数据库的唯一约束一致
+    fn username_taken(&self, username: &str) -> bool {
+        self.users.lock().unwrap().iter().any(|user| user.username == username)
+    }
+
+    fn teacher_deleted(&self, teacher_id: i32) -> bool {
+        self.deleted_teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(teacher, _)| teacher.id == teacher_id)
+    }
+}
+
+fn next_id<T>(rows: &[T], id: impl Fn(&T) -> i32) -> i32 {
+    rows.iter().map(id).max().unwrap_or(0) + 1
+}
+
+//和SQL里的排序一致: 没有价格按0排序 排序键相同时按id排序
+fn compare(a: &Course, b: &Course, sort: CourseSort) -> Ordering {
+    let ordering = match sort {
+        CourseSort::IdAsc | CourseSort::IdDesc => Ordering::Equal,
+        CourseSort::PriceAsc | CourseSort::PriceDesc => a.price.unwrap_or(0).cmp(&b.price.unwrap_or(0)),
+        CourseSort::NameAsc | CourseSort::NameDesc => a.name.cmp(&b.name),
+    }
+    .then(a.id.cmp(&b.id));
+    match sort {
+        CourseSort::IdDesc | CourseSort::PriceDesc | CourseSort::NameDesc => ordering.reverse(),
+        _ => ordering,
+    }
+}
+
+fn matches(course: &Course, teacher_id: Option<i32>, query: &CourseQuery) -> bool {
+    let equals = |value: &Option<String>, filter: Option<&str>| filter.is_none_or(|filter| value.as_deref() == Some(filter));
+    let contains = |text: &str, q: &str| text.to_lowercase().contains(&q.to_lowercase());
+    teacher_id.is_none_or(|teacher_id| course.teacher_id == teacher_id)
+        && equals(&course.language, query.language.as_deref())
+        && equals(&course.level, query.level.map(|level| level.as_str()))
+        && equals(&course.format, query.format.map(|format| format.as_str()))
+        && query.min_price.is_none_or(|min| course.price.is_some_and(|price| price >= min))
+        && query.max_price.is_none_or(|max| course.price.is_some_and(|price| price <= max))
+        && query.search().is_none_or(|q| {
+            contains(&course.name, q) || course.description.as_deref().is_some_and(|text| contains(text, q))
+        })
+}
+
+impl PoolStatus for MemoryStore {
+    fn pool_status(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+#[async_trait]
+impl TeacherRepository for MemoryStore {
+    async fn list_teachers(&self, query: &TeacherQuery) -> Result<Page<Teacher>, MyError> {
+        let limit = page_size(query.limit)?;
+        let mut teachers = self.teachers.lock().unwrap().clone();
+        teachers.sort_by_key(|teacher| teacher.id);
+        let total = teachers.len() as i64;
+        let cursor = query.cursor.unwrap_or(0);
+        let rows = teachers
+            .into_iter()
+            .filter(|teacher| teacher.id > cursor)
+            .take(limit as usize + 1)
+            .collect();
+        Ok(Page::new(rows, limit, total, |teacher| teacher.id))
+    }
+
+    async fn get_teacher(&self, teacher_id: i32) -> Result<Teacher, MyError> {
+        self.teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|teacher| teacher.id == teacher_id)
+            .cloned()
+            .ok_or_else(|| MyError::NotFound("NO teacher found".into()))
+    }
+
+    //已删除的id不会再分配 和数据库的自增id一致
+    async fn create_teacher(&self, new_teacher: CreateTeacher, actor_id: i32) -> Result<Teacher, MyError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let deleted = self.deleted_teachers.lock().unwrap();
+        let teacher = Teacher {
+            id: next_id(&teachers, |teacher| teacher.id).max(next_id(&deleted, |(teacher, _)| teacher.id)),
+            name: new_teacher.name,
+            picture_url: new_teacher.picture_url,
+            profile: new_teacher.profile,
+        };
+        teachers.push(teacher.clone());
+        self.record_audit(AuditRecord::teacher(AuditAction::Create, actor_id, None, Some(&teacher)));
+        Ok(teacher)
+    }
+
+    async fn update_teacher(&self, teacher_id: i32, update_teacher: UpdateTeacher, actor_id: i32) -> Result<Teacher, MyError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let teacher = teachers
+            .iter_mut()
+            .find(|teacher| teacher.id == teacher_id)
+            .ok_or_else(|| MyError::NotFound("Teacher id not found".into()))?;
+        let old = teacher.clone();
+        if let Some(name) = update_teacher.name {
+            teacher.name = name;
+        }
+        if let Some(picture_url) = update_teacher.picture_url {
+            teacher.picture_url = picture_url;
+        }
+        if let Some(profile) = update_teacher.profile {
+            teacher.profile = profile;
+        }
+        self.record_audit(AuditRecord::teacher(AuditAction::Update, actor_id, Some(&old), Some(teacher)));
+        Ok(teacher.clone())
+    }
+
+    //课程和老师一起移到已删除列表 选课记录保留 恢复后仍然有效
+    async fn delete_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<String, MyError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let index = teachers
+            .iter()
+            .position(|teacher| teacher.id == teacher_id)
+            .ok_or_else(|| MyError::NotFound("NO teacher found".into()))?;
+        let teacher = teachers.remove(index);
+        let deleted_at = Utc::now().naive_utc();
+        let mut deleted_teachers = self.deleted_teachers.lock().unwrap();
+        let mut courses = self.courses.lock().unwrap();
+        let mut deleted_courses = self.deleted_courses.lock().unwrap();
+        let (removed, kept): (Vec<Course>, Vec<Course>) =
+            courses.drain(..).partition(|course| course.teacher_id == teacher_id);
+        *courses = kept;
+        for course in &removed {
+            self.record_audit(AuditRecord::course(AuditAction::Delete, actor_id, Some(course), None));
+        }
+        self.record_audit(AuditRecord::teacher(AuditAction::Delete, actor_id, Some(&teacher), None));
+        let count = removed.len();
+        deleted_courses.extend(removed.into_iter().map(|course| (course, deleted_at)));
+        deleted_teachers.push((teacher, deleted_at));
+        Ok(format!("Deleted teacher and {} courses", count))
+    }
+
+    async fn restore_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<Teacher, MyError> {
+        let mut teachers = self.teachers.lock().unwrap();
+        let mut deleted_teachers = self.deleted_teachers.lock().unwrap();
+        let index = deleted_teachers
+            .iter()
+            .position(|(teacher, _)| teacher.id == teacher_id)
+            .ok_or_else(|| MyError::NotFound("No deleted teacher found".into()))?;
+        let (teacher, deleted_at) = deleted_teachers.remove(index);
+        let mut courses = self.courses.lock().unwrap();
+        let mut deleted_courses = self.deleted_courses.lock().unwrap();
+        //只恢复和老师一起删除的课程
+        let (restored, kept): (Vec<_>, Vec<_>) = deleted_courses
+            .drain(..)
+            .partition(|(course, at)| course.teacher_id == teacher_id && *at == deleted_at);
+        *deleted_courses = kept;
+        self.record_audit(AuditRecord::teacher(AuditAction::Restore, actor_id, None, Some(&teacher)));
+        for (course, _) in restored {
+            self.record_audit(AuditRecord::course(AuditAction::Restore, actor_id, None, Some(&course)));
+            courses.push(course);
+        }
+        courses.sort_by_key(|course| course.id);
+        teachers.push(teacher.clone());
+        teachers.sort_by_key(|teacher| teacher.id);
+        Ok(teacher)
+    }
+}
+
+#[async_trait]
+impl CourseRepository for MemoryStore {
+    async fn list_courses(&self, teacher_id: Option<i32>, query: &CourseQuery) -> Result<Page<Course>, MyError> {
+        let limit = query.validate()?;
+        let courses = self.courses.lock().unwrap();
+        let mut rows: Vec<Course> = courses
+            .iter()
+            .filter(|course| matches(course, teacher_id, query))
+            .cloned()
+            .collect();
+        let total = rows.len() as i64;
+        rows.sort_by(|a, b| compare(a, b, query.sort));
+
+        //cursor对应的课程不存在时 和SQL一样返回空页
+        if let Some(cursor) = query.cursor {
+            match courses.iter().find(|course| course.id == cursor) {
+                Some(last) => rows.retain(|course| compare(course, last, query.sort) == Ordering::Greater),
+                None => rows.clear(),
+            }
+        }
+        rows.truncate(limit as usize + 1);
+        Ok(Page::new(rows, limit, total, |course| course.id))
+    }
+
+    async fn get_course(&self, teacher_id: i32, course_id: i32) -> Result<Course, MyError> {
+        self.courses
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|course| course.teacher_id == teacher_id && course.id == course_id)
+            .cloned()
+            .ok_or_else(|| MyError::NotFound("Course ID not found".into()))
+    }
+
+    async fn create_course(&self, new_course: CreateCourse, actor_id: i32) -> Result<Course, MyError> {
+        if !self
+            .teachers
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|teacher| teacher.id == new_course.teacher_id)
+        {
+            return Err(MyError::invalid_field("teacher_id", "does not reference an existing record"));
+        }
+        let mut courses = self.courses.lock().unwrap();
+        let deleted = self.deleted_courses.lock().unwrap();
+        let course = Course {
+            teacher_id: new_course.teacher_id,
+            id: next_id(&courses, |course| course.id).max(next_id(&deleted, |(course, _)| course.id)),
+            name: new_course.name,
+            time: Some(Utc::now().naive_utc()),
+            description: new_course.description,
+            format: new_course.format.map(|format| format.to_string()),
+            structure: new_course.structure,
+            duration: new_course.duration,
+            price: new_course.price,
+            language: new_course.language,
+            level: new_course.level.map(|level| level.to_string()),
+            capacity: new_course.capacity,
+        };
+        courses.push(course.clone());
+        self.record_audit(AuditRecord::course(AuditAction::Create, actor_id, None, Some(&course)));
+        Ok(course)
+    }
+
+    async fn update_course(&self, teacher_id: i32, course_id: i32, update_course: UpdateCourse, actor_id: i32) -> Result<Course, MyError> {
+        let mut courses = self.courses.lock().unwrap();
+        let course = courses
+            .iter_mut()
+            .find(|course| course.teacher_id == teacher_id && course.id == course_id)
+            .ok_or_else(|| MyError::NotFound("Course Id not found".into()))?;
+        let old = course.clone();
+        if let Some(name) = update_course.name {
+            course.name = name;
+        }
+        course.description = update_course.description.or(course.description.take());
+        course.format = update_course.format.map(|format| format.to_string()).or(course.format.take());
+        course.structure = update_course.structure.or(course.structure.take());
+        course.duration = update_course.duration.or(course.duration.take());
+        course.price = update_course.price.or(course.price);
+        course.language = update_course.language.or(course.language.take());
+        course.level = update_course.level.map(|level| level.to_string()).or(course.level.take());
+        course.capacity = update_course.capacity.or(course.capacity);
+        self.record_audit(AuditRecord::course(AuditAction::Update, actor_id, Some(&old), Some(course)));
+        Ok(course.clone())
+    }
+
+    async fn delete_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<String, MyError> {
+        let mut courses = self.courses.lock().unwrap();
+        let index = courses
+            .iter()
+            .position(|course| course.teacher_id == teacher_id && course.id == course_id)
+            .ok_or_else(|| MyError::NotFound("Course id not found".into()))?;
+        let course = courses.remove(index);
+        self.record_audit(AuditRecord::course(AuditAction::Delete, actor_id, Some(&course), None));
+        self.deleted_courses.lock().unwrap().push((course, Utc::now().naive_utc()));
+        Ok("Deleted 1 record".to_string())
+    }
+
+    async fn restore_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<Course, MyError> {
+        if self.teacher_deleted(teacher_id) {
+            return Err(MyError::Conflict("Teacher is deleted, restore the teacher first".into()));
+        }
+        if !self.teachers.lock().unwrap().iter().any(|teacher| teacher.id == teacher_id) {
+            return Err(MyError::NotFound("NO teacher found".into()));
+        }
+        let mut courses = self.courses.lock().unwrap();
+        let mut deleted_courses = self.deleted_courses.lock().unwrap();
+        let index = deleted_courses
+            .iter()
+            .position(|(course, _)| course.teacher_id == teacher_id && course.id == course_id)
+            .ok_or_else(|| MyError::NotFound("No deleted course found".into()))?;
+        let (course, _) = deleted_courses.remove(index);
+        self.record_audit(AuditRecord::course(AuditAction::Restore, actor_id, None, Some(&course)));
+        courses.push(course.clone());
+        courses.sort_by_key(|course| course.id);
+        Ok(course)
+    }
+}
+
+#[async_trait]
+impl AuditRepository for MemoryStore {
+    async fn list_audit(&self, entity_type: EntityType, entity_id: i32) -> Result<Vec<AuditEntry>, MyError> {
+        Ok(self
+            .audit
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.entity_type == entity_type.as_str() && entry.entity_id == entity_id)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryStore {
+    //老师被删除后账号查不到
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MyError> {
+        let user = self
+            .users
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|user| user.username == username)
+            .cloned();
+        Ok(user.filter(|user| !user.teacher_id.is_some_and(|id| self.teacher_deleted(id))))
+    }
+
+    async fn get_user(&self, user_id: i32) -> Result<Option<User>, MyError> {
+        let user = self.users.lock().unwrap().iter().find(|user| user.id == user_id).cloned();
+        Ok(user.filter(|user| !user.teacher_id.is_some_and(|id| self.teacher_deleted(id))))
+    }
+
+    //修改记录的操作者是新注册的账号 先分配账号id再创建老师
+    async fn register_teacher(&self, new_user: RegisterTeacher, password_hash: String) -> Result<User, MyError> {
+        if self.username_taken(&new_user.username) {
+            return Err(MyError::Conflict("Username is already taken".into()));
+        }
+        let user_id = next_id(&self.users.lock().unwrap(), |user| user.id);
+        let teacher = self
+            .create_teacher(
+                CreateTeacher {
+                    name: new_user.name,
+                    picture_url: new_user.picture_url,
+                    profile: new_user.profile,
+                },
+                user_id,
+            )
+            .await?;
+        let user = User {
+            id: user_id,
+            username: new_user.username,
+            password_hash,
+            role: "teacher".into(),
+            teacher_id: Some(teacher.id),
+            student_id: None,
+        };
+        self.users.lock().unwrap().push(user.clone());
+        Ok(user)
+    }
+
+    async fn register_student(&self, new_user: RegisterStudent, password_hash: String) -> Result<User, MyError> {
+        if self.username_taken(&new_user.username) {
+            return Err(MyError::Conflict("Username is already taken".into()));
+        }
+        let mut students = self.students.lock().unwrap();
+        if students.iter().any(|student| student.email == new_user.email) {
+            return Err(MyError::Conflict("Email is already registered".into()));
+        }
+        let student = Student {
+            id: next_id(&students, |student| student.id),
+            name: new_user.name,
+            email: new_user.email,
+        };
+        students.push(student.clone());
+        let mut users = self.users.lock().unwrap();
+        let user = User {
+            id: next_id(&users, |user| user.id),
+            username: new_user.username,
+            password_hash,
+            role: "student".into(),
+            teacher_id: None,
+            student_id: Some(student.id),
+        };
+        users.push(user.clone());
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl EnrollmentRepository for MemoryStore {
+    //先锁住选课记录再检查 检查和插入之间不会有其他请求
+    async fn enroll(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<Enrollment, MyError> {
+        let course = self.get_course(teacher_id, course_id).await?;
+        let mut enrollments = self.enrollments.lock().unwrap();
+        if enrollments
+            .iter()
+            .any(|enrollment| enrollment.course_id == course_id && enrollment.student_id == student_id)
+        {
+            return Err(MyError::AlreadyEnrolled("Already enrolled in this course".into()));
+        }
+        if let Some(capacity) = course.capacity {
+            let count = enrollments.iter().filter(|enrollment| enrollment.course_id == course_id).count();
+            if count as i64 >= capacity as i64 {
+                return Err(MyError::CourseFull("Course is full".into()));
+            }
+        }
+        let enrollment = Enrollment {
+            course_id,
+            student_id,
+            enrolled_at: Utc::now().naive_utc(),
+        };
+        enrollments.push(enrollment.clone());
+        Ok(enrollment)
+    }
+
+    async fn withdraw(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<String, MyError> {
+        self.get_course(teacher_id, course_id).await?;
+        let mut enrollments = self.enrollments.lock().unwrap();
+        let count = enrollments.len();
+        enrollments.retain(|enrollment| !(enrollment.course_id == course_id && enrollment.student_id == student_id));
+        match count - enrollments.len() {
+            0 => Err(MyError::NotEnrolled("Not enrolled in this course".into())),
+            n => Ok(format!("Deleted {} record", n)),
+        }
+    }
+
+    //选课记录按时间顺序追加 不需要再排序
+    async fn list_roster(&self, teacher_id: i32, course_id: i32) -> Result<Vec<Student>, MyError> {
+        self.get_course(teacher_id, course_id).await?;
+        let students = self.students.lock().unwrap();
+        Ok(self
+            .enrollments
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|enrollment| enrollment.course_id == course_id)
+            .filter_map(|enrollment| students.iter().find(|student| student.id == enrollment.student_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn list_student_courses(&self, student_id: i32) -> Result<Vec<Course>, MyError> {
+        let courses = self.courses.lock().unwrap();
+        Ok(self
+            .enrollments
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|enrollment| enrollment.student_id == student_id)
+            .filter_map(|enrollment| courses.iter().find(|course| course.id == enrollment.course_id))
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::MyError;
+use crate::models::audit::{AuditEntry, EntityType};
+use crate::models::course::{Course, CourseQuery, CreateCourse, UpdateCourse};
+use crate::models::page::Page;
+use crate::models::student::{Enrollment, Student};
+use crate::models::teacher::{CreateTeacher, Teacher, TeacherQuery, UpdateTeacher};
+use crate::models::user::{RegisterStudent, RegisterTeacher, User};
+use async_trait::async_trait;
+
+pub mod memory;
+pub mod postgres;
+pub mod sql;
+pub mod sqlite;
+
+pub use memory::MemoryStore;
+pub use postgres::PgStore;
+pub use sqlite::SqliteStore;
+
+//处理函数只依赖下面的trait 使用Postgres、SQLite还是内存存储在启动时决定
+//三种实现的行为保持一致: 找不到记录返回MyError::NotFound
+//删除是软删除 已删除的老师和课程查不到 选课记录保留 删除老师时一起删除他的课程 他的账号不能再登录
+//老师和课程的增删改和恢复都在audit_log里记录 actor_id是操作者的账号id
+
+//老师
+#[async_trait]
+pub trait TeacherRepository: Send + Sync {
+    async fn list_teachers(&self, query: &TeacherQuery) -> Result<Page<Teacher>, MyError>;
+    async fn get_teacher(&self, teacher_id: i32) -> Result<Teacher, MyError>;
+    async fn create_teacher(&self, new_teacher: CreateTeacher, actor_id: i32) -> Result<Teacher, MyError>;
+    async fn update_teacher(&self, teacher_id: i32, update_teacher: UpdateTeacher, actor_id: i32) -> Result<Teacher, MyError>;
+    async fn delete_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<String, MyError>;
+    //恢复老师和跟他一起删除的课程 没有已删除的老师返回MyError::NotFound
+    async fn restore_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<Teacher, MyError>;
+}
+
+//课程 teacher_id为None时查询所有老师的课程
+#[async_trait]
+pub trait CourseRepository: Send + Sync {
+    async fn list_courses(&self, teacher_id: Option<i32>, query: &CourseQuery) -> Result<Page<Course>, MyError>;
+    async fn get_course(&self, teacher_id: i32, course_id: i32) -> Result<Course, MyError>;
+    async fn create_course(&self, new_course: CreateCourse, actor_id: i32) -> Result<Course, MyError>;
+    async fn update_course(&self, teacher_id: i32, course_id: i32, update_course: UpdateCourse, actor_id: i32) -> Result<Course, MyError>;
+    async fn delete_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<String, MyError>;
+    //老师已被删除时返回MyError::Conflict 需要先恢复老师
+    async fn restore_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<Course, MyError>;
+}
+
+//登录账号
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MyError>;
+    async fn get_user(&self, user_id: i32) -> Result<Option<User>, MyError>;
+    //同时创建老师资料和teacher角色的账号 修改记录的操作者是新账号
+    async fn register_teacher(&self, new_user: RegisterTeacher, password_hash: String) -> Result<User, MyError>;
+    //同时创建学生和student角色的账号
+    async fn register_student(&self, new_user: RegisterStudent, password_hash: String) -> Result<User, MyError>;
+}
+
+//选课 课程和课程接口一样用teacher_id和course_id确定 课程不存在返回MyError::NotFound
+//人数已满返回MyError::CourseFull 重复选课返回MyError::AlreadyEnrolled 没有选过返回MyError::NotEnrolled
+#[async_trait]
+pub trait EnrollmentRepository: Send + Sync {
+    async fn enroll(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<Enrollment, MyError>;
+    async fn withdraw(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<String, MyError>;
+    async fn list_roster(&self, teacher_id: i32, course_id: i32) -> Result<Vec<Student>, MyError>;
+    async fn list_student_courses(&self, student_id: i32) -> Result<Vec<Course>, MyError>;
+}
+
+//修改记录 按时间顺序返回
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn list_audit(&self, entity_type: EntityType, entity_id: i32) -> Result<Vec<AuditEntry>, MyError>;
+}
+
+//连接池里正在使用和空闲的连接数
+pub struct PoolStats {
+    pub in_use: u32,
+    pub idle: u32,
+}
+
+//连接池状态 用于/metrics 内存存储没有连接池 返回None
+pub trait PoolStatus: Send + Sync {
+    fn pool_status(&self) -> Option<PoolStats>;
+}
@@ -0,0 +1,134 @@
+use super::{AuditRepository, CourseRepository, EnrollmentRepository, PoolStats, PoolStatus, TeacherRepository, UserRepository};
+use crate::dbaccess::{audit, course, student, teacher, user};
+use crate::error::MyError;
+use crate::models::audit::{AuditEntry, EntityType};
+use crate::models::course::{Course, CourseQuery, CreateCourse, UpdateCourse};
+use crate::models::page::Page;
+use crate::models::student::{Enrollment, Student};
+use crate::models::teacher::{CreateTeacher, Teacher, TeacherQuery, UpdateTeacher};
+use crate::models::user::{RegisterStudent, RegisterTeacher, User};
+use async_trait::async_trait;
+use sqlx::postgres::PgPool;
+
+//Postgres存储 具体的SQL在dbaccess里 使用运行时绑定参数的查询 编译时不需要连接数据库
+pub struct PgStore {
+    pool: PgPool,
+}
+
+impl PgStore {
+    pub fn new(pool: PgPool) -> Self {
+        PgStore { pool }
+    }
+}
+
+impl PoolStatus for PgStore {
+    fn pool_status(&self) -> Option<PoolStats> {
+        let idle = self.pool.num_idle() as u32;
+        Some(PoolStats {
+            in_use: self.pool.size().saturating_sub(idle),
+            idle,
+        })
+    }
+}
+
+#[async_trait]
+impl TeacherRepository for PgStore {
+    async fn list_teachers(&self, query: &TeacherQuery) -> Result<Page<Teacher>, MyError> {
+        teacher::get_all_teachers_db(&self.pool, query).await
+    }
+
+    async fn get_teacher(&self, teacher_id: i32) -> Result<Teacher, MyError> {
+        teacher::get_teacher_details_db(&self.pool, teacher_id).await
+    }
+
+    async fn create_teacher(&self, new_teacher: CreateTeacher, actor_id: i32) -> Result<Teacher, MyError> {
+        teacher::post_new_teacher_db(&self.pool, new_teacher, actor_id).await
+    }
+
+    async fn update_teacher(&self, teacher_id: i32, update_teacher: UpdateTeacher, actor_id: i32) -> Result<Teacher, MyError> {
+        teacher::update_teacher_details_db(&self.pool, teacher_id, update_teacher, actor_id).await
+    }
+
+    async fn delete_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<String, MyError> {
+        teacher::delete_teacher_db(&self.pool, teacher_id, actor_id).await
+    }
+
+    async fn restore_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<Teacher, MyError> {
+        teacher::restore_teacher_db(&self.pool, teacher_id, actor_id).await
+    }
+}
+
+#[async_trait]
+impl CourseRepository for PgStore {
+    async fn list_courses(&self, teacher_id: Option<i32>, query: &CourseQuery) -> Result<Page<Course>, MyError> {
+        match teacher_id {
+            Some(teacher_id) => course::get_course_for_teacher_db(&self.pool, teacher_id, query).await,
+            None => course::get_courses_db(&self.pool, query).await,
+        }
+    }
+
+    async fn get_course(&self, teacher_id: i32, course_id: i32) -> Result<Course, MyError> {
+        course::get_course_details_db(&self.pool, teacher_id, course_id).await
+    }
+
+    async fn create_course(&self, new_course: CreateCourse, actor_id: i32) -> Result<Course, MyError> {
+        course::post_new_course_db(&self.pool, new_course, actor_id).await
+    }
+
+    async fn update_course(&self, teacher_id: i32, course_id: i32, update_course: UpdateCourse, actor_id: i32) -> Result<Course, MyError> {
+        course::update_course_details_db(&self.pool, teacher_id, course_id, update_course, actor_id).await
+    }
+
+    async fn delete_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<String, MyError> {
+        course::delete_course_db(&self.pool, teacher_id, course_id, actor_id).await
+    }
+
+    async fn restore_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<Course, MyError> {
+        course::restore_course_db(&self.pool, teacher_id, course_id, actor_id).await
+    }
+}
+
+#[async_trait]
+impl AuditRepository for PgStore {
+    async fn list_audit(&self, entity_type: EntityType, entity_id: i32) -> Result<Vec<AuditEntry>, MyError> {
+        audit::get_audit_log_db(&self.pool, entity_type, entity_id).await
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgStore {
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MyError> {
+        user::get_user_by_username_db(&self.pool, username).await
+    }
+
+    async fn get_user(&self, user_id: i32) -> Result<Option<User>, MyError> {
+        user::get_user_db(&self.pool, user_id).await
+    }
+
+    async fn register_teacher(&self, new_user: RegisterTeacher, password_hash: String) -> Result<User, MyError> {
+        user::post_new_teacher_user_db(&self.pool, new_user, password_hash).await
+    }
+
+    async fn register_student(&self, new_user: RegisterStudent, password_hash: String) -> Result<User, MyError> {
+        user::post_new_student_user_db(&self.pool, new_user, password_hash).await
+    }
+}
+
+#[async_trait]
+impl EnrollmentRepository for PgStore {
+    async fn enroll(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<Enrollment, MyError> {
+        student::enroll_db(&self.pool, teacher_id, course_id, student_id).await
+    }
+
+    async fn withdraw(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<String, MyError> {
+        student::withdraw_db(&self.pool, teacher_id, course_id, student_id).await
+    }
+
+    async fn list_roster(&self, teacher_id: i32, course_id: i32) -> Result<Vec<Student>, MyError> {
+        student::get_course_roster_db(&self.pool, teacher_id, course_id).await
+    }
+
+    async fn list_student_courses(&self, student_id: i32) -> Result<Vec<Course>, MyError> {
+        student::get_student_courses_db(&self.pool, student_id).await
+    }
+}
@@ -0,0 +1,170 @@
+use crate::models::course::{CourseQuery, CourseSort};
+use crate::models::page::like_pattern;
+
+//Postgres和SQLite共用的课程列表SQL 两者只有参数占位符和LIKE的写法不同
+pub struct Dialect {
+    //占位符前缀 Postgres是$1 SQLite是?1
+    pub placeholder: &'static str,
+    //不区分大小写的LIKE SQLite的LIKE对ASCII本来就不区分大小写
+    pub like: &'static str,
+}
+
+pub const POSTGRES: Dialect = Dialect {
+    placeholder: "$",
+    like: "ILIKE",
+};
+
+pub const SQLITE: Dialect = Dialect {
+    placeholder: "?",
+    like: "LIKE",
+};
+
+//查询条件里用到的参数 按出现顺序绑定
+pub enum Param {
+    Int(i32),
+    Text(String),
+}
+
+//动态拼接的WHERE条件 除了deleted_at IS NULL 每个条件带一个参数 条件里的$?替换成参数的序号
+struct Filters {
+    dialect: &'static Dialect,
+    conditions: Vec<String>,
+    params: Vec<Param>,
+}
+
+impl Filters {
+    fn push(&mut self, condition: &str, param: Param) {
+        self.params.push(param);
+        let placeholder = format!("{}{}", self.dialect.placeholder, self.params.len());
+        self.conditions.push(condition.replace("$?", &placeholder));
+    }
+
+    //只使用前count个条件
+    fn where_clause(&self, count: usize) -> String {
+        if count == 0 {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.conditions[..count].join(" AND "))
+        }
+    }
+}
+
+//sql查询一页课程 多查一条用来判断是否有下一页 count_sql统计总数 只使用前count_params个参数
+pub struct CourseListSql {
+    pub sql: String,
+    pub count_sql: String,
+    pub params: Vec<Param>,
+    pub count_params: usize,
+}
+
+//筛选条件是动态的 这里拼接SQL 所有的值都通过参数绑定
+//翻页使用cursor对应记录的排序键 (排序键, id)比它大(倒序时小)的记录就是下一页
+pub fn course_list_sql(dialect: &'static Dialect, teacher_id: Option<i32>, query: &CourseQuery, limit: i64) -> CourseListSql {
+    //已删除的课程不出现在列表里
+    let mut filters = Filters {
+        dialect,
+        conditions: vec!["deleted_at IS NULL".to_string()],
+        params: Vec::new(),
+    };
+
+    if let Some(teacher_id) = teacher_id {
+        filters.push("teacher_id = $?", Param::Int(teacher_id));
+    }
+    if let Some(language) = &query.language {
+        filters.push("language = $?", Param::Text(language.clone()));
+    }
+    if let Some(level) = &query.level {
+        filters.push("level = $?", Param::Text(level.to_string()));
+    }
+    if let Some(format) = &query.format {
+        filters.push("format = $?", Param::Text(format.to_string()));
+    }
+    if let Some(min_price) = query.min_price {
+        filters.push("price >= $?", Param::Int(min_price));
+    }
+    if let Some(max_price) = query.max_price {
+        filters.push("price <= $?", Param::Int(max_price));
+    }
+    if let Some(q) = query.search() {
+        let condition = format!(
+            "(name {like} $? ESCAPE '\\' OR description {like} $? ESCAPE '\\')",
+            like = dialect.like
+        );
+        filters.push(&condition, Param::Text(like_pattern(q)));
+    }
+    //总数不受翻页影响 在加上cursor条件之前统计
+    let count_conditions = filters.conditions.len();
+    let count_params = filters.params.len();
+
+    let (key, descending) = sort_key(query.sort);
+    if let Some(cursor) = query.cursor {
+        let condition = format!(
+            "({key}, id) {op} (SELECT {key}, id FROM course WHERE id = $?)",
+            key = key,
+            op = if descending { "<" } else { ">" }
+        );
+        filters.push(&condition, Param::Int(cursor));
+    }
+
+    let direction = if descending { "DESC" } else { "ASC" };
+    let order = if key == "id" {
+        format!("id {}", direction)
+    } else {
+        format!("{} {}, id {}", key, direction, direction)
+    };
+    CourseListSql {
+        sql: format!(
+            "SELECT * FROM course{} ORDER BY {} LIMIT {}",
+            filters.where_clause(filters.conditions.len()),
+            order,
+            limit + 1
+        ),
+        count_sql: format!("SELECT COUNT(*) FROM course{}", filters.where_clause(count_conditions)),
+        params: filters.params,
+        count_params,
+    }
+}
+
+//排序使用的列和是否倒序 没有价格的课程按0排序
+fn sort_key(sort: CourseSort) -> (&'static str, bool) {
+    match sort {
+        CourseSort::IdAsc => ("id", false),
+        CourseSort::IdDesc => ("id", true),
+        CourseSort::PriceAsc => ("COALESCE(price, 0)", false),
+        CourseSort::PriceDesc => ("COALESCE(price, 0)", true),
+        CourseSort::NameAsc => ("name", false),
+        CourseSort::NameDesc => ("name", true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn course_list_sql_test() {
+        let query = CourseQuery {
+            language: Some("English".into()),
+            q: Some("rust".into()),
+            cursor: Some(5),
+            sort: CourseSort::PriceDesc,
+            ..Default::default()
+        };
+
+        let list = course_list_sql(&POSTGRES, Some(1), &query, 10);
+        assert_eq!(
+            list.sql,
+            "SELECT * FROM course WHERE deleted_at IS NULL AND teacher_id = $1 AND language = $2 \
+             AND (name ILIKE $3 ESCAPE '\\' OR description ILIKE $3 ESCAPE '\\') \
+             AND (COALESCE(price, 0), id) < (SELECT COALESCE(price, 0), id FROM course WHERE id = $4) \
+             ORDER BY COALESCE(price, 0) DESC, id DESC LIMIT 11"
+        );
+        assert_eq!(list.params.len(), 4);
+        assert_eq!(list.count_params, 3);
+        assert!(!list.count_sql.contains("$4"));
+
+        let list = course_list_sql(&SQLITE, None, &CourseQuery::default(), 20);
+        assert_eq!(list.sql, "SELECT * FROM course WHERE deleted_at IS NULL ORDER BY id ASC LIMIT 21");
+        assert_eq!(list.count_sql, "SELECT COUNT(*) FROM course WHERE deleted_at IS NULL");
+    }
+}
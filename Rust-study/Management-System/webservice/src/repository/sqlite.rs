@@ -0,0 +1,699 @@
+use super::sql::{course_list_sql, Param, SQLITE};
+use super::{AuditRepository, CourseRepository, EnrollmentRepository, PoolStats, PoolStatus, TeacherRepository, UserRepository};
+use crate::error::MyError;
+use crate::models::audit::{AuditAction, AuditEntry, AuditRecord, EntityType};
+use crate::models::course::{Course, CourseQuery, CreateCourse, UpdateCourse};
+use crate::models::page::{page_size, Page};
+use crate::models::student::{Enrollment, Student};
+use crate::models::teacher::{CreateTeacher, Teacher, TeacherQuery, UpdateTeacher};
+use crate::models::user::{RegisterStudent, RegisterTeacher, User};
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::sqlite::{Sqlite, SqlitePool};
+use sqlx::Transaction;
+
+//SQLite存储 适合单机部署和不想安装Postgres的开发环境 表结构见migrations_sqlite
+//和Postgres一样使用运行时绑定参数的查询
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteStore { pool }
+    }
+}
+
+impl PoolStatus for SqliteStore {
+    fn pool_status(&self) -> Option<PoolStats> {
+        let idle = self.pool.num_idle() as u32;
+        Some(PoolStats {
+            in_use: self.pool.size().saturating_sub(idle),
+            idle,
+        })
+    }
+}
+
+//写入修改记录 和修改放在同一个事务里
+async fn insert_audit(tx: &mut Transaction<'_, Sqlite>, record: AuditRecord) -> Result<(), MyError> {
+    sqlx::query(
+        "INSERT INTO audit_log (entity_type, entity_id, action, actor_id, old_value, new_value)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )
+    .bind(record.entity_type.as_str())
+    .bind(record.entity_id)
+    .bind(record.action.as_str())
+    .bind(record.actor_id)
+    .bind(record.old_value)
+    .bind(record.new_value)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl TeacherRepository for SqliteStore {
+    async fn list_teachers(&self, query: &TeacherQuery) -> Result<Page<Teacher>, MyError> {
+        let limit = page_size(query.limit)?;
+        let rows = sqlx::query_as::<_, Teacher>(
+            "SELECT * FROM teacher WHERE deleted_at IS NULL AND id > ?1 ORDER BY id LIMIT ?2",
+        )
+        .bind(query.cursor.unwrap_or(0))
+        .bind(limit + 1)
+        .fetch_all(&self.pool)
+        .await?;
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM teacher WHERE deleted_at IS NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(Page::new(rows, limit, total, |teacher| teacher.id))
+    }
+
+    async fn get_teacher(&self, teacher_id: i32) -> Result<Teacher, MyError> {
+        sqlx::query_as::<_, Teacher>("SELECT * FROM teacher WHERE id = ?1 AND deleted_at IS NULL")
+            .bind(teacher_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| MyError::NotFound("NO teacher found".into()))
+    }
+
+    async fn create_teacher(&self, new_teacher: CreateTeacher, actor_id: i32) -> Result<Teacher, MyError> {
+        let mut tx = self.pool.begin().await?;
+        let teacher = sqlx::query_as::<_, Teacher>(
+            "INSERT INTO teacher (name, picture_url, profile) VALUES (?1, ?2, ?3) RETURNING *",
+        )
+        .bind(new_teacher.name)
+        .bind(new_teacher.picture_url)
+        .bind(new_teacher.profile)
+        .fetch_one(&mut tx)
+        .await?;
+        insert_audit(&mut tx, AuditRecord::teacher(AuditAction::Create, actor_id, None, Some(&teacher))).await?;
+        tx.commit().await?;
+        Ok(teacher)
+    }
+
+    //SQLite的写事务是串行的 读取和更新之间数据不会被其他请求修改
+    async fn update_teacher(&self, teacher_id: i32, update_teacher: UpdateTeacher, actor_id: i32) -> Result<Teacher, MyError> {
+        let mut tx = self.pool.begin().await?;
+        let current = sqlx::query_as::<_, Teacher>("SELECT * FROM teacher WHERE id = ?1 AND deleted_at IS NULL")
+            .bind(teacher_id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| MyError::NotFound("Teacher id not found".into()))?;
+        let teacher = sqlx::query_as::<_, Teacher>(
+            "UPDATE teacher SET name = ?1, picture_url = ?2, profile = ?3 WHERE id = ?4 RETURNING *",
+        )
+        .bind(update_teacher.name.unwrap_or_else(|| current.name.clone()))
+        .bind(update_teacher.picture_url.unwrap_or_else(|| current.picture_url.clone()))
+        .bind(update_teacher.profile.unwrap_or_else(|| current.profile.clone()))
+        .bind(teacher_id)
+        .fetch_one(&mut tx)
+        .await?;
+        insert_audit(&mut tx, AuditRecord::teacher(AuditAction::Update, actor_id, Some(&current), Some(&teacher))).await?;
+        tx.commit().await?;
+        Ok(teacher)
+    }
+
+    //老师和他的课程使用同一个删除时间 恢复时根据这个时间找到一起删除的课程
+    async fn delete_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<String, MyError> {
+        let deleted_at = Utc::now().naive_utc();
+        let mut tx = self.pool.begin().await?;
+        let teacher = sqlx::query_as::<_, Teacher>(
+            "UPDATE teacher SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(teacher_id)
+        .bind(deleted_at)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| MyError::NotFound("NO teacher found".into()))?;
+        let courses = sqlx::query_as::<_, Course>(
+            "UPDATE course SET deleted_at = ?2 WHERE teacher_id = ?1 AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(teacher_id)
+        .bind(deleted_at)
+        .fetch_all(&mut tx)
+        .await?;
+        for course in &courses {
+            insert_audit(&mut tx, AuditRecord::course(AuditAction::Delete, actor_id, Some(course), None)).await?;
+        }
+        insert_audit(&mut tx, AuditRecord::teacher(AuditAction::Delete, actor_id, Some(&teacher), None)).await?;
+        tx.commit().await?;
+        Ok(format!("Deleted teacher and {} courses", courses.len()))
+    }
+
+    async fn restore_teacher(&self, teacher_id: i32, actor_id: i32) -> Result<Teacher, MyError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted_at = sqlx::query_scalar::<_, NaiveDateTime>(
+            "SELECT deleted_at FROM teacher WHERE id = ?1 AND deleted_at IS NOT NULL",
+        )
+        .bind(teacher_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| MyError::NotFound("No deleted teacher found".into()))?;
+        let teacher = sqlx::query_as::<_, Teacher>("UPDATE teacher SET deleted_at = NULL WHERE id = ?1 RETURNING *")
+            .bind(teacher_id)
+            .fetch_one(&mut tx)
+            .await?;
+        let courses = sqlx::query_as::<_, Course>(
+            "UPDATE course SET deleted_at = NULL WHERE teacher_id = ?1 AND deleted_at = ?2 RETURNING *",
+        )
+        .bind(teacher_id)
+        .bind(deleted_at)
+        .fetch_all(&mut tx)
+        .await?;
+        insert_audit(&mut tx, AuditRecord::teacher(AuditAction::Restore, actor_id, None, Some(&teacher))).await?;
+        for course in &courses {
+            insert_audit(&mut tx, AuditRecord::course(AuditAction::Restore, actor_id, None, Some(course))).await?;
+        }
+        tx.commit().await?;
+        Ok(teacher)
+    }
+}
+
+#[async_trait]
+impl CourseRepository for SqliteStore {
+    async fn list_courses(&self, teacher_id: Option<i32>, query: &CourseQuery) -> Result<Page<Course>, MyError> {
+        let limit = query.validate()?;
+        let list = course_list_sql(&SQLITE, teacher_id, query, limit);
+
+        let mut rows_query = sqlx::query_as::<_, Course>(&list.sql);
+        let mut count_query = sqlx::query_scalar::<_, i64>(&list.count_sql);
+        for (i, param) in list.params.iter().enumerate() {
+            rows_query = match param {
+                Param::Int(value) => rows_query.bind(*value),
+                Param::Text(value) => rows_query.bind(value.clone()),
+            };
+            if i < list.count_params {
+                count_query = match param {
+                    Param::Int(value) => count_query.bind(*value),
+                    Param::Text(value) => count_query.bind(value.clone()),
+                };
+            }
+        }
+
+        let rows = rows_query.fetch_all(&self.pool).await?;
+        let total = count_query.fetch_one(&self.pool).await?;
+        Ok(Page::new(rows, limit, total, |course| course.id))
+    }
+
+    async fn get_course(&self, teacher_id: i32, course_id: i32) -> Result<Course, MyError> {
+        sqlx::query_as::<_, Course>("SELECT * FROM course WHERE teacher_id = ?1 AND id = ?2 AND deleted_at IS NULL")
+            .bind(teacher_id)
+            .bind(course_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| MyError::NotFound("Course ID not found".into()))
+    }
+
+    //老师已删除时外键检查不会失败 需要单独检查
+    async fn create_course(&self, new_course: CreateCourse, actor_id: i32) -> Result<Course, MyError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = sqlx::query_scalar::<_, i32>("SELECT id FROM teacher WHERE id = ?1 AND deleted_at IS NOT NULL")
+            .bind(new_course.teacher_id)
+            .fetch_optional(&mut tx)
+            .await?;
+        if deleted.is_some() {
+            return Err(MyError::invalid_field("teacher_id", "does not reference an existing record"));
+        }
+        let course = sqlx::query_as::<_, Course>(
+            "INSERT INTO course (teacher_id, name, description, format, structure, duration, price, language, level, capacity)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            RETURNING *",
+        )
+        .bind(new_course.teacher_id)
+        .bind(new_course.name)
+        .bind(new_course.description)
+        .bind(new_course.format.map(|format| format.as_str()))
+        .bind(new_course.structure)
+        .bind(new_course.duration)
+        .bind(new_course.price)
+        .bind(new_course.language)
+        .bind(new_course.level.map(|level| level.as_str()))
+        .bind(new_course.capacity)
+        .fetch_one(&mut tx)
+        .await?;
+        insert_audit(&mut tx, AuditRecord::course(AuditAction::Create, actor_id, None, Some(&course))).await?;
+        tx.commit().await?;
+        Ok(course)
+    }
+
+    async fn update_course(&self, teacher_id: i32, course_id: i32, update_course: UpdateCourse, actor_id: i32) -> Result<Course, MyError> {
+        let mut tx = self.pool.begin().await?;
+        let current = sqlx::query_as::<_, Course>("SELECT * FROM course WHERE teacher_id = ?1 AND id = ?2 AND deleted_at IS NULL")
+            .bind(teacher_id)
+            .bind(course_id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| MyError::NotFound("Course Id not found".into()))?;
+        let course = sqlx::query_as::<_, Course>(
+            "UPDATE course SET name = ?1, description = ?2, format = ?3,
+            structure = ?4, duration = ?5, price = ?6, language = ?7,
+            level = ?8, capacity = ?9 WHERE teacher_id = ?10 AND id = ?11
+            RETURNING *",
+        )
+        .bind(update_course.name.unwrap_or_else(|| current.name.clone()))
+        .bind(update_course.description.or_else(|| current.description.clone()))
+        .bind(update_course.format.map(|format| format.to_string()).or_else(|| current.format.clone()))
+        .bind(update_course.structure.or_else(|| current.structure.clone()))
+        .bind(update_course.duration.or_else(|| current.duration.clone()))
+        .bind(update_course.price.or(current.price))
+        .bind(update_course.language.or_else(|| current.language.clone()))
+        .bind(update_course.level.map(|level| level.to_string()).or_else(|| current.level.clone()))
+        .bind(update_course.capacity.or(current.capacity))
+        .bind(teacher_id)
+        .bind(course_id)
+        .fetch_one(&mut tx)
+        .await?;
+        insert_audit(&mut tx, AuditRecord::course(AuditAction::Update, actor_id, Some(&current), Some(&course))).await?;
+        tx.commit().await?;
+        Ok(course)
+    }
+
+    async fn delete_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<String, MyError> {
+        let mut tx = self.pool.begin().await?;
+        let course = sqlx::query_as::<_, Course>(
+            "UPDATE course SET deleted_at = ?3 WHERE teacher_id = ?1 AND id = ?2 AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(teacher_id)
+        .bind(course_id)
+        .bind(Utc::now().naive_utc())
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| MyError::NotFound("Course id not found".into()))?;
+        insert_audit(&mut tx, AuditRecord::course(AuditAction::Delete, actor_id, Some(&course), None)).await?;
+        tx.commit().await?;
+        Ok("Deleted 1 record".to_string())
+    }
+
+    async fn restore_course(&self, teacher_id: i32, course_id: i32, actor_id: i32) -> Result<Course, MyError> {
+        let mut tx = self.pool.begin().await?;
+        let teacher_deleted_at = sqlx::query_scalar::<_, Option<NaiveDateTime>>("SELECT deleted_at FROM teacher WHERE id = ?1")
+            .bind(teacher_id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| MyError::NotFound("NO teacher found".into()))?;
+        if teacher_deleted_at.is_some() {
+            return Err(MyError::Conflict("Teacher is deleted, restore the teacher first".into()));
+        }
+        let course = sqlx::query_as::<_, Course>(
+            "UPDATE course SET deleted_at = NULL WHERE teacher_id = ?1 AND id = ?2 AND deleted_at IS NOT NULL RETURNING *",
+        )
+        .bind(teacher_id)
+        .bind(course_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| MyError::NotFound("No deleted course found".into()))?;
+        insert_audit(&mut tx, AuditRecord::course(AuditAction::Restore, actor_id, None, Some(&course))).await?;
+        tx.commit().await?;
+        Ok(course)
+    }
+}
+
+#[async_trait]
+impl AuditRepository for SqliteStore {
+    async fn list_audit(&self, entity_type: EntityType, entity_id: i32) -> Result<Vec<AuditEntry>, MyError> {
+        let rows = sqlx::query_as::<_, AuditEntry>(
+            "SELECT * FROM audit_log WHERE entity_type = ?1 AND entity_id = ?2 ORDER BY id",
+        )
+        .bind(entity_type.as_str())
+        .bind(entity_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteStore {
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, MyError> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE username = ?1
+            AND NOT EXISTS (SELECT 1 FROM teacher t WHERE t.id = users.teacher_id AND t.deleted_at IS NOT NULL)",
+        )
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(user)
+    }
+
+    async fn get_user(&self, user_id: i32) -> Result<Option<User>, MyError> {
+        let user = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE id = ?1
+            AND NOT EXISTS (SELECT 1 FROM teacher t WHERE t.id = users.teacher_id AND t.deleted_at IS NOT NULL)",
+        )
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(user)
+    }
+
+    //老师资料和账号在同一个事务里创建
+    async fn register_teacher(&self, new_user: RegisterTeacher, password_hash: String) -> Result<User, MyError> {
+        let mut tx = self.pool.begin().await?;
+
+        let taken = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE username = ?1")
+            .bind(&new_user.username)
+            .fetch_optional(&mut tx)
+            .await?;
+        if taken.is_some() {
+            return Err(MyError::Conflict("Username is already taken".into()));
+        }
+
+        let teacher = sqlx::query_as::<_, Teacher>(
+            "INSERT INTO teacher (name, picture_url, profile) VALUES (?1, ?2, ?3) RETURNING *",
+        )
+        .bind(new_user.name)
+        .bind(new_user.picture_url)
+        .bind(new_user.profile)
+        .fetch_one(&mut tx)
+        .await?;
+
+        let user = sqlx::query_as::<_, User>(
+            "INSERT INTO users (username, password_hash, role, teacher_id) VALUES (?1, ?2, 'teacher', ?3)
+            RETURNING *",
+        )
+        .bind(new_user.username)
+        .bind(password_hash)
+        .bind(teacher.id)
+        .fetch_one(&mut tx)
+        .await?;
+
+        insert_audit(&mut tx, AuditRecord::teacher(AuditAction::Create, user.id, None, Some(&teacher))).await?;
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    //学生和账号在同一个事务里创建
+    async fn register_student(&self, new_user: RegisterStudent, password_hash: String) -> Result<User, MyError> {
+        let mut tx = self.pool.begin().await?;
+
+        let taken = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE username = ?1")
+            .bind(&new_user.username)
+            .fetch_optional(&mut tx)
+            .await?;
+        if taken.is_some() {
+            return Err(MyError::Conflict("Username is already taken".into()));
+        }
+        let registered = sqlx::query_scalar::<_, i32>("SELECT id FROM student WHERE email = ?1")
+            .bind(&new_user.email)
+            .fetch_optional(&mut tx)
+            .await?;
+        if registered.is_some() {
+            return Err(MyError::Conflict("Email is already registered".into()));
+        }
+
+        let student_id = sqlx::query_scalar::<_, i32>("INSERT INTO student (name, email) VALUES (?1, ?2) RETURNING id")
+            .bind(new_user.name)
+            .bind(new_user.email)
+            .fetch_one(&mut tx)
+            .await?;
+
+        let user = sqlx::query_as::<_, User>(
+            "INSERT INTO users (username, password_hash, role, student_id) VALUES (?1, ?2, 'student', ?3)
+            RETURNING *",
+        )
+        .bind(new_user.username)
+        .bind(password_hash)
+        .bind(student_id)
+        .fetch_one(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl EnrollmentRepository for SqliteStore {
+    //SQLite没有SELECT ... FOR UPDATE 容量检查和插入放在同一条语句里 写操作是串行的 人数不会超过容量
+    async fn enroll(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<Enrollment, MyError> {
+        self.get_course(teacher_id, course_id).await?;
+        let enrolled = sqlx::query_scalar::<_, i32>("SELECT student_id FROM enrollment WHERE course_id = ?1 AND student_id = ?2")
+            .bind(course_id)
+            .bind(student_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        if enrolled.is_some() {
+            return Err(MyError::AlreadyEnrolled("Already enrolled in this course".into()));
+        }
+
+        sqlx::query_as::<_, Enrollment>(
+            "INSERT INTO enrollment (course_id, student_id)
+            SELECT ?1, ?2 FROM course
+            WHERE id = ?1 AND deleted_at IS NULL AND (capacity IS NULL OR capacity > (SELECT COUNT(*) FROM enrollment WHERE course_id = ?1))
+            RETURNING *",
+        )
+        .bind(course_id)
+        .bind(student_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| MyError::CourseFull("Course is full".into()))
+    }
+
+    async fn withdraw(&self, teacher_id: i32, course_id: i32, student_id: i32) -> Result<String, MyError> {
+        self.get_course(teacher_id, course_id).await?;
+        let result = sqlx::query("DELETE FROM enrollment WHERE course_id = ?1 AND student_id = ?2")
+            .bind(course_id)
+            .bind(student_id)
+            .execute(&self.pool)
+            .await?;
+        match result.rows_affected() {
+            0 => Err(MyError::NotEnrolled("Not enrolled in this course".into())),
+            n => Ok(format!("Deleted {} record", n)),
+        }
+    }
+
+    async fn list_roster(&self, teacher_id: i32, course_id: i32) -> Result<Vec<Student>, MyError> {
+        self.get_course(teacher_id, course_id).await?;
+        let rows = sqlx::query_as::<_, Student>(
+            "SELECT s.* FROM student s
+            JOIN enrollment e ON e.student_id = s.id
+            WHERE e.course_id = ?1
+            ORDER BY e.enrolled_at, s.id",
+        )
+        .bind(course_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    async fn list_student_courses(&self, student_id: i32) -> Result<Vec<Course>, MyError> {
+        let rows = sqlx::query_as::<_, Course>(
+            "SELECT c.* FROM course c
+            JOIN enrollment e ON e.course_id = c.id
+            WHERE e.student_id = ?1 AND c.deleted_at IS NULL
+            ORDER BY e.enrolled_at, c.id",
+        )
+        .bind(student_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::course::CourseSort;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    //内存数据库每个连接是独立的 连接池只保留一个连接
+    async fn store() -> SqliteStore {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db::run_sqlite_migrations(&pool).await.unwrap();
+        SqliteStore::new(pool)
+    }
+
+    fn course(teacher_id: i32, name: &str, price: i32) -> CreateCourse {
+        CreateCourse {
+            teacher_id,
+            name: name.into(),
+            description: Some(format!("About {}", name)),
+            format: None,
+            structure: None,
+            duration: None,
+            price: Some(price),
+            language: Some("English".into()),
+            level: None,
+            capacity: Some(1),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn sqlite_store_test() {
+        let store = store().await;
+        let user = store
+            .register_teacher(
+                RegisterTeacher {
+                    username: "alice".into(),
+                    password: String::new(),
+                    name: "Alice".into(),
+                    picture_url: "https://example.com/a.png".into(),
+                    profile: "Rust".into(),
+                },
+                "hash".into(),
+            )
+            .await
+            .unwrap();
+        let teacher_id = user.teacher_id.unwrap();
+        for (name, price) in [("Rust basics", 100), ("Async Rust", 300), ("Web 100%", 200)] {
+            store.create_course(course(teacher_id, name, price), user.id).await.unwrap();
+        }
+
+        let query = CourseQuery {
+            sort: CourseSort::PriceDesc,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = store.list_courses(Some(teacher_id), &query).await.unwrap();
+        assert_eq!(page.total, 3);
+        let names: Vec<_> = page.items.iter().map(|course| course.name.as_str()).collect();
+        assert_eq!(names, ["Async Rust", "Web 100%"]);
+        let query = CourseQuery {
+            cursor: page.next_cursor,
+            ..query
+        };
+        let page = store.list_courses(None, &query).await.unwrap();
+        assert_eq!(page.items[0].name, "Rust basics");
+        assert_eq!(page.next_cursor, None);
+
+        //%按普通字符搜索
+        let query = CourseQuery {
+            q: Some("100%".into()),
+            ..Default::default()
+        };
+        assert_eq!(store.list_courses(None, &query).await.unwrap().total, 1);
+
+        //每门课程只能选一个学生
+        let student = store
+            .register_student(
+                RegisterStudent {
+                    username: "dave".into(),
+                    password: String::new(),
+                    name: "Dave".into(),
+                    email: "dave@example.com".into(),
+                },
+                "hash".into(),
+            )
+            .await
+            .unwrap();
+        let student_id = student.student_id.unwrap();
+        let course_id = page.items[0].id;
+        store.enroll(teacher_id, course_id, student_id).await.unwrap();
+        assert!(matches!(
+            store.enroll(teacher_id, course_id, student_id).await,
+            Err(MyError::AlreadyEnrolled(_))
+        ));
+        assert!(matches!(
+            store.enroll(teacher_id + 1, course_id, student_id).await,
+            Err(MyError::NotFound(_))
+        ));
+        assert_eq!(store.list_roster(teacher_id, course_id).await.unwrap()[0].name, "Dave");
+        assert_eq!(store.list_student_courses(student_id).await.unwrap()[0].name, "Rust basics");
+        sqlx::query("INSERT INTO student (name, email) VALUES ('Erin', 'erin@example.com')")
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.enroll(teacher_id, course_id, student_id + 1).await,
+            Err(MyError::CourseFull(_))
+        ));
+        store.withdraw(teacher_id, course_id, student_id).await.unwrap();
+        assert!(matches!(
+            store.withdraw(teacher_id, course_id, student_id).await,
+            Err(MyError::NotEnrolled(_))
+        ));
+        store.enroll(teacher_id, course_id, student_id).await.unwrap();
+
+        //删除老师时课程和账号一起隐藏 选课记录保留
+        assert_eq!(
+            store.delete_teacher(teacher_id, user.id).await.unwrap(),
+            "Deleted teacher and 3 courses"
+        );
+        assert_eq!(store.list_courses(None, &CourseQuery::default()).await.unwrap().total, 0);
+        assert!(store.find_user_by_username("alice").await.unwrap().is_none());
+        assert!(store.list_student_courses(student_id).await.unwrap().is_empty());
+        assert!(matches!(
+            store.create_course(course(teacher_id, "Late", 100), user.id).await,
+            Err(MyError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            store.restore_course(teacher_id, course_id, user.id).await,
+            Err(MyError::Conflict(_))
+        ));
+
+        //恢复老师时一起删除的课程也恢复
+        store.restore_teacher(teacher_id, user.id).await.unwrap();
+        assert_eq!(store.list_courses(None, &CourseQuery::default()).await.unwrap().total, 3);
+        assert!(store.find_user_by_username("alice").await.unwrap().is_some());
+        assert_eq!(store.list_student_courses(student_id).await.unwrap()[0].name, "Rust basics");
+        assert!(matches!(
+            store.restore_teacher(teacher_id, user.id).await,
+            Err(MyError::NotFound(_))
+        ));
+
+        //单独删除和恢复课程 每次修改都有记录
+        store.delete_course(teacher_id, course_id, user.id).await.unwrap();
+        assert!(matches!(store.get_course(teacher_id, course_id).await, Err(MyError::NotFound(_))));
+        store.restore_course(teacher_id, course_id, user.id).await.unwrap();
+        let actions: Vec<_> = store
+            .list_audit(EntityType::Course, course_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.action)
+            .collect();
+        assert_eq!(actions, ["create", "delete", "restore", "delete", "restore"]);
+        let history = store.list_audit(EntityType::Teacher, teacher_id).await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].actor_id, Some(user.id));
+        assert_eq!(history[1].old_value.as_ref().unwrap()["name"], "Alice");
+        assert!(history[1].new_value.is_none());
+    }
+
+    //数据库约束错误转换成对应的MyError 见error.rs
+    #[actix_rt::test]
+    async fn constraint_error_test() {
+        let store = store().await;
+        match store.create_course(course(42, "Orphan", 100), 1).await {
+            Err(MyError::InvalidInput(err)) => assert!(err.fields.contains_key("reference")),
+            other => panic!("expected invalid input, got {:?}", other),
+        }
+
+        let insert_student = || {
+            sqlx::query("INSERT INTO student (name, email) VALUES ('Erin', 'erin@example.com')").execute(&store.pool)
+        };
+        insert_student().await.unwrap();
+        match insert_student().await.map_err(MyError::from) {
+            Err(MyError::Conflict(msg)) => assert_eq!(msg, "email is already taken"),
+            other => panic!("expected conflict, got {:?}", other),
+        }
+
+        let teacher = store
+            .create_teacher(CreateTeacher {
+                name: "Alice".into(),
+                picture_url: "https://example.com/a.png".into(),
+                profile: "Rust".into(),
+            }, 1)
+            .await
+            .unwrap();
+        let err = sqlx::query("INSERT INTO course (teacher_id, name, capacity) VALUES (?1, 'Full', -1)")
+            .bind(teacher.id)
+            .execute(&store.pool)
+            .await
+            .map_err(MyError::from)
+            .unwrap_err();
+        assert_eq!(err.code(), "validation_failed");
+
+        let err = sqlx::query_as::<_, Teacher>("SELECT * FROM teacher WHERE id = 99")
+            .fetch_one(&store.pool)
+            .await
+            .map_err(MyError::from)
+            .unwrap_err();
+        assert_eq!(err.code(), "not_found");
+    }
+}
@@ -1,30 +1,110 @@
-use crate::handlers::{course, general, teacher};
-use actix_web::web;
+use crate::error::MyError;
+use crate::handlers::{audit, auth, course, general, student, teacher};
+use crate::openapi;
+use actix_web::http::Method;
+use actix_web::{web, FromRequest, Handler, Responder, Route};
+#[cfg(feature = "swagger-ui")]
+use utoipa_swagger_ui::{Config, SwaggerUi};
 
-//配置路由规则
+//服务和测试使用同样的配置: 所有路由以及请求数据解析失败时的错误格式
+pub fn app_config(cfg: &mut web::ServiceConfig) {
+    cfg
+        .app_data(web::JsonConfig::default().error_handler(|err, _req| MyError::from(err).into()))
+        .app_data(web::PathConfig::default().error_handler(|err, _req| MyError::from(err).into()))
+        .app_data(web::QueryConfig::default().error_handler(|err, _req| {
+            MyError::InvalidInput(format!("Invalid query parameters: {}", err).into()).into()
+        }));
+    for group in route_table() {
+        group.register(cfg);
+    }
+    //Swagger UI不是单独的路由 不放在路由表里
+    #[cfg(feature = "swagger-ui")]
+    cfg.service(SwaggerUi::new("/swagger-ui/{_:.*}").config(Config::from("/openapi.json")));
+}
 
-pub fn general_routes(cfg: &mut web::ServiceConfig) {
-    cfg.route("/health", web::get().to(general::health_check_handler));
+//配置路由规则 openapi的测试也用这张表检查每个路由都有文档
+pub fn route_table() -> Vec<RouteGroup> {
+    vec![
+        RouteGroup::new("")
+            .route(Method::GET, "/health", general::health_check_handler)
+            .route(Method::GET, "/metrics", general::metrics_handler),
+        //登录相关 不需要token
+        RouteGroup::new("/auth")
+            .route(Method::POST, "/register", auth::register)
+            .route(Method::POST, "/register/student", auth::register_student)
+            .route(Method::POST, "/login", auth::login)
+            .route(Method::POST, "/refresh", auth::refresh),
+        RouteGroup::new("/courses")
+            .route(Method::GET, "/", course::get_all_courses)
+            .route(Method::POST, "/", course::post_new_course)
+            .route(Method::GET, "/{teacher_id}", course::get_courses_for_teacher)
+            .route(Method::GET, "/{teacher_id}/{course_id}", course::get_course_detail)
+            .route(Method::DELETE, "/{teacher_id}/{course_id}", course::delete_course)
+            .route(Method::PUT, "/{teacher_id}/{course_id}", course::update_course_details)
+            .route(Method::POST, "/{teacher_id}/{course_id}/restore", course::restore_course)
+            .route(Method::POST, "/{teacher_id}/{course_id}/enrollment", student::enroll)
+            .route(Method::DELETE, "/{teacher_id}/{course_id}/enrollment", student::withdraw)
+            .route(Method::GET, "/{teacher_id}/{course_id}/students", student::get_course_roster),
+        RouteGroup::new("/teacher")
+            .route(Method::GET, "/", teacher::get_all_teachers)
+            .route(Method::POST, "/", teacher::post_new_teacher)
+            .route(Method::GET, "/{teacher_id}", teacher::get_teacher_details)
+            .route(Method::PUT, "/{teacher_id}", teacher::update_teacher_details)
+            .route(Method::DELETE, "/{teacher_id}", teacher::delete_teacher)
+            .route(Method::POST, "/{teacher_id}/restore", teacher::restore_teacher),
+        //学生
+        RouteGroup::new("/students")
+            .route(Method::GET, "/{student_id}/courses", student::get_student_courses),
+        //修改记录 只有管理员可以查看
+        RouteGroup::new("/audit")
+            .route(Method::GET, "/{entity_type}/{entity_id}", audit::get_audit_log),
+        //接口文档 启用swagger-ui feature时在/swagger-ui/提供Swagger UI 页面读取/openapi.json
+        RouteGroup::new("")
+            .route(Method::GET, "/openapi.json", openapi::openapi_json),
+    ]
 }
 
-pub fn course_routes(cfg: &mut web::ServiceConfig) {
-    cfg
-        .service(web::scope("/courses")
-            .route("/", web::post().to(course::post_new_course))
-            .route("/{teacher_id}", web::get().to(course::get_courses_for_teacher))
-            .route("/{teacher_id}/{course_id}", web::get().to(course::get_course_detail))
-            .route("/{teacher_id}/{course_id}", web::delete().to(course::delete_course))
-            .route("/{teacher_id}/{course_id}", web::put().to(course::update_course_details))
-        );
+//同一个web::scope下的路由 scope为空时直接注册在根路径下
+pub struct RouteGroup {
+    scope: &'static str,
+    routes: Vec<(Method, &'static str, Route)>,
 }
 
-pub fn teacher_routes(cfg: &mut web::ServiceConfig) {
-    cfg
-        .service(web::scope("/teacher")
-            .route("/", web::get().to(teacher::get_all_teachers))
-            .route("/", web::post().to(teacher::post_new_teacher))
-            .route("/{teacher_id}", web::get().to(teacher::get_teacher_details))
-            .route("/{teacher_id}", web::put().to(teacher::update_teacher_details))
-            .route("/{teacher_id}", web::delete().to(teacher::delete_teacher))
-        );
-}
\ No newline at end of file
+impl RouteGroup {
+    fn new(scope: &'static str) -> Self {
+        RouteGroup { scope, routes: Vec::new() }
+    }
+
+    fn route<F, Args>(mut self, method: Method, path: &'static str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        let route = web::method(method.clone()).to(handler);
+        self.routes.push((method, path, route));
+        self
+    }
+
+    //方法和加上scope前缀的路径 和文档里的格式一样 例如 GET /courses/{teacher_id}
+    #[cfg(test)]
+    pub fn paths(&self) -> impl Iterator<Item = String> + '_ {
+        self.routes
+            .iter()
+            .map(move |(method, path, _)| format!("{} {}{}", method, self.scope, path))
+    }
+
+    fn register(self, cfg: &mut web::ServiceConfig) {
+        if self.scope.is_empty() {
+            for (_, path, route) in self.routes {
+                cfg.route(path, route);
+            }
+        } else {
+            let mut scope = web::scope(self.scope);
+            for (_, path, route) in self.routes {
+                scope = scope.route(path, route);
+            }
+            cfg.service(scope);
+        }
+    }
+}
@@ -1,11 +1,50 @@
-use std::sync::Mutex;
-use sqlx::postgres::PgPool;
+use crate::auth::AuthConfig;
+use crate::metrics::Metrics;
+use crate::repository::{AuditRepository, CourseRepository, EnrollmentRepository, PoolStatus, TeacherRepository, UserRepository};
+use std::sync::Arc;
 
-//Mutex保证线程安全
+//所有worker共享的状态 存储和指标自身保证线程安全
 pub struct AppState {
     pub health_check_response: String,
-    pub visit_count: Mutex<u32>,
     // pub courses:Mutex<Vec<Course>>,
-    // 原先是用内存存储 即Vec 
-    pub db:PgPool,
-}
\ No newline at end of file
+    // 原先是用内存存储 即Vec 后来改成直接使用PgPool
+    // 现在通过repository的trait访问 可以是Postgres、SQLite或内存存储
+    pub teachers: Arc<dyn TeacherRepository>,
+    pub courses: Arc<dyn CourseRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub enrollments: Arc<dyn EnrollmentRepository>,
+    // 老师和课程的修改记录
+    pub audits: Arc<dyn AuditRepository>,
+    // 签发和校验JWT
+    pub auth: AuthConfig,
+    // 请求数 耗时和错误数 由中间件记录 /metrics导出
+    pub metrics: Metrics,
+    pub pool: Arc<dyn PoolStatus>,
+}
+
+impl AppState {
+    //同一个存储同时提供老师、课程、账号、选课、修改记录和连接池状态
+    pub fn new<S>(store: S, auth: AuthConfig) -> Self
+    where
+        S: TeacherRepository
+            + CourseRepository
+            + UserRepository
+            + EnrollmentRepository
+            + AuditRepository
+            + PoolStatus
+            + 'static,
+    {
+        let store = Arc::new(store);
+        AppState {
+            health_check_response: "I'm ok".to_string(),
+            teachers: store.clone(),
+            courses: store.clone(),
+            users: store.clone(),
+            enrollments: store.clone(),
+            audits: store.clone(),
+            pool: store,
+            auth,
+            metrics: Metrics::new(),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+#![no_main]
+//对请求解析器做模糊测试 任意输入都只能返回Ok或Err 不能panic
+use http::httprequest::HttpRequest;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(req) = HttpRequest::parse(data) {
+        //解析成功的请求 头部名一定是小写
+        assert!(req.headers.keys().all(|k| k.bytes().all(|b| !b.is_ascii_uppercase())));
+    }
+});
@@ -0,0 +1,476 @@
+//client模块 简单的阻塞式HTTP/1.1客户端 只支持http://
+//用HttpRequest::write_to发送请求 用HttpResponse::from_head加transfer模块读取响应
+//支持跟随重定向、超时和keep-alive连接复用(每个host:port保留少量空闲连接)
+use super::httprequest::{HttpRequest, Method, ParseError};
+use super::httpresponse::HttpResponse;
+use super::statuscode::StatusCode;
+use super::transfer::{self, BodyLength};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+//每个host:port最多保留的空闲连接数
+const MAX_IDLE_PER_HOST: usize = 4;
+//重定向到其他host:port时不再发送的头部 避免把凭据泄露给第三方
+const SENSITIVE_HEADERS: [&str; 3] = ["Authorization", "Cookie", "Proxy-Authorization"];
+
+#[derive(Debug)]
+pub enum ClientError {
+    InvalidUrl(String),            //不是 http://host[:port]/path 的格式
+    Io(io::Error),                 //连接失败、超时或者连接被中断
+    InvalidResponse(ParseError),   //服务器返回的不是合法的HTTP响应
+    TooManyRedirects(usize),       //超过了允许的重定向次数
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::InvalidUrl(url) => write!(f, "invalid url: {}", url),
+            ClientError::Io(e) => write!(f, "i/o error: {}", e),
+            ClientError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+            ClientError::TooManyRedirects(n) => write!(f, "too many redirects ({})", n),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+impl From<ParseError> for ClientError {
+    fn from(e: ParseError) -> Self {
+        ClientError::InvalidResponse(e)
+    }
+}
+
+//拆开的http url
+#[derive(Debug, PartialEq, Clone)]
+struct Url {
+    host: String,
+    port: u16,
+    target: String, //路径和查询字符串 至少是"/"
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Url, ClientError> {
+        let invalid = || ClientError::InvalidUrl(url.to_string());
+        let scheme_end = url.find("://").ok_or_else(invalid)?;
+        if !url[..scheme_end].eq_ignore_ascii_case("http") {
+            return Err(invalid());
+        }
+        let rest = &url[scheme_end + 3..];
+        //片段标识符不发给服务器
+        let rest = rest.split('#').next().unwrap_or("");
+        let (authority, target) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('/') => (&rest[..i], rest[i..].to_string()),
+            Some(i) => (&rest[..i], format!("/{}", &rest[i..])),
+            None => (rest, "/".to_string()),
+        };
+
+        //IPv6地址写在方括号里 例如 [::1]:8080
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, port.parse::<u16>().map_err(|_| invalid())?),
+            _ => (authority, 80),
+        };
+        if host.is_empty() || host.contains(['@', ' ']) {
+            return Err(invalid());
+        }
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            target,
+        })
+    }
+
+    //连接池的键 同时也是Host头部(默认端口省略)
+    fn authority(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    fn host_header(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            self.authority()
+        }
+    }
+
+    //按Location头部计算重定向的目标 支持绝对url、//host/path、/path和相对路径
+    fn join(&self, location: &str) -> Result<Url, ClientError> {
+        if location.contains("://") {
+            return Url::parse(location);
+        }
+        if let Some(rest) = location.strip_prefix("//") {
+            return Url::parse(&format!("http://{}", rest));
+        }
+        let target = if location.starts_with('/') {
+            location.to_string()
+        } else {
+            //相对路径 替换当前路径的最后一段
+            let path = self.target.split('?').next().unwrap_or("/");
+            let dir = &path[..path.rfind('/').map(|i| i + 1).unwrap_or(0)];
+            format!("{}{}", dir, location)
+        };
+        Ok(Url {
+            target,
+            ..self.clone()
+        })
+    }
+}
+
+type Connection = BufReader<TcpStream>;
+
+pub struct Client {
+    timeout: Duration,
+    max_redirects: usize,
+    idle: Mutex<HashMap<String, Vec<Connection>>>, //host:port -> 空闲的keep-alive连接
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Client {
+            timeout: DEFAULT_TIMEOUT,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    //连接、读、写各自的超时时间
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    //为0时不跟随重定向 直接返回3xx响应
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn get(&self, url: &str) -> Result<HttpResponse, ClientError> {
+        self.send(Method::Get, url, &[], Vec::new())
+    }
+
+    pub fn post(&self, url: &str, content_type: &str, body: impl Into<Vec<u8>>) -> Result<HttpResponse, ClientError> {
+        self.send(Method::Post, url, &[("Content-Type", content_type)], body.into())
+    }
+
+    //发送任意方法的请求 自动加上Host头部并跟随重定向
+    //301/302/303重定向改用GET且不带消息体 307/308保持原方法和消息体
+    //重定向离开最初的host:port后 不再发送Authorization、Cookie等凭据头部
+    pub fn send(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<HttpResponse, ClientError> {
+        let mut url = Url::parse(url)?;
+        let origin = url.authority();
+        let mut method = method;
+        let mut body = body;
+        let mut redirects = 0;
+        loop {
+            let mut req = HttpRequest::new(method, &url.target).map_err(|_| ClientError::InvalidUrl(url.target.clone()))?;
+            req.set_header("Host", url.host_header());
+            let same_origin = url.authority().eq_ignore_ascii_case(&origin);
+            for (name, value) in headers {
+                if !same_origin && SENSITIVE_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+                    continue;
+                }
+                req.set_header(name, *value);
+            }
+            req.body = body.clone();
+
+            let resp = self.execute(&url, &req)?;
+            let location = match resp.status_code() {
+                status if status.is_redirection() && status != StatusCode::NotModified => resp.header("Location"),
+                _ => None,
+            };
+            let Some(location) = location else {
+                return Ok(resp);
+            };
+            if redirects == self.max_redirects {
+                if self.max_redirects == 0 {
+                    return Ok(resp);
+                }
+                return Err(ClientError::TooManyRedirects(redirects));
+            }
+            redirects += 1;
+
+            url = url.join(location)?;
+            if !matches!(resp.status_code(), StatusCode::TemporaryRedirect | StatusCode::PermanentRedirect)
+                && method != Method::Head
+            {
+                method = Method::Get;
+                body = Vec::new();
+            }
+        }
+    }
+
+    //在一个连接上完成一次请求/响应 优先复用空闲连接
+    //空闲连接可能已经被服务器关闭 幂等请求在还没收到任何响应时换新连接重试一次
+    fn execute(&self, url: &Url, req: &HttpRequest) -> Result<HttpResponse, ClientError> {
+        let key = url.authority();
+        if let Some(conn) = self.take_idle(&key) {
+            match self.exchange(conn, &key, req) {
+                Err(ClientError::Io(e)) if is_stale(&e) && is_idempotent(req.method) => {}
+                result => return result,
+            }
+        }
+        let conn = self.connect(url)?;
+        self.exchange(conn, &key, req)
+    }
+
+    fn exchange(&self, mut conn: Connection, key: &str, req: &HttpRequest) -> Result<HttpResponse, ClientError> {
+        req.write_to(conn.get_mut())?;
+        conn.get_mut().flush()?;
+
+        //跳过100 Continue之类的临时响应
+        let (head, mut resp) = loop {
+            let head = transfer::read_head(&mut conn)?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed before response"))?;
+            let resp = HttpResponse::from_head(&head)?;
+            if resp.status_code().as_u16() >= 200 {
+                break (head, resp);
+            }
+        };
+        let length = transfer::response_body_length(&head, resp.status_code().as_u16(), req.method == Method::Head)?;
+        let reusable = length != BodyLength::UntilClose && transfer::is_keep_alive(&head);
+        let body = transfer::read_body(&mut conn, length)?;
+        if !body.is_empty() {
+            resp.set_body(body);
+        }
+
+        if reusable {
+            self.put_idle(key, conn);
+        }
+        Ok(resp)
+    }
+
+    fn connect(&self, url: &Url) -> Result<Connection, ClientError> {
+        let host = url.host.trim_start_matches('[').trim_end_matches(']');
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, format!("no address for {}", url.host));
+        for addr in (host, url.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    return Ok(BufReader::new(stream));
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err.into())
+    }
+
+    fn take_idle(&self, key: &str) -> Option<Connection> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner()).get_mut(key)?.pop()
+    }
+
+    fn put_idle(&self, key: &str, conn: Connection) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let conns = idle.entry(key.to_string()).or_default();
+        if conns.len() < MAX_IDLE_PER_HOST {
+            conns.push(conn);
+        }
+    }
+}
+
+//连接在发送请求或读取响应头之前就已经断开
+fn is_stale(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+fn is_idempotent(method: Method) -> bool {
+    !matches!(method, Method::Post | Method::Patch | Method::Connect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    //最简单的测试服务器: 每个连接上循环读取请求 用respond生成响应原文
+    //返回地址和已接受的连接数
+    fn serve(respond: fn(&HttpRequest) -> String) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&connections);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut reader = BufReader::new(stream.unwrap());
+                thread::spawn(move || {
+                    while let Ok(Some(head)) = transfer::read_head(&mut reader) {
+                        let mut req = HttpRequest::from_head(&head).unwrap();
+                        req.body = transfer::read_body(&mut reader, transfer::body_length(&head).unwrap()).unwrap();
+                        let raw = respond(&req);
+                        reader.get_mut().write_all(raw.as_bytes()).unwrap();
+                        if raw.contains("Connection: close") {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        (addr, connections)
+    }
+
+    fn ok(body: &str) -> String {
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    #[test]
+    fn test_url_parse_and_join() {
+        let url = Url::parse("http://localhost:3001/a/b?c=d#frag").unwrap();
+        assert_eq!((url.host.as_str(), url.port, url.target.as_str()), ("localhost", 3001, "/a/b?c=d"));
+        assert_eq!(Url::parse("http://example.com").unwrap().target, "/");
+        assert_eq!(Url::parse("http://[::1]:8080/x").unwrap().host, "[::1]");
+        assert_eq!(Url::parse("http://example.com?x=1").unwrap().target, "/?x=1");
+        for bad in ["ftp://a/b", "localhost:3001", "http://:80/", "http://host:abc/"] {
+            assert!(matches!(Url::parse(bad), Err(ClientError::InvalidUrl(_))), "{}", bad);
+        }
+
+        assert_eq!(url.join("/root").unwrap().target, "/root");
+        assert_eq!(url.join("sibling").unwrap().target, "/a/sibling");
+        assert_eq!(url.join("http://other:81/x").unwrap().authority(), "other:81");
+        assert_eq!(url.join("//other/y").unwrap().port, 80);
+    }
+
+    #[test]
+    fn test_get_and_post() {
+        let (addr, _) = serve(|req| {
+            let body = format!("{} {} host={}", req.method, req.body_str().unwrap(), req.header("Host").unwrap());
+            ok(&body)
+        });
+        let client = Client::new();
+        let resp = client.get(&format!("{}/x", addr)).unwrap();
+        assert_eq!(resp.status_code(), StatusCode::Ok);
+        assert!(resp.body().starts_with(b"GET  host=127.0.0.1:"));
+
+        let resp = client.post(&format!("{}/x", addr), "text/plain", "hello").unwrap();
+        assert!(resp.body().starts_with(b"POST hello host="));
+    }
+
+    #[test]
+    fn test_keep_alive_connections_are_reused() {
+        let (addr, connections) = serve(|_| ok("pong"));
+        let client = Client::new();
+        for _ in 0..3 {
+            assert_eq!(client.get(&addr).unwrap().body(), b"pong");
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_follow_redirects() {
+        let (addr, _) = serve(|req| match req.target().as_str() {
+            "/old" => "HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\nContent-Length: 0\r\n\r\n".into(),
+            "/loop" => "HTTP/1.1 302 Found\r\nLocation: /loop\r\nContent-Length: 0\r\n\r\n".into(),
+            "/new" => ok(&format!("{} at new", req.method)),
+            _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".into(),
+        });
+        let client = Client::new().max_redirects(3);
+        //POST被301重定向后改用GET
+        let resp = client.post(&format!("{}/old", addr), "text/plain", "x").unwrap();
+        assert_eq!(resp.body(), b"GET at new");
+        assert!(matches!(client.get(&format!("{}/loop", addr)), Err(ClientError::TooManyRedirects(3))));
+
+        let resp = Client::new().max_redirects(0).get(&format!("{}/old", addr)).unwrap();
+        assert_eq!(resp.status_code(), StatusCode::MovedPermanently);
+    }
+
+    #[test]
+    fn test_credentials_not_sent_to_other_host() {
+        //第二个服务器记录收到的凭据头部
+        let (other, _) = serve(|req| {
+            let auth = req.header("Authorization").unwrap_or("-");
+            let cookie = req.header("Cookie").unwrap_or("-");
+            ok(&format!("{} {} {}", auth, cookie, req.header("X-Trace").unwrap_or("-")))
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let head = transfer::read_head(&mut reader).unwrap().unwrap();
+                let req = HttpRequest::from_head(&head).unwrap();
+                let raw = match req.target().as_str() {
+                    "/away" => format!("HTTP/1.1 302 Found\r\nLocation: {}/x\r\nConnection: close\r\n\r\n", other),
+                    _ => ok(&format!("{} {}", req.header("Authorization").unwrap_or("-"), req.header("Cookie").unwrap_or("-"))),
+                };
+                reader.get_mut().write_all(raw.as_bytes()).unwrap();
+            }
+        });
+
+        let client = Client::new();
+        let headers = [("Authorization", "Bearer secret"), ("Cookie", "sid=1"), ("X-Trace", "t1")];
+        let resp = client.send(Method::Get, &format!("{}/home", addr), &headers, Vec::new()).unwrap();
+        assert_eq!(resp.body(), b"Bearer secret sid=1");
+        let resp = client.send(Method::Get, &format!("{}/away", addr), &headers, Vec::new()).unwrap();
+        assert_eq!(resp.body(), b"- - t1");
+    }
+
+    #[test]
+    fn test_read_until_close_and_timeout() {
+        let (addr, _) = serve(|req| match req.target().as_str() {
+            "/close" => "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nbody until close".into(),
+            _ => {
+                thread::sleep(Duration::from_millis(500));
+                ok("late")
+            }
+        });
+        let client = Client::new().timeout(Duration::from_millis(100));
+        assert_eq!(client.get(&format!("{}/close", addr)).unwrap().body(), b"body until close");
+        match client.get(&format!("{}/slow", addr)) {
+            Err(ClientError::Io(e)) => assert!(matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)),
+            other => panic!("expected timeout, got {:?}", other.map(|r| r.status_code())),
+        }
+    }
+
+    #[test]
+    fn test_stale_idle_connection_is_retried() {
+        //每个响应之后服务器都关闭连接 但没有发送Connection: close
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).unwrap();
+                stream.write_all(ok("fresh").as_bytes()).unwrap();
+            }
+        });
+        let client = Client::new();
+        for _ in 0..3 {
+            assert_eq!(client.get(&addr).unwrap().body(), b"fresh");
+        }
+    }
+}
@@ -0,0 +1,613 @@
+//表单解析 支持application/x-www-form-urlencoded和multipart/form-data
+//multipart按块从Read中读取 文件部分一边读一边写进指定目录的临时文件 不会整个放进内存
+//每个文件、每个普通字段和部分的数量都有上限 超过时返回TooLarge 已经写出的临时文件会被删除
+use super::httprequest::{percent_decode, ParseError};
+use super::transfer;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const URLENCODED: &str = "application/x-www-form-urlencoded";
+pub const MULTIPART: &str = "multipart/form-data";
+
+//一次从连接读取的字节数
+const READ_CHUNK: usize = 8 * 1024;
+//每个部分的头部上限
+const MAX_PART_HEAD: usize = 8 * 1024;
+
+//同一进程内临时文件名的序号
+static NEXT_UPLOAD: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub enum FormError {
+    UnsupportedContentType(String), //请求的Content-Type不是要求的表单类型
+    Malformed(String),              //消息体不符合表单格式
+    InvalidEncoding,                //非法的百分号编码或者非UTF-8字段
+    TooLarge(String),               //超过了MultipartLimits中的某个上限
+    Missing(String),                //没有这个字段
+    InvalidValue(String, String),   //字段的值不能转换成要求的类型 (字段名, 值)
+    Io(io::Error),                  //读取消息体或者写临时文件出错
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormError::UnsupportedContentType(ct) => write!(f, "unsupported content type: {:?}", ct),
+            FormError::Malformed(msg) => write!(f, "malformed form: {}", msg),
+            FormError::InvalidEncoding => write!(f, "invalid form encoding"),
+            FormError::TooLarge(what) => write!(f, "{} is too large", what),
+            FormError::Missing(name) => write!(f, "missing field '{}'", name),
+            FormError::InvalidValue(name, value) => write!(f, "invalid value {:?} for field '{}'", value, name),
+            FormError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FormError {}
+
+impl From<io::Error> for FormError {
+    fn from(e: io::Error) -> Self {
+        //流式读取消息体时超过了服务器给的上限
+        if transfer::is_body_too_large(&e) {
+            return FormError::TooLarge("request body".into());
+        }
+        FormError::Io(e)
+    }
+}
+
+impl From<ParseError> for FormError {
+    fn from(_: ParseError) -> Self {
+        FormError::InvalidEncoding
+    }
+}
+
+//普通字段 保持提交时的顺序 同名字段可以出现多次(例如多选框)
+#[derive(Debug, Default, PartialEq)]
+pub struct Form {
+    fields: Vec<(String, String)>,
+}
+
+impl Form {
+    //第一个同名字段的值
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.fields
+            .iter()
+            .filter(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    //必填字段 转换成需要的类型 例如 form.value::<u32>("quantity")
+    pub fn value<T: FromStr>(&self, name: &str) -> Result<T, FormError> {
+        let value = self.get(name).ok_or_else(|| FormError::Missing(name.to_string()))?;
+        value
+            .parse()
+            .map_err(|_| FormError::InvalidValue(name.to_string(), value.to_string()))
+    }
+
+    //选填字段 没有或者为空时返回None
+    pub fn optional<T: FromStr>(&self, name: &str) -> Result<Option<T>, FormError> {
+        match self.get(name) {
+            None | Some("") => Ok(None),
+            Some(_) => self.value(name).map(Some),
+        }
+    }
+
+    //复选框 出现即为true 值为false/off/0时为false
+    pub fn flag(&self, name: &str) -> bool {
+        self.get(name)
+            .is_some_and(|v| !matches!(v.to_ascii_lowercase().as_str(), "false" | "off" | "0"))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    fn push(&mut self, name: String, value: String) {
+        self.fields.push((name, value));
+    }
+}
+
+//解析 a=1&b=2 形式的表单 '+'表示空格 和查询字符串的区别是保留同名字段
+pub fn parse_urlencoded(body: &[u8]) -> Result<Form, FormError> {
+    let body = std::str::from_utf8(body).map_err(|_| FormError::InvalidEncoding)?;
+    let mut form = Form::default();
+    for pair in body.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        form.push(percent_decode(key, true)?, percent_decode(value, true)?);
+    }
+    Ok(form)
+}
+
+//multipart的各项上限
+#[derive(Debug, Clone)]
+pub struct MultipartLimits {
+    max_file_size: u64,
+    max_files: usize,
+    max_field_size: usize,
+    max_parts: usize,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        MultipartLimits {
+            max_file_size: 10 * 1024 * 1024,
+            max_files: 16,
+            max_field_size: 64 * 1024,
+            max_parts: 128,
+        }
+    }
+}
+
+impl MultipartLimits {
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = count;
+        self
+    }
+
+    pub fn max_field_size(mut self, bytes: usize) -> Self {
+        self.max_field_size = bytes;
+        self
+    }
+
+    //文件和普通字段加在一起的数量
+    pub fn max_parts(mut self, count: usize) -> Self {
+        self.max_parts = count;
+        self
+    }
+}
+
+//上传的文件 内容在临时文件里 没有调用persist的话drop时删除临时文件
+#[derive(Debug)]
+pub struct UploadedFile {
+    pub field: String,
+    pub file_name: Option<String>, //客户端提供的原始文件名 不能直接用作路径
+    pub content_type: Option<String>,
+    pub size: u64,
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl UploadedFile {
+    //临时文件的位置
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    //把临时文件移动到dest 目标和临时文件要在同一个文件系统上
+    pub fn persist(mut self, dest: impl AsRef<Path>) -> io::Result<()> {
+        fs::rename(&self.path, dest)?;
+        self.persisted = true;
+        Ok(())
+    }
+
+    //去掉路径部分和危险字符之后的文件名 可以安全地拼接到目录后面
+    //只剩下点号或者空字符串时返回None
+    pub fn safe_file_name(&self) -> Option<String> {
+        let name = self.file_name.as_deref()?;
+        let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        let name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let name = name.trim_start_matches('.');
+        if name.is_empty() {
+            None
+        } else {
+            Some(name.to_string())
+        }
+    }
+}
+
+impl Drop for UploadedFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Multipart {
+    pub fields: Form,
+    pub files: Vec<UploadedFile>,
+}
+
+impl Multipart {
+    //第一个字段名为name的文件
+    pub fn file(&self, name: &str) -> Option<&UploadedFile> {
+        self.files.iter().find(|f| f.field == name)
+    }
+}
+
+//从Content-Type中取出boundary参数 不是multipart/form-data时返回None
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params.next()?.trim().eq_ignore_ascii_case(MULTIPART) {
+        return None;
+    }
+    params
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        .filter(|b| !b.is_empty() && b.len() <= 70)
+}
+
+//解析multipart消息体 文件部分写到dir下的临时文件
+//出错时已经创建的临时文件随UploadedFile一起删除
+pub fn parse_multipart(
+    reader: impl Read,
+    boundary: &str,
+    dir: &Path,
+    limits: &MultipartLimits,
+) -> Result<Multipart, FormError> {
+    //在开头补上CRLF 这样第一个分隔符和后面的格式一样都是 CRLF--boundary
+    let mut scanner = Scanner {
+        reader,
+        buf: b"\r\n".to_vec(),
+        eof: false,
+    };
+    let delimiter = format!("\r\n--{}", boundary).into_bytes();
+    let mut multipart = Multipart::default();
+
+    //第一个分隔符之前的内容(preamble)直接丢弃
+    scanner.copy_until(&delimiter, &mut io::sink(), u64::MAX, "preamble")?;
+    let mut parts = 0;
+    loop {
+        //分隔符后面是"--"表示结束 否则是CRLF 然后是下一个部分
+        let line = scanner.read_line(MAX_PART_HEAD)?;
+        let line = line.trim_end_matches([' ', '\t', '\r', '\n']);
+        if line == "--" {
+            return Ok(multipart);
+        }
+        if !line.is_empty() {
+            return Err(FormError::Malformed("unexpected data after boundary".into()));
+        }
+
+        parts += 1;
+        if parts > limits.max_parts {
+            return Err(FormError::TooLarge("number of form parts".into()));
+        }
+        let head = PartHead::read(&mut scanner)?;
+        match head.file_name {
+            //浏览器对没有选择文件的文件输入框发送空文件名和空内容
+            Some(file_name) if file_name.is_empty() => {
+                let what = format!("file field '{}'", head.name);
+                scanner.copy_until(&delimiter, &mut io::sink(), limits.max_file_size, &what)?;
+            }
+            Some(file_name) => {
+                if multipart.files.len() >= limits.max_files {
+                    return Err(FormError::TooLarge("number of uploaded files".into()));
+                }
+                let path = dir.join(format!(
+                    ".upload-{}-{}.tmp",
+                    process::id(),
+                    NEXT_UPLOAD.fetch_add(1, Ordering::SeqCst)
+                ));
+                let mut file = UploadedFile {
+                    field: head.name,
+                    file_name: Some(file_name),
+                    content_type: head.content_type,
+                    size: 0,
+                    persisted: false,
+                    path,
+                };
+                let mut out = File::options().write(true).create_new(true).open(&file.path)?;
+                //先放进列表 出错返回时临时文件也能被删除
+                let what = format!("file '{}'", file.file_name.as_deref().unwrap_or_default());
+                let result = scanner.copy_until(&delimiter, &mut out, limits.max_file_size, &what);
+                file.size = out.metadata().map(|m| m.len()).unwrap_or(0);
+                multipart.files.push(file);
+                result?;
+            }
+            None => {
+                let mut value = Vec::new();
+                let what = format!("field '{}'", head.name);
+                scanner.copy_until(&delimiter, &mut value, limits.max_field_size as u64, &what)?;
+                let value = String::from_utf8(value).map_err(|_| FormError::InvalidEncoding)?;
+                multipart.fields.push(head.name, value);
+            }
+        }
+    }
+}
+
+//部分的头部 只关心Content-Disposition和Content-Type
+struct PartHead {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+impl PartHead {
+    fn read<R: Read>(scanner: &mut Scanner<R>) -> Result<PartHead, FormError> {
+        let mut disposition = None;
+        let mut content_type = None;
+        let mut size = 0;
+        loop {
+            let line = scanner.read_line(MAX_PART_HEAD - size)?;
+            size += line.len();
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| FormError::Malformed(format!("invalid part header: {:?}", line)))?;
+            if key.trim().eq_ignore_ascii_case("Content-Disposition") {
+                disposition = Some(value.trim().to_string());
+            } else if key.trim().eq_ignore_ascii_case("Content-Type") {
+                content_type = Some(value.trim().to_string());
+            }
+        }
+
+        let disposition = disposition.ok_or_else(|| FormError::Malformed("part without Content-Disposition".into()))?;
+        let mut params = disposition_params(&disposition);
+        if !params.first().is_some_and(|(k, _)| k.eq_ignore_ascii_case("form-data")) {
+            return Err(FormError::Malformed("part is not form-data".into()));
+        }
+        let mut take = |name: &str| {
+            params
+                .iter()
+                .position(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|i| params.remove(i).1)
+        };
+        let name = take("name").ok_or_else(|| FormError::Malformed("part without a name".into()))?;
+        Ok(PartHead {
+            name,
+            file_name: take("filename"),
+            content_type,
+        })
+    }
+}
+
+//form-data; name="a;b"; filename="x.txt" 按不在引号里的分号切分 第一项是类型本身
+//浏览器把引号编码成%22 不使用反斜杠转义 所以反斜杠按普通字符处理(Windows路径)
+fn disposition_params(value: &str) -> Vec<(String, String)> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => items.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    items.push(current);
+    items
+        .into_iter()
+        .map(|item| match item.split_once('=') {
+            Some((k, v)) => (k.trim().to_string(), v.trim().to_string()),
+            None => (item.trim().to_string(), String::new()),
+        })
+        .collect()
+}
+
+//在字节流中查找分隔符 buf里只保留还没有处理的数据
+struct Scanner<R> {
+    reader: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Scanner<R> {
+    //读入更多数据 已经读到结尾时返回false
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        let start = self.buf.len();
+        self.buf.resize(start + READ_CHUNK, 0);
+        let n = self.reader.read(&mut self.buf[start..])?;
+        self.buf.truncate(start + n);
+        self.eof = n == 0;
+        Ok(n > 0)
+    }
+
+    //把分隔符之前的数据写入out 并跳过分隔符 写入超过limit字节时报错
+    //buf末尾可能是分隔符的前半截 所以保留最后delimiter.len() - 1个字节等下一次读入
+    fn copy_until(&mut self, delimiter: &[u8], out: &mut impl Write, limit: u64, what: &str) -> Result<(), FormError> {
+        let mut written = 0u64;
+        loop {
+            let found = find(&self.buf, delimiter);
+            let ready = match found {
+                Some(i) => i,
+                None => self.buf.len().saturating_sub(delimiter.len() - 1),
+            };
+            written += ready as u64;
+            if written > limit {
+                return Err(FormError::TooLarge(what.to_string()));
+            }
+            out.write_all(&self.buf[..ready])?;
+            if found.is_some() {
+                self.buf.drain(..ready + delimiter.len());
+                return Ok(());
+            }
+            self.buf.drain(..ready);
+            if !self.fill()? {
+                return Err(FormError::Malformed("missing closing boundary".into()));
+            }
+        }
+    }
+
+    //读取到换行为止(包含换行) 超过limit字节还没有换行时报错
+    fn read_line(&mut self, limit: usize) -> Result<String, FormError> {
+        loop {
+            if let Some(i) = self.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=i).collect();
+                return String::from_utf8(line).map_err(|_| FormError::InvalidEncoding);
+            }
+            if self.buf.len() > limit {
+                return Err(FormError::TooLarge("part header".into()));
+            }
+            if !self.fill()? {
+                return Err(FormError::Malformed("unexpected end of body".into()));
+            }
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //每次最多返回n个字节 检验分隔符被拆到两次读取之间的情况
+    struct Trickle<'a>(&'a [u8], usize);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(self.1).min(buf.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    const BODY: &str = "preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Hello; world\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"tags\"\r\n\
+        \r\n\
+        a\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"tags\"\r\n\
+        \r\n\
+        b\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"doc\"; filename=\"../../etc/pa ss.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        line one\r\n-XyZ\r\nline two --XyZ\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    #[test]
+    fn test_urlencoded_typed_accessors() {
+        let form = parse_urlencoded(b"name=Ada+Lovelace&age=36&tags=a&tags=b%26c&subscribe=on&note=").unwrap();
+        assert_eq!(form.get("name"), Some("Ada Lovelace"));
+        assert_eq!(form.value::<u32>("age").unwrap(), 36);
+        assert_eq!(form.get_all("tags"), ["a", "b&c"]);
+        assert!(form.flag("subscribe"));
+        assert!(!form.flag("missing"));
+        assert_eq!(form.optional::<u32>("note").unwrap(), None);
+        assert!(matches!(form.value::<u32>("name"), Err(FormError::InvalidValue(..))));
+        assert!(matches!(form.value::<u32>("missing"), Err(FormError::Missing(_))));
+        assert!(matches!(parse_urlencoded(b"a=%zz"), Err(FormError::InvalidEncoding)));
+    }
+
+    #[test]
+    fn test_boundary_from_content_type() {
+        assert_eq!(boundary("multipart/form-data; boundary=XyZ").as_deref(), Some("XyZ"));
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\"").as_deref(),
+            Some("a b")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("text/plain; boundary=XyZ"), None);
+    }
+
+    #[test]
+    fn test_multipart_fields_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        for chunk in [1, 3, 7, BODY.len()] {
+            let reader = Trickle(BODY.as_bytes(), chunk);
+            let multipart = parse_multipart(reader, "XyZ", dir.path(), &MultipartLimits::default()).unwrap();
+            assert_eq!(multipart.fields.get("title"), Some("Hello; world"));
+            assert_eq!(multipart.fields.get_all("tags"), ["a", "b"]);
+
+            let file = multipart.file("doc").unwrap();
+            assert_eq!(file.file_name.as_deref(), Some("../../etc/pa ss.txt"));
+            assert_eq!(file.safe_file_name().as_deref(), Some("pa_ss.txt"));
+            assert_eq!(file.content_type.as_deref(), Some("text/plain"));
+            //内容中不在行首的--XyZ不是分隔符
+            let content = "line one\r\n-XyZ\r\nline two --XyZ";
+            assert_eq!(fs::read_to_string(file.path()).unwrap(), content);
+            assert_eq!(file.size, content.len() as u64);
+        }
+        //没有persist的临时文件已经删除
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_multipart_limits_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let parse = |body: &str, limits: MultipartLimits| parse_multipart(body.as_bytes(), "XyZ", dir.path(), &limits);
+
+        let err = parse(BODY, MultipartLimits::default().max_file_size(10)).unwrap_err();
+        assert!(
+            matches!(&err, FormError::TooLarge(what) if what.contains("pa ss.txt")),
+            "{}",
+            err
+        );
+        assert!(matches!(
+            parse(BODY, MultipartLimits::default().max_field_size(4)),
+            Err(FormError::TooLarge(_))
+        ));
+        assert!(matches!(
+            parse(BODY, MultipartLimits::default().max_parts(3)),
+            Err(FormError::TooLarge(_))
+        ));
+        assert!(matches!(
+            parse(BODY, MultipartLimits::default().max_files(0)),
+            Err(FormError::TooLarge(_))
+        ));
+
+        //缺少结束分隔符
+        let truncated = &BODY[..BODY.find("--XyZ--").unwrap()];
+        assert!(matches!(
+            parse(truncated, MultipartLimits::default()),
+            Err(FormError::Malformed(_))
+        ));
+        let no_name = "--XyZ\r\nContent-Disposition: form-data\r\n\r\nx\r\n--XyZ--\r\n";
+        assert!(matches!(
+            parse(no_name, MultipartLimits::default()),
+            Err(FormError::Malformed(_))
+        ));
+
+        //出错时已经写出的临时文件也删除了
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_persist_keeps_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut multipart = parse_multipart(BODY.as_bytes(), "XyZ", dir.path(), &MultipartLimits::default()).unwrap();
+        let dest = dir.path().join("kept.txt");
+        multipart.files.remove(0).persist(&dest).unwrap();
+        assert!(fs::read_to_string(&dest).unwrap().starts_with("line one"));
+    }
+}
@@ -1,37 +1,96 @@
 //httprequest模块 请求模块
-//Method实现三个trait get post uninitialized
+//Method覆盖RFC 7231/5789定义的全部方法 其他方法为Uninitialized
 //Method通过实现From<&str>方法来返回不同的Method变体
+//HttpRequest::parse按照RFC 7230解析请求 格式错误时返回ParseError
+//HttpRequest::write_to把请求写成报文 供客户端使用
+use super::form::{self, Form, FormError, Multipart, MultipartLimits};
+use super::transfer;
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum Method {
     Get,
+    Head,
     Post,
+    Put,
+    Delete,
+    Patch,
+    Options,
+    Connect,
+    Trace,
     Uninitialized,
 }
 
 // 目的就一个 通过条件 获得不同的Method变体 http连接方法
+// 方法名区分大小写(RFC 7231 4.1)
 impl From<&str> for Method {
     fn from(s:&str) -> Method {
         match s {
             "GET" => Method::Get,
+            "HEAD" => Method::Head,
             "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "PATCH" => Method::Patch,
+            "OPTIONS" => Method::Options,
+            "CONNECT" => Method::Connect,
+            "TRACE" => Method::Trace,
             _ => Method::Uninitialized,
         }
     }
 }
 
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Options => "OPTIONS",
+            Method::Connect => "CONNECT",
+            Method::Trace => "TRACE",
+            Method::Uninitialized => "UNINITIALIZED",
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Version {
+    V1_0,
     V1_1,
     V2_0,
     Uninitialized,
 }
 
+impl Version {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::V1_0 => "HTTP/1.0",
+            Version::V1_1 => "HTTP/1.1",
+            Version::V2_0 => "HTTP/2.0",
+            Version::Uninitialized => "HTTP/?",
+        }
+    }
+}
+
 // 目的就一个 通过条件 获得不同的Version变体 http版本
 impl From<&str> for Version {
     fn from(s:&str) -> Version {
         match s {
+            "HTTP/1.0" => Version::V1_0,
             "HTTP/1.1" => Version::V1_1,
             "HTTP/2.0" => Version::V2_0,
             _=> Version::Uninitialized,
@@ -41,80 +100,407 @@ impl From<&str> for Version {
 
 #[derive(Debug, PartialEq)]
 pub enum Resource {
-    Path(String), // 路径
+    Path(String), // 路径 已经去掉查询字符串并做过百分号解码 编码的'/'(%2F)会被拒绝 所以按'/'切分的段和原始路径一致
+}
+
+//解析失败的原因
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    Empty,                  //没有任何内容
+    IncompleteHead,         //没有找到头部结束的空行
+    InvalidRequestLine,     //请求行不是 "方法 路径 版本" 的格式
+    InvalidStatusLine,      //状态行不是 "版本 状态码 原因短语" 的格式 或者状态码不在100-599
+    InvalidMethod,          //方法名包含非法字符
+    InvalidVersion,         //不是HTTP/x.y
+    InvalidHeader(String),  //某个头部行格式错误
+    InvalidEncoding,        //非法的百分号编码或者非UTF-8字符
+    InvalidBody(String),    //消息体长度与Content-Length/chunked编码不符
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty request"),
+            ParseError::IncompleteHead => write!(f, "incomplete request head"),
+            ParseError::InvalidRequestLine => write!(f, "invalid request line"),
+            ParseError::InvalidStatusLine => write!(f, "invalid status line"),
+            ParseError::InvalidMethod => write!(f, "invalid method"),
+            ParseError::InvalidVersion => write!(f, "invalid http version"),
+            ParseError::InvalidHeader(line) => write!(f, "invalid header line: {:?}", line),
+            ParseError::InvalidEncoding => write!(f, "invalid percent-encoding"),
+            ParseError::InvalidBody(msg) => write!(f, "invalid body: {}", msg),
+        }
+    }
 }
 
+impl std::error::Error for ParseError {}
+
+//查询参数 同名参数的值按出现顺序保存
+pub type Query = HashMap<String, Vec<String>>;
+
 #[derive(Debug)]
 pub struct HttpRequest { // http请求的结构
     pub method: Method,
     pub version: Version,
     pub resource: Resource,
-    pub headers: HashMap<String, String>, //headers是一个key value集合
-    pub body: String,
-}
-
-impl From<String> for HttpRequest {
-    fn from(req: String) -> Self {
-        //以下定义为初始化
-        let mut parsed_method = Method::Uninitialized;
-        let mut parsed_version = Version::V1_1;
-        let mut parsed_resource = Resource::Path(String::from(""));
-        let mut parsed_headers = HashMap::new();
-        let mut parsed_body = "";
-
-        for line in req.lines() {
-            if line.contains("HTTP"){
-                let (method, resource, version) = process_req_line(line);
-                parsed_method = method;
-                parsed_resource = resource;
-                parsed_version = version;
-            } else if line.contains(":"){
-                let (key, value) = process_header_line(line);
-                parsed_headers.insert(key, value);
-            } else if line.len() == 0 {
-
-            } else {
-                parsed_body = line;
+    pub query: Query, //查询字符串 键和值都已解码
+    pub headers: HashMap<String, Vec<String>>, //头部名统一为小写 同名头部按出现顺序保存
+    pub body: Vec<u8>, //原始字节 可能不是文本
+    pub params: HashMap<String, String>, //路由匹配出的路径参数 由服务器的路由表填写
+    pub peer_addr: Option<SocketAddr>, //客户端地址 由服务器填写 直接解析报文时为None
+}
+
+impl HttpRequest {
+    //构造一个HTTP/1.1请求 target是请求目标(例如 /orders?status=Pending) 格式和请求行中的一样
+    pub fn new(method: Method, target: &str) -> Result<HttpRequest, ParseError> {
+        let (path, query) = split_target(target)?;
+        Ok(HttpRequest {
+            method,
+            version: Version::V1_1,
+            resource: Resource::Path(path),
+            query,
+            headers: HashMap::new(),
+            body: Vec::new(),
+            params: HashMap::new(),
+            peer_addr: None,
+        })
+    }
+
+    //解析一个完整的请求报文(头部+消息体)
+    pub fn parse(bytes: &[u8]) -> Result<HttpRequest, ParseError> {
+        let head_end = find_head_end(bytes).ok_or(if bytes.is_empty() {
+            ParseError::Empty
+        } else {
+            ParseError::IncompleteHead
+        })?;
+        let head = std::str::from_utf8(&bytes[..head_end]).map_err(|_| ParseError::InvalidEncoding)?;
+        let mut req = HttpRequest::from_head(head)?;
+
+        //用transfer模块按Content-Length或chunked编码取出消息体
+        let rest = &bytes[head_end..];
+        let length = transfer::body_length(head).map_err(|e| ParseError::InvalidBody(e.to_string()))?;
+        if let transfer::BodyLength::Fixed(len) = length {
+            if rest.len() < len {
+                return Err(ParseError::InvalidBody("body shorter than content-length".into()));
+            }
+        }
+        let mut reader = BufReader::new(rest);
+        req.body = transfer::read_body(&mut reader, length).map_err(|e| ParseError::InvalidBody(e.to_string()))?;
+        Ok(req)
+    }
+
+    //只解析头部 消息体留空 供已经自行读取消息体的调用者使用(例如server)
+    pub fn from_head(head: &str) -> Result<HttpRequest, ParseError> {
+        //按行切分 行尾是CRLF 也容忍单独的LF
+        let mut lines = head.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line));
+
+        //允许请求行之前有空行
+        let request_line = lines
+            .by_ref()
+            .find(|line| !line.is_empty())
+            .ok_or(ParseError::Empty)?;
+        let (method, resource, query, version) = process_req_line(request_line)?;
+
+        let mut headers: HashMap<String, Vec<String>> = HashMap::new();
+        for line in lines {
+            if line.is_empty() {
+                break;
             }
+            let (key, value) = process_header_line(line)?;
+            headers.entry(key).or_default().push(value);
+        }
+
+        Ok(HttpRequest {
+            method,
+            version,
+            resource,
+            query,
+            headers,
+            body: Vec::new(),
+            params: HashMap::new(),
+            peer_addr: None,
+        })
+    }
+
+    //取某个头部的第一个值 名称不区分大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .and_then(|values| values.first())
+            .map(|v| v.as_str())
+    }
+
+    //取某个头部的所有值
+    pub fn header_all(&self, name: &str) -> &[String] {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(|values| values.as_slice())
+            .unwrap_or(&[])
+    }
+
+    //取某个查询参数的第一个值 名称区分大小写
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.get(name).and_then(|values| values.first()).map(|v| v.as_str())
+    }
+
+    //取某个查询参数的所有值 例如 ?tag=a&tag=b
+    pub fn query_all(&self, name: &str) -> &[String] {
+        self.query.get(name).map(|values| values.as_slice()).unwrap_or(&[])
+    }
+
+    //消息体是合法UTF-8时按文本返回
+    pub fn body_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.body).ok()
+    }
+
+    //Content-Type的类型部分 去掉参数并转成小写 例如 "multipart/form-data"
+    pub fn content_type(&self) -> Option<String> {
+        self.header("Content-Type")
+            .map(|ct| ct.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+    }
+
+    //解析application/x-www-form-urlencoded消息体
+    pub fn form(&self) -> Result<Form, FormError> {
+        match self.content_type() {
+            Some(ct) if ct == form::URLENCODED => form::parse_urlencoded(&self.body),
+            ct => Err(FormError::UnsupportedContentType(ct.unwrap_or_default())),
+        }
+    }
+
+    //解析multipart/form-data消息体 文件写到dir下的临时文件 见form::parse_multipart
+    pub fn multipart(&self, dir: &Path, limits: &MultipartLimits) -> Result<Multipart, FormError> {
+        self.multipart_from(self.body.as_slice(), dir, limits)
+    }
+
+    //和multipart一样 但是消息体从body读取 例如直接从连接上流式读取(见transfer::BodyReader)
+    pub fn multipart_from(&self, body: impl Read, dir: &Path, limits: &MultipartLimits) -> Result<Multipart, FormError> {
+        let content_type = self.header("Content-Type").unwrap_or_default();
+        let boundary = form::boundary(content_type)
+            .ok_or_else(|| FormError::UnsupportedContentType(self.content_type().unwrap_or_default()))?;
+        form::parse_multipart(body, &boundary, dir, limits)
+    }
+
+    //设置头部 替换同名的已有值
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        self.headers.insert(name.to_ascii_lowercase(), vec![value.into()]);
+    }
+
+    //重新编码出请求目标 查询参数按名称排序 保证输出稳定 同名参数保持原来的顺序
+    pub fn target(&self) -> String {
+        let Resource::Path(path) = &self.resource;
+        let mut target = if path == "*" {
+            path.clone()
+        } else {
+            percent_encode(path, true)
+        };
+        if !self.query.is_empty() {
+            let mut names: Vec<&String> = self.query.keys().collect();
+            names.sort();
+            let pairs: Vec<String> = names
+                .into_iter()
+                .flat_map(|k| {
+                    self.query[k]
+                        .iter()
+                        .map(move |v| format!("{}={}", percent_encode(k, false), percent_encode(v, false)))
+                })
+                .collect();
+            target.push('?');
+            target.push_str(&pairs.join("&"));
         }
+        target
+    }
 
-        HttpRequest { 
-            method: parsed_method, 
-            version: parsed_version, 
-            resource: parsed_resource, 
-            headers: parsed_headers, 
-            body: parsed_body.to_string(),
+    //按报文格式写出请求 消息体长度总是用Content-Length表示
+    pub fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        let mut head = format!("{} {} {}\r\n", self.method, self.target(), self.version.as_str());
+        let mut names: Vec<&String> = self.headers.keys().collect();
+        names.sort();
+        for name in names {
+            if name == "content-length" || name == "transfer-encoding" {
+                continue;
+            }
+            for value in &self.headers[name] {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        //带消息体的方法即使消息体为空也要说明长度 否则服务器不知道消息体在哪结束
+        if !self.body.is_empty() || matches!(self.method, Method::Post | Method::Put | Method::Patch) {
+            head.push_str(&format!("content-length: {}\r\n", self.body.len()));
         }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)
     }
 }
 
-fn process_req_line(s: &str) -> (Method, Resource, Version) {
-    let mut words = s.split_whitespace();
-    let method = words.next().unwrap();
-    let resource = words.next().unwrap();
-    let version = words.next().unwrap();
+impl TryFrom<&[u8]> for HttpRequest {
+    type Error = ParseError;
 
-    (
-        method.into(),
-        Resource::Path(resource.to_string()),
-        version.into(),
-    )
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        HttpRequest::parse(bytes)
+    }
 }
 
-fn process_header_line(s: &str) -> (String, String) {
-    let mut header_items = s.split(":");
-    let mut key = String::from("");
-    let mut value = String::from("");
+impl TryFrom<String> for HttpRequest {
+    type Error = ParseError;
+
+    fn try_from(req: String) -> Result<Self, Self::Error> {
+        HttpRequest::parse(req.as_bytes())
+    }
+}
 
-    if let Some(k) = header_items.next() {
-        key = k.to_string();
+//找到头部结束的位置(空行之后) 同时支持CRLF和LF
+pub(crate) fn find_head_end(bytes: &[u8]) -> Option<usize> {
+    let mut line_start = 0;
+    let mut seen_line = false;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'\n' {
+            let line = &bytes[line_start..i];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() && seen_line {
+                return Some(i + 1);
+            }
+            seen_line |= !line.is_empty();
+            line_start = i + 1;
+        }
     }
+    None
+}
 
-    if let Some(v) = header_items.next() {
-        value = v.to_string();
+fn process_req_line(s: &str) -> Result<(Method, Resource, Query, Version), ParseError> {
+    //请求行必须正好是 "方法 SP 请求目标 SP 版本"
+    let mut words = s.split(' ');
+    let (method, target, version) = match (words.next(), words.next(), words.next(), words.next()) {
+        (Some(m), Some(t), Some(v), None) if !m.is_empty() && !t.is_empty() => (m, t, v),
+        _ => return Err(ParseError::InvalidRequestLine),
+    };
+
+    if !method.bytes().all(is_token_char) {
+        return Err(ParseError::InvalidMethod);
+    }
+    if !is_http_version(version) {
+        return Err(ParseError::InvalidVersion);
     }
 
-    (key, value)
+    let (path, query) = split_target(target)?;
+    Ok((method.into(), Resource::Path(path), query, version.into()))
+}
+
+//把请求目标拆成解码后的路径和查询参数
+//支持 origin-form(/a?b=c) absolute-form(http://host/a) 和 asterisk-form(*)
+fn split_target(target: &str) -> Result<(String, Query), ParseError> {
+    let target = match target.find("://") {
+        Some(scheme_end) => {
+            let after_scheme = &target[scheme_end + 3..];
+            match after_scheme.find(['/', '?']) {
+                Some(i) if after_scheme[i..].starts_with('/') => &after_scheme[i..],
+                Some(i) => return split_target(&format!("/{}", &after_scheme[i..])),
+                None => "/",
+            }
+        }
+        None => target,
+    };
+    if target != "*" && !target.starts_with('/') {
+        return Err(ParseError::InvalidRequestLine);
+    }
+    //片段标识符不会发给服务器 出现的话直接丢弃
+    let target = target.split('#').next().unwrap_or("");
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, parse_query(query)?),
+        None => (target, HashMap::new()),
+    };
+    //路由按'/'切分解码后的路径 编码的'/'会让/orders/a%2Fb变成两段 直接拒绝
+    if path.as_bytes().windows(3).any(|w| w[0] == b'%' && w[1] == b'2' && w[2].eq_ignore_ascii_case(&b'f')) {
+        return Err(ParseError::InvalidEncoding);
+    }
+    Ok((percent_decode(path, false)?, query))
+}
+
+//解析 a=1&b=2 形式的查询字符串 '+'表示空格 同名参数的值都保留
+pub fn parse_query(query: &str) -> Result<Query, ParseError> {
+    let mut params = Query::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.entry(percent_decode(key, true)?).or_default().push(percent_decode(value, true)?);
+    }
+    Ok(params)
+}
+
+//百分号解码 plus_as_space为true时把'+'解码成空格(表单和查询字符串)
+pub fn percent_decode(s: &str, plus_as_space: bool) -> Result<String, ParseError> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3).ok_or(ParseError::InvalidEncoding)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return Err(ParseError::InvalidEncoding);
+                }
+                let hex = std::str::from_utf8(hex).map_err(|_| ParseError::InvalidEncoding)?;
+                let byte = u8::from_str_radix(hex, 16).map_err(|_| ParseError::InvalidEncoding)?;
+                decoded.push(byte);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| ParseError::InvalidEncoding)
+}
+
+//百分号编码 只保留RFC 3986的非保留字符 keep_slash为true时保留'/'(路径)
+pub fn percent_encode(s: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+//响应解析也使用同样的头部行规则
+pub(crate) fn process_header_line(s: &str) -> Result<(String, String), ParseError> {
+    //以空白开头的是已废弃的折行写法 直接拒绝
+    if s.starts_with([' ', '\t']) {
+        return Err(ParseError::InvalidHeader(s.to_string()));
+    }
+    //只在第一个':'处切分 所以 "Host: localhost:3001" 的端口会保留
+    let (key, value) = s.split_once(':').ok_or_else(|| ParseError::InvalidHeader(s.to_string()))?;
+    //头部名不能为空 也不能包含空白(包括冒号前的空白)
+    if key.is_empty() || !key.bytes().all(is_token_char) {
+        return Err(ParseError::InvalidHeader(s.to_string()));
+    }
+    if value.bytes().any(|b| b.is_ascii_control() && b != b'\t') {
+        return Err(ParseError::InvalidHeader(s.to_string()));
+    }
+
+    Ok((key.to_ascii_lowercase(), value.trim_matches([' ', '\t']).to_string()))
+}
+
+//RFC 7230 token允许的字符
+fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+pub(crate) fn is_http_version(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() == 8
+        && s.starts_with("HTTP/")
+        && bytes[5].is_ascii_digit()
+        && bytes[6] == b'.'
+        && bytes[7].is_ascii_digit()
 }
 
 //简单测试
@@ -128,6 +514,12 @@ mod tests {
         // assert_eq!(Method::Get.into(), "GET");
         let m:Method = "GET".into();
         assert_eq!(m, Method::Get);
+        for name in ["GET", "HEAD", "POST", "PUT", "DELETE", "PATCH", "OPTIONS", "CONNECT", "TRACE"] {
+            let m: Method = name.into();
+            assert_eq!(m.as_str(), name);
+        }
+        let m: Method = "get".into();
+        assert_eq!(m, Method::Uninitialized);
     }
 
     #[test]
@@ -137,4 +529,165 @@ mod tests {
         let v:Version = "HTTP/1.1".into();
         assert_eq!(v, Version::V1_1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_read_http() {
+        let s = "GET /greeting?name=Li%20Lei&lang=zh+cn HTTP/1.1\r\nHost: localhost:3001\r\nAccept: */*\r\nUser-Agent: curl/7.64.1\r\n\r\n";
+        let req = HttpRequest::parse(s.as_bytes()).unwrap();
+        assert_eq!(req.method, Method::Get);
+        assert_eq!(req.version, Version::V1_1);
+        assert_eq!(req.resource, Resource::Path("/greeting".to_string()));
+        assert_eq!(req.query_param("name"), Some("Li Lei"));
+        assert_eq!(req.query_param("lang"), Some("zh cn"));
+        assert_eq!(req.header("host"), Some("localhost:3001"));
+        assert_eq!(req.header("USER-AGENT"), Some("curl/7.64.1"));
+        assert!(req.body.is_empty());
+    }
+
+    #[test]
+    fn test_repeated_query_params_are_kept() {
+        let req = HttpRequest::new(Method::Get, "/search?tag=b&tag=a&q=rust").unwrap();
+        assert_eq!(req.query_all("tag"), &["b".to_string(), "a".to_string()]);
+        assert_eq!(req.query_param("tag"), Some("b"));
+        assert!(req.query_all("missing").is_empty());
+        assert_eq!(req.target(), "/search?q=rust&tag=b&tag=a");
+    }
+
+    #[test]
+    fn test_encoded_slash_in_path_is_rejected() {
+        let s = "GET /api/shipping/orders/a%2Fb HTTP/1.1\r\n\r\n";
+        assert_eq!(HttpRequest::parse(s.as_bytes()).unwrap_err(), ParseError::InvalidEncoding);
+        assert!(HttpRequest::new(Method::Get, "/a%2fb").is_err());
+        //查询字符串里的%2F仍然可以使用
+        let req = HttpRequest::new(Method::Get, "/files?name=a%2Fb").unwrap();
+        assert_eq!(req.query_param("name"), Some("a/b"));
+    }
+
+    #[test]
+    fn test_repeated_headers_are_kept() {
+        let s = "GET / HTTP/1.1\r\nCookie: a=1\r\ncookie: b=2\r\n\r\n";
+        let req = HttpRequest::parse(s.as_bytes()).unwrap();
+        assert_eq!(req.header_all("Cookie"), &["a=1".to_string(), "b=2".to_string()]);
+    }
+
+    #[test]
+    fn test_binary_body_with_content_length() {
+        let mut raw = b"POST /upload HTTP/1.1\r\nContent-Length: 4\r\n\r\n".to_vec();
+        raw.extend_from_slice(&[0, 159, 146, 150]);
+        let req = HttpRequest::parse(&raw).unwrap();
+        assert_eq!(req.method, Method::Post);
+        assert_eq!(req.body, vec![0, 159, 146, 150]);
+        assert_eq!(req.body_str(), None);
+    }
+
+    #[test]
+    fn test_multi_line_and_chunked_body() {
+        let s = "POST / HTTP/1.1\r\nContent-Length: 11\r\n\r\nline1\nline2";
+        let req = HttpRequest::parse(s.as_bytes()).unwrap();
+        assert_eq!(req.body_str(), Some("line1\nline2"));
+
+        let s = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+        let req = HttpRequest::parse(s.as_bytes()).unwrap();
+        assert_eq!(req.body, b"abc");
+    }
+
+    #[test]
+    fn test_absolute_form_target() {
+        let s = "GET http://localhost:3001/api/shipping/orders?x=1 HTTP/1.1\r\n\r\n";
+        let req = HttpRequest::parse(s.as_bytes()).unwrap();
+        assert_eq!(req.resource, Resource::Path("/api/shipping/orders".to_string()));
+        assert_eq!(req.query_param("x"), Some("1"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases: Vec<(&[u8], ParseError)> = vec![
+            (b"", ParseError::Empty),
+            (b"GET / HTTP/1.1\r\nHost: x\r\n", ParseError::IncompleteHead),
+            (b"GET /\r\n\r\n", ParseError::InvalidRequestLine),
+            (b"GET  / HTTP/1.1\r\n\r\n", ParseError::InvalidRequestLine),
+            (b"GET index.html HTTP/1.1\r\n\r\n", ParseError::InvalidRequestLine),
+            (b"G(T / HTTP/1.1\r\n\r\n", ParseError::InvalidMethod),
+            (b"GET / HTTP/one\r\n\r\n", ParseError::InvalidVersion),
+            (b"GET /%zz HTTP/1.1\r\n\r\n", ParseError::InvalidEncoding),
+            (b"GET /%+1 HTTP/1.1\r\n\r\n", ParseError::InvalidEncoding),
+            (b"GET / HTTP/1.1\r\nHost : x\r\n\r\n", ParseError::InvalidHeader("Host : x".into())),
+            (b"GET / HTTP/1.1\r\nNoColon\r\n\r\n", ParseError::InvalidHeader("NoColon".into())),
+            (b"GET / HTTP/1.1\r\nA: b\r\n  folded\r\n\r\n", ParseError::InvalidHeader("  folded".into())),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(HttpRequest::parse(raw).unwrap_err(), expected, "{:?}", String::from_utf8_lossy(raw));
+        }
+        let truncated = b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc";
+        assert!(matches!(HttpRequest::parse(truncated), Err(ParseError::InvalidBody(_))));
+    }
+
+    //简单的模糊测试: 对合法请求做随机变异 解析器只能返回Ok或Err 不能panic
+    #[test]
+    fn test_fuzz_mutated_requests_never_panic() {
+        let seeds: [&[u8]; 3] = [
+            b"GET /a/b?c=d%20e HTTP/1.1\r\nHost: localhost:3001\r\n\r\n",
+            b"POST /x HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc",
+            b"POST /x HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+        ];
+        //xorshift伪随机数 保证每次运行结果一致
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..20_000 {
+            let mut input = seeds[next() as usize % seeds.len()].to_vec();
+            for _ in 0..(next() % 4 + 1) {
+                let pos = next() as usize % (input.len() + 1);
+                match next() % 3 {
+                    0 if pos < input.len() => input[pos] = next() as u8,
+                    1 => input.insert(pos, next() as u8),
+                    _ => input.truncate(pos),
+                }
+            }
+            let _ = HttpRequest::parse(&input);
+        }
+    }
+
+    #[test]
+    fn test_write_to_round_trip() {
+        let mut req = HttpRequest::new(Method::Post, "/a%20b/c?q=x%26y&n=1").unwrap();
+        req.set_header("Host", "localhost:3001");
+        req.body = b"{\"id\":1}".to_vec();
+        let mut out = Vec::new();
+        req.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out.clone()).unwrap(),
+            "POST /a%20b/c?n=1&q=x%26y HTTP/1.1\r\nhost: localhost:3001\r\ncontent-length: 8\r\n\r\n{\"id\":1}"
+        );
+
+        let parsed = HttpRequest::parse(&out).unwrap();
+        assert_eq!(parsed.resource, Resource::Path("/a b/c".into()));
+        assert_eq!(parsed.query_param("q"), Some("x&y"));
+        assert_eq!(parsed.body, req.body);
+        assert_eq!(percent_encode("a/b c", true), "a/b%20c");
+        assert_eq!(percent_encode("a/b c", false), "a%2Fb%20c");
+    }
+
+    #[test]
+    fn test_form_accessors_check_content_type() {
+        let mut req = HttpRequest::new(Method::Post, "/submit").unwrap();
+        req.set_header("Content-Type", "Application/X-WWW-Form-Urlencoded; charset=UTF-8");
+        req.body = b"qty=3&name=a+b".to_vec();
+        let form = req.form().unwrap();
+        assert_eq!(form.value::<u32>("qty").unwrap(), 3);
+        assert_eq!(form.get("name"), Some("a b"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let limits = MultipartLimits::default();
+        assert!(matches!(req.multipart(dir.path(), &limits), Err(FormError::UnsupportedContentType(_))));
+
+        req.set_header("Content-Type", "multipart/form-data; boundary=b");
+        req.body = b"--b\r\nContent-Disposition: form-data; name=\"qty\"\r\n\r\n3\r\n--b--\r\n".to_vec();
+        assert_eq!(req.multipart(dir.path(), &limits).unwrap().fields.value::<u32>("qty").unwrap(), 3);
+        assert!(matches!(req.form(), Err(FormError::UnsupportedContentType(ct)) if ct == "multipart/form-data"));
+    }
+}
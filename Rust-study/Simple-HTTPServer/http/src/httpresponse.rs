@@ -1,113 +1,362 @@
 //httpresponse模块
+//HttpResponse自己持有所有头部(String) 处理函数可以随意设置头部和cookie 不用操心生命周期
+//客户端用HttpResponse::parse/from_head解析收到的响应
+use super::httprequest::{self, ParseError};
+use super::statuscode::StatusCode;
+use super::transfer::{self, ChunkedWriter};
 use std::collections::HashMap;
-use std::io::{Result, Write};
-use std::mem::replace;
+use std::fmt;
+use std::io::{BufReader, Result, Write};
 
 //Debug 打印调试信息 PartialEq成员可以与其他变量比较 clone本身可以克隆
 #[derive(Debug, PartialEq, Clone)]
-pub struct HttpResponse<'a> {
-    version: &'a str,
-    status_code: &'a str,
-    status_text: &'a str,
-    headers: Option<HashMap<&'a str, &'a str>>,
-    body: Option<String>,
+pub struct HttpResponse {
+    version: String,
+    status_code: StatusCode,
+    headers: Vec<(String, String)>, //按设置顺序输出 同名头部(例如Set-Cookie)可以出现多次
+    body: Option<Vec<u8>>, //原始字节 可以是图片或者压缩后的数据
 }
 
-impl<'a> Default for HttpResponse<'a> {
+impl Default for HttpResponse {
     fn default() -> Self {
         Self {
             version: "HTTP/1.1".into(),
-            status_code: "200".into(),
-            status_text: "OK".into(),
-            headers: None,
+            status_code: StatusCode::Ok,
+            headers: Vec::new(),
             body: None,
         }
     }
 }
 
-impl<'a> From<HttpResponse<'a>> for String {
+impl From<HttpResponse> for String {
     fn from(res: HttpResponse) -> String {
-        let res1 = res.clone();
         format!(
-            "{} {} {}\r\n{}Content-Length: {}\r\n\r\n{}", 
-            &res1.version(), 
-            &res1.status_code(), 
-            &res1.status_text(),
-            &res1.headers(),
-            &res.body.unwrap().len(),
-            &res1.body()
+            "{}{}",
+            &res.head(),
+            String::from_utf8_lossy(res.body())
         )
     }
 }
-impl<'a> HttpResponse<'a> {
+
+impl HttpResponse {
+    //headers为None时默认Content-Type: text/html
     pub fn new(
-        status_code: &'a str, 
-        headers:Option<HashMap<&'a str, &'a str>>,
+        status_code: StatusCode,
+        headers:Option<HashMap<&str, &str>>,
         body:Option<String>
-    ) -> HttpResponse<'a> {
-        let mut response: HttpResponse<'a> = HttpResponse::default();
-        if status_code != "200" {
-            response.status_code = status_code.into();
-        };
-        response.headers = match &headers{
-            Some(_h) => headers,
-            None => {
-                let mut h = HashMap::new();
-                h.insert("Content-Type", "text/html");
-                Some(h)
-            },
-        };
-        response.status_text = match response.status_code {
-            "200" => "OK".into(),
-            "400" => "Bad Request".into(),
-            "404" => "Not Found".into(),
-            "500" => "Internal Server Error".into(),
-            _ => "ERROR".into(),
+    ) -> HttpResponse {
+        let mut response = HttpResponse::builder().status(status_code);
+        response = match headers {
+            Some(h) => h.into_iter().fold(response, |r, (k, v)| r.header(k, v)),
+            None => response.header("Content-Type", "text/html"),
         };
+        if let Some(body) = body {
+            response = response.body(body);
+        }
+        response.build()
+    }
+
+    //解析一个完整的响应报文(头部+消息体) 没有长度信息时剩下的字节都是消息体
+    pub fn parse(bytes: &[u8]) -> std::result::Result<HttpResponse, ParseError> {
+        let head_end = httprequest::find_head_end(bytes).ok_or(if bytes.is_empty() {
+            ParseError::Empty
+        } else {
+            ParseError::IncompleteHead
+        })?;
+        let head = std::str::from_utf8(&bytes[..head_end]).map_err(|_| ParseError::InvalidEncoding)?;
+        let mut resp = HttpResponse::from_head(head)?;
 
-        response.body = body;
-        response
+        let rest = &bytes[head_end..];
+        let length = transfer::response_body_length(head, resp.status_code.as_u16(), false)
+            .map_err(|e| ParseError::InvalidBody(e.to_string()))?;
+        if let transfer::BodyLength::Fixed(len) = length {
+            if rest.len() < len {
+                return Err(ParseError::InvalidBody("body shorter than content-length".into()));
+            }
+        }
+        let mut reader = BufReader::new(rest);
+        let body = transfer::read_body(&mut reader, length).map_err(|e| ParseError::InvalidBody(e.to_string()))?;
+        if !body.is_empty() {
+            resp.body = Some(body);
+        }
+        Ok(resp)
+    }
+
+    //只解析状态行和头部 消息体留空 供已经自行读取消息体的调用者使用(例如client)
+    //头部名统一转成小写 和HttpRequest一致 查找时不区分大小写
+    pub fn from_head(head: &str) -> std::result::Result<HttpResponse, ParseError> {
+        let mut lines = head.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line));
+        let status_line = lines.by_ref().find(|line| !line.is_empty()).ok_or(ParseError::Empty)?;
+
+        //状态行是 "版本 SP 状态码 SP 原因短语" 原因短语可以为空 这里不使用
+        let mut words = status_line.splitn(3, ' ');
+        let version = words.next().unwrap_or("");
+        if !httprequest::is_http_version(version) {
+            return Err(ParseError::InvalidVersion);
+        }
+        let status_code = words
+            .next()
+            .filter(|code| code.len() == 3 && code.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|code| code.parse::<u16>().ok())
+            .filter(|code| (100..600).contains(code))
+            .map(|code| StatusCode::try_from(code).unwrap_or(StatusCode::Other(code)))
+            .ok_or(ParseError::InvalidStatusLine)?;
+
+        let mut headers = Vec::new();
+        for line in lines {
+            if line.is_empty() {
+                break;
+            }
+            headers.push(httprequest::process_header_line(line)?);
+        }
+        Ok(HttpResponse {
+            version: version.to_string(),
+            status_code,
+            headers,
+            body: None,
+        })
+    }
+
+    //用构建器创建响应 默认200且没有任何头部
+    pub fn builder() -> HttpResponseBuilder {
+        HttpResponseBuilder {
+            response: HttpResponse::default(),
+        }
     }
 
     //接受write_stream参数 该参数要实现Write这个trait 
+    //头部里有Transfer-Encoding: chunked时 消息体按chunked格式分块发送
     pub fn send_response(&self, write_stream: &mut impl Write) -> Result<()> {
-        // 对HttpResponse克隆
-        let res = self.clone();
-        let response_string:String = String::from(res);
-        let _ = write!(write_stream, "{}", response_string);
+        write_stream.write_all(self.head().as_bytes())?;
+        if !self.status_code.allows_body() {
+            //1xx/204/304不发送消息体
+        } else if self.is_chunked() {
+            let mut chunked = ChunkedWriter::new(&mut *write_stream);
+            chunked.write_all(self.body())?;
+            chunked.finish()?;
+        } else {
+            write_stream.write_all(self.body())?;
+        }
+        write_stream.flush()
+    }
 
-        Ok(())
+    //HEAD请求的响应 头部和GET一样(包括Content-Length) 但不发送消息体
+    pub fn send_head(&self, write_stream: &mut impl Write) -> Result<()> {
+        write_stream.write_all(self.head().as_bytes())?;
+        write_stream.flush()
+    }
+
+    //设置(或覆盖)一个响应头部 头部名不区分大小写
+    pub fn set_header(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(&key));
+        self.headers.push((key, value.into()));
+    }
+
+    //追加一个头部 不覆盖已有的同名头部
+    pub fn append_header(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.headers.push((key.into(), value.into()));
+    }
+
+    pub fn remove_header(&mut self, key: &str) {
+        self.headers.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+    }
+
+    pub fn set_status(&mut self, status_code: StatusCode) {
+        self.status_code = status_code;
+    }
+
+    pub fn set_body(&mut self, body: impl Into<Vec<u8>>) {
+        self.body = Some(body.into());
+    }
+
+    //状态行+头部+空行 chunked响应和不允许消息体的响应(1xx/204/304)不带Content-Length
+    fn head(&self) -> String {
+        let no_length = self.is_chunked()
+            || !self.status_code.allows_body()
+            || self.header("Content-Length").is_some();
+        let length = if no_length {
+            "".to_string()
+        } else {
+            format!("Content-Length: {}\r\n", self.body().len())
+        };
+        format!(
+            "{} {} {}\r\n{}{}\r\n",
+            self.version(),
+            self.status_code.as_u16(),
+            self.status_code.reason_phrase(),
+            self.headers_string(),
+            length
+        )
+    }
+
+    fn is_chunked(&self) -> bool {
+        self.header("Transfer-Encoding")
+            .map(|v| v.eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false)
     }
 
     //实现一系列查看方法
     fn version(&self) -> &str {
-        self.version
+        &self.version
     }
 
-    fn status_code(&self) -> &str {
+    pub fn status_code(&self) -> StatusCode {
         self.status_code
     }
 
-    fn status_text(&self) -> &str {
-        self.status_text
+    //取某个头部的第一个值 名称不区分大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
     }
 
-    //对外查看接口 所以不能用Some(...) 要用String
-    fn headers(&self) -> String{
-        let map:HashMap<&str, &str> = self.headers.clone().unwrap();
+    //所有头部拼成 "key: value\r\n" 的形式
+    fn headers_string(&self) -> String{
         let mut header_string:String = "".into();
-        for (key, value) in map.iter() {
+        for (key, value) in self.headers.iter() {
             header_string = format!("{}{}: {}\r\n", header_string,key, value);
         }
         header_string
     }
 
-    pub fn body(&self) -> &str {
+    pub fn body(&self) -> &[u8] {
         match &self.body {
-            Some(b) => b.as_str(),
-            None => "",
+            Some(b) => b.as_slice(),
+            None => &[],
+        }
+    }
+}
+
+//HttpResponse的构建器 链式调用后build()
+#[derive(Debug)]
+pub struct HttpResponseBuilder {
+    response: HttpResponse,
+}
+
+impl HttpResponseBuilder {
+    pub fn status(mut self, status_code: StatusCode) -> Self {
+        self.response.status_code = status_code;
+        self
+    }
+
+    //设置头部 已有的同名头部会被覆盖
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.response.set_header(key, value);
+        self
+    }
+
+    //追加头部 保留已有的同名头部
+    pub fn append_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.response.append_header(key, value);
+        self
+    }
+
+    //每个cookie对应一个Set-Cookie头部
+    pub fn cookie(mut self, cookie: Cookie) -> Self {
+        self.response.append_header("Set-Cookie", cookie.to_string());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.response.body = Some(body.into());
+        self
+    }
+
+    pub fn build(self) -> HttpResponse {
+        self.response
+    }
+}
+
+//Set-Cookie的值 只有名字和值是必需的 其他属性按需设置
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<u64>,
+    http_only: bool,
+    secure: bool,
+    same_site: Option<String>,
+}
+
+impl Cookie {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Cookie {
+            name: name.into(),
+            value: value.into(),
+            path: None,
+            domain: None,
+            max_age: None,
+            http_only: false,
+            secure: false,
+            same_site: None,
         }
     }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    //单位为秒 0表示让浏览器立即删除
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    //Strict Lax 或 None
+    pub fn same_site(mut self, same_site: impl Into<String>) -> Self {
+        self.same_site = Some(same_site.into());
+        self
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if let Some(same_site) = &self.same_site {
+            write!(f, "; SameSite={}", same_site)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -116,16 +365,11 @@ mod test {
 
     #[test]
     fn test_response_struct_creation_200() {
-        let response_actual = HttpResponse::new("200",None,Some("xxx".into()));
+        let response_actual = HttpResponse::new(StatusCode::Ok,None,Some("xxx".into()));
         let response_expected = HttpResponse{
-            version:"HTTP/1.1",
-            status_code:"200",
-            status_text:"OK",
-            headers: {
-                let mut h = HashMap::new();
-                h.insert("Content-Type", "text/html");
-                Some(h)
-            },
+            version:"HTTP/1.1".into(),
+            status_code:StatusCode::Ok,
+            headers: vec![("Content-Type".into(), "text/html".into())],
             body:Some("xxx".into()),
         };
         assert_eq!(response_actual, response_expected);
@@ -133,18 +377,123 @@ mod test {
 
     #[test]
     fn test_response_struct_creation_404() {
-        let response_actual = HttpResponse::new("404",None,Some("xxx".into()));
+        let response_actual = HttpResponse::new(StatusCode::NotFound,None,Some("xxx".into()));
         let response_expected = HttpResponse{
-            version:"HTTP/1.1",
-            status_code:"404",
-            status_text:"Not Found",
-            headers: {
-                let mut h = HashMap::new();
-                h.insert("Content-Type", "text/html");
-                Some(h)
-            },
+            version:"HTTP/1.1".into(),
+            status_code:StatusCode::NotFound,
+            headers: vec![("Content-Type".into(), "text/html".into())],
             body:Some("xxx".into()),
         };
         assert_eq!(response_actual, response_expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_send_response_without_body() {
+        let response = HttpResponse::new(StatusCode::NotFound, None, None);
+        let mut out: Vec<u8> = Vec::new();
+        response.send_response(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(out.ends_with("Content-Length: 0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_send_head_omits_body() {
+        let response = HttpResponse::builder().body("hello").build();
+        let mut out: Vec<u8> = Vec::new();
+        response.send_head(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Content-Length: 5\r\n"));
+        assert!(out.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_send_chunked_response() {
+        let response = HttpResponse::builder()
+            .header("Transfer-Encoding", "chunked")
+            .body("hello")
+            .build();
+        let mut out: Vec<u8> = Vec::new();
+        response.send_response(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("Content-Length"));
+        assert!(out.ends_with("\r\n\r\n5\r\nhello\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_builder_headers_and_cookies() {
+        let response = HttpResponse::builder()
+            .status(StatusCode::Created)
+            .header("Content-Type", "text/plain")
+            .header("content-type", "application/json")
+            .header("X-Order-Id", format!("{}", 42))
+            .cookie(Cookie::new("session", "abc").path("/").http_only(true))
+            .cookie(Cookie::new("theme", "dark").max_age(3600))
+            .body("{}")
+            .build();
+        assert_eq!(response.status_code(), StatusCode::Created);
+        assert_eq!(response.header("Content-Type"), Some("application/json"));
+        let cookies: Vec<&str> = response
+            .headers()
+            .iter()
+            .filter(|(k, _)| k == "Set-Cookie")
+            .map(|(_, v)| v.as_str())
+            .collect();
+        assert_eq!(cookies, vec!["session=abc; Path=/; HttpOnly", "theme=dark; Max-Age=3600"]);
+
+        let out: String = response.into();
+        assert!(out.starts_with("HTTP/1.1 201 Created\r\ncontent-type: application/json\r\n"));
+    }
+
+    #[test]
+    fn test_no_content_has_no_body() {
+        let response = HttpResponse::builder().status(StatusCode::NoContent).body("ignored").build();
+        let mut out: Vec<u8> = Vec::new();
+        response.send_response(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "HTTP/1.1 204 No Content\r\n\r\n");
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let resp = HttpResponse::parse(
+            b"HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n{}\r\n0\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(resp.status_code(), StatusCode::Created);
+        assert_eq!(resp.header("content-type"), Some("application/json"));
+        assert_eq!(resp.body(), b"{}");
+
+        //没有长度信息 剩下的都是消息体
+        let resp = HttpResponse::parse(b"HTTP/1.0 200 OK\r\n\r\nuntil close").unwrap();
+        assert_eq!(resp.body(), b"until close");
+
+        //服务器自己生成的响应可以原样解析回来
+        let original = HttpResponse::builder().status(StatusCode::NotFound).body("missing").build();
+        let mut out = Vec::new();
+        original.send_response(&mut out).unwrap();
+        let parsed = HttpResponse::parse(&out).unwrap();
+        assert_eq!(parsed.status_code(), StatusCode::NotFound);
+        assert_eq!(parsed.body(), b"missing");
+    }
+
+    #[test]
+    fn test_parse_invalid_status_line() {
+        assert_eq!(HttpResponse::parse(b""), Err(ParseError::Empty));
+        assert_eq!(HttpResponse::parse(b"HTTP/1.1 200 OK\r\n"), Err(ParseError::IncompleteHead));
+        assert_eq!(HttpResponse::parse(b"HTTP/x 200 OK\r\n\r\n"), Err(ParseError::InvalidVersion));
+        for line in ["HTTP/1.1 20 OK", "HTTP/1.1 abc OK", "HTTP/1.1 099 Low", "HTTP/1.1 600 High", "HTTP/1.1"] {
+            let raw = format!("{}\r\n\r\n", line);
+            assert_eq!(HttpResponse::parse(raw.as_bytes()), Err(ParseError::InvalidStatusLine), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_parse_unlisted_status_code() {
+        //没有列出的状态码也是合法的响应 例如一些服务器返回的299/599
+        let resp = HttpResponse::parse(b"HTTP/1.1 299 Custom\r\nContent-Length: 2\r\n\r\nok").unwrap();
+        assert_eq!(resp.status_code(), StatusCode::Other(299));
+        assert_eq!(resp.body(), b"ok");
+        let resp = HttpResponse::parse(b"HTTP/1.1 599 \r\n\r\n").unwrap();
+        assert!(resp.status_code().is_server_error());
+    }
+}
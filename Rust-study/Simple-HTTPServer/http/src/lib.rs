@@ -1,2 +1,8 @@
+pub mod client;
+pub mod form;
 pub mod httprequest;
 pub mod httpresponse;
+pub mod statuscode;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transfer;
@@ -0,0 +1,175 @@
+//statuscode模块 标准http状态码及其原因短语
+//用宏一次性生成枚举、数值转换和原因短语 避免三处列表不一致
+use std::fmt;
+
+macro_rules! status_codes {
+    ($($name:ident = $code:expr, $reason:expr;)+) => {
+        #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+        pub enum StatusCode {
+            $($name,)+
+            Other(u16), //上面没有列出的状态码 例如服务器自定义的299 原因短语为空
+        }
+
+        impl StatusCode {
+            pub fn as_u16(&self) -> u16 {
+                match self {
+                    $(StatusCode::$name => $code,)+
+                    StatusCode::Other(code) => *code,
+                }
+            }
+
+            pub fn reason_phrase(&self) -> &'static str {
+                match self {
+                    $(StatusCode::$name => $reason,)+
+                    StatusCode::Other(_) => "",
+                }
+            }
+        }
+
+        //不认识的状态码返回Err 原样带回数值 需要接受它时用StatusCode::Other
+        impl TryFrom<u16> for StatusCode {
+            type Error = u16;
+
+            fn try_from(code: u16) -> Result<Self, Self::Error> {
+                match code {
+                    $($code => Ok(StatusCode::$name),)+
+                    _ => Err(code),
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+
+    Ok = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    PayloadTooLarge = 413, "Payload Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    ImATeapot = 418, "I'm a teapot";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableEntity = 422, "Unprocessable Entity";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl StatusCode {
+    //1xx/204/304响应不允许带消息体
+    pub fn allows_body(&self) -> bool {
+        let code = self.as_u16();
+        code >= 200 && code != 204 && code != 304
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
+}
+
+//显示为 "404 Not Found" 没有原因短语时只显示数值
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason_phrase() {
+            "" => write!(f, "{}", self.as_u16()),
+            reason => write!(f, "{} {}", self.as_u16(), reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_code_round_trip() {
+        for code in 100..600 {
+            if let Ok(status) = StatusCode::try_from(code) {
+                assert_eq!(status.as_u16(), code);
+                assert!(!status.reason_phrase().is_empty());
+            }
+        }
+        assert_eq!(StatusCode::try_from(404), Ok(StatusCode::NotFound));
+        assert_eq!(StatusCode::try_from(299), Err(299));
+        assert_eq!(StatusCode::Other(299).as_u16(), 299);
+        assert_eq!(StatusCode::Other(299).to_string(), "299");
+        assert!(StatusCode::Other(299).is_success());
+    }
+
+    #[test]
+    fn test_status_code_display_and_classes() {
+        assert_eq!(StatusCode::NotFound.to_string(), "404 Not Found");
+        assert!(StatusCode::Created.is_success());
+        assert!(StatusCode::Found.is_redirection());
+        assert!(StatusCode::Conflict.is_client_error());
+        assert!(StatusCode::BadGateway.is_server_error());
+        assert!(!StatusCode::NoContent.allows_body());
+        assert!(!StatusCode::NotModified.allows_body());
+    }
+}
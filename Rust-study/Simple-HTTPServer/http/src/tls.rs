@@ -0,0 +1,124 @@
+//tls模块 从PEM文件加载证书和私钥 生成rustls的服务端/客户端配置
+//只在开启tls特性时编译 加密算法统一使用ring
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+//依赖方直接通过http::tls::rustls使用StreamOwned等类型 不用单独声明依赖
+pub use rustls;
+
+//读取PEM文件中的全部证书 服务端证书链按 叶子证书 -> 中间证书 的顺序排列
+pub fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(invalid_input(format!("no certificates found in {}", path.display())));
+    }
+    Ok(certs)
+}
+
+//读取PEM文件中的第一个私钥 支持PKCS#1、PKCS#8和SEC1格式
+pub fn load_private_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| invalid_input(format!("no private key found in {}", path.display())))
+}
+
+//服务端配置 不要求客户端证书
+pub fn server_config(cert_path: &Path, key_path: &Path) -> io::Result<Arc<ServerConfig>> {
+    let certs = load_certs(cert_path)?;
+    let key = load_private_key(key_path)?;
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(invalid_input)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(invalid_input)?;
+    Ok(Arc::new(config))
+}
+
+//客户端配置 只信任ca_path中的证书(自签名证书直接把服务端证书当作CA)
+pub fn client_config(ca_path: &Path) -> io::Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_path)? {
+        roots.add(cert).map_err(invalid_input)?;
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(invalid_input)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+fn invalid_input(err: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConnection, ServerConnection, StreamOwned};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::PathBuf;
+    use std::thread;
+
+    //为localhost生成自签名证书 返回(证书路径, 私钥路径)
+    fn self_signed(dir: &Path) -> (PathBuf, PathBuf) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[test]
+    fn test_handshake_with_self_signed_cert() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, key_path) = self_signed(dir.path());
+        let server = server_config(&cert_path, &key_path).unwrap();
+        let client = client_config(&cert_path).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut tls = StreamOwned::new(ServerConnection::new(server).unwrap(), stream);
+            let mut buf = [0; 5];
+            tls.read_exact(&mut buf).unwrap();
+            tls.write_all(&buf).unwrap();
+            tls.flush().unwrap();
+        });
+
+        let name = ServerName::try_from("localhost").unwrap();
+        let conn = ClientConnection::new(client, name).unwrap();
+        let mut tls = StreamOwned::new(conn, TcpStream::connect(addr).unwrap());
+        tls.write_all(b"hello").unwrap();
+        let mut buf = [0; 5];
+        tls.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_missing_or_invalid_pem_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert_path, _) = self_signed(dir.path());
+        assert_eq!(
+            load_certs(&dir.path().join("missing.pem")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        //证书文件里没有私钥
+        assert_eq!(load_private_key(&cert_path).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,482 @@
+//transfer模块 负责http/1.1消息在字节流上的分帧
+//读取: 先读到头部结束(空行) 再按Content-Length或chunked编码读取消息体
+//响应没有长度信息时读到连接关闭为止
+//BodyReader按同样的规则流式读取消息体 用于不适合整个放进内存的请求(例如上传文件)
+//写出: ChunkedWriter把任意写入包装成Transfer-Encoding: chunked格式
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+//头部和消息体的大小上限 防止恶意客户端耗尽内存
+pub const MAX_HEAD_SIZE: usize = 8 * 1024;
+pub const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+//消息体的长度如何确定
+#[derive(Debug, PartialEq)]
+pub enum BodyLength {
+    Empty,
+    Fixed(usize),
+    Chunked,
+    UntilClose, //只出现在响应中 连接关闭表示消息体结束 连接不能复用
+}
+
+//消息体超过上限 服务器据此回复413而不是400
+#[derive(Debug)]
+pub struct BodyTooLarge;
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "body too large")
+    }
+}
+
+impl Error for BodyTooLarge {}
+
+//读取错误是不是因为消息体超过了上限
+pub fn is_body_too_large(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|e| e.is::<BodyTooLarge>())
+}
+
+//读取消息头部(起始行+头部行+空行) 返回包含结尾空行的原文
+//连接在任何字节到达之前就关闭了 返回None
+pub fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        let n = read_line_limited(reader, &mut line, MAX_HEAD_SIZE - head.len())?;
+        if n == 0 {
+            if head.is_empty() {
+                return Ok(None);
+            }
+            return Err(invalid_data("connection closed before end of headers"));
+        }
+        //允许请求之间出现多余的空行(RFC 7230 3.5)
+        if head.is_empty() && (line == "\r\n" || line == "\n") {
+            continue;
+        }
+        head.push_str(&line);
+        if line == "\r\n" || line == "\n" {
+            return Ok(Some(head));
+        }
+    }
+}
+
+//在头部原文中查找某个头部的值 头部名称不区分大小写
+pub fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+//某个头部的所有值 按出现顺序
+fn header_values<'a>(head: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .filter(move |(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+//根据头部判断消息体长度 Content-Length超过MAX_BODY_SIZE时报错
+//同时有Transfer-Encoding和Content-Length 或者Content-Length不止一个时报错(RFC 7230 3.3.3)
+//前面有代理时 两边对消息体长度的理解不同会导致请求走私
+pub fn body_length(head: &str) -> io::Result<BodyLength> {
+    let length = declared_body_length(head)?;
+    if matches!(length, BodyLength::Fixed(len) if len > MAX_BODY_SIZE) {
+        return Err(too_large());
+    }
+    Ok(length)
+}
+
+//和body_length一样 但是不检查大小 由调用方按自己的上限检查(见BodyReader)
+pub fn declared_body_length(head: &str) -> io::Result<BodyLength> {
+    let lengths: Vec<&str> = header_values(head, "Content-Length").collect();
+    //多个Transfer-Encoding头部按顺序合并 最后一个编码必须是chunked
+    let codings: Vec<&str> = header_values(head, "Transfer-Encoding").collect();
+    if !codings.is_empty() {
+        if !lengths.is_empty() {
+            return Err(invalid_data("both transfer-encoding and content-length"));
+        }
+        if codings
+            .join(",")
+            .rsplit(',')
+            .next()
+            .map(|last| last.trim().eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false)
+        {
+            return Ok(BodyLength::Chunked);
+        }
+        return Err(invalid_data("unsupported transfer-encoding"));
+    }
+    match lengths[..] {
+        [] => Ok(BodyLength::Empty),
+        //逗号分隔的多个值也算多个Content-Length
+        [len] if !len.is_empty() && len.bytes().all(|b| b.is_ascii_digit()) => {
+            let len: usize = len
+                .parse()
+                .map_err(|_| invalid_data("invalid content-length"))?;
+            if len == 0 {
+                Ok(BodyLength::Empty)
+            } else {
+                Ok(BodyLength::Fixed(len))
+            }
+        }
+        [_] => Err(invalid_data("invalid content-length")),
+        _ => Err(invalid_data("multiple content-length headers")),
+    }
+}
+
+//按照长度读取消息体 chunked编码会被解码成原始字节
+pub fn read_body<R: BufRead>(reader: &mut R, length: BodyLength) -> io::Result<Vec<u8>> {
+    match length {
+        BodyLength::Empty => Ok(Vec::new()),
+        BodyLength::Fixed(len) => {
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body)?;
+            Ok(body)
+        }
+        length => {
+            let mut body = Vec::new();
+            BodyReader::new(reader, length, MAX_BODY_SIZE as u64)?.read_to_end(&mut body)?;
+            Ok(body)
+        }
+    }
+}
+
+//响应消息体长度(RFC 7230 3.3.3) HEAD请求的响应和1xx/204/304响应没有消息体
+//没有Transfer-Encoding和Content-Length时读到连接关闭
+pub fn response_body_length(head: &str, status: u16, head_request: bool) -> io::Result<BodyLength> {
+    if head_request || status < 200 || status == 204 || status == 304 {
+        return Ok(BodyLength::Empty);
+    }
+    if header_value(head, "Transfer-Encoding").is_none() && header_value(head, "Content-Length").is_none() {
+        return Ok(BodyLength::UntilClose);
+    }
+    body_length(head)
+}
+
+//http/1.1默认保持连接 除非Connection: close; http/1.0需要显式Connection: keep-alive
+//请求行的版本在末尾 状态行的版本在开头
+pub fn is_keep_alive(head: &str) -> bool {
+    let http_1_0 = head
+        .lines()
+        .next()
+        .map(|line| line.trim_end().ends_with(" HTTP/1.0") || line.starts_with("HTTP/1.0 "))
+        .unwrap_or(false);
+    let connection = header_value(head, "Connection").map(|v| v.to_ascii_lowercase());
+    match connection.as_deref() {
+        Some(v) if v.split(',').any(|t| t.trim() == "close") => false,
+        Some(v) if v.split(',').any(|t| t.trim() == "keep-alive") => true,
+        _ => !http_1_0,
+    }
+}
+
+//流式读取消息体 读到消息体结尾时read返回0 不会多读属于下一个请求的字节
+//chunked编码边读边解码 读出的字节超过limit时返回BodyTooLarge错误
+pub struct BodyReader<'a, R: BufRead> {
+    reader: &'a mut R,
+    state: BodyState,
+    limit: u64,
+    read: u64, //已经读出的消息体字节数
+}
+
+enum BodyState {
+    Fixed(u64),      //还剩多少字节
+    ChunkHead,       //下一行是chunk大小
+    ChunkData(u64),  //当前chunk还剩多少字节
+    UntilClose,
+    Done,
+}
+
+impl<'a, R: BufRead> BodyReader<'a, R> {
+    //Content-Length已经超过limit时直接报错 不读取任何字节
+    pub fn new(reader: &'a mut R, length: BodyLength, limit: u64) -> io::Result<Self> {
+        let state = match length {
+            BodyLength::Empty => BodyState::Done,
+            BodyLength::Fixed(len) if len as u64 > limit => return Err(too_large()),
+            BodyLength::Fixed(len) => BodyState::Fixed(len as u64),
+            BodyLength::Chunked => BodyState::ChunkHead,
+            BodyLength::UntilClose => BodyState::UntilClose,
+        };
+        Ok(BodyReader {
+            reader,
+            state,
+            limit,
+            read: 0,
+        })
+    }
+
+    //消息体已经完整读出 连接上接下来是下一个请求
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, BodyState::Done)
+    }
+
+    //读取chunk大小行 最后一个chunk之后跳过trailer部分
+    fn next_chunk(&mut self) -> io::Result<()> {
+        let mut size_line = String::new();
+        if read_line_limited(self.reader, &mut size_line, MAX_HEAD_SIZE)? == 0 {
+            return Err(invalid_data("connection closed inside chunked body"));
+        }
+        //chunk大小后面可能带有扩展 例如 "1a;name=value"
+        let size_str = size_line.trim().split(';').next().unwrap_or("").trim();
+        let size = u64::from_str_radix(size_str, 16).map_err(|_| invalid_data("invalid chunk size"))?;
+        if size == 0 {
+            loop {
+                let mut trailer = String::new();
+                let n = read_line_limited(self.reader, &mut trailer, MAX_HEAD_SIZE)?;
+                if n == 0 || trailer == "\r\n" || trailer == "\n" {
+                    self.state = BodyState::Done;
+                    return Ok(());
+                }
+            }
+        }
+        //size来自客户端 先减再比较 避免相加溢出
+        if size > self.limit - self.read {
+            return Err(too_large());
+        }
+        self.state = BodyState::ChunkData(size);
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for BodyReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let remaining = match self.state {
+                BodyState::Done => return Ok(0),
+                BodyState::ChunkHead => {
+                    self.next_chunk()?;
+                    continue;
+                }
+                BodyState::Fixed(n) | BodyState::ChunkData(n) => n,
+                //多读一个字节才能发现超过了上限
+                BodyState::UntilClose => (self.limit - self.read).saturating_add(1),
+            };
+            let max = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+            let n = self.reader.read(&mut buf[..max])?;
+            self.read += n as u64;
+            match &mut self.state {
+                BodyState::UntilClose if n == 0 => self.state = BodyState::Done,
+                BodyState::UntilClose if self.read > self.limit => return Err(too_large()),
+                _ if n == 0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed inside body")),
+                BodyState::Fixed(left) => {
+                    *left -= n as u64;
+                    if *left == 0 {
+                        self.state = BodyState::Done;
+                    }
+                }
+                BodyState::ChunkData(left) => {
+                    *left -= n as u64;
+                    if *left == 0 {
+                        let mut crlf = String::new();
+                        read_line_limited(self.reader, &mut crlf, 2)?;
+                        if crlf != "\r\n" && crlf != "\n" {
+                            return Err(invalid_data("missing CRLF after chunk"));
+                        }
+                        self.state = BodyState::ChunkHead;
+                    }
+                }
+                _ => {}
+            }
+            return Ok(n);
+        }
+    }
+}
+
+//读取一行 超过limit字节还没遇到换行就报错
+fn read_line_limited<R: BufRead>(reader: &mut R, line: &mut String, limit: usize) -> io::Result<usize> {
+    let mut bytes = Vec::new();
+    let n = Read::take(&mut *reader, limit as u64 + 1).read_until(b'\n', &mut bytes)?;
+    if n > limit {
+        return Err(invalid_data("line too long"));
+    }
+    if n > 0 && !bytes.ends_with(b"\n") {
+        return Err(invalid_data("unexpected end of line"));
+    }
+    line.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(n)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, BodyTooLarge)
+}
+
+//把写入的数据按chunked格式输出 finish()写出结束块
+//用于事先不知道长度的流式消息体
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ChunkedWriter { inner }
+    }
+
+    //写出长度为0的结束块 返回内部的writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        //空写入不能发出去 否则会被当成结束块
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.inner, "{:x}\r\n", buf.len())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_read_head_and_fixed_body() {
+        let raw = "POST /api HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\n\r\n";
+        let mut reader = BufReader::new(raw.as_bytes());
+        let head = read_head(&mut reader).unwrap().unwrap();
+        assert_eq!(head, "POST /api HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\n");
+        assert_eq!(body_length(&head).unwrap(), BodyLength::Fixed(5));
+        let body = read_body(&mut reader, body_length(&head).unwrap()).unwrap();
+        assert_eq!(body, b"hello");
+        //同一连接上的下一个请求
+        let next = read_head(&mut reader).unwrap().unwrap();
+        assert_eq!(next, "GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(read_head(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_chunked_body() {
+        let raw = "4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        let mut reader = BufReader::new(raw.as_bytes());
+        let body = read_body(&mut reader, BodyLength::Chunked).unwrap();
+        assert_eq!(body, b"Wikipedia");
+    }
+
+    #[test]
+    fn test_chunk_size_too_large() {
+        let raw = "4\r\nWiki\r\nffffffffffffffff\r\npedia\r\n0\r\n\r\n";
+        let mut reader = BufReader::new(raw.as_bytes());
+        let err = read_body(&mut reader, BodyLength::Chunked).unwrap_err();
+        assert_eq!(err.to_string(), "body too large");
+        assert!(is_body_too_large(&err));
+    }
+
+    #[test]
+    fn test_body_reader_streams_with_own_limit() {
+        //超过MAX_BODY_SIZE的Content-Length只有body_length拒绝
+        let head = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+        assert!(is_body_too_large(&body_length(&head).unwrap_err()));
+        assert_eq!(declared_body_length(&head).unwrap(), BodyLength::Fixed(MAX_BODY_SIZE + 1));
+
+        //chunked消息体读完就停下 后面的字节留给下一个请求
+        let raw = "4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+        let mut reader = BufReader::new(raw.as_bytes());
+        let mut body = BodyReader::new(&mut reader, BodyLength::Chunked, 9).unwrap();
+        let mut out = Vec::new();
+        body.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"Wikipedia");
+        assert!(body.is_done());
+        assert_eq!(read_head(&mut reader).unwrap().unwrap(), "GET / HTTP/1.1\r\n\r\n");
+
+        //超过自己的上限
+        let mut reader = BufReader::new(raw.as_bytes());
+        let mut body = BodyReader::new(&mut reader, BodyLength::Chunked, 8).unwrap();
+        assert!(is_body_too_large(&body.read_to_end(&mut Vec::new()).unwrap_err()));
+        assert!(!body.is_done());
+        let mut reader = BufReader::new(&b"abc"[..]);
+        assert!(BodyReader::new(&mut reader, BodyLength::Fixed(3), 2).is_err());
+        let mut body = BodyReader::new(&mut reader, BodyLength::UntilClose, 2).unwrap();
+        assert!(is_body_too_large(&body.read_to_end(&mut Vec::new()).unwrap_err()));
+    }
+
+    #[test]
+    fn test_ambiguous_body_length_is_rejected() {
+        let heads = [
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 5, 5\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: +5\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: gzip\r\n\r\n",
+        ];
+        for head in heads {
+            let err = body_length(head).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", head);
+            assert!(!is_body_too_large(&err), "{}", head);
+        }
+        let head = "POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(body_length(head).unwrap(), BodyLength::Chunked);
+    }
+
+    #[test]
+    fn test_header_lookup_is_case_insensitive() {
+        let head = "GET / HTTP/1.1\r\ncontent-length: 3\r\nHost: localhost:3001\r\n\r\n";
+        assert_eq!(header_value(head, "Content-Length"), Some("3"));
+        assert_eq!(header_value(head, "host"), Some("localhost:3001"));
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        assert!(is_keep_alive("GET / HTTP/1.1\r\n\r\n"));
+        assert!(!is_keep_alive("GET / HTTP/1.1\r\nConnection: close\r\n\r\n"));
+        assert!(!is_keep_alive("GET / HTTP/1.0\r\n\r\n"));
+        assert!(is_keep_alive("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n"));
+        assert!(is_keep_alive("HTTP/1.1 200 OK\r\n\r\n"));
+        assert!(!is_keep_alive("HTTP/1.0 200 OK\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_response_body_length() {
+        let head = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n";
+        assert_eq!(response_body_length(head, 200, false).unwrap(), BodyLength::Fixed(5));
+        assert_eq!(response_body_length(head, 200, true).unwrap(), BodyLength::Empty);
+        assert_eq!(response_body_length(head, 304, false).unwrap(), BodyLength::Empty);
+        let head = "HTTP/1.0 200 OK\r\n\r\n";
+        assert_eq!(response_body_length(head, 200, false).unwrap(), BodyLength::UntilClose);
+
+        //读到连接关闭为止
+        let mut reader = BufReader::new(&b"until the end"[..]);
+        assert_eq!(read_body(&mut reader, BodyLength::UntilClose).unwrap(), b"until the end");
+    }
+
+    #[test]
+    fn test_head_too_large() {
+        let raw = format!("GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", "a".repeat(MAX_HEAD_SIZE));
+        let mut reader = BufReader::new(raw.as_bytes());
+        assert!(read_head(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_chunked_writer_round_trip() {
+        let mut writer = ChunkedWriter::new(Vec::new());
+        writer.write_all(b"hello ").unwrap();
+        writer.write_all(b"world").unwrap();
+        let encoded = writer.finish().unwrap();
+        assert_eq!(encoded, b"6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n");
+        let mut reader = BufReader::new(&encoded[..]);
+        assert_eq!(read_body(&mut reader, BodyLength::Chunked).unwrap(), b"hello world");
+    }
+}
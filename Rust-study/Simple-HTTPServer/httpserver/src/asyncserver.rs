@@ -0,0 +1,713 @@
+//异步服务器 和Server共用路由表、中间件和处理函数 运行在tokio的多线程运行时上
+//每个连接一个异步任务 等待客户端时不占用线程 空闲的keep-alive连接只占一点内存
+//处理函数通过Handler::handle_async调用 默认实现用block_in_place调用同步的handle 不会阻塞其他连接
+//WebSocket会话沿用同步实现: 握手后把连接转成阻塞模式 交给tokio的阻塞线程池
+//TLS目前只有同步服务器支持
+use super::log::LogLevel;
+use super::metrics::ServerMetrics;
+use super::middleware::{Middleware, Pipeline};
+use super::router::Router;
+use super::server::{DEFAULT_KEEP_ALIVE_TIMEOUT, DEFAULT_MAX_CONNECTIONS, DEFAULT_WORKERS, DEFAULT_WRITE_TIMEOUT};
+use super::websocket;
+use http::httprequest::{HttpRequest, Method};
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use http::transfer::{self, BodyLength, MAX_BODY_SIZE, MAX_HEAD_SIZE};
+use std::io::{self, Cursor, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+use tokio::time::{self, timeout};
+
+//没有新连接时检查关闭标志的间隔
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct AsyncServer {
+    addrs: Vec<String>,
+    pipeline: Arc<Pipeline>,
+    keep_alive_timeout: Duration,
+    write_timeout: Duration,
+    workers: usize,
+    max_connections: usize,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+}
+
+//所有连接任务共享的状态
+struct Shared {
+    pipeline: Arc<Pipeline>,
+    keep_alive_timeout: Duration,
+    write_timeout: Duration,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+}
+
+impl AsyncServer {
+    pub fn new(socket_addr: &str, router: Router) -> Self {
+        AsyncServer {
+            addrs: vec![socket_addr.to_string()],
+            pipeline: Arc::new(Pipeline::new(router)),
+            keep_alive_timeout: DEFAULT_KEEP_ALIVE_TIMEOUT,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            workers: DEFAULT_WORKERS,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(ServerMetrics::default()),
+        }
+    }
+
+    //注册中间件 先注册的在外层 必须在run之前调用
+    pub fn wrap(mut self, middleware: impl Middleware + 'static) -> Self {
+        Arc::get_mut(&mut self.pipeline)
+            .expect("middleware must be registered before the server starts")
+            .wrap(middleware);
+        self
+    }
+
+    pub fn listen(mut self, addrs: &[String]) -> Self {
+        self.addrs = addrs.to_vec();
+        self
+    }
+
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.keep_alive_timeout = timeout;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    //运行时的工作线程数量
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    //同时处理的连接上限 超过的连接直接回复503
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    //把标志设为true 服务器停止接受新连接 处理完已有请求后run返回
+    pub fn shutdown_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.shutdown)
+    }
+
+    pub fn metrics(&self) -> Arc<ServerMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    //使用外部创建的指标 例如已经交给MetricsHandler的指标 运行时通过路由查看
+    pub fn with_metrics(mut self, metrics: Arc<ServerMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    //绑定所有监听地址 任何一个绑定失败都直接返回错误 不会只监听一部分地址
+    pub fn run(&self) -> io::Result<()> {
+        let mut listeners = Vec::new();
+        for addr in &self.addrs {
+            let listener = std::net::TcpListener::bind(addr)
+                .map_err(|e| io::Error::new(e.kind(), format!("cannot listen on {}: {}", addr, e)))?;
+            crate::log!(LogLevel::Info, "Running on http://{} (async)", addr);
+            listeners.push(listener);
+        }
+        self.serve_all(listeners)
+    }
+
+    //启动运行时 在已经绑定的监听器上接受连接 直到设置了关闭标志并且所有连接都结束
+    pub fn serve_all(&self, listeners: Vec<std::net::TcpListener>) -> io::Result<()> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(self.workers.max(1))
+            .enable_all()
+            .build()?;
+        let shared = Arc::new(Shared {
+            pipeline: Arc::clone(&self.pipeline),
+            keep_alive_timeout: self.keep_alive_timeout,
+            write_timeout: self.write_timeout,
+            shutdown: Arc::clone(&self.shutdown),
+            metrics: Arc::clone(&self.metrics),
+        });
+        let max_connections = self.max_connections.min(Semaphore::MAX_PERMITS) as u32;
+
+        runtime.block_on(async move {
+            //每个连接持有一个许可 关闭时取回全部许可就说明所有连接都结束了
+            let permits = Arc::new(Semaphore::new(max_connections as usize));
+            let mut acceptors = Vec::new();
+            for listener in listeners {
+                listener.set_nonblocking(true)?;
+                let listener = TcpListener::from_std(listener)?;
+                acceptors.push(tokio::spawn(accept(listener, Arc::clone(&permits), Arc::clone(&shared))));
+            }
+            for acceptor in acceptors {
+                let _ = acceptor.await;
+            }
+
+            crate::log!(LogLevel::Info, "Shutting down, draining in-flight requests");
+            let _ = permits.acquire_many(max_connections).await;
+            Ok(())
+        })
+    }
+}
+
+//在一个监听器上接受连接 每个连接一个任务
+async fn accept(listener: TcpListener, permits: Arc<Semaphore>, shared: Arc<Shared>) {
+    while !shared.shutdown.load(Ordering::SeqCst) {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    crate::log!(LogLevel::Error, "Accept failed: {}", e);
+                    continue;
+                }
+            },
+            _ = time::sleep(SHUTDOWN_POLL_INTERVAL) => continue,
+        };
+        crate::log!(LogLevel::Debug, "Connecting established");
+        shared.metrics.total_connections.fetch_add(1, Ordering::SeqCst);
+
+        let permit = match Arc::clone(&permits).try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                shared.metrics.rejected_connections.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(reject_busy(stream));
+                continue;
+            }
+        };
+        let shared = Arc::clone(&shared);
+        tokio::spawn(async move {
+            shared.metrics.active_connections.fetch_add(1, Ordering::SeqCst);
+            //连接在单独的任务里处理 处理函数panic时只结束这个任务
+            let result = tokio::spawn(handle_connection(stream, Arc::clone(&shared))).await;
+            shared.metrics.active_connections.fetch_sub(1, Ordering::SeqCst);
+            match result {
+                Ok(Err(e)) => crate::log!(LogLevel::Warn, "Connection closed with error: {}", e),
+                Err(_) => crate::log!(LogLevel::Error, "Connection handler panicked"),
+                Ok(Ok(())) => {}
+            }
+            drop(permit);
+        });
+    }
+}
+
+//处理一个连接上的所有请求 和同步服务器的serve_requests逻辑一致
+async fn handle_connection(stream: TcpStream, shared: Arc<Shared>) -> io::Result<()> {
+    let peer_addr = stream.peer_addr().ok();
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let head = match timeout(shared.keep_alive_timeout, read_head(&mut reader)).await {
+            //空闲超时
+            Err(_) => return Ok(()),
+            Ok(Ok(Some(head))) => head,
+            //客户端已经关闭连接
+            Ok(Ok(None)) => return Ok(()),
+            Ok(Err(e)) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Ok(Err(e)) => return reject(reader.get_mut(), e, &shared).await,
+        };
+        let mut req = match HttpRequest::from_head(&head) {
+            Ok(req) => req,
+            Err(e) => return reject(reader.get_mut(), io::Error::new(io::ErrorKind::InvalidData, e), &shared).await,
+        };
+        req.peer_addr = peer_addr;
+        let mut keep_alive = transfer::is_keep_alive(&head) && !shared.shutdown.load(Ordering::SeqCst);
+
+        //要求流式读取消息体的处理函数在block_in_place里直接从连接读取 和同步服务器一样
+        let mut resp = match shared.pipeline.body_limit(&mut req) {
+            Some(limit) => {
+                let result = tokio::task::block_in_place(|| {
+                    let length = transfer::declared_body_length(&head)?;
+                    let mut conn = BlockingReader {
+                        reader: &mut reader,
+                        runtime: Handle::current(),
+                        timeout: shared.keep_alive_timeout,
+                    };
+                    let mut body = transfer::BodyReader::new(&mut conn, length, limit)?;
+                    shared.metrics.total_requests.fetch_add(1, Ordering::SeqCst);
+                    let resp = shared.pipeline.handle_stream(&mut req, &mut body);
+                    Ok::<_, io::Error>((resp, body.is_done()))
+                });
+                match result {
+                    Ok((resp, done)) => {
+                        keep_alive &= done;
+                        resp
+                    }
+                    Err(e) => return reject(reader.get_mut(), e, &shared).await,
+                }
+            }
+            None => {
+                let body = match transfer::body_length(&head) {
+                    Ok(length) => timeout(shared.keep_alive_timeout, read_body(&mut reader, length))
+                        .await
+                        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "timed out reading body"))),
+                    Err(e) => Err(e),
+                };
+                req.body = match body {
+                    Ok(body) => body,
+                    Err(e) => return reject(reader.get_mut(), e, &shared).await,
+                };
+                shared.metrics.total_requests.fetch_add(1, Ordering::SeqCst);
+                shared.pipeline.handle_async(&mut req).await
+            }
+        };
+        let upgraded = resp.status_code() == StatusCode::SwitchingProtocols;
+        if !upgraded {
+            resp.set_header("Connection", if keep_alive { "keep-alive" } else { "close" });
+        }
+        write_response(reader.get_mut(), &resp, req.method == Method::Head, shared.write_timeout).await?;
+
+        if upgraded {
+            return upgrade(reader, req, shared).await;
+        }
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+//WebSocket会话在阻塞线程上运行 已经读进缓冲区的字节放在最前面
+async fn upgrade(reader: BufReader<TcpStream>, req: HttpRequest, shared: Arc<Shared>) -> io::Result<()> {
+    let buffered = reader.buffer().to_vec();
+    let stream = reader.into_inner().into_std()?;
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(websocket::POLL_INTERVAL))?;
+    stream.set_write_timeout(Some(shared.write_timeout))?;
+    tokio::task::spawn_blocking(move || match shared.pipeline.router().websocket_handler(&req) {
+        Some(handler) => {
+            let mut reader = io::BufReader::new(Rewind {
+                buffered: Cursor::new(buffered),
+                stream,
+            });
+            websocket::serve(&mut reader, &req, handler, &shared.shutdown)
+        }
+        None => Ok(()),
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+//在block_in_place里按阻塞方式读取异步连接 直接使用tokio BufReader的缓冲区
+//所以消息体后面的字节仍然留在缓冲区里 给同一个连接上的下一个请求
+struct BlockingReader<'a> {
+    reader: &'a mut BufReader<TcpStream>,
+    runtime: Handle,
+    timeout: Duration,
+}
+
+impl Read for BlockingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = io::BufRead::fill_buf(self)?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        io::BufRead::consume(self, n);
+        Ok(n)
+    }
+}
+
+impl io::BufRead for BlockingReader<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let limit = self.timeout;
+        let reader = &mut *self.reader;
+        self.runtime.block_on(async move {
+            timeout(limit, reader.fill_buf())
+                .await
+                .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "timed out reading body")))
+        })
+    }
+
+    fn consume(&mut self, amt: usize) {
+        AsyncBufReadExt::consume(&mut *self.reader, amt);
+    }
+}
+
+//先读出buffered中剩余的字节 再从连接读取
+struct Rewind {
+    buffered: Cursor<Vec<u8>>,
+    stream: std::net::TcpStream,
+}
+
+impl Read for Rewind {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        //tokio的AsyncReadExt也有read方法 这里明确使用std的Read
+        match Read::read(&mut self.buffered, buf)? {
+            0 => Read::read(&mut self.stream, buf),
+            n => Ok(n),
+        }
+    }
+}
+
+impl Write for Rewind {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+//响应先序列化到内存再一次写出 客户端迟迟不接收时按写超时关闭连接
+//HEAD请求的响应只写出头部
+async fn write_response(stream: &mut TcpStream, resp: &HttpResponse, head_request: bool, limit: Duration) -> io::Result<()> {
+    let mut bytes = Vec::new();
+    if head_request {
+        resp.send_head(&mut bytes)?;
+    } else {
+        resp.send_response(&mut bytes)?;
+    }
+    timeout(limit, stream.write_all(&bytes))
+        .await
+        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "timed out writing response")))
+}
+
+//请求格式错误时回复400 消息体超过上限时回复413 然后关闭连接
+async fn reject(stream: &mut TcpStream, err: io::Error, shared: &Shared) -> io::Result<()> {
+    let status = if transfer::is_body_too_large(&err) {
+        StatusCode::PayloadTooLarge
+    } else {
+        StatusCode::BadRequest
+    };
+    let mut resp = HttpResponse::new(status, None, None);
+    resp.set_header("Connection", "close");
+    let _ = write_response(stream, &resp, false, shared.write_timeout).await;
+    Err(err)
+}
+
+//连接数已满 回复503后关闭连接
+async fn reject_busy(mut stream: TcpStream) {
+    let mut resp = HttpResponse::new(StatusCode::ServiceUnavailable, None, None);
+    resp.set_header("Connection", "close");
+    let _ = write_response(&mut stream, &resp, false, DEFAULT_WRITE_TIMEOUT).await;
+}
+
+//下面是http::transfer中读取函数的异步版本 大小限制相同
+
+async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        let n = read_line_limited(reader, &mut line, MAX_HEAD_SIZE - head.len()).await?;
+        if n == 0 {
+            if head.is_empty() {
+                return Ok(None);
+            }
+            return Err(invalid_data("connection closed before end of headers"));
+        }
+        //允许请求之间出现多余的空行(RFC 7230 3.5)
+        if head.is_empty() && (line == "\r\n" || line == "\n") {
+            continue;
+        }
+        head.push_str(&line);
+        if line == "\r\n" || line == "\n" {
+            return Ok(Some(head));
+        }
+    }
+}
+
+async fn read_body<R: AsyncBufRead + Unpin>(reader: &mut R, length: BodyLength) -> io::Result<Vec<u8>> {
+    match length {
+        BodyLength::Empty => Ok(Vec::new()),
+        BodyLength::Fixed(len) => {
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).await?;
+            Ok(body)
+        }
+        BodyLength::Chunked => read_chunked_body(reader).await,
+        //只有响应才会读到连接关闭
+        BodyLength::UntilClose => Err(invalid_data("request body without length")),
+    }
+}
+
+async fn read_chunked_body<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        if read_line_limited(reader, &mut size_line, MAX_HEAD_SIZE).await? == 0 {
+            return Err(invalid_data("connection closed inside chunked body"));
+        }
+        let size_str = size_line.trim().split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_str, 16).map_err(|_| invalid_data("invalid chunk size"))?;
+        if size == 0 {
+            loop {
+                let mut trailer = String::new();
+                let n = read_line_limited(reader, &mut trailer, MAX_HEAD_SIZE).await?;
+                if n == 0 || trailer == "\r\n" || trailer == "\n" {
+                    return Ok(body);
+                }
+            }
+        }
+        //size来自客户端 先减再比较 避免相加溢出
+        if size > MAX_BODY_SIZE - body.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, transfer::BodyTooLarge));
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).await?;
+        let mut crlf = String::new();
+        read_line_limited(reader, &mut crlf, 2).await?;
+        if crlf != "\r\n" && crlf != "\n" {
+            return Err(invalid_data("missing CRLF after chunk"));
+        }
+    }
+}
+
+async fn read_line_limited<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut String, limit: usize) -> io::Result<usize> {
+    let mut bytes = Vec::new();
+    let n = (&mut *reader).take(limit as u64 + 1).read_until(b'\n', &mut bytes).await?;
+    if n > limit {
+        return Err(invalid_data("line too long"));
+    }
+    if n > 0 && !bytes.ends_with(b"\n") {
+        return Err(invalid_data("unexpected end of line"));
+    }
+    line.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(n)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::{BoxFuture, Handler};
+    use crate::websocket::echo::Echo;
+    use std::io::BufRead;
+    use std::net::SocketAddr;
+    use std::thread;
+    use std::time::Instant;
+
+    //同步调用时阻塞线程 异步调用时只挂起任务
+    struct Sleep(Duration);
+
+    impl Handler for Sleep {
+        fn handle(&self, _req: &HttpRequest) -> HttpResponse {
+            thread::sleep(self.0);
+            HttpResponse::builder().body("slept").build()
+        }
+
+        fn handle_async<'a>(&'a self, _req: &'a HttpRequest) -> BoxFuture<'a, HttpResponse> {
+            Box::pin(async move {
+                time::sleep(self.0).await;
+                HttpResponse::builder().body("slept").build()
+            })
+        }
+    }
+
+    //从连接上流式读取消息体 最多8个字节
+    struct Streamed;
+
+    impl Handler for Streamed {
+        fn handle(&self, _req: &HttpRequest) -> HttpResponse {
+            unreachable!("body is streamed")
+        }
+
+        fn body_limit(&self, _req: &HttpRequest) -> Option<u64> {
+            Some(8)
+        }
+
+        fn handle_stream(&self, _req: &HttpRequest, body: &mut dyn Read) -> HttpResponse {
+            let mut text = String::new();
+            match body.read_to_string(&mut text) {
+                Ok(_) => HttpResponse::builder().body(text).build(),
+                Err(e) if transfer::is_body_too_large(&e) => HttpResponse::new(StatusCode::PayloadTooLarge, None, None),
+                Err(_) => HttpResponse::new(StatusCode::BadRequest, None, None),
+            }
+        }
+    }
+
+    //在随机端口上启动服务器 返回地址、关闭标志和服务线程
+    fn start(server: AsyncServer) -> (SocketAddr, Arc<AtomicBool>, thread::JoinHandle<io::Result<()>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shutdown = server.shutdown_handle();
+        let handle = thread::spawn(move || server.serve_all(vec![listener]));
+        (addr, shutdown, handle)
+    }
+
+    fn read_response(reader: &mut impl BufRead) -> (String, Vec<u8>) {
+        let head = transfer::read_head(reader).unwrap().unwrap();
+        let body = transfer::read_body(reader, transfer::body_length(&head).unwrap()).unwrap();
+        (head, body)
+    }
+
+    #[test]
+    fn test_blocking_handler_does_not_stall_runtime() {
+        //只重写了handle的处理函数 在唯一的运行时线程上阻塞
+        let mut router = Router::new();
+        router.get("/block", |_req: &HttpRequest| {
+            thread::sleep(Duration::from_millis(500));
+            HttpResponse::builder().body("blocked").build()
+        });
+        router.get("/fast", |_req: &HttpRequest| HttpResponse::builder().body("fast").build());
+        let (addr, shutdown, handle) = start(AsyncServer::new("127.0.0.1:0", router).workers(1));
+
+        let mut slow = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        slow.get_mut().write_all(b"GET /block HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let start = Instant::now();
+        let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        reader.get_mut().write_all(b"GET /fast HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let (_, body) = read_response(&mut reader);
+        assert_eq!(body, b"fast");
+        assert!(start.elapsed() < Duration::from_millis(300), "{:?}", start.elapsed());
+
+        let (_, body) = read_response(&mut slow);
+        assert_eq!(body, b"blocked");
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_streamed_body() {
+        let mut router = Router::new();
+        router.post("/stream", Streamed);
+        let (addr, shutdown, handle) = start(AsyncServer::new("127.0.0.1:0", router).workers(1));
+
+        //消息体读完后 同一个连接上的下一个请求不受影响
+        let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        reader
+            .get_mut()
+            .write_all(b"POST /stream HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\nGET /missing HTTP/1.1\r\n\r\n")
+            .unwrap();
+        let (head, body) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        assert_eq!(body, b"abc");
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+
+        //超过处理函数的上限
+        reader.get_mut().write_all(b"POST /stream HTTP/1.1\r\nContent-Length: 9\r\n\r\n").unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 413"), "{}", head);
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_keep_alive_and_chunked_body() {
+        let mut router = Router::new();
+        router.post("/echo", |req: &HttpRequest| HttpResponse::builder().body(req.body.clone()).build());
+        let server = AsyncServer::new("127.0.0.1:0", router).workers(1);
+        let metrics = server.metrics();
+        let (addr, shutdown, handle) = start(server);
+
+        let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        reader.get_mut().write_all(b"GET /missing HTTP/1.1\r\n\r\n").unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 404"));
+        assert_eq!(transfer::header_value(&head, "Connection"), Some("keep-alive"));
+
+        reader
+            .get_mut()
+            .write_all(b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n3\r\nabc\r\n0\r\n\r\n")
+            .unwrap();
+        let (head, body) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 200"));
+        assert_eq!(body, b"abc");
+        assert_eq!(transfer::read_head(&mut reader).unwrap(), None);
+
+        //格式错误的请求得到400
+        let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        reader.get_mut().write_all(b"POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n").unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 400"));
+
+        //同时有chunked和Content-Length 回复400并关闭连接
+        let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        reader
+            .get_mut()
+            .write_all(b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n0\r\n\r\n")
+            .unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 400"));
+        assert_eq!(transfer::header_value(&head, "Connection"), Some("close"));
+        assert_eq!(transfer::read_head(&mut reader).unwrap(), None);
+
+        //chunk大小接近usize上限 不会溢出 按消息体过大回复413
+        let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        reader
+            .get_mut()
+            .write_all(b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\nffffffffffffffff\r\n")
+            .unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 413"));
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        assert_eq!(metrics.total_requests.load(Ordering::SeqCst), 2);
+        assert_eq!(metrics.active_connections.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_async_handler_does_not_block_worker() {
+        //只有一个工作线程 十个请求的等待仍然可以重叠
+        let mut router = Router::new();
+        router.get("/sleep", Sleep(Duration::from_millis(300)));
+        let (addr, shutdown, handle) = start(AsyncServer::new("127.0.0.1:0", router).workers(1));
+
+        let started = Instant::now();
+        let clients: Vec<_> = (0..10)
+            .map(|_| {
+                thread::spawn(move || {
+                    let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+                    reader.get_mut().write_all(b"GET /sleep HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+                    read_response(&mut reader).1
+                })
+            })
+            .collect();
+        for client in clients {
+            assert_eq!(client.join().unwrap(), b"slept");
+        }
+        assert!(started.elapsed() < Duration::from_millis(1500), "{:?}", started.elapsed());
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_connection_limit_and_websocket_upgrade() {
+        let mut router = Router::new();
+        router.websocket("/ws/echo", Echo);
+        let server = AsyncServer::new("127.0.0.1:0", router).max_connections(1);
+        let metrics = server.metrics();
+        let (addr, shutdown, handle) = start(server);
+
+        let mut ws = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        ws.get_mut()
+            .write_all(
+                b"GET /ws/echo HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .unwrap();
+        let head = transfer::read_head(&mut ws).unwrap().unwrap();
+        assert!(head.starts_with("HTTP/1.1 101"));
+
+        //WebSocket连接占着唯一的名额 新连接得到503
+        let mut reader = io::BufReader::new(std::net::TcpStream::connect(addr).unwrap());
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 503"));
+        assert_eq!(metrics.rejected_connections.load(Ordering::SeqCst), 1);
+
+        //握手后的连接由同步的WebSocket实现接管
+        let frame = websocket::frame::Frame::new(websocket::frame::Opcode::Text, "hi");
+        ws.get_mut().write_all(&frame.encode(Some([1, 2, 3, 4]))).unwrap();
+        let mut reply = [0; 4];
+        ws.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, [0x81, 2, b'h', b'i']);
+
+        //关闭时服务器发送1001关闭帧 会话结束后run返回
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+//压力测试 用多个keep-alive连接在固定时间内不停发送请求 统计每秒处理的请求数
+//不指定--addr时 在本地随机端口上依次启动同步服务器和异步服务器(开启async特性时) 用同样的路由测试
+//--delay模拟等待IO的处理函数: 同步服务器阻塞工作线程 异步服务器只挂起任务
+//运行: cargo run --release -p httpserver --features async --bin loadtest -- --connections 64 --delay 10
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+use http::transfer;
+#[cfg(feature = "async")]
+use httpserver::asyncserver::AsyncServer;
+#[cfg(feature = "async")]
+use httpserver::handler::BoxFuture;
+use httpserver::handler::Handler;
+use httpserver::router::Router;
+use httpserver::server::{Server, DEFAULT_WORKERS};
+use std::env;
+use std::io::{self, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::process;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+Usage: loadtest [OPTIONS]
+
+Options:
+      --addr <HOST:PORT>   test a running server instead of the built-in ones
+      --path <PATH>        request path (default: /hello, or /delay with --delay)
+  -c, --connections <N>    concurrent keep-alive connections (default: 32)
+  -d, --duration <SECS>    how long each test runs (default: 5)
+  -w, --workers <N>        worker threads of the built-in servers (default: 4)
+      --delay <MS>         time the built-in /delay handler waits per request (default: 0)
+  -h, --help               print this help
+";
+
+struct Options {
+    addr: Option<String>,
+    path: Option<String>,
+    connections: usize,
+    duration: Duration,
+    workers: usize,
+    delay: Duration,
+}
+
+//一次测试的结果
+struct Report {
+    requests: usize,
+    errors: usize,
+    elapsed: Duration,
+}
+
+//模拟等待IO的处理函数
+struct Delay(Duration);
+
+impl Handler for Delay {
+    fn handle(&self, _req: &HttpRequest) -> HttpResponse {
+        thread::sleep(self.0);
+        HttpResponse::builder().body("done").build()
+    }
+
+    #[cfg(feature = "async")]
+    fn handle_async<'a>(&'a self, _req: &'a HttpRequest) -> BoxFuture<'a, HttpResponse> {
+        Box::pin(async move {
+            tokio::time::sleep(self.0).await;
+            HttpResponse::builder().body("done").build()
+        })
+    }
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|e| {
+        eprint!("{}", e);
+        process::exit(if e == USAGE { 0 } else { 2 });
+    });
+    let path = options
+        .path
+        .clone()
+        .unwrap_or_else(|| if options.delay.is_zero() { "/hello" } else { "/delay" }.to_string());
+    println!(
+        "{} connections, {}s per test, GET {}",
+        options.connections,
+        options.duration.as_secs(),
+        path
+    );
+
+    if let Some(addr) = &options.addr {
+        let resolved = addr.to_socket_addrs().ok().and_then(|mut addrs| addrs.next()).unwrap_or_else(|| {
+            eprintln!("loadtest: cannot resolve '{}'", addr);
+            process::exit(2);
+        });
+        print_report(addr, &load(resolved, &path, &options));
+        return;
+    }
+
+    //同步服务器的排队上限要能容纳所有连接 否则多出的连接会得到503
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new("127.0.0.1:0", router(options.delay))
+        .workers(options.workers)
+        .queue_size(options.connections);
+    let shutdown = server.shutdown_handle();
+    let handle = thread::spawn(move || server.serve(listener));
+    print_report("sync", &load(addr, &path, &options));
+    shutdown.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+
+    #[cfg(feature = "async")]
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = AsyncServer::new("127.0.0.1:0", router(options.delay)).workers(options.workers);
+        let shutdown = server.shutdown_handle();
+        let handle = thread::spawn(move || server.serve_all(vec![listener]));
+        print_report("async", &load(addr, &path, &options));
+        shutdown.store(true, Ordering::SeqCst);
+        if let Err(e) = handle.join().unwrap() {
+            eprintln!("loadtest: async server failed: {}", e);
+        }
+    }
+    #[cfg(not(feature = "async"))]
+    println!("async: skipped, build with --features async to compare");
+}
+
+fn router(delay: Duration) -> Router {
+    let mut router = Router::new();
+    router
+        .get("/hello", |_: &HttpRequest| HttpResponse::builder().body("Hello").build())
+        .get("/delay", Delay(delay));
+    router
+}
+
+//每个连接一个线程 出错时重新连接并记一次错误
+fn load(addr: SocketAddr, path: &str, options: &Options) -> Report {
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr);
+    let started = Instant::now();
+    let deadline = started + options.duration;
+    let clients: Vec<_> = (0..options.connections)
+        .map(|_| {
+            let request = request.clone();
+            thread::spawn(move || {
+                let (mut requests, mut errors) = (0, 0);
+                let mut conn = None;
+                while Instant::now() < deadline {
+                    if conn.is_none() {
+                        conn = TcpStream::connect(addr).ok().map(BufReader::new);
+                    }
+                    let result = match conn.as_mut() {
+                        Some(reader) => round_trip(reader, request.as_bytes()),
+                        None => Err(io::Error::new(io::ErrorKind::ConnectionRefused, "connect failed")),
+                    };
+                    match result {
+                        Ok(true) => requests += 1,
+                        Ok(false) => errors += 1,
+                        Err(_) => {
+                            errors += 1;
+                            conn = None;
+                        }
+                    }
+                }
+                (requests, errors)
+            })
+        })
+        .collect();
+
+    let mut report = Report {
+        requests: 0,
+        errors: 0,
+        elapsed: Duration::ZERO,
+    };
+    for client in clients {
+        let (requests, errors) = client.join().unwrap();
+        report.requests += requests;
+        report.errors += errors;
+    }
+    report.elapsed = started.elapsed();
+    report
+}
+
+//发送一个请求并读完响应 状态码不是2xx时返回false
+fn round_trip(reader: &mut BufReader<TcpStream>, request: &[u8]) -> io::Result<bool> {
+    reader.get_mut().write_all(request)?;
+    let head = transfer::read_head(reader)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection"))?;
+    transfer::read_body(reader, transfer::body_length(&head)?)?;
+    Ok(head.split_whitespace().nth(1).is_some_and(|status| status.starts_with('2')))
+}
+
+fn print_report(name: &str, report: &Report) {
+    let secs = report.elapsed.as_secs_f64();
+    println!(
+        "{}: {} requests in {:.1}s, {:.0} req/s, {} errors",
+        name,
+        report.requests,
+        secs,
+        report.requests as f64 / secs,
+        report.errors
+    );
+}
+
+//出错时返回要打印的信息 -h返回USAGE本身
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        addr: None,
+        path: None,
+        connections: 32,
+        duration: Duration::from_secs(5),
+        workers: DEFAULT_WORKERS,
+        delay: Duration::ZERO,
+    };
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        if flag == "-h" || flag == "--help" {
+            return Err(USAGE.to_string());
+        }
+        let value = args.next().ok_or_else(|| format!("{} requires a value\n\n{}", flag, USAGE))?;
+        let number = || -> Result<u64, String> {
+            match value.parse() {
+                Ok(n) if n > 0 || flag == "--delay" => Ok(n),
+                _ => Err(format!("{} expects a positive number, got '{}'\n", flag, value)),
+            }
+        };
+        match flag.as_str() {
+            "--addr" => options.addr = Some(value.clone()),
+            "--path" => options.path = Some(value.clone()),
+            "-c" | "--connections" => options.connections = number()? as usize,
+            "-d" | "--duration" => options.duration = Duration::from_secs(number()?),
+            "-w" | "--workers" => options.workers = number()? as usize,
+            "--delay" => options.delay = Duration::from_millis(number()?),
+            _ => return Err(format!("unknown option '{}'\n\n{}", flag, USAGE)),
+        }
+    }
+    Ok(options)
+}
@@ -0,0 +1,371 @@
+//服务器配置 优先级从低到高: 默认值 < 配置文件(TOML) < 命令行参数
+//没有用--config指定配置文件时 当前目录下有httpserver.toml就读取它
+//配置文件中的相对路径相对于配置文件所在目录 默认值和命令行中的相对路径相对于当前目录
+use super::log::LogLevel;
+use super::server;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_CONFIG_FILE: &str = "httpserver.toml";
+
+pub const USAGE: &str = "\
+Usage: httpserver [OPTIONS]
+
+Options:
+  -c, --config <FILE>              TOML config file (default: ./httpserver.toml if present)
+  -l, --listen <ADDR>              address to listen on, may be repeated (replaces `listen`)
+      --docroot <DIR>              directory served for unknown hosts
+      --data-dir <DIR>             directory holding order.json
+  -w, --workers <N>                number of worker threads
+      --queue-size <N>             connections allowed to wait for a worker
+      --max-connections <N>        concurrent connections (async server only)
+      --keep-alive-timeout <SECS>  idle time before a keep-alive connection is closed
+      --write-timeout <SECS>       time allowed for a client to accept a response
+      --log-level <LEVEL>          error, warn, info or debug
+      --list-directories           list directories without an index.html
+  -h, --help                       print this help
+";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen: Vec<String>,
+    pub docroot: PathBuf,
+    pub data_dir: PathBuf,
+    pub workers: usize,
+    pub queue_size: usize,
+    pub max_connections: usize, //异步服务器使用 同步服务器的上限是workers + queue_size
+    pub keep_alive_timeout: u64, //秒
+    pub write_timeout: u64,      //秒
+    pub log_level: LogLevel,
+    pub list_directories: bool,
+    #[serde(rename = "vhost")]
+    pub vhosts: Vec<VirtualHostConfig>,
+}
+
+//[[vhost]] 一组主机名共用一个静态文件目录
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualHostConfig {
+    pub names: Vec<String>,
+    pub docroot: PathBuf,
+    #[serde(default)]
+    pub list_directories: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: vec!["localhost:3001".to_string()],
+            docroot: PathBuf::from("public"),
+            data_dir: PathBuf::from("data"),
+            workers: server::DEFAULT_WORKERS,
+            queue_size: server::DEFAULT_QUEUE_SIZE,
+            max_connections: server::DEFAULT_MAX_CONNECTIONS,
+            keep_alive_timeout: server::DEFAULT_KEEP_ALIVE_TIMEOUT.as_secs(),
+            write_timeout: server::DEFAULT_WRITE_TIMEOUT.as_secs(),
+            log_level: LogLevel::Info,
+            list_directories: false,
+            vhosts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Help, //命令行要求打印帮助 不算真正的错误
+    Usage(String),
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "{}", USAGE),
+            ConfigError::Usage(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+            ConfigError::Read(path, e) => write!(f, "cannot read config file {}: {}", path.display(), e),
+            //toml的错误信息里已经带有行号和出错的位置
+            ConfigError::Parse(path, e) => write!(f, "invalid config file {}: {}", path.display(), e),
+            ConfigError::Invalid(msg) => write!(f, "invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+//命令行中出现的设置 没出现的保持None
+#[derive(Default)]
+struct Overrides {
+    config: Option<PathBuf>,
+    listen: Vec<String>,
+    docroot: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    workers: Option<usize>,
+    queue_size: Option<usize>,
+    max_connections: Option<usize>,
+    keep_alive_timeout: Option<u64>,
+    write_timeout: Option<u64>,
+    log_level: Option<LogLevel>,
+    list_directories: bool,
+}
+
+impl Config {
+    //读取配置文件和命令行参数(不含程序名) 并检查配置是否可用
+    pub fn load(args: impl IntoIterator<Item = String>) -> Result<Config, ConfigError> {
+        let overrides = parse_args(args)?;
+        let mut config = match &overrides.config {
+            Some(path) => Config::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Config::default(),
+        };
+        config.apply(overrides);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        let mut config: Config = toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))?;
+        //相对路径改为相对于配置文件所在目录
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.docroot = base.join(&config.docroot);
+        config.data_dir = base.join(&config.data_dir);
+        for vhost in &mut config.vhosts {
+            vhost.docroot = base.join(&vhost.docroot);
+        }
+        Ok(config)
+    }
+
+    pub fn keep_alive_timeout(&self) -> Duration {
+        Duration::from_secs(self.keep_alive_timeout)
+    }
+
+    pub fn write_timeout(&self) -> Duration {
+        Duration::from_secs(self.write_timeout)
+    }
+
+    fn apply(&mut self, overrides: Overrides) {
+        if !overrides.listen.is_empty() {
+            self.listen = overrides.listen;
+        }
+        if let Some(docroot) = overrides.docroot {
+            self.docroot = docroot;
+        }
+        if let Some(data_dir) = overrides.data_dir {
+            self.data_dir = data_dir;
+        }
+        if let Some(workers) = overrides.workers {
+            self.workers = workers;
+        }
+        if let Some(queue_size) = overrides.queue_size {
+            self.queue_size = queue_size;
+        }
+        if let Some(max_connections) = overrides.max_connections {
+            self.max_connections = max_connections;
+        }
+        if let Some(secs) = overrides.keep_alive_timeout {
+            self.keep_alive_timeout = secs;
+        }
+        if let Some(secs) = overrides.write_timeout {
+            self.write_timeout = secs;
+        }
+        if let Some(level) = overrides.log_level {
+            self.log_level = level;
+        }
+        self.list_directories |= overrides.list_directories;
+    }
+
+    //启动前把能发现的问题一次说清楚 而不是运行到一半才出错
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.listen.is_empty() {
+            return Err(ConfigError::Invalid("at least one listen address is required".into()));
+        }
+        for addr in &self.listen {
+            let port = addr.rsplit_once(':').map(|(_, port)| port.parse::<u16>());
+            if !matches!(port, Some(Ok(_))) {
+                return Err(ConfigError::Invalid(format!("listen address '{}' must be host:port", addr)));
+            }
+        }
+        for (name, value) in [
+            ("workers", self.workers as u64),
+            ("queue_size", self.queue_size as u64),
+            ("max_connections", self.max_connections as u64),
+            ("keep_alive_timeout", self.keep_alive_timeout),
+            ("write_timeout", self.write_timeout),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::Invalid(format!("{} must be greater than 0", name)));
+            }
+        }
+        check_dir("docroot", &self.docroot)?;
+
+        let mut seen = HashSet::new();
+        for vhost in &self.vhosts {
+            if vhost.names.is_empty() {
+                return Err(ConfigError::Invalid(format!(
+                    "vhost with docroot {} has no names",
+                    vhost.docroot.display()
+                )));
+            }
+            for name in &vhost.names {
+                if !seen.insert(name.to_ascii_lowercase()) {
+                    return Err(ConfigError::Invalid(format!("vhost name '{}' is used more than once", name)));
+                }
+            }
+            check_dir(&format!("docroot of vhost '{}'", vhost.names[0]), &vhost.docroot)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_dir(what: &str, path: &Path) -> Result<(), ConfigError> {
+    if path.is_dir() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(format!("{} {} is not a directory", what, path.display())))
+    }
+}
+
+//支持 --name value 和 --name=value 两种写法
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Overrides, ConfigError> {
+    let mut overrides = Overrides::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Err(ConfigError::Help),
+            "--list-directories" => {
+                overrides.list_directories = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| ConfigError::Usage(format!("{} requires a value", flag)))
+        };
+        match flag.as_str() {
+            "-c" | "--config" => overrides.config = Some(PathBuf::from(value()?)),
+            "-l" | "--listen" => overrides.listen.push(value()?),
+            "--docroot" => overrides.docroot = Some(PathBuf::from(value()?)),
+            "--data-dir" => overrides.data_dir = Some(PathBuf::from(value()?)),
+            "-w" | "--workers" => overrides.workers = Some(parse_number(&flag, &value()?)?),
+            "--queue-size" => overrides.queue_size = Some(parse_number(&flag, &value()?)?),
+            "--max-connections" => overrides.max_connections = Some(parse_number(&flag, &value()?)?),
+            "--keep-alive-timeout" => overrides.keep_alive_timeout = Some(parse_number(&flag, &value()?)?),
+            "--write-timeout" => overrides.write_timeout = Some(parse_number(&flag, &value()?)?),
+            "--log-level" => overrides.log_level = Some(value()?.parse().map_err(ConfigError::Usage)?),
+            _ => return Err(ConfigError::Usage(format!("unknown option '{}'", arg))),
+        }
+    }
+    Ok(overrides)
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::Usage(format!("{} expects a number, got '{}'", flag, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    //临时目录里的配置文件和两个静态文件目录
+    fn fixture(toml: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("public")).unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        let path = dir.path().join("server.toml");
+        fs::write(&path, toml).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_file_then_command_line() {
+        let (dir, path) = fixture(
+            r#"
+            listen = ["127.0.0.1:8080", "[::1]:8080"]
+            docroot = "public"
+            workers = 8
+            log_level = "debug"
+
+            [[vhost]]
+            names = ["docs.localhost"]
+            docroot = "docs"
+            list_directories = true
+            "#,
+        );
+        let config = Config::load(args(&format!("--config {} -w 2 --keep-alive-timeout=10", path.display()))).unwrap();
+        assert_eq!(config.listen, ["127.0.0.1:8080", "[::1]:8080"]);
+        //相对路径相对于配置文件所在目录
+        assert_eq!(config.docroot, dir.path().join("public"));
+        assert_eq!(config.data_dir, dir.path().join("data"));
+        assert_eq!(config.vhosts[0].docroot, dir.path().join("docs"));
+        assert!(config.vhosts[0].list_directories);
+        //命令行覆盖配置文件 没有出现的保持配置文件或默认值
+        assert_eq!(config.workers, 2);
+        assert_eq!(config.keep_alive_timeout(), Duration::from_secs(10));
+        assert_eq!(config.write_timeout, server::DEFAULT_WRITE_TIMEOUT.as_secs());
+        assert_eq!(config.log_level, LogLevel::Debug);
+
+        let config = Config::load(args(&format!("-c {} -l 0.0.0.0:80 -l 0.0.0.0:81", path.display()))).unwrap();
+        assert_eq!(config.listen, ["0.0.0.0:80", "0.0.0.0:81"]);
+    }
+
+    #[test]
+    fn test_config_errors_are_clear() {
+        let (dir, path) = fixture("workers = \"many\"\n");
+        let err = Config::load(args(&format!("--config {}", path.display()))).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse(..)));
+        assert!(err.to_string().contains("workers"), "{}", err);
+
+        fs::write(&path, "listen = [\"localhost\"]\ndocroot = \"public\"\n").unwrap();
+        let err = Config::load(args(&format!("-c {}", path.display()))).unwrap_err();
+        assert_eq!(err.to_string(), "invalid configuration: listen address 'localhost' must be host:port");
+
+        fs::write(&path, "docroot = \"missing\"\n").unwrap();
+        let err = Config::load(args(&format!("-c {}", path.display()))).unwrap_err();
+        assert!(err.to_string().contains("is not a directory"), "{}", err);
+
+        fs::write(&path, "docroot = \"public\"\n[[vhost]]\nnames = [\"a\", \"A\"]\ndocroot = \"docs\"\n").unwrap();
+        let err = Config::load(args(&format!("-c {}", path.display()))).unwrap_err();
+        assert!(err.to_string().contains("used more than once"), "{}", err);
+
+        //未知的配置项同样报错 拼错的设置不会被悄悄忽略
+        fs::write(&path, "docroot = \"public\"\nworker = 2\n").unwrap();
+        assert!(matches!(Config::load(args(&format!("-c {}", path.display()))), Err(ConfigError::Parse(..))));
+
+        let missing = dir.path().join("missing.toml");
+        let err = Config::load(args(&format!("-c {}", missing.display()))).unwrap_err();
+        assert!(matches!(err, ConfigError::Read(..)));
+    }
+
+    #[test]
+    fn test_command_line_errors() {
+        assert!(matches!(Config::load(args("--help")), Err(ConfigError::Help)));
+        for line in ["--workers", "--workers four", "--log-level loud", "--bogus"] {
+            assert!(matches!(Config::load(args(line)), Err(ConfigError::Usage(_))), "{}", line);
+        }
+        let (_dir, path) = fixture("docroot = \"public\"\n");
+        let err = Config::load(args(&format!("-c {} --queue-size 0", path.display()))).unwrap_err();
+        assert_eq!(err.to_string(), "invalid configuration: queue_size must be greater than 0");
+    }
+}
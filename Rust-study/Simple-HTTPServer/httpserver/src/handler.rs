@@ -1,89 +1,395 @@
-use http::{httprequest::HttpRequest, httpresponse::HttpResponse};
+use super::log::LogLevel;
+use super::store::{OrderStatus, OrderStore, StoreError};
+use super::websocket::broadcast::Broadcast;
+use http::httprequest::{HttpRequest, Method};
+use http::{httpresponse::HttpResponse, statuscode::StatusCode};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::env;
-use std::fs;
+use std::future::Future;
+use std::io::Read;
+use std::pin::Pin;
+use std::sync::Arc;
 
-pub trait Handler {
-    fn handle(req: &HttpRequest) -> HttpResponse;
-    fn load_file(file_name: &str) -> Option<String> {
-        let default_path = format!("{}/public",env!("CARGO_MANIFEST_DIR"));
-        let public_path = env::var("PUBLIC_PATH").unwrap_or(default_path);
-        let full_path = format!("{}/{}",public_path,file_name);
+//异步处理函数返回的future 要能在异步运行时的线程之间移动 所以要求Send
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
-        let contents = fs::read_to_string(full_path);
-        contents.ok()
+//处理函数在多个工作线程之间共享 所以要求Send + Sync
+//同步服务器调用handle 异步服务器调用handle_async
+//handle_async默认用tokio的block_in_place调用handle 运行时先把当前线程上的其他任务交给别的线程
+//所以读文件、加文件锁这类阻塞的处理函数不会卡住整个异步服务器
+//能够真正异步等待的处理函数可以重写handle_async 等待期间不占用任何线程
+pub trait Handler: Send + Sync {
+    fn handle(&self, req: &HttpRequest) -> HttpResponse;
+
+    fn handle_async<'a>(&'a self, req: &'a HttpRequest) -> BoxFuture<'a, HttpResponse> {
+        //block_in_place只能在多线程运行时里调用 AsyncServer总是使用多线程运行时
+        #[cfg(feature = "async")]
+        return Box::pin(async move { tokio::task::block_in_place(|| self.handle(req)) });
+        #[cfg(not(feature = "async"))]
+        Box::pin(async move { self.handle(req) })
+    }
+
+    //返回Some(上限)时服务器不预先读取消息体 而是调用handle_stream让处理函数直接从连接读取
+    //用于上传大文件这类不适合整个放进内存的请求 上限由处理函数决定 可以超过MAX_BODY_SIZE
+    fn body_limit(&self, _req: &HttpRequest) -> Option<u64> {
+        None
+    }
+
+    //body只能读出这个请求的消息体 超过上限时读取返回transfer::BodyTooLarge错误
+    //处理函数没有读完消息体时 服务器回复后关闭连接
+    fn handle_stream(&self, req: &HttpRequest, _body: &mut dyn Read) -> HttpResponse {
+        self.handle(req)
     }
 }
 
-pub struct StaticPageHandler;
+//闭包也可以直接注册为处理函数
+impl<F> Handler for F
+where
+    F: Fn(&HttpRequest) -> HttpResponse + Send + Sync,
+{
+    fn handle(&self, req: &HttpRequest) -> HttpResponse {
+        self(req)
+    }
+}
+
+//没有匹配的路由时使用 静态文件目录里的404.html由StaticPageHandler负责
 pub struct PageNotFoundHandler;
-pub struct WebServiceHandler;
 
-//对应json文件中数据 所以需要实现序列化和反序列化
-#[derive(Serialize, Deserialize)]
-pub struct OrderStatus {
+impl Handler for PageNotFoundHandler {
+    fn handle(&self, _req: &HttpRequest) -> HttpResponse {
+        HttpResponse::builder()
+            .status(StatusCode::NotFound)
+            .header("Content-Type", "text/plain")
+            .body("404 Not Found")
+            .build()
+    }
+}
+
+//订单接口 同一个处理函数注册到下面几个路由 按方法和有没有:id参数区分操作
+//GET    /api/shipping/orders?status=&date=  列出订单 可以按状态和日期过滤
+//POST   /api/shipping/orders                创建订单
+//GET    /api/shipping/orders/:id            查询单个订单
+//PUT    /api/shipping/orders/:id            修改订单状态
+//DELETE /api/shipping/orders/:id            删除订单
+//设置了广播频道时 订单的每次修改都以json事件推送给订阅的WebSocket连接
+#[derive(Clone)]
+pub struct WebServiceHandler {
+    store: Arc<OrderStore>,
+    events: Option<Broadcast>,
+}
+
+//推送的订单事件 删除时order为null
+#[derive(Serialize)]
+struct OrderEvent<'a> {
+    event: &'a str,
     order_id: i32,
+    order: Option<&'a OrderStatus>,
+}
+
+//创建订单的请求体 不指定order_id时自动分配
+#[derive(Deserialize)]
+struct NewOrder {
+    order_id: Option<i32>,
     order_date: String,
     order_status: String,
 }
 
-impl Handler for PageNotFoundHandler {
-    fn handle(_req: &HttpRequest) -> HttpResponse {
-        HttpResponse::new("404", None, Self::load_file("404.html"))
-    }
-}
-
-impl Handler for StaticPageHandler {
-    fn handle(req: &HttpRequest) -> HttpResponse {
-        let http::httprequest::Resource::Path(s) = &req.resource;
-        let route: Vec<&str> = s.split("/").collect();
-        match route[1] {
-            "" => HttpResponse::new("200", None, Self::load_file("index.html")),
-            "health" => HttpResponse::new("200", None, Self::load_file("health.html")),
-            path => match Self::load_file(path) {
-                Some(contents) => {
-                    let mut map: HashMap<&str, &str> = HashMap::new();
-                    if path.ends_with(".css") {
-                        map.insert("Content-Type", "text/css");
-                    } else if path.ends_with(".js") {
-                        map.insert("Content-Type", "text/javaScript");
-                    } else {
-                        map.insert("Content-Type", "text/html");
-                    }
-                    HttpResponse::new("200",Some(map),Some(contents))
-                }
-                None => HttpResponse::new("404",None,Self::load_file("404.html"))
-            }
+//修改订单状态的请求体
+#[derive(Deserialize)]
+struct StatusUpdate {
+    order_status: String,
+}
+
+//接口错误 以 {"error": "..."} 的json格式返回给客户端
+#[derive(Debug)]
+pub(crate) struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+impl ApiError {
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            message: message.into(),
         }
     }
+
+    fn not_found(order_id: i32) -> Self {
+        ApiError::new(StatusCode::NotFound, format!("order {} not found", order_id))
+    }
+
+    pub(crate) fn into_response(self) -> HttpResponse {
+        json_response(self.status, &ErrorBody { error: &self.message })
+    }
+}
+
+//存储出错属于服务器内部错误 细节只打印在服务器日志里
+impl From<StoreError> for ApiError {
+    fn from(e: StoreError) -> Self {
+        crate::log!(LogLevel::Error, "{}", e);
+        ApiError::new(StatusCode::InternalServerError, "order storage unavailable")
+    }
 }
 
 impl WebServiceHandler {
-    fn load_json() -> Vec<OrderStatus> {
-        let default_path = format!("{}/data", env!("CARGO_MANIFEST_DIR"));
-        let data_path = env::var("DATA_PATH").unwrap_or(default_path);
-        let full_path = format!("{}/{}", data_path, "order.json");
-        let json_contents = fs::read_to_string(full_path);
-        let orders: Vec<OrderStatus> = 
-        serde_json::from_str(&json_contents.unwrap().as_str()).unwrap();
-        orders
+    pub fn new(store: Arc<OrderStore>) -> Self {
+        WebServiceHandler { store, events: None }
+    }
+
+    pub fn notify(mut self, events: Broadcast) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn publish(&self, event: &str, order_id: i32, order: Option<&OrderStatus>) {
+        if let Some(events) = &self.events {
+            let event = OrderEvent { event, order_id, order };
+            events.broadcast(&serde_json::to_string(&event).unwrap_or_default());
+        }
+    }
+
+    fn list(&self, req: &HttpRequest) -> Result<HttpResponse, ApiError> {
+        let status = req.query_param("status");
+        let date = req.query_param("date");
+        let orders: Vec<OrderStatus> = self
+            .store
+            .list()
+            .into_iter()
+            .filter(|o| status.is_none_or(|s| o.order_status.eq_ignore_ascii_case(s)))
+            .filter(|o| date.is_none_or(|d| o.order_date == d))
+            .collect();
+        Ok(json_response(StatusCode::Ok, &orders))
+    }
+
+    fn get(&self, order_id: i32) -> Result<HttpResponse, ApiError> {
+        let order = self.store.get(order_id).ok_or_else(|| ApiError::not_found(order_id))?;
+        Ok(json_response(StatusCode::Ok, &order))
+    }
+
+    fn create(&self, req: &HttpRequest) -> Result<HttpResponse, ApiError> {
+        let new: NewOrder = parse_body(req)?;
+        let order_date = required(new.order_date, "order_date")?;
+        let order_status = required(new.order_status, "order_status")?;
+
+        let order = self.store.update(|orders| {
+            let order_id = match new.order_id {
+                Some(id) if orders.iter().any(|o| o.order_id == id) => {
+                    return Err(ApiError::new(StatusCode::Conflict, format!("order {} already exists", id)));
+                }
+                Some(id) => id,
+                //客户端可以指定最大的id 这时不能再自动分配
+                None => orders
+                    .iter()
+                    .map(|o| o.order_id)
+                    .max()
+                    .unwrap_or(0)
+                    .checked_add(1)
+                    .ok_or_else(|| ApiError::new(StatusCode::Conflict, "no order id left, specify order_id"))?,
+            };
+            let order = OrderStatus {
+                order_id,
+                order_date,
+                order_status,
+            };
+            orders.push(order.clone());
+            Ok(order)
+        })??;
+        self.publish("created", order.order_id, Some(&order));
+
+        let mut resp = json_response(StatusCode::Created, &order);
+        resp.set_header("Location", format!("/api/shipping/orders/{}", order.order_id));
+        Ok(resp)
+    }
+
+    fn update_status(&self, order_id: i32, req: &HttpRequest) -> Result<HttpResponse, ApiError> {
+        let update: StatusUpdate = parse_body(req)?;
+        let order_status = required(update.order_status, "order_status")?;
+        let order = self
+            .store
+            .update(|orders| {
+                let order = orders.iter_mut().find(|o| o.order_id == order_id)?;
+                order.order_status = order_status;
+                Some(order.clone())
+            })?
+            .ok_or_else(|| ApiError::not_found(order_id))?;
+        self.publish("updated", order_id, Some(&order));
+        Ok(json_response(StatusCode::Ok, &order))
+    }
+
+    fn delete(&self, order_id: i32) -> Result<HttpResponse, ApiError> {
+        let removed = self.store.update(|orders| {
+            let before = orders.len();
+            orders.retain(|o| o.order_id != order_id);
+            orders.len() != before
+        })?;
+        if !removed {
+            return Err(ApiError::not_found(order_id));
+        }
+        self.publish("deleted", order_id, None);
+        Ok(HttpResponse::builder().status(StatusCode::NoContent).build())
     }
 }
 
 impl Handler for WebServiceHandler {
-    fn handle(req: &HttpRequest) -> HttpResponse {
-        let http::httprequest::Resource::Path(s) = &req.resource;
-        let route: Vec<&str> = s.split("/").collect();
-
-        match route[2] {
-            "shipping" if route.len() > 2 && route[3] =="orders" => {
-                let body = Some(serde_json::to_string(&Self::load_json()).unwrap());
-                let mut headers: HashMap<&str, &str> = HashMap::new();
-                headers.insert("Content-Type", "application/json");
-                HttpResponse::new("200",Some(headers),body)
+    fn handle(&self, req: &HttpRequest) -> HttpResponse {
+        let order_id = match req.params.get("id").map(|id| id.parse::<i32>()) {
+            Some(Ok(id)) => Some(id),
+            Some(Err(_)) => {
+                return ApiError::new(StatusCode::BadRequest, "order id must be an integer").into_response();
             }
-            _ => HttpResponse::new("404",None, Self::load_file("404.html"))
+            None => None,
+        };
+        let result = match (req.method, order_id) {
+            (Method::Get, None) => self.list(req),
+            (Method::Post, None) => self.create(req),
+            (Method::Get, Some(id)) => self.get(id),
+            (Method::Put, Some(id)) => self.update_status(id, req),
+            (Method::Delete, Some(id)) => self.delete(id),
+            _ => Err(ApiError::new(StatusCode::MethodNotAllowed, "method not allowed")),
+        };
+        result.unwrap_or_else(ApiError::into_response)
+    }
+}
+
+pub(crate) fn json_response(status: StatusCode, value: &impl Serialize) -> HttpResponse {
+    //这里序列化的都是普通结构体 不会失败
+    let body = serde_json::to_string(value).unwrap_or_default();
+    HttpResponse::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .build()
+}
+
+fn parse_body<'a, T: Deserialize<'a>>(req: &'a HttpRequest) -> Result<T, ApiError> {
+    serde_json::from_slice(&req.body)
+        .map_err(|e| ApiError::new(StatusCode::BadRequest, format!("invalid request body: {}", e)))
+}
+
+//必填字段去掉首尾空白后不能为空
+fn required(value: String, field: &str) -> Result<String, ApiError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(ApiError::new(StatusCode::BadRequest, format!("{} must not be empty", field)));
+    }
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Router;
+    use std::fs;
+
+    fn router(dir: &tempfile::TempDir) -> Router {
+        let path = dir.path().join("order.json");
+        fs::write(
+            &path,
+            r#"[{"order_id":1,"order_date":"21 Jan 2020","order_status":"Delivered"},
+                {"order_id":2,"order_date":"2 Feb 2020","order_status":"Pending"}]"#,
+        )
+        .unwrap();
+        let orders = WebServiceHandler::new(Arc::new(OrderStore::open(path).unwrap()));
+        let mut router = Router::new();
+        router
+            .get("/api/shipping/orders", orders.clone())
+            .post("/api/shipping/orders", orders.clone())
+            .get("/api/shipping/orders/:id", orders.clone())
+            .put("/api/shipping/orders/:id", orders.clone())
+            .delete("/api/shipping/orders/:id", orders);
+        router
+    }
+
+    fn send(router: &Router, method: &str, path: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let raw = format!("{} {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", method, path, body.len(), body);
+        let resp = router.route(&mut HttpRequest::parse(raw.as_bytes()).unwrap());
+        let json = serde_json::from_slice(resp.body()).unwrap_or(serde_json::Value::Null);
+        (resp.status_code(), json)
+    }
+
+    #[test]
+    fn test_list_and_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let router = router(&dir);
+        let (status, json) = send(&router, "GET", "/api/shipping/orders", "");
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(json.as_array().unwrap().len(), 2);
+
+        let (_, json) = send(&router, "GET", "/api/shipping/orders?status=pending", "");
+        assert_eq!(json[0]["order_id"], 2);
+        let (_, json) = send(&router, "GET", "/api/shipping/orders?date=21%20Jan%202020", "");
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["order_id"], 1);
+    }
+
+    #[test]
+    fn test_order_id_exhausted() {
+        let dir = tempfile::tempdir().unwrap();
+        let router = router(&dir);
+        let body = format!(r#"{{"order_id":{},"order_date":"3 Mar 2020","order_status":"Shipped"}}"#, i32::MAX);
+        assert_eq!(send(&router, "POST", "/api/shipping/orders", &body).0, StatusCode::Created);
+
+        let (status, json) = send(
+            &router,
+            "POST",
+            "/api/shipping/orders",
+            r#"{"order_date":"4 Mar 2020","order_status":"Pending"}"#,
+        );
+        assert_eq!(status, StatusCode::Conflict);
+        assert_eq!(json["error"], "no order id left, specify order_id");
+    }
+
+    #[test]
+    fn test_create_update_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let router = router(&dir);
+        let (status, json) = send(
+            &router,
+            "POST",
+            "/api/shipping/orders",
+            r#"{"order_date":"3 Mar 2020","order_status":"Shipped"}"#,
+        );
+        assert_eq!(status, StatusCode::Created);
+        assert_eq!(json["order_id"], 3);
+
+        let (status, json) = send(&router, "PUT", "/api/shipping/orders/3", r#"{"order_status":"Delivered"}"#);
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(json["order_status"], "Delivered");
+
+        //修改已经写入文件
+        let saved = OrderStore::open(dir.path().join("order.json")).unwrap();
+        assert_eq!(saved.get(3).unwrap().order_status, "Delivered");
+
+        assert_eq!(send(&router, "DELETE", "/api/shipping/orders/3", "").0, StatusCode::NoContent);
+        assert_eq!(send(&router, "GET", "/api/shipping/orders/3", "").0, StatusCode::NotFound);
+    }
+
+    #[test]
+    fn test_errors_are_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let router = router(&dir);
+        let cases = [
+            ("GET", "/api/shipping/orders/abc", "", StatusCode::BadRequest),
+            ("GET", "/api/shipping/orders/99", "", StatusCode::NotFound),
+            ("DELETE", "/api/shipping/orders/99", "", StatusCode::NotFound),
+            ("POST", "/api/shipping/orders", "{not json", StatusCode::BadRequest),
+            ("POST", "/api/shipping/orders", r#"{"order_date":"","order_status":"x"}"#, StatusCode::BadRequest),
+            (
+                "POST",
+                "/api/shipping/orders",
+                r#"{"order_id":1,"order_date":"1 Jan 2020","order_status":"x"}"#,
+                StatusCode::Conflict,
+            ),
+        ];
+        for (method, path, body, expected) in cases {
+            let (status, json) = send(&router, method, path, body);
+            assert_eq!(status, expected, "{} {} {}", method, path, body);
+            assert!(json["error"].is_string(), "{} {} {}", method, path, body);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,17 @@
+//httpserver库 main调用server server调用router router调用handler
+//拆成库是为了让tests目录下的集成测试也能直接启动服务器
+#[cfg(feature = "async")]
+pub mod asyncserver;
+pub mod config;
+pub mod handler;
+pub mod log;
+pub mod metrics;
+pub mod middleware;
+pub mod pool;
+pub mod router;
+pub mod server;
+pub mod staticfile;
+pub mod store;
+pub mod upload;
+pub mod vhost;
+pub mod websocket;
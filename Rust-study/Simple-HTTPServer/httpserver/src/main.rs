@@ -1,11 +1,127 @@
 // main调用server server调用router router调用handler
-mod server;
-mod router;
-mod handler;
-
-use server::Server;
+use httpserver::config::{Config, ConfigError};
+use httpserver::handler::WebServiceHandler;
+use httpserver::log;
+use httpserver::metrics::{MetricsHandler, ServerMetrics};
+use httpserver::middleware::accesslog::AccessLog;
+use httpserver::middleware::basicauth::BasicAuth;
+use httpserver::middleware::cors::Cors;
+use httpserver::middleware::gzip::Gzip;
+use httpserver::middleware::requestid::RequestId;
+use httpserver::router::Router;
+#[cfg(not(feature = "async"))]
+use httpserver::server::Server;
+#[cfg(feature = "async")]
+use httpserver::asyncserver::AsyncServer;
+use httpserver::staticfile::StaticPageHandler;
+use httpserver::store::OrderStore;
+use httpserver::upload::UploadHandler;
+use httpserver::vhost::VirtualHosts;
+use httpserver::websocket::broadcast::Broadcast;
+use httpserver::websocket::echo::Echo;
+use std::env;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
 fn main() {
-    let server = Server::new("localhost:3001");
-    server.run();
+    //配置有问题时说明原因后直接退出
+    let config = Config::load(env::args().skip(1)).unwrap_or_else(|e| match e {
+        ConfigError::Help => {
+            print!("{}", e);
+            process::exit(0);
+        }
+        e => {
+            eprintln!("httpserver: {}", e);
+            process::exit(2);
+        }
+    });
+    log::set_level(config.log_level);
+
+    //静态文件按Host头部选择虚拟主机 没有匹配时使用默认的docroot
+    let mut static_files =
+        VirtualHosts::new(StaticPageHandler::new(&config.docroot).list_directories(config.list_directories));
+    for vhost in &config.vhosts {
+        let handler = StaticPageHandler::new(&vhost.docroot).list_directories(vhost.list_directories);
+        static_files = static_files.host(&vhost.names, handler);
+    }
+
+    //订单数据启动时读入内存 文件损坏时直接退出 不要带着空数据运行
+    let order_file = config.data_dir.join("order.json");
+    let store = OrderStore::open(&order_file).unwrap_or_else(|e| {
+        eprintln!("Failed to load {}: {}", order_file.display(), e);
+        process::exit(1);
+    });
+    //订单变化通过/ws/orders推送给浏览器
+    let order_events = Broadcast::default();
+    let orders = WebServiceHandler::new(Arc::new(store)).notify(order_events.clone());
+
+    //运行指标由服务器更新 通过/metrics随时查看
+    let metrics = Arc::new(ServerMetrics::default());
+
+    //注册路由 按注册顺序匹配 所以静态文件的通配路由放在最后
+    let mut router = Router::new();
+    router
+        .get("/api/shipping/orders", orders.clone())
+        .post("/api/shipping/orders", orders.clone())
+        .get("/api/shipping/orders/:id", orders.clone())
+        .put("/api/shipping/orders/:id", orders.clone())
+        .delete("/api/shipping/orders/:id", orders)
+        .post("/api/uploads", UploadHandler::new(config.data_dir.join("uploads")))
+        .websocket("/ws/orders", order_events)
+        .websocket("/ws/echo", Echo)
+        .get("/metrics", MetricsHandler::new(Arc::clone(&metrics)))
+        .get("/*path", static_files);
+
+    //中间件按注册顺序包在路由外面 访问日志在最外层 记录最终的响应
+    //CORS_ORIGINS是逗号分隔的允许来源 不设置表示允许任意来源
+    let origins = env::var("CORS_ORIGINS")
+        .map(|v| v.split(',').map(|o| o.trim().to_string()).filter(|o| !o.is_empty()).collect())
+        .unwrap_or_default();
+    //开启async特性时使用tokio上的异步服务器 两种服务器的构建方法相同
+    #[cfg(not(feature = "async"))]
+    let server = Server::new(&config.listen[0], router).queue_size(config.queue_size);
+    #[cfg(feature = "async")]
+    let server = AsyncServer::new(&config.listen[0], router).max_connections(config.max_connections);
+    let mut server = server
+        .listen(&config.listen)
+        .workers(config.workers)
+        .with_metrics(metrics)
+        .keep_alive_timeout(config.keep_alive_timeout())
+        .write_timeout(config.write_timeout())
+        .wrap(AccessLog)
+        .wrap(RequestId::default())
+        .wrap(Cors::new(origins));
+    //设置了BASIC_AUTH=用户名:密码 才开启认证 放在CORS之后 预检请求不需要认证
+    if let Some((name, password)) = env::var("BASIC_AUTH").ok().as_deref().and_then(|v| v.split_once(':')) {
+        server = server.wrap(BasicAuth::new("httpserver").user(name, password));
+    }
+    server = server.wrap(Gzip::default());
+
+    //开启tls特性并且设置了TLS_CERT和TLS_KEY时使用HTTPS 异步服务器还不支持TLS
+    #[cfg(all(feature = "tls", not(feature = "async")))]
+    if let (Ok(cert), Ok(key)) = (env::var("TLS_CERT"), env::var("TLS_KEY")) {
+        let config = http::tls::server_config(cert.as_ref(), key.as_ref()).unwrap_or_else(|e| {
+            eprintln!("Failed to load TLS certificate: {}", e);
+            process::exit(1);
+        });
+        server = server.tls(config);
+    }
+
+    #[cfg(all(feature = "tls", feature = "async"))]
+    if env::var("TLS_CERT").is_ok() {
+        eprintln!("httpserver: TLS is not supported by the async server, build without the async feature");
+        process::exit(2);
+    }
+
+    //收到Ctrl-C(SIGINT)后优雅关闭
+    let shutdown = server.shutdown_handle();
+    ctrlc::set_handler(move || shutdown.store(true, Ordering::SeqCst))
+        .expect("Error setting Ctrl-C handler");
+
+    if let Err(e) = server.run() {
+        eprintln!("httpserver: {}", e);
+        process::exit(1);
+    }
+    print!("{}", server.metrics());
 }
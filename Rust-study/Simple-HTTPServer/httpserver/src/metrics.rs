@@ -0,0 +1,70 @@
+//服务器运行指标 用原子计数器在线程间共享 无需加锁
+use super::handler::Handler;
+use http::httprequest::HttpRequest;
+use http::{httpresponse::HttpResponse, statuscode::StatusCode};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+pub struct ServerMetrics {
+    pub active_connections: AtomicUsize,    //正在被工作线程处理的连接
+    pub queue_depth: AtomicUsize,           //已接受但还在排队的连接
+    pub total_connections: AtomicUsize,     //累计接受的连接
+    pub rejected_connections: AtomicUsize,  //队列已满被拒绝的连接
+    pub total_requests: AtomicUsize,        //累计处理的请求
+    pub websocket_connections: AtomicUsize, //正在进行的WebSocket会话(同步服务器)
+}
+
+//按照 "名称 数值" 每行一个指标输出
+impl fmt::Display for ServerMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "active_connections {}", self.active_connections.load(Ordering::SeqCst))?;
+        writeln!(f, "queue_depth {}", self.queue_depth.load(Ordering::SeqCst))?;
+        writeln!(f, "total_connections {}", self.total_connections.load(Ordering::SeqCst))?;
+        writeln!(f, "rejected_connections {}", self.rejected_connections.load(Ordering::SeqCst))?;
+        writeln!(f, "total_requests {}", self.total_requests.load(Ordering::SeqCst))?;
+        writeln!(f, "websocket_connections {}", self.websocket_connections.load(Ordering::SeqCst))
+    }
+}
+
+//把当前的指标以纯文本返回 和服务器共用同一个ServerMetrics(见Server::with_metrics)
+pub struct MetricsHandler {
+    metrics: Arc<ServerMetrics>,
+}
+
+impl MetricsHandler {
+    pub fn new(metrics: Arc<ServerMetrics>) -> Self {
+        MetricsHandler { metrics }
+    }
+}
+
+impl Handler for MetricsHandler {
+    fn handle(&self, _req: &HttpRequest) -> HttpResponse {
+        HttpResponse::builder()
+            .status(StatusCode::Ok)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .header("Cache-Control", "no-store")
+            .body(self.metrics.to_string())
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_handler() {
+        let metrics = Arc::new(ServerMetrics::default());
+        let handler = MetricsHandler::new(Arc::clone(&metrics));
+        metrics.active_connections.fetch_add(2, Ordering::SeqCst);
+        metrics.queue_depth.fetch_add(1, Ordering::SeqCst);
+        let req = HttpRequest::try_from("GET /metrics HTTP/1.1\r\n\r\n".to_string()).unwrap();
+        let resp = handler.handle(&req);
+        assert_eq!(resp.status_code(), StatusCode::Ok);
+        let body = String::from_utf8(resp.body().to_vec()).unwrap();
+        assert!(body.contains("active_connections 2\n"));
+        assert!(body.contains("queue_depth 1\n"));
+    }
+}
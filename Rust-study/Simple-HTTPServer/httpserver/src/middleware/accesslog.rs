@@ -0,0 +1,71 @@
+//访问日志 每个请求输出一行Common Log Format
+//host ident authuser [date] "request line" status bytes
+//应该最先注册 这样记录的是其他中间件处理后的最终响应
+use super::basicauth;
+use super::Middleware;
+use crate::log::LogLevel;
+use chrono::{DateTime, Local, TimeZone};
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+
+pub struct AccessLog;
+
+impl Middleware for AccessLog {
+    fn after(&self, req: &HttpRequest, resp: &mut HttpResponse) {
+        crate::log!(LogLevel::Info, "{}", common_log_line(req, resp, &Local::now()));
+    }
+}
+
+fn common_log_line<Tz: TimeZone>(req: &HttpRequest, resp: &HttpResponse, time: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let host = req.peer_addr.map(|a| a.ip().to_string()).unwrap_or_else(|| "-".into());
+    let user = basicauth::credentials(req).map(|(name, _)| name).unwrap_or_else(|| "-".into());
+    let bytes = match resp.body().len() {
+        0 => "-".to_string(),
+        n => n.to_string(),
+    };
+    format!(
+        "{} - {} [{}] \"{} {} {}\" {} {}",
+        host,
+        user,
+        time.format("%d/%b/%Y:%H:%M:%S %z"),
+        req.method,
+        req.target(),
+        req.version.as_str(),
+        resp.status_code().as_u16(),
+        bytes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+    use http::statuscode::StatusCode;
+
+    #[test]
+    fn test_common_log_format() {
+        let mut req = HttpRequest::parse(
+            b"GET /apache_pb.gif HTTP/1.0\r\nAuthorization: Basic ZnJhbms6c2VjcmV0\r\n\r\n",
+        )
+        .unwrap();
+        req.peer_addr = Some("127.0.0.1:50000".parse().unwrap());
+        let resp = HttpResponse::builder().body(vec![0u8; 2326]).build();
+        let time = FixedOffset::west_opt(7 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2000, 10, 10, 13, 55, 36)
+            .unwrap();
+        assert_eq!(
+            common_log_line(&req, &resp, &time),
+            "127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] \"GET /apache_pb.gif HTTP/1.0\" 200 2326"
+        );
+
+        //没有客户端地址、用户和消息体时用"-"
+        let req = HttpRequest::parse(b"DELETE /x HTTP/1.1\r\n\r\n").unwrap();
+        let resp = HttpResponse::builder().status(StatusCode::NoContent).build();
+        assert!(common_log_line(&req, &resp, &time).starts_with("- - - ["));
+        assert!(common_log_line(&req, &resp, &time).ends_with("\"DELETE /x HTTP/1.1\" 204 -"));
+    }
+}
@@ -0,0 +1,90 @@
+//HTTP Basic认证(RFC 7617) 用户名密码不对时回复401并要求浏览器弹出登录框
+use super::Middleware;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use std::collections::HashMap;
+
+pub struct BasicAuth {
+    realm: String,
+    users: HashMap<String, String>,
+}
+
+impl BasicAuth {
+    pub fn new(realm: impl Into<String>) -> Self {
+        BasicAuth {
+            realm: realm.into(),
+            users: HashMap::new(),
+        }
+    }
+
+    pub fn user(mut self, name: impl Into<String>, password: impl Into<String>) -> Self {
+        self.users.insert(name.into(), password.into());
+        self
+    }
+
+    fn unauthorized(&self) -> HttpResponse {
+        HttpResponse::builder()
+            .status(StatusCode::Unauthorized)
+            .header("WWW-Authenticate", format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm))
+            .header("Content-Type", "text/plain")
+            .body("Unauthorized")
+            .build()
+    }
+}
+
+impl Middleware for BasicAuth {
+    fn before(&self, req: &mut HttpRequest) -> Option<HttpResponse> {
+        match credentials(req) {
+            Some((name, password)) if self.users.get(&name) == Some(&password) => None,
+            _ => Some(self.unauthorized()),
+        }
+    }
+}
+
+//从Authorization头部取出用户名和密码 不是Basic方案或者格式错误时返回None
+pub fn credentials(req: &HttpRequest) -> Option<(String, String)> {
+    let value = req.header("Authorization")?;
+    let (scheme, encoded) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (name, password) = decoded.split_once(':')?;
+    Some((name.to_string(), password.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> HttpRequest {
+        let header = authorization.map(|a| format!("Authorization: {}\r\n", a)).unwrap_or_default();
+        HttpRequest::parse(format!("GET / HTTP/1.1\r\n{}\r\n", header).as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_credentials_decoding() {
+        //"alice:open sesame"
+        let req = request(Some("Basic YWxpY2U6b3BlbiBzZXNhbWU="));
+        assert_eq!(credentials(&req), Some(("alice".into(), "open sesame".into())));
+        assert_eq!(credentials(&request(Some("Bearer abc"))), None);
+        assert_eq!(credentials(&request(Some("Basic !!!"))), None);
+        assert_eq!(credentials(&request(None)), None);
+    }
+
+    #[test]
+    fn test_wrong_or_missing_credentials_get_401() {
+        let auth = BasicAuth::new("orders").user("alice", "open sesame");
+        assert!(auth.before(&mut request(Some("Basic YWxpY2U6b3BlbiBzZXNhbWU="))).is_none());
+
+        //"alice:wrong"
+        for header in [None, Some("Basic YWxpY2U6d3Jvbmc=")] {
+            let resp = auth.before(&mut request(header)).unwrap();
+            assert_eq!(resp.status_code(), StatusCode::Unauthorized);
+            assert_eq!(resp.header("WWW-Authenticate"), Some("Basic realm=\"orders\", charset=\"UTF-8\""));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//跨域资源共享(CORS) 预检请求(OPTIONS + Access-Control-Request-Method)直接回复204
+//普通跨域请求在响应里加上Access-Control-Allow-Origin
+use super::Middleware;
+use http::httprequest::{HttpRequest, Method};
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+
+const ALLOW_METHODS: &str = "GET, HEAD, POST, PUT, DELETE, PATCH, OPTIONS";
+//浏览器缓存预检结果的秒数
+const MAX_AGE: u32 = 86400;
+
+pub struct Cors {
+    origins: Vec<String>, //允许的来源 为空表示允许任意来源
+}
+
+impl Cors {
+    pub fn new(origins: Vec<String>) -> Self {
+        Cors { origins }
+    }
+
+    //来源被允许时返回Access-Control-Allow-Origin的值
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.origins.is_empty() {
+            Some("*".to_string())
+        } else if self.origins.iter().any(|o| o == origin) {
+            Some(origin.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+impl Middleware for Cors {
+    fn before(&self, req: &mut HttpRequest) -> Option<HttpResponse> {
+        if req.method != Method::Options || req.header("Access-Control-Request-Method").is_none() {
+            return None;
+        }
+        let origin = req.header("Origin")?;
+        //来源不被允许时也回复204 只是不带允许的头部 浏览器会拦下真正的请求
+        let mut resp = HttpResponse::builder().status(StatusCode::NoContent).build();
+        if self.allow_origin(origin).is_some() {
+            resp.set_header("Access-Control-Allow-Methods", ALLOW_METHODS);
+            if let Some(headers) = req.header("Access-Control-Request-Headers") {
+                resp.set_header("Access-Control-Allow-Headers", headers);
+            }
+            resp.set_header("Access-Control-Max-Age", MAX_AGE.to_string());
+        }
+        Some(resp)
+    }
+
+    fn after(&self, req: &HttpRequest, resp: &mut HttpResponse) {
+        let Some(origin) = req.header("Origin") else {
+            return;
+        };
+        if let Some(allowed) = self.allow_origin(origin) {
+            resp.set_header("Access-Control-Allow-Origin", allowed);
+        }
+        //按来源区分响应时 缓存必须按Origin区分
+        if !self.origins.is_empty() {
+            resp.append_header("Vary", "Origin");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(raw: &str) -> HttpRequest {
+        HttpRequest::parse(raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_preflight_short_circuits() {
+        let cors = Cors::new(vec!["https://shop.example".to_string()]);
+        let mut req = request(
+            "OPTIONS /api/shipping/orders HTTP/1.1\r\nOrigin: https://shop.example\r\n\
+             Access-Control-Request-Method: PUT\r\nAccess-Control-Request-Headers: content-type\r\n\r\n",
+        );
+        let mut resp = cors.before(&mut req).unwrap();
+        cors.after(&req, &mut resp);
+        assert_eq!(resp.status_code(), StatusCode::NoContent);
+        assert_eq!(resp.header("Access-Control-Allow-Origin"), Some("https://shop.example"));
+        assert_eq!(resp.header("Access-Control-Allow-Methods"), Some(ALLOW_METHODS));
+        assert_eq!(resp.header("Access-Control-Allow-Headers"), Some("content-type"));
+
+        //普通的OPTIONS请求交给路由处理
+        assert!(cors.before(&mut request("OPTIONS / HTTP/1.1\r\n\r\n")).is_none());
+    }
+
+    #[test]
+    fn test_allow_origin_header() {
+        let mut resp = HttpResponse::builder().build();
+        Cors::new(Vec::new()).after(&request("GET / HTTP/1.1\r\nOrigin: https://a.example\r\n\r\n"), &mut resp);
+        assert_eq!(resp.header("Access-Control-Allow-Origin"), Some("*"));
+
+        let cors = Cors::new(vec!["https://shop.example".to_string()]);
+        let mut resp = HttpResponse::builder().build();
+        cors.after(&request("GET / HTTP/1.1\r\nOrigin: https://evil.example\r\n\r\n"), &mut resp);
+        assert_eq!(resp.header("Access-Control-Allow-Origin"), None);
+        assert_eq!(resp.header("Vary"), Some("Origin"));
+
+        //同源请求没有Origin头部 不做任何修改
+        let mut resp = HttpResponse::builder().build();
+        cors.after(&request("GET / HTTP/1.1\r\n\r\n"), &mut resp);
+        assert!(resp.header("Vary").is_none());
+    }
+}
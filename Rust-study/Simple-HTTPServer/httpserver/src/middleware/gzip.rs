@@ -0,0 +1,136 @@
+//gzip响应压缩 客户端的Accept-Encoding接受gzip并且消息体足够大时才压缩
+//已经编码过的响应、部分内容(206)和图片等本身已压缩的类型保持原样
+use super::Middleware;
+use crate::log::LogLevel;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use std::io::Write;
+
+//小于这个字节数的消息体压缩后省不了多少 不值得
+const DEFAULT_MIN_SIZE: usize = 1024;
+
+pub struct Gzip {
+    min_size: usize,
+}
+
+impl Default for Gzip {
+    fn default() -> Self {
+        Gzip {
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+}
+
+impl Middleware for Gzip {
+    fn after(&self, req: &HttpRequest, resp: &mut HttpResponse) {
+        if resp.body().len() < self.min_size
+            || !resp.status_code().allows_body()
+            || resp.status_code() == StatusCode::PartialContent
+            || resp.header("Content-Encoding").is_some()
+            || !is_compressible(resp.header("Content-Type").unwrap_or(""))
+        {
+            return;
+        }
+        //不管这次是否压缩 响应内容都随Accept-Encoding变化
+        resp.append_header("Vary", "Accept-Encoding");
+        if !accepts_gzip(req.header("Accept-Encoding").unwrap_or("")) {
+            return;
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let compressed = match encoder.write_all(resp.body()).and_then(|_| encoder.finish()) {
+            Ok(compressed) => compressed,
+            Err(e) => {
+                crate::log!(LogLevel::Warn, "Gzip compression failed: {}", e);
+                return;
+            }
+        };
+        resp.set_body(compressed);
+        resp.set_header("Content-Encoding", "gzip");
+        //压缩后的字节和原文件不同 强ETag改成弱ETag
+        if let Some(etag) = resp.header("ETag").filter(|e| !e.starts_with("W/")).map(|e| format!("W/{}", e)) {
+            resp.set_header("ETag", etag);
+        }
+    }
+}
+
+//按逗号拆分 "gzip;q=0" 表示明确拒绝
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut parts = item.split(';').map(str::trim);
+        let coding = parts.next().unwrap_or("");
+        let rejected = parts.any(|p| {
+            p.strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        (coding.eq_ignore_ascii_case("gzip") || coding == "*") && !rejected
+    })
+}
+
+fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+xml")
+        || mime.ends_with("+json")
+        || matches!(
+            mime.as_str(),
+            "application/json" | "application/javascript" | "application/xml" | "image/svg+xml"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn run(accept_encoding: &str, body: &str) -> HttpResponse {
+        let req = HttpRequest::parse(format!("GET / HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n", accept_encoding).as_bytes())
+            .unwrap();
+        let mut resp = HttpResponse::builder()
+            .header("Content-Type", "text/html")
+            .header("ETag", "\"abc\"")
+            .body(body)
+            .build();
+        Gzip::default().after(&req, &mut resp);
+        resp
+    }
+
+    #[test]
+    fn test_large_body_is_compressed() {
+        let body = "<p>order</p>".repeat(200);
+        let resp = run("deflate, gzip;q=0.8", &body);
+        assert_eq!(resp.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(resp.header("Vary"), Some("Accept-Encoding"));
+        assert_eq!(resp.header("ETag"), Some("W/\"abc\""));
+        assert!(resp.body().len() < body.len());
+
+        let mut decoded = String::new();
+        GzDecoder::new(resp.body()).read_to_string(&mut decoded).unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[test]
+    fn test_small_or_unaccepted_body_is_unchanged() {
+        assert!(run("gzip", "tiny").header("Content-Encoding").is_none());
+
+        let body = "x".repeat(4096);
+        for accept in ["identity", "gzip;q=0", ""] {
+            let resp = run(accept, &body);
+            assert!(resp.header("Content-Encoding").is_none(), "{}", accept);
+            assert_eq!(resp.body(), body.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_compressible_types() {
+        assert!(is_compressible("text/css; charset=utf-8"));
+        assert!(is_compressible("application/json"));
+        assert!(!is_compressible("image/png"));
+        assert!(!is_compressible(""));
+    }
+}
@@ -0,0 +1,166 @@
+//中间件模块 在路由前后处理请求和响应 用于日志、鉴权、跨域、压缩等横切功能
+//before按注册顺序执行 返回Some(resp)时短路 后面的中间件和路由都不再执行
+//after按相反顺序执行 只有执行过before的中间件才会执行after
+pub mod accesslog;
+pub mod basicauth;
+pub mod cors;
+pub mod gzip;
+pub mod requestid;
+
+use super::router::Router;
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+use std::io::Read;
+
+//中间件在多个工作线程之间共享 所以要求Send + Sync
+pub trait Middleware: Send + Sync {
+    fn before(&self, _req: &mut HttpRequest) -> Option<HttpResponse> {
+        None
+    }
+
+    fn after(&self, _req: &HttpRequest, _resp: &mut HttpResponse) {}
+}
+
+//中间件链 最后交给路由表处理
+pub struct Pipeline {
+    middlewares: Vec<Box<dyn Middleware>>,
+    router: Router,
+}
+
+impl Pipeline {
+    pub fn new(router: Router) -> Self {
+        Pipeline {
+            middlewares: Vec::new(),
+            router,
+        }
+    }
+
+    //先注册的中间件在外层 最先看到请求 最后看到响应
+    pub fn wrap(&mut self, middleware: impl Middleware + 'static) -> &mut Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    pub fn handle(&self, req: &mut HttpRequest) -> HttpResponse {
+        let (ran, short_circuit) = self.before(req);
+        let mut resp = match short_circuit {
+            Some(resp) => resp,
+            None => self.router.route(req),
+        };
+        self.after(ran, req, &mut resp);
+        resp
+    }
+
+    //异步服务器使用 中间件本身仍然是同步的 只有路由到的处理函数异步执行
+    pub async fn handle_async(&self, req: &mut HttpRequest) -> HttpResponse {
+        let (ran, short_circuit) = self.before(req);
+        let mut resp = match short_circuit {
+            Some(resp) => resp,
+            None => self.router.route_async(req).await,
+        };
+        self.after(ran, req, &mut resp);
+        resp
+    }
+
+    pub fn body_limit(&self, req: &mut HttpRequest) -> Option<u64> {
+        self.router.body_limit(req)
+    }
+
+    //消息体留在连接上由处理函数流式读取 中间件看到的req.body是空的
+    //中间件短路时消息体没有被读取 服务器回复后关闭连接
+    pub fn handle_stream(&self, req: &mut HttpRequest, body: &mut dyn Read) -> HttpResponse {
+        let (ran, short_circuit) = self.before(req);
+        let mut resp = match short_circuit {
+            Some(resp) => resp,
+            None => self.router.route_stream(req, body),
+        };
+        self.after(ran, req, &mut resp);
+        resp
+    }
+
+    //返回执行过before的中间件数量和短路的响应
+    fn before(&self, req: &mut HttpRequest) -> (usize, Option<HttpResponse>) {
+        let mut ran = 0;
+        for middleware in &self.middlewares {
+            ran += 1;
+            if let Some(resp) = middleware.before(req) {
+                return (ran, Some(resp));
+            }
+        }
+        (ran, None)
+    }
+
+    fn after(&self, ran: usize, req: &HttpRequest, resp: &mut HttpResponse) {
+        for middleware in self.middlewares[..ran].iter().rev() {
+            middleware.after(req, resp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::statuscode::StatusCode;
+    use std::sync::{Arc, Mutex};
+
+    //把调用顺序记录到共享的列表里
+    struct Trace {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        block: bool,
+    }
+
+    impl Middleware for Trace {
+        fn before(&self, _req: &mut HttpRequest) -> Option<HttpResponse> {
+            self.log.lock().unwrap().push(format!("before {}", self.name));
+            if self.block {
+                return Some(HttpResponse::builder().status(StatusCode::Forbidden).build());
+            }
+            None
+        }
+
+        fn after(&self, _req: &HttpRequest, resp: &mut HttpResponse) {
+            self.log.lock().unwrap().push(format!("after {}", self.name));
+            resp.append_header("X-Trace", self.name);
+        }
+    }
+
+    fn pipeline(log: &Arc<Mutex<Vec<String>>>, block_second: bool) -> Pipeline {
+        let mut router = Router::new();
+        router.get("/", |_: &HttpRequest| HttpResponse::builder().body("hello").build());
+        let mut pipeline = Pipeline::new(router);
+        for (name, block) in [("a", false), ("b", block_second), ("c", false)] {
+            pipeline.wrap(Trace {
+                name,
+                log: Arc::clone(log),
+                block,
+            });
+        }
+        pipeline
+    }
+
+    #[test]
+    fn test_before_in_order_after_in_reverse() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut req = HttpRequest::parse(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let resp = pipeline(&log, false).handle(&mut req);
+        assert_eq!(resp.body(), b"hello");
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["before a", "before b", "before c", "after c", "after b", "after a"]
+        );
+    }
+
+    #[test]
+    fn test_short_circuit_skips_inner_middleware_and_router() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut req = HttpRequest::parse(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let resp = pipeline(&log, true).handle(&mut req);
+        assert_eq!(resp.status_code(), StatusCode::Forbidden);
+        assert_eq!(*log.lock().unwrap(), ["before a", "before b", "after b", "after a"]);
+    }
+}
@@ -0,0 +1,74 @@
+//请求ID 客户端带了X-Request-Id就沿用 否则生成一个
+//ID写回请求头部供处理函数使用 同时放进响应头部方便对照日志排查问题
+use super::Middleware;
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEADER: &str = "x-request-id";
+//客户端提供的ID过长或者含有不可见字符时丢弃 重新生成
+const MAX_ID_LEN: usize = 128;
+
+#[derive(Default)]
+pub struct RequestId {
+    counter: AtomicU64,
+}
+
+impl RequestId {
+    //启动时间(纳秒)加上自增序号 同一进程内不会重复
+    fn generate(&self) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let seq = self.counter.fetch_add(1, Ordering::Relaxed);
+        format!("{:x}-{:04x}", nanos, seq)
+    }
+}
+
+impl Middleware for RequestId {
+    fn before(&self, req: &mut HttpRequest) -> Option<HttpResponse> {
+        let valid = req
+            .header(HEADER)
+            .filter(|id| !id.is_empty() && id.len() <= MAX_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+            .map(|id| id.to_string());
+        let id = valid.unwrap_or_else(|| self.generate());
+        req.headers.insert(HEADER.to_string(), vec![id]);
+        None
+    }
+
+    fn after(&self, req: &HttpRequest, resp: &mut HttpResponse) {
+        if let Some(id) = req.header(HEADER) {
+            resp.set_header("X-Request-Id", id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(middleware: &RequestId, raw: &str) -> HttpResponse {
+        let mut req = HttpRequest::parse(raw.as_bytes()).unwrap();
+        assert!(middleware.before(&mut req).is_none());
+        let mut resp = HttpResponse::builder().build();
+        middleware.after(&req, &mut resp);
+        resp
+    }
+
+    #[test]
+    fn test_request_id_is_kept_or_generated() {
+        let middleware = RequestId::default();
+        let resp = run(&middleware, "GET / HTTP/1.1\r\nX-Request-Id: abc-123\r\n\r\n");
+        assert_eq!(resp.header("X-Request-Id"), Some("abc-123"));
+
+        let first = run(&middleware, "GET / HTTP/1.1\r\n\r\n");
+        let second = run(&middleware, "GET / HTTP/1.1\r\nX-Request-Id: has space\r\n\r\n");
+        let first = first.header("X-Request-Id").unwrap();
+        let second = second.header("X-Request-Id").unwrap();
+        assert!(!first.is_empty());
+        assert_ne!(first, second);
+        assert_ne!(second, "has space");
+    }
+}
@@ -0,0 +1,147 @@
+//线程池模块 固定数量的工作线程从有界队列中取任务
+//队列满时execute把任务原样还给调用者 由调用者决定如何拒绝
+use super::log::LogLevel;
+use super::metrics::ServerMetrics;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub struct ThreadPool<T: Send + 'static> {
+    workers: Vec<Worker>,
+    sender: Option<SyncSender<T>>,
+    metrics: Arc<ServerMetrics>,
+}
+
+struct Worker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> ThreadPool<T> {
+    //size个工作线程 最多queue_size个任务排队等待 每个任务交给handler处理
+    pub fn new<F>(size: usize, queue_size: usize, metrics: Arc<ServerMetrics>, handler: F) -> ThreadPool<T>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        assert!(size > 0, "thread pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::sync_channel(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let handler = Arc::new(handler);
+
+        let workers = (0..size)
+            .map(|id| Worker::new(id, Arc::clone(&receiver), Arc::clone(&metrics), Arc::clone(&handler)))
+            .collect();
+
+        ThreadPool {
+            workers,
+            sender: Some(sender),
+            metrics,
+        }
+    }
+
+    //把任务放进队列 队列已满时返回Err(job)
+    pub fn execute(&self, job: T) -> Result<(), T> {
+        let sender = self.sender.as_ref().expect("thread pool already shut down");
+        //先计数再发送 保证工作线程取出时不会减到负数
+        self.metrics.queue_depth.fetch_add(1, Ordering::SeqCst);
+        match sender.try_send(job) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(job)) | Err(TrySendError::Disconnected(job)) => {
+                self.metrics.queue_depth.fetch_sub(1, Ordering::SeqCst);
+                Err(job)
+            }
+        }
+    }
+}
+
+//丢弃线程池时先关闭队列 工作线程处理完剩余任务后退出 再逐个join
+impl<T: Send + 'static> Drop for ThreadPool<T> {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                if thread.join().is_err() {
+                    crate::log!(LogLevel::Error, "Worker {} panicked", worker.id);
+                }
+            }
+        }
+    }
+}
+
+impl Worker {
+    fn new<T, F>(id: usize, receiver: Arc<Mutex<Receiver<T>>>, metrics: Arc<ServerMetrics>, handler: Arc<F>) -> Worker
+    where
+        T: Send + 'static,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let thread = thread::spawn(move || loop {
+            //锁只在取任务时持有 处理任务时已经释放
+            let job = receiver.lock().unwrap().recv();
+            match job {
+                Ok(job) => {
+                    metrics.queue_depth.fetch_sub(1, Ordering::SeqCst);
+                    handler(job);
+                }
+                //发送端已关闭且队列为空
+                Err(_) => break,
+            }
+        });
+
+        Worker {
+            id,
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pool_runs_all_jobs_before_drop_returns() {
+        let metrics = Arc::new(ServerMetrics::default());
+        let (done_tx, done_rx) = channel();
+        let done_tx = Mutex::new(done_tx);
+        let pool = ThreadPool::new(2, 8, Arc::clone(&metrics), move |n: usize| {
+            thread::sleep(Duration::from_millis(10));
+            done_tx.lock().unwrap().send(n).unwrap();
+        });
+        for n in 0..6 {
+            pool.execute(n).unwrap();
+        }
+        drop(pool);
+
+        let mut finished: Vec<usize> = done_rx.try_iter().collect();
+        finished.sort();
+        assert_eq!(finished, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(metrics.queue_depth.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_full_queue_returns_job() {
+        let metrics = Arc::new(ServerMetrics::default());
+        let (block_tx, block_rx) = channel::<()>();
+        let block_rx = Mutex::new(block_rx);
+        let pool = ThreadPool::new(1, 1, Arc::clone(&metrics), move |_: usize| {
+            let _ = block_rx.lock().unwrap().recv();
+        });
+        //第一个任务占住唯一的工作线程 等它被取走
+        pool.execute(1).unwrap();
+        while metrics.queue_depth.load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+        //第二个任务占满队列 第三个被退回
+        pool.execute(2).unwrap();
+        assert_eq!(pool.execute(3), Err(3));
+        assert_eq!(metrics.queue_depth.load(Ordering::SeqCst), 1);
+
+        drop(block_tx);
+        drop(pool);
+    }
+}
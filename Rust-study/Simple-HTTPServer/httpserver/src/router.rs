@@ -1,31 +1,302 @@
-use super::handler::{Handler, PageNotFoundHandler, StaticPageHandler,WebServiceHandler};
-use http::{httprequest, httprequest::HttpRequest, httpresponse::HttpResponse};
-use std::io::prelude::*;
+//路由表 按"方法+路径模式"注册处理函数
+//路径模式支持静态段(/api)、参数段(/:id)和通配段(/*path 只能放在最后 匹配剩余所有段)
+//按注册顺序匹配 第一个匹配的路由生效 WebSocket路由优先于普通路由
+//没有HEAD路由时HEAD请求使用GET路由 服务器发送响应时去掉消息体
+use super::handler::{BoxFuture, Handler, PageNotFoundHandler};
+use super::websocket::{self, WsHandler};
+use http::httprequest::{HttpRequest, Method, Resource};
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use std::collections::HashMap;
+use std::io::Read;
 
-pub struct Router;
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Static(String),
+    Param(String),
+    Wildcard(String),
+}
+
+struct Route {
+    method: Method,
+    pattern: Vec<Segment>,
+    handler: Box<dyn Handler>,
+}
+
+//WebSocket路由只接受GET 握手成功后由服务器把连接交给handler
+struct WsRoute {
+    pattern: Vec<Segment>,
+    handler: Box<dyn WsHandler>,
+}
+
+pub struct Router {
+    routes: Vec<Route>,
+    ws_routes: Vec<WsRoute>,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router::new()
+    }
+}
 
 impl Router {
-    pub fn router(req: HttpRequest, stream:&mut impl Write) -> () {
-        match req.method {
-            httprequest::Method::Get => match &req.resource {
-                httprequest::Resource::Path(s) => {
-                    let route:Vec<&str> = s.split("/").collect();
-                    match route[1] {
-                        "api" => {
-                            let resp: HttpResponse = WebServiceHandler::handle(&req);
-                            let _ = resp.send_response(stream);
-                        }
-                        _ => {
-                            let resp: HttpResponse = StaticPageHandler::handle(&req);
-                            let _ = resp.send_response(stream);
-                        }
-                    }
+    pub fn new() -> Self {
+        Router {
+            routes: Vec::new(),
+            ws_routes: Vec::new(),
+        }
+    }
+
+    //注册路由 路径模式格式错误(例如通配段不在最后)时直接panic 这属于编程错误
+    pub fn add(&mut self, method: Method, pattern: &str, handler: impl Handler + 'static) -> &mut Self {
+        self.routes.push(Route {
+            method,
+            pattern: parse_pattern(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get(&mut self, pattern: &str, handler: impl Handler + 'static) -> &mut Self {
+        self.add(Method::Get, pattern, handler)
+    }
+
+    pub fn post(&mut self, pattern: &str, handler: impl Handler + 'static) -> &mut Self {
+        self.add(Method::Post, pattern, handler)
+    }
+
+    pub fn put(&mut self, pattern: &str, handler: impl Handler + 'static) -> &mut Self {
+        self.add(Method::Put, pattern, handler)
+    }
+
+    pub fn delete(&mut self, pattern: &str, handler: impl Handler + 'static) -> &mut Self {
+        self.add(Method::Delete, pattern, handler)
+    }
+
+    pub fn websocket(&mut self, pattern: &str, handler: impl WsHandler + 'static) -> &mut Self {
+        self.ws_routes.push(WsRoute {
+            pattern: parse_pattern(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    //根据请求找到处理函数 匹配出的路径参数写入req.params
+    //路径没有匹配返回404 路径匹配但方法不对返回405并带上Allow头部
+    pub fn route(&self, req: &mut HttpRequest) -> HttpResponse {
+        match self.resolve(req) {
+            Ok(handler) => handler.handle(req),
+            Err(resp) => resp,
+        }
+    }
+
+    //异步服务器使用 匹配规则和route一样
+    pub fn route_async<'a>(&'a self, req: &'a mut HttpRequest) -> BoxFuture<'a, HttpResponse> {
+        match self.resolve(req) {
+            Ok(handler) => handler.handle_async(req),
+            Err(resp) => Box::pin(std::future::ready(resp)),
+        }
+    }
+
+    //匹配的处理函数要求流式读取消息体时返回它的上限 见Handler::body_limit
+    pub fn body_limit(&self, req: &mut HttpRequest) -> Option<u64> {
+        self.resolve(req).ok()?.body_limit(req)
+    }
+
+    //body_limit返回Some时使用 消息体由处理函数从body读取
+    pub fn route_stream(&self, req: &mut HttpRequest, body: &mut dyn Read) -> HttpResponse {
+        match self.resolve(req) {
+            Ok(handler) => handler.handle_stream(req, body),
+            Err(resp) => resp,
+        }
+    }
+
+    //返回匹配的处理函数 不需要处理函数的情况(握手、404、405)直接返回响应
+    fn resolve(&self, req: &mut HttpRequest) -> Result<&dyn Handler, HttpResponse> {
+        let Resource::Path(path) = &req.resource;
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        let mut allowed: Vec<Method> = Vec::new();
+        //WebSocket路由返回握手结果 101或者说明失败原因的错误响应
+        for route in &self.ws_routes {
+            if let Some(params) = match_pattern(&route.pattern, &segments) {
+                if req.method == Method::Get {
+                    req.params = params;
+                    return Err(websocket::handshake(req));
                 }
-            },
-            _ => {
-                let resp: HttpResponse = PageNotFoundHandler::handle(&req);
-                let _ = resp.send_response(stream);
+                allowed.push(Method::Get);
+                break;
             }
         }
+        let mut get_route = None;
+        for route in &self.routes {
+            if let Some(params) = match_pattern(&route.pattern, &segments) {
+                if route.method == req.method {
+                    req.params = params;
+                    return Ok(route.handler.as_ref());
+                }
+                if route.method == Method::Get && get_route.is_none() {
+                    get_route = Some((route, params));
+                }
+                if !allowed.contains(&route.method) {
+                    allowed.push(route.method);
+                }
+            }
+        }
+        if req.method == Method::Head {
+            if let Some((route, params)) = get_route {
+                req.params = params;
+                return Ok(route.handler.as_ref());
+            }
+        }
+        if allowed.contains(&Method::Get) && !allowed.contains(&Method::Head) {
+            allowed.push(Method::Head);
+        }
+
+        if allowed.is_empty() {
+            return Ok(&PageNotFoundHandler);
+        }
+        let allow: Vec<&str> = allowed.iter().map(|m| m.as_str()).collect();
+        Err(HttpResponse::builder()
+            .status(StatusCode::MethodNotAllowed)
+            .header("Allow", allow.join(", "))
+            .header("Content-Type", "text/plain")
+            .body("Method Not Allowed")
+            .build())
+    }
+
+    //握手成功后 服务器用它找到接管连接的处理函数
+    pub fn websocket_handler(&self, req: &HttpRequest) -> Option<&dyn WsHandler> {
+        let Resource::Path(path) = &req.resource;
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        self.ws_routes
+            .iter()
+            .find(|route| match_pattern(&route.pattern, &segments).is_some())
+            .map(|route| route.handler.as_ref())
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let parts: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if let Some(name) = part.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = part.strip_prefix('*') {
+                assert!(i == parts.len() - 1, "wildcard must be the last segment: {}", pattern);
+                Segment::Wildcard(name.to_string())
+            } else {
+                Segment::Static(part.to_string())
+            }
+        })
+        .collect()
+}
+
+//匹配成功返回路径参数 通配段的值是剩余各段用'/'连接(可以为空)
+fn match_pattern(pattern: &[Segment], segments: &[&str]) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    for (i, seg) in pattern.iter().enumerate() {
+        match seg {
+            Segment::Wildcard(name) => {
+                params.insert(name.clone(), segments.get(i..).unwrap_or(&[]).join("/"));
+                return Some(params);
+            }
+            Segment::Static(s) if segments.get(i) == Some(&s.as_str()) => {}
+            Segment::Param(name) => {
+                params.insert(name.clone(), segments.get(i)?.to_string());
+            }
+            Segment::Static(_) => return None,
+        }
+    }
+    if pattern.len() == segments.len() {
+        Some(params)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(raw: &str) -> HttpRequest {
+        HttpRequest::parse(raw.as_bytes()).unwrap()
+    }
+
+    //把路径参数按名称排序后拼成字符串 方便断言
+    fn echo_params(req: &HttpRequest) -> HttpResponse {
+        let mut params: Vec<String> = req.params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        params.sort();
+        HttpResponse::builder().body(params.join("&")).build()
+    }
+
+    fn test_router() -> Router {
+        let mut router = Router::new();
+        router
+            .get("/api/shipping/orders/:id", echo_params)
+            .add(Method::Put, "/api/shipping/orders/:id", |_: &HttpRequest| {
+                HttpResponse::builder().status(StatusCode::NoContent).build()
+            })
+            .get("/files/*rest", echo_params);
+        router
+    }
+
+    #[test]
+    fn test_param_extraction() {
+        let router = test_router();
+        let mut req = request("GET /api/shipping/orders/42 HTTP/1.1\r\n\r\n");
+        let resp = router.route(&mut req);
+        assert_eq!(resp.status_code(), StatusCode::Ok);
+        assert_eq!(resp.body(), b"id=42");
+
+        let mut req = request("PUT /api/shipping/orders/42 HTTP/1.1\r\n\r\n");
+        assert_eq!(router.route(&mut req).status_code(), StatusCode::NoContent);
+    }
+
+    #[test]
+    fn test_wildcard_matches_rest_of_path() {
+        let router = test_router();
+        let mut req = request("GET /files/css/site.css HTTP/1.1\r\n\r\n");
+        assert_eq!(router.route(&mut req).body(), b"rest=css/site.css");
+        let mut req = request("GET /files HTTP/1.1\r\n\r\n");
+        assert_eq!(router.route(&mut req).body(), b"rest=");
+    }
+
+    #[test]
+    fn test_not_found_and_method_not_allowed() {
+        let router = test_router();
+        //路径过短或者过长都不会panic
+        for path in ["/", "/api", "/api/shipping/orders", "/api/shipping/orders/1/extra"] {
+            let mut req = request(&format!("GET {} HTTP/1.1\r\n\r\n", path));
+            assert_eq!(router.route(&mut req).status_code(), StatusCode::NotFound, "{}", path);
+        }
+
+        let mut req = request("DELETE /api/shipping/orders/1 HTTP/1.1\r\n\r\n");
+        let resp = router.route(&mut req);
+        assert_eq!(resp.status_code(), StatusCode::MethodNotAllowed);
+        assert_eq!(resp.header("Allow"), Some("GET, PUT, HEAD"));
+    }
+
+    #[test]
+    fn test_head_uses_get_route() {
+        let mut router = test_router();
+        let mut req = request("HEAD /api/shipping/orders/42 HTTP/1.1\r\n\r\n");
+        assert_eq!(router.route(&mut req).body(), b"id=42");
+
+        //单独注册的HEAD路由优先
+        router.add(Method::Head, "/api/shipping/orders/:id", |_: &HttpRequest| {
+            HttpResponse::builder().status(StatusCode::NoContent).build()
+        });
+        let mut req = request("HEAD /api/shipping/orders/42 HTTP/1.1\r\n\r\n");
+        assert_eq!(router.route(&mut req).status_code(), StatusCode::NoContent);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wildcard_must_be_last() {
+        Router::new().get("/*rest/more", echo_params);
     }
 }
@@ -1,32 +1,651 @@
 //主要功能程序
+use super::log::LogLevel;
+use super::metrics::ServerMetrics;
+use super::middleware::{Middleware, Pipeline};
+use super::pool::ThreadPool;
 use super::router::Router;
-use http::httprequest::HttpRequest;
-use std::io::prelude::*;
-use std::net::TcpListener;
-use std::str;
+use super::websocket;
+use http::httprequest::{HttpRequest, Method};
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use http::transfer;
+#[cfg(feature = "tls")]
+use http::tls::rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
-pub struct Server<'a> {
-    socket_addr: &'a str,
+//keep-alive连接默认的空闲超时时间 超过这个时间没有新请求就关闭连接
+pub const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+//客户端迟迟不接收响应时 写操作最多等待的时间
+pub const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+//默认工作线程数和排队上限
+pub const DEFAULT_WORKERS: usize = 4;
+pub const DEFAULT_QUEUE_SIZE: usize = 64;
+//异步服务器默认同时处理的连接上限 异步连接空闲时不占用线程 所以可以比同步服务器大得多
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+//同步服务器同时保持的WebSocket会话上限 每个会话占用一个单独的线程 不占用工作线程
+pub const DEFAULT_MAX_WEBSOCKETS: usize = 64;
+//非阻塞accept没有新连接时的等待间隔 也决定了响应关闭信号的速度
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+//连接的读写超时
+#[derive(Clone, Copy)]
+struct Timeouts {
+    keep_alive: Duration,
+    write: Duration,
+}
+
+//所有连接共享的状态 WebSocket会话转到单独的线程时一起带走
+struct Shared {
+    pipeline: Arc<Pipeline>,
+    timeouts: Timeouts,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+    max_websockets: usize,
+}
+
+pub struct Server {
+    addrs: Vec<String>, //run时监听的所有地址
+    pipeline: Arc<Pipeline>,
+    timeouts: Timeouts,
+    workers: usize,
+    queue_size: usize,
+    max_websockets: usize,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<ServerConfig>>, //设置后所有连接都走TLS
 }
 
-impl<'a> Server<'a> {
-    pub fn new(socket_addr: &'a str) -> Self {
-        Server { socket_addr }
+impl Server {
+    pub fn new(socket_addr: &str, router: Router) -> Self {
+        Server {
+            addrs: vec![socket_addr.to_string()],
+            pipeline: Arc::new(Pipeline::new(router)),
+            timeouts: Timeouts {
+                keep_alive: DEFAULT_KEEP_ALIVE_TIMEOUT,
+                write: DEFAULT_WRITE_TIMEOUT,
+            },
+            workers: DEFAULT_WORKERS,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            max_websockets: DEFAULT_MAX_WEBSOCKETS,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(ServerMetrics::default()),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    //注册中间件 先注册的在外层 必须在run之前调用
+    pub fn wrap(mut self, middleware: impl Middleware + 'static) -> Self {
+        Arc::get_mut(&mut self.pipeline)
+            .expect("middleware must be registered before the server starts")
+            .wrap(middleware);
+        self
     }
 
-    pub fn run(&self) {
-        let connection_listener = TcpListener::bind(self.socket_addr).unwrap();
-        println!("Running on {}", self.socket_addr);
+    //启用HTTPS 配置可以用http::tls::server_config从PEM文件加载
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: Arc<ServerConfig>) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    //替换监听地址 所有地址上的连接共用同一个线程池
+    pub fn listen(mut self, addrs: &[String]) -> Self {
+        self.addrs = addrs.to_vec();
+        self
+    }
+
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.keep_alive = timeout;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.write = timeout;
+        self
+    }
 
-        for stream in connection_listener.incoming(){
-            let mut stream = stream.unwrap();
-            println!("Connecting established");
-            
-            let mut read_buffer = [0;200];
-            stream.read(&mut read_buffer).unwrap();
-            //HttpRequest实现了from方法就可以调用into来进行类型转换
-            let req:HttpRequest = String::from_utf8(read_buffer.to_vec()).unwrap().into();
-            Router::router(req, &mut stream);
+    //设置工作线程数量
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    //设置排队连接的上限 超过的连接直接回复503
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size;
+        self
+    }
+
+    //设置同时保持的WebSocket会话上限 达到上限后新的握手请求回复503
+    pub fn max_websockets(mut self, max_websockets: usize) -> Self {
+        self.max_websockets = max_websockets;
+        self
+    }
+
+    //把标志设为true 服务器停止接受新连接 处理完已有请求后run返回
+    pub fn shutdown_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.shutdown)
+    }
+
+    pub fn metrics(&self) -> Arc<ServerMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    //使用外部创建的指标 例如已经交给MetricsHandler的指标 运行时通过路由查看
+    pub fn with_metrics(mut self, metrics: Arc<ServerMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    //绑定所有监听地址 任何一个绑定失败都直接返回错误 不会只监听一部分地址
+    pub fn run(&self) -> io::Result<()> {
+        let scheme = if self.is_tls() { "https" } else { "http" };
+        let mut listeners = Vec::new();
+        for addr in &self.addrs {
+            let listener = TcpListener::bind(addr)
+                .map_err(|e| io::Error::new(e.kind(), format!("cannot listen on {}: {}", addr, e)))?;
+            crate::log!(LogLevel::Info, "Running on {}://{}", scheme, addr);
+            listeners.push(listener);
         }
+        self.serve_all(listeners)
     }
-}
\ No newline at end of file
+
+    //在已经绑定的监听器上接受连接 并交给线程池处理
+    pub fn serve(&self, connection_listener: TcpListener) {
+        if let Err(e) = self.serve_all(vec![connection_listener]) {
+            crate::log!(LogLevel::Error, "Failed to serve: {}", e);
+        }
+    }
+
+    //同时在多个监听器上接受连接
+    pub fn serve_all(&self, listeners: Vec<TcpListener>) -> io::Result<()> {
+        //非阻塞accept 这样才能定期检查关闭标志
+        for listener in &listeners {
+            listener.set_nonblocking(true)?;
+        }
+
+        let shared = Arc::new(Shared {
+            pipeline: Arc::clone(&self.pipeline),
+            timeouts: self.timeouts,
+            shutdown: Arc::clone(&self.shutdown),
+            metrics: Arc::clone(&self.metrics),
+            max_websockets: self.max_websockets,
+        });
+        #[cfg(feature = "tls")]
+        let tls = self.tls.clone();
+        let pool = ThreadPool::new(self.workers, self.queue_size, Arc::clone(&self.metrics), move |stream: TcpStream| {
+            let metrics = &shared.metrics;
+            metrics.active_connections.fetch_add(1, Ordering::SeqCst);
+            //单个连接的panic不能带走工作线程
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                #[cfg(feature = "tls")]
+                if let Some(config) = &tls {
+                    return handle_tls_connection(stream, Arc::clone(config), &shared);
+                }
+                handle_connection(stream, &shared)
+            }));
+            metrics.active_connections.fetch_sub(1, Ordering::SeqCst);
+            match result {
+                Ok(Err(e)) => crate::log!(LogLevel::Warn, "Connection closed with error: {}", e),
+                Err(_) => crate::log!(LogLevel::Error, "Connection handler panicked"),
+                Ok(Ok(())) => {}
+            }
+        });
+
+        while !self.shutdown.load(Ordering::SeqCst) {
+            //每一轮依次检查所有监听器 都没有新连接时才等待
+            let mut idle = true;
+            for listener in &listeners {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        idle = false;
+                        crate::log!(LogLevel::Debug, "Connecting established");
+                        self.metrics.total_connections.fetch_add(1, Ordering::SeqCst);
+                        if let Err(e) = stream.set_nonblocking(false) {
+                            crate::log!(LogLevel::Warn, "Failed to configure connection: {}", e);
+                            continue;
+                        }
+                        if let Err(stream) = pool.execute(stream) {
+                            self.metrics.rejected_connections.fetch_add(1, Ordering::SeqCst);
+                            //TLS连接还没有握手 没法回复明文的503 直接关闭
+                            if !self.is_tls() {
+                                reject_busy(stream);
+                            }
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => crate::log!(LogLevel::Error, "Accept failed: {}", e),
+                }
+            }
+            if idle {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            }
+        }
+
+        crate::log!(LogLevel::Info, "Shutting down, draining in-flight requests");
+        //drop会等待所有排队和正在处理的连接结束
+        drop(pool);
+        //WebSocket会话看到关闭标志后发送1001关闭帧并退出
+        while self.metrics.websocket_connections.load(Ordering::SeqCst) > 0 {
+            thread::sleep(ACCEPT_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    fn is_tls(&self) -> bool {
+        #[cfg(feature = "tls")]
+        return self.tls.is_some();
+        #[cfg(not(feature = "tls"))]
+        false
+    }
+}
+
+//连接使用的流 升级为WebSocket后需要通过它调整底层TCP连接的读超时
+//WebSocket会话会转到单独的线程 所以要求Send + 'static
+trait Transport: Read + Write + Send + 'static {
+    fn tcp(&self) -> &TcpStream;
+}
+
+impl Transport for TcpStream {
+    fn tcp(&self) -> &TcpStream {
+        self
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Transport for StreamOwned<ServerConnection, TcpStream> {
+    fn tcp(&self) -> &TcpStream {
+        &self.sock
+    }
+}
+
+//明文连接
+fn handle_connection(stream: TcpStream, shared: &Arc<Shared>) -> io::Result<()> {
+    stream.set_read_timeout(Some(shared.timeouts.keep_alive))?;
+    stream.set_write_timeout(Some(shared.timeouts.write))?;
+    let peer_addr = stream.peer_addr().ok();
+    serve_requests(stream, peer_addr, shared)
+}
+
+//TLS连接 握手在第一次读写时由rustls自动完成
+#[cfg(feature = "tls")]
+fn handle_tls_connection(stream: TcpStream, config: Arc<ServerConfig>, shared: &Arc<Shared>) -> io::Result<()> {
+    stream.set_read_timeout(Some(shared.timeouts.keep_alive))?;
+    stream.set_write_timeout(Some(shared.timeouts.write))?;
+    let peer_addr = stream.peer_addr().ok();
+    let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+    serve_requests(StreamOwned::new(conn, stream), peer_addr, shared)
+}
+
+//处理一个连接上的所有请求 直到客户端关闭、空闲超时或者请求要求关闭连接
+//服务器关闭时 当前请求处理完就关闭连接
+//WebSocket握手成功后 连接转到单独的线程运行会话 工作线程马上可以处理其他连接
+fn serve_requests<S: Transport>(stream: S, peer_addr: Option<SocketAddr>, shared: &Arc<Shared>) -> io::Result<()> {
+    let Shared {
+        pipeline,
+        shutdown,
+        metrics,
+        ..
+    } = shared.as_ref();
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let head = match transfer::read_head(&mut reader) {
+            Ok(Some(head)) => head,
+            //客户端已经关闭连接
+            Ok(None) => return Ok(()),
+            //空闲超时
+            Err(e) if is_timeout(&e) => return Ok(()),
+            //TLS客户端没有发送close_notify就断开 同样当作正常关闭
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return reject(reader.get_mut(), e),
+        };
+        let mut req = match HttpRequest::from_head(&head) {
+            Ok(req) => req,
+            Err(e) => return reject(reader.get_mut(), io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        req.peer_addr = peer_addr;
+        let mut keep_alive = transfer::is_keep_alive(&head) && !shutdown.load(Ordering::SeqCst);
+
+        //要求流式读取消息体的处理函数直接从连接读取 其他请求先把消息体读进内存
+        let mut resp = match pipeline.body_limit(&mut req) {
+            Some(limit) => {
+                let mut body = match transfer::declared_body_length(&head)
+                    .and_then(|len| transfer::BodyReader::new(&mut reader, len, limit))
+                {
+                    Ok(body) => body,
+                    Err(e) => return reject(reader.get_mut(), e),
+                };
+                metrics.total_requests.fetch_add(1, Ordering::SeqCst);
+                let resp = pipeline.handle_stream(&mut req, &mut body);
+                //连接上还留着没读完的消息体 没法继续读下一个请求
+                keep_alive &= body.is_done();
+                resp
+            }
+            None => {
+                req.body = match transfer::body_length(&head).and_then(|len| transfer::read_body(&mut reader, len)) {
+                    Ok(body) => body,
+                    Err(e) => return reject(reader.get_mut(), e),
+                };
+                metrics.total_requests.fetch_add(1, Ordering::SeqCst);
+                pipeline.handle(&mut req)
+            }
+        };
+        let mut upgraded = resp.status_code() == StatusCode::SwitchingProtocols;
+        //先占用一个WebSocket名额再回复101 名额用完时回复503
+        if upgraded && !reserve_websocket(shared) {
+            upgraded = false;
+            resp = HttpResponse::builder()
+                .status(StatusCode::ServiceUnavailable)
+                .header("Content-Type", "text/plain")
+                .body("too many WebSocket connections")
+                .build();
+        }
+        if !upgraded {
+            resp.set_header("Connection", if keep_alive { "keep-alive" } else { "close" });
+        }
+        //头部和消息体合成一次写出 分两次写会被Nagle算法和延迟确认拖慢几十毫秒
+        let mut out = BufWriter::new(reader.get_mut());
+        if req.method == Method::Head {
+            resp.send_head(&mut out)?;
+        } else {
+            resp.send_response(&mut out)?;
+        }
+        out.flush()?;
+        drop(out);
+
+        if upgraded {
+            return spawn_websocket(reader, req, Arc::clone(shared));
+        }
+
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+//名额没有用完时把WebSocket会话数加一
+fn reserve_websocket(shared: &Shared) -> bool {
+    shared
+        .metrics
+        .websocket_connections
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < shared.max_websockets).then_some(n + 1))
+        .is_ok()
+}
+
+//会话结束(包括处理函数panic)时归还名额
+struct WebSocketSlot(Arc<Shared>);
+
+impl Drop for WebSocketSlot {
+    fn drop(&mut self) {
+        self.0.metrics.websocket_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+//在单独的线程上运行已经回复101的WebSocket会话 调用前必须已经用reserve_websocket占用了名额
+fn spawn_websocket<S: Transport>(mut reader: BufReader<S>, req: HttpRequest, shared: Arc<Shared>) -> io::Result<()> {
+    let slot = WebSocketSlot(shared);
+    reader.get_ref().tcp().set_read_timeout(Some(websocket::POLL_INTERVAL))?;
+    thread::Builder::new().name("websocket".into()).spawn(move || {
+        let shared = &slot.0;
+        let result = match shared.pipeline.router().websocket_handler(&req) {
+            Some(handler) => websocket::serve(&mut reader, &req, handler, &shared.shutdown),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            crate::log!(LogLevel::Warn, "WebSocket closed with error: {}", e);
+        }
+    })?;
+    Ok(())
+}
+
+//请求格式错误时回复400 消息体超过上限时回复413 然后关闭连接
+fn reject(stream: &mut impl Write, err: io::Error) -> io::Result<()> {
+    let status = if transfer::is_body_too_large(&err) {
+        StatusCode::PayloadTooLarge
+    } else {
+        StatusCode::BadRequest
+    };
+    let mut resp = HttpResponse::new(status, None, None);
+    resp.set_header("Connection", "close");
+    let _ = resp.send_response(stream).and_then(|_| stream.flush());
+    Err(err)
+}
+
+//线程池队列已满 回复503后关闭连接
+fn reject_busy(mut stream: TcpStream) {
+    let mut resp = HttpResponse::new(StatusCode::ServiceUnavailable, None, None);
+    resp.set_header("Connection", "close");
+    let _ = resp.send_response(&mut stream);
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::thread;
+
+    fn test_shared(router: Router, max_websockets: usize) -> Arc<Shared> {
+        Arc::new(Shared {
+            pipeline: Arc::new(Pipeline::new(router)),
+            timeouts: Timeouts {
+                keep_alive: Duration::from_millis(500),
+                write: Duration::from_millis(500),
+            },
+            shutdown: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(ServerMetrics::default()),
+            max_websockets,
+        })
+    }
+
+    //在随机端口上启动只处理一个连接的服务器
+    fn spawn_one_connection() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let _ = handle_connection(stream, &test_shared(Router::new(), DEFAULT_MAX_WEBSOCKETS));
+        });
+        addr
+    }
+
+    //读取一个完整响应 返回头部和消息体
+    fn read_response(reader: &mut impl io::BufRead) -> (String, Vec<u8>) {
+        let head = transfer::read_head(reader).unwrap().unwrap();
+        let body = transfer::read_body(reader, transfer::body_length(&head).unwrap()).unwrap();
+        (head, body)
+    }
+
+    #[test]
+    fn test_keep_alive_serves_multiple_requests() {
+        let addr = spawn_one_connection();
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream);
+
+        //请求头超过原来200字节的缓冲区 也能完整读取
+        let padding = "x".repeat(300);
+        write!(reader.get_mut(), "GET /missing HTTP/1.1\r\nX-Padding: {}\r\n\r\n", padding).unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 404"));
+        assert_eq!(transfer::header_value(&head, "Connection"), Some("keep-alive"));
+
+        //同一个连接上发送chunked请求体 并要求关闭连接
+        reader
+            .get_mut()
+            .write_all(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n3\r\nabc\r\n0\r\n\r\n")
+            .unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 404"));
+        assert_eq!(transfer::header_value(&head, "Connection"), Some("close"));
+        assert_eq!(transfer::read_head(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_head_response_has_no_body() {
+        let addr = spawn_one_connection();
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        reader.get_mut().write_all(b"HEAD /missing HTTP/1.1\r\n\r\n").unwrap();
+        let head = transfer::read_head(&mut reader).unwrap().unwrap();
+        assert!(head.starts_with("HTTP/1.1 404"));
+        assert_eq!(transfer::header_value(&head, "Content-Length"), Some("13"));
+
+        //连接上紧接着的是下一个响应 而不是HEAD响应的消息体
+        reader.get_mut().write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let (head, body) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 404"));
+        assert_eq!(body, b"404 Not Found");
+    }
+
+    #[test]
+    fn test_invalid_request_line_gets_400() {
+        let addr = spawn_one_connection();
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        reader.get_mut().write_all(b"GET\r\n\r\n").unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn test_malformed_request_gets_400() {
+        let addr = spawn_one_connection();
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream);
+        reader
+            .get_mut()
+            .write_all(b"POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n")
+            .unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 400"));
+    }
+
+    //chunked和Content-Length同时出现 后面的字节可能被代理当作另一个请求 回复400并关闭连接
+    #[test]
+    fn test_ambiguous_body_length_gets_400_and_close() {
+        for raw in [
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n0\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 0\r\n\r\nabc",
+        ] {
+            let addr = spawn_one_connection();
+            let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+            reader.get_mut().write_all(raw.as_bytes()).unwrap();
+            let (head, _) = read_response(&mut reader);
+            assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+            assert_eq!(transfer::header_value(&head, "Connection"), Some("close"));
+            assert_eq!(transfer::read_head(&mut reader).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_body_too_large_gets_413() {
+        let addr = spawn_one_connection();
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        write!(reader.get_mut(), "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", transfer::MAX_BODY_SIZE + 1).unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 413"), "{}", head);
+        assert_eq!(transfer::header_value(&head, "Connection"), Some("close"));
+    }
+
+    #[test]
+    fn test_multiple_listeners_share_workers() {
+        let listeners = vec![TcpListener::bind("127.0.0.1:0").unwrap(), TcpListener::bind("127.0.0.1:0").unwrap()];
+        let addrs: Vec<_> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+        let server = Server::new("127.0.0.1:0", Router::new()).workers(1);
+        let shutdown = server.shutdown_handle();
+        let metrics = server.metrics();
+        let handle = thread::spawn(move || server.serve_all(listeners));
+
+        for addr in addrs {
+            let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+            reader.get_mut().write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+            let (head, _) = read_response(&mut reader);
+            assert!(head.starts_with("HTTP/1.1 404"));
+        }
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap().unwrap();
+        assert_eq!(metrics.total_connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_run_reports_bind_errors() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap().to_string();
+        let err = Server::new(&addr, Router::new()).run().unwrap_err();
+        assert!(err.to_string().starts_with(&format!("cannot listen on {}", addr)), "{}", err);
+    }
+
+    #[test]
+    fn test_slow_client_does_not_block_others() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1:0", Router::new()).workers(2).queue_size(4);
+        let shutdown = server.shutdown_handle();
+        let metrics = server.metrics();
+        let handle = thread::spawn(move || server.serve(listener));
+
+        //慢客户端只发了一半请求头 占住一个工作线程
+        let mut slow = TcpStream::connect(addr).unwrap();
+        slow.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+
+        //另一个客户端仍然可以得到响应
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        reader.get_mut().write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 404"));
+
+        //关闭时等待慢客户端的请求处理完
+        shutdown.store(true, Ordering::SeqCst);
+        slow.write_all(b"\r\n").unwrap();
+        let mut reader = BufReader::new(slow);
+        let (head, _) = read_response(&mut reader);
+        assert_eq!(transfer::header_value(&head, "Connection"), Some("close"));
+        handle.join().unwrap();
+        assert_eq!(metrics.active_connections.load(Ordering::SeqCst), 0);
+        assert_eq!(metrics.total_connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_https_request_with_self_signed_cert() {
+        use http::tls::rustls::pki_types::ServerName;
+        use http::tls::rustls::ClientConnection;
+
+        //测试时为localhost生成自签名证书
+        let dir = tempfile::tempdir().unwrap();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = http::tls::server_config(&cert_path, &key_path).unwrap();
+        let server = Server::new("127.0.0.1:0", Router::new()).tls(config);
+        let shutdown = server.shutdown_handle();
+        let handle = thread::spawn(move || server.serve(listener));
+
+        let client = http::tls::client_config(&cert_path).unwrap();
+        let conn = ClientConnection::new(client, ServerName::try_from("localhost").unwrap()).unwrap();
+        let stream = StreamOwned::new(conn, TcpStream::connect(addr).unwrap());
+        let mut reader = BufReader::new(stream);
+        reader.get_mut().write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let (head, _) = read_response(&mut reader);
+        assert!(head.starts_with("HTTP/1.1 404"));
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+}
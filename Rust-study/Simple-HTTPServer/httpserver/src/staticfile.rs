@@ -0,0 +1,469 @@
+//静态文件服务 注册为 /*path 通配路由 path是去掉开头'/'之后的路径(已解码)
+//路径先规范化 不允许跳出根目录 符号链接指向根目录以外的文件同样拒绝
+//支持ETag/Last-Modified条件请求(304)和单个Range请求(206) 可选目录列表
+use super::handler::{Handler, PageNotFoundHandler};
+use super::log::LogLevel;
+use chrono::{DateTime, TimeZone, Utc};
+use http::httprequest::{percent_encode, HttpRequest, Resource};
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const INDEX_FILE: &str = "index.html";
+//根目录下有这个文件时 找不到的页面用它作为404响应的内容
+const NOT_FOUND_FILE: &str = "404.html";
+
+pub struct StaticPageHandler {
+    root: PathBuf,
+    list_directories: bool, //目录下没有index.html时是否列出目录内容
+}
+
+impl StaticPageHandler {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        StaticPageHandler {
+            root: root.into(),
+            list_directories: false,
+        }
+    }
+
+    pub fn list_directories(mut self, enabled: bool) -> Self {
+        self.list_directories = enabled;
+        self
+    }
+
+    //把URL路径映射到根目录下的文件 无法安全映射时返回None
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let relative = normalize(path)?;
+        let root = self.root.canonicalize().ok()?;
+        let full = root.join(&relative);
+        //没有扩展名的路径可以省略.html 例如 /health 对应 health.html
+        let full = if !full.exists() && full.extension().is_none() && !relative.as_os_str().is_empty() {
+            full.with_extension("html")
+        } else {
+            full
+        };
+        //解析符号链接后仍然必须在根目录下
+        let real = full.canonicalize().ok()?;
+        if real.starts_with(&root) {
+            Some(real)
+        } else {
+            None
+        }
+    }
+
+    //找不到的页面 优先使用根目录下的404.html
+    fn not_found(&self, req: &HttpRequest) -> HttpResponse {
+        match fs::read(self.root.join(NOT_FOUND_FILE)) {
+            Ok(page) => HttpResponse::builder()
+                .status(StatusCode::NotFound)
+                .header("Content-Type", "text/html; charset=utf-8")
+                .body(page)
+                .build(),
+            Err(_) => PageNotFoundHandler.handle(req),
+        }
+    }
+}
+
+impl Handler for StaticPageHandler {
+    fn handle(&self, req: &HttpRequest) -> HttpResponse {
+        let path = req.params.get("path").map(|p| p.as_str()).unwrap_or("");
+        let file = match self.resolve(path) {
+            Some(file) => file,
+            None => return self.not_found(req),
+        };
+
+        if file.is_dir() {
+            //目录必须以'/'结尾 否则页面里的相对链接会指错位置
+            let Resource::Path(url) = &req.resource;
+            if !url.ends_with('/') {
+                return HttpResponse::builder()
+                    .status(StatusCode::MovedPermanently)
+                    .header("Location", format!("{}/", percent_encode(url, true)))
+                    .build();
+            }
+            let index = file.join(INDEX_FILE);
+            if index.is_file() {
+                return serve_file(req, &index);
+            }
+            if self.list_directories {
+                return match directory_listing(url, &file) {
+                    Ok(html) => HttpResponse::builder()
+                        .header("Content-Type", "text/html; charset=utf-8")
+                        .body(html)
+                        .build(),
+                    Err(e) => server_error(e),
+                };
+            }
+            return self.not_found(req);
+        }
+        serve_file(req, &file)
+    }
+}
+
+//把URL路径拆成段 处理"."和".." 跳出根目录时返回None
+fn normalize(path: &str) -> Option<PathBuf> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            //反斜杠和NUL在某些平台上有特殊含义 直接拒绝
+            s if s.contains('\\') || s.contains('\0') => return None,
+            s => segments.push(s),
+        }
+    }
+    let relative: PathBuf = segments.iter().collect();
+    //再确认一遍只包含普通路径段(例如Windows上的盘符前缀)
+    if relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        Some(relative)
+    } else {
+        None
+    }
+}
+
+fn serve_file(req: &HttpRequest, file: &Path) -> HttpResponse {
+    match try_serve_file(req, file) {
+        Ok(resp) => resp,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => PageNotFoundHandler.handle(req),
+        Err(e) => server_error(e),
+    }
+}
+
+fn try_serve_file(req: &HttpRequest, file: &Path) -> io::Result<HttpResponse> {
+    let metadata = fs::metadata(file)?;
+    let len = metadata.len();
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let etag = format!("\"{:x}-{:x}\"", modified, len);
+    let last_modified = http_date(modified);
+
+    let mut resp = HttpResponse::builder()
+        .header("Content-Type", content_type(file))
+        .header("ETag", etag.as_str())
+        .header("Last-Modified", last_modified.as_str())
+        .header("Accept-Ranges", "bytes")
+        .build();
+
+    if is_not_modified(req, &etag, modified) {
+        resp.set_status(StatusCode::NotModified);
+        return Ok(resp);
+    }
+
+    //If-Range和当前版本不一致时忽略Range 返回完整内容
+    let range = req
+        .header("Range")
+        .filter(|_| req.header("If-Range").is_none_or(|v| v == etag || v == last_modified));
+    let mut reader = File::open(file)?;
+    match range.map(|r| parse_range(r, len)) {
+        Some(RangeSpec::Satisfiable(start, end)) => {
+            let mut body = Vec::new();
+            reader.seek(SeekFrom::Start(start))?;
+            reader.take(end - start + 1).read_to_end(&mut body)?;
+            resp.set_status(StatusCode::PartialContent);
+            resp.set_header("Content-Range", format!("bytes {}-{}/{}", start, end, len));
+            resp.set_body(body);
+        }
+        Some(RangeSpec::Unsatisfiable) => {
+            resp.set_status(StatusCode::RangeNotSatisfiable);
+            resp.set_header("Content-Range", format!("bytes */{}", len));
+        }
+        Some(RangeSpec::Ignored) | None => {
+            let mut body = Vec::with_capacity(len as usize);
+            reader.read_to_end(&mut body)?;
+            resp.set_body(body);
+        }
+    }
+    Ok(resp)
+}
+
+//If-None-Match优先 存在时忽略If-Modified-Since
+fn is_not_modified(req: &HttpRequest, etag: &str, modified: u64) -> bool {
+    if let Some(tags) = req.header("If-None-Match") {
+        //弱比较 W/"x" 和 "x" 视为相同
+        return tags
+            .split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == "*" || t == etag);
+    }
+    match req.header("If-Modified-Since").and_then(|v| DateTime::parse_from_rfc2822(v).ok()) {
+        Some(since) => modified as i64 <= since.timestamp(),
+        None => false,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum RangeSpec {
+    Satisfiable(u64, u64), //闭区间 [start, end]
+    Unsatisfiable,
+    Ignored, //格式错误或者多个区间 按照规范可以当作没有Range返回完整内容
+}
+
+//只支持单个区间: bytes=a-b / bytes=a- / bytes=-n
+fn parse_range(value: &str, len: u64) -> RangeSpec {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return RangeSpec::Ignored;
+    };
+    if spec.contains(',') {
+        return RangeSpec::Ignored;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeSpec::Ignored;
+    };
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        //后缀区间 最后n个字节
+        (Err(_), Ok(n)) if start.is_empty() => {
+            if n == 0 {
+                return RangeSpec::Unsatisfiable;
+            }
+            (len.saturating_sub(n), len.saturating_sub(1))
+        }
+        _ => return RangeSpec::Ignored,
+    };
+    if len == 0 || start >= len {
+        RangeSpec::Unsatisfiable
+    } else {
+        RangeSpec::Satisfiable(start, end)
+    }
+}
+
+//按扩展名判断Content-Type 不认识的类型当作二进制数据
+fn content_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "map" => "application/json",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "bmp" => "image/bmp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+//IMF-fixdate格式 例如 "Sun, 06 Nov 1994 08:49:37 GMT"
+fn http_date(secs: u64) -> String {
+    Utc.timestamp_opt(secs as i64, 0)
+        .single()
+        .unwrap_or_default()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn directory_listing(url: &str, dir: &Path) -> io::Result<String> {
+    let mut entries: Vec<(String, bool)> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| {
+            let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            (e.file_name().to_string_lossy().into_owned(), is_dir)
+        })
+        .collect();
+    //目录在前 再按名称排序
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let title = escape_html(url);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n<body>\n<h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    if url != "/" {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (name, is_dir) in entries {
+        let suffix = if is_dir { "/" } else { "" };
+        html.push_str(&format!(
+            "<li><a href=\"{}{}\">{}{}</a></li>\n",
+            escape_html(&percent_encode(&name, true)),
+            suffix,
+            escape_html(&name),
+            suffix
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    Ok(html)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn server_error(err: io::Error) -> HttpResponse {
+    crate::log!(LogLevel::Error, "Failed to read static file: {}", err);
+    HttpResponse::new(StatusCode::InternalServerError, None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    //临时根目录: index.html、二进制图片、子目录docs(没有index.html)
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(dir.path().join("health.html"), "ok").unwrap();
+        fs::write(dir.path().join("logo.png"), [0x89, b'P', b'N', b'G', 0xff, 0x00, 0xfe]).unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/a&b.txt"), "0123456789").unwrap();
+        dir
+    }
+
+    fn get(handler: &StaticPageHandler, path: &str, headers: &[(&str, &str)]) -> HttpResponse {
+        let mut raw = format!("GET {} HTTP/1.1\r\n", path);
+        for (k, v) in headers {
+            raw.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        raw.push_str("\r\n");
+        let mut req = HttpRequest::parse(raw.as_bytes()).unwrap();
+        let Resource::Path(p) = &req.resource;
+        req.params = HashMap::from([("path".to_string(), p.trim_start_matches('/').to_string())]);
+        handler.handle(&req)
+    }
+
+    #[test]
+    fn test_normalize_refuses_to_escape_root() {
+        assert_eq!(normalize("a/./b/../c"), Some(PathBuf::from("a/c")));
+        assert_eq!(normalize(""), Some(PathBuf::new()));
+        assert_eq!(normalize(".."), None);
+        assert_eq!(normalize("a/../../etc/passwd"), None);
+        assert_eq!(normalize("a\\..\\b"), None);
+
+        let root = fixture();
+        let handler = StaticPageHandler::new(root.path());
+        //%2e%2e 解码后同样是".."
+        for path in ["/../Cargo.toml", "/docs/%2e%2e/%2e%2e/x"] {
+            assert_eq!(get(&handler, path, &[]).status_code(), StatusCode::NotFound, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_custom_not_found_page() {
+        let root = fixture();
+        let handler = StaticPageHandler::new(root.path());
+        assert_eq!(get(&handler, "/missing", &[]).body(), b"404 Not Found");
+        fs::write(root.path().join("404.html"), "<h1>gone</h1>").unwrap();
+        let resp = get(&handler, "/missing", &[]);
+        assert_eq!(resp.status_code(), StatusCode::NotFound);
+        assert_eq!(resp.body(), b"<h1>gone</h1>");
+    }
+
+    #[test]
+    fn test_binary_file_and_mime_type() {
+        let root = fixture();
+        let handler = StaticPageHandler::new(root.path());
+        let resp = get(&handler, "/logo.png", &[]);
+        assert_eq!(resp.status_code(), StatusCode::Ok);
+        assert_eq!(resp.header("Content-Type"), Some("image/png"));
+        assert_eq!(resp.body(), [0x89, b'P', b'N', b'G', 0xff, 0x00, 0xfe]);
+
+        assert_eq!(get(&handler, "/", &[]).body(), b"<h1>home</h1>");
+        assert_eq!(get(&handler, "/health", &[]).body(), b"ok");
+        assert_eq!(content_type(Path::new("x.WOFF2")), "font/woff2");
+        assert_eq!(content_type(Path::new("x.unknown")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_conditional_requests_get_304() {
+        let root = fixture();
+        let handler = StaticPageHandler::new(root.path());
+        let resp = get(&handler, "/index.html", &[]);
+        let etag = resp.header("ETag").unwrap().to_string();
+        let last_modified = resp.header("Last-Modified").unwrap().to_string();
+
+        let resp = get(&handler, "/index.html", &[("If-None-Match", &format!("W/{}", etag))]);
+        assert_eq!(resp.status_code(), StatusCode::NotModified);
+        assert!(resp.body().is_empty());
+        assert_eq!(get(&handler, "/index.html", &[("If-None-Match", "\"other\"")]).status_code(), StatusCode::Ok);
+
+        let resp = get(&handler, "/index.html", &[("If-Modified-Since", &last_modified)]);
+        assert_eq!(resp.status_code(), StatusCode::NotModified);
+        let resp = get(&handler, "/index.html", &[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        assert_eq!(resp.status_code(), StatusCode::Ok);
+    }
+
+    #[test]
+    fn test_range_requests() {
+        assert_eq!(parse_range("bytes=2-4", 10), RangeSpec::Satisfiable(2, 4));
+        assert_eq!(parse_range("bytes=8-", 10), RangeSpec::Satisfiable(8, 9));
+        assert_eq!(parse_range("bytes=-3", 10), RangeSpec::Satisfiable(7, 9));
+        assert_eq!(parse_range("bytes=5-100", 10), RangeSpec::Satisfiable(5, 9));
+        assert_eq!(parse_range("bytes=10-", 10), RangeSpec::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), RangeSpec::Ignored);
+        assert_eq!(parse_range("items=0-1", 10), RangeSpec::Ignored);
+
+        let root = fixture();
+        let handler = StaticPageHandler::new(root.path());
+        let resp = get(&handler, "/docs/a%26b.txt", &[("Range", "bytes=2-4")]);
+        assert_eq!(resp.status_code(), StatusCode::PartialContent);
+        assert_eq!(resp.header("Content-Range"), Some("bytes 2-4/10"));
+        assert_eq!(resp.body(), b"234");
+
+        let resp = get(&handler, "/docs/a%26b.txt", &[("Range", "bytes=20-")]);
+        assert_eq!(resp.status_code(), StatusCode::RangeNotSatisfiable);
+        assert_eq!(resp.header("Content-Range"), Some("bytes */10"));
+
+        //If-Range不匹配时返回完整内容
+        let resp = get(&handler, "/docs/a%26b.txt", &[("Range", "bytes=2-4"), ("If-Range", "\"stale\"")]);
+        assert_eq!(resp.status_code(), StatusCode::Ok);
+        assert_eq!(resp.body(), b"0123456789");
+    }
+
+    #[test]
+    fn test_directory_listing() {
+        let root = fixture();
+        let resp = get(&StaticPageHandler::new(root.path()), "/docs/", &[]);
+        assert_eq!(resp.status_code(), StatusCode::NotFound);
+
+        let handler = StaticPageHandler::new(root.path()).list_directories(true);
+        let resp = get(&handler, "/docs", &[]);
+        assert_eq!(resp.status_code(), StatusCode::MovedPermanently);
+        assert_eq!(resp.header("Location"), Some("/docs/"));
+
+        let resp = get(&handler, "/docs/", &[]);
+        let html = String::from_utf8(resp.body().to_vec()).unwrap();
+        assert!(html.contains("<a href=\"a%26b.txt\">a&amp;b.txt</a>"));
+        assert!(html.contains("<a href=\"../\">"));
+    }
+}
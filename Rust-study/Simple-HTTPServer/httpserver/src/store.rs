@@ -0,0 +1,180 @@
+//订单存储 启动时从json文件读入内存 修改时写回文件
+//写文件时持有旁边的.lock文件的排他锁 避免多个进程同时写
+//先写临时文件再重命名 写到一半崩溃也不会留下损坏的文件
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+//对应json文件中数据 所以需要实现序列化和反序列化
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderStatus {
+    pub order_id: i32,
+    pub order_date: String,
+    pub order_status: String,
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    Corrupt(serde_json::Error), //文件内容不是合法的订单列表
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "order storage i/o error: {}", e),
+            StoreError::Corrupt(e) => write!(f, "order storage is corrupt: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Corrupt(e)
+    }
+}
+
+pub struct OrderStore {
+    path: PathBuf,
+    orders: Mutex<Vec<OrderStatus>>,
+}
+
+impl OrderStore {
+    //文件不存在时从空列表开始 第一次修改时创建
+    pub fn open(path: impl Into<PathBuf>) -> Result<OrderStore, StoreError> {
+        let path = path.into();
+        let orders = read_orders(&path)?;
+        Ok(OrderStore {
+            path,
+            orders: Mutex::new(orders),
+        })
+    }
+
+    pub fn list(&self) -> Vec<OrderStatus> {
+        self.lock().clone()
+    }
+
+    pub fn get(&self, order_id: i32) -> Option<OrderStatus> {
+        self.lock().iter().find(|o| o.order_id == order_id).cloned()
+    }
+
+    //修改订单列表并写回文件 f返回的值原样返回
+    //修改前先在文件锁下重新读取文件 不会覆盖其他进程刚写入的内容
+    pub fn update<T>(&self, f: impl FnOnce(&mut Vec<OrderStatus>) -> T) -> Result<T, StoreError> {
+        let mut cache = self.lock();
+        let lock_file = self.lock_file()?;
+        lock_file.lock()?;
+
+        let mut orders = read_orders(&self.path)?;
+        let before = orders.clone();
+        let result = f(&mut orders);
+        //没有实际修改(例如要删除的订单不存在)时不写文件
+        if orders != before {
+            write_orders(&self.path, &orders)?;
+        }
+        *cache = orders;
+        //lock_file关闭时自动释放文件锁
+        Ok(result)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<OrderStatus>> {
+        //持锁线程panic不会破坏列表 继续使用
+        self.orders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_file(&self) -> io::Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(sibling(&self.path, "lock"))
+    }
+}
+
+fn read_orders(path: &Path) -> Result<Vec<OrderStatus>, StoreError> {
+    match fs::read_to_string(path) {
+        Ok(contents) if contents.trim().is_empty() => Ok(Vec::new()),
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_orders(path: &Path, orders: &[OrderStatus]) -> Result<(), StoreError> {
+    let tmp = sibling(path, "tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(&serde_json::to_vec_pretty(orders)?)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+//同一目录下加后缀的文件 例如 order.json.lock
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(order_id: i32, status: &str) -> OrderStatus {
+        OrderStatus {
+            order_id,
+            order_date: "21 Jan 2020".into(),
+            order_status: status.into(),
+        }
+    }
+
+    #[test]
+    fn test_missing_file_starts_empty_and_writes_through() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data/order.json");
+        let store = OrderStore::open(&path).unwrap();
+        assert!(store.list().is_empty());
+
+        store.update(|orders| orders.push(order(1, "Shipped"))).unwrap();
+        assert_eq!(store.get(1), Some(order(1, "Shipped")));
+
+        //重新打开能读到写入的内容
+        let reopened = OrderStore::open(&path).unwrap();
+        assert_eq!(reopened.list(), vec![order(1, "Shipped")]);
+        assert!(!sibling(&path, "tmp").exists());
+    }
+
+    #[test]
+    fn test_update_sees_changes_from_other_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("order.json");
+        let first = OrderStore::open(&path).unwrap();
+        let second = OrderStore::open(&path).unwrap();
+        first.update(|orders| orders.push(order(1, "Shipped"))).unwrap();
+        second.update(|orders| orders.push(order(2, "Pending"))).unwrap();
+        assert_eq!(second.list(), vec![order(1, "Shipped"), order(2, "Pending")]);
+    }
+
+    #[test]
+    fn test_corrupt_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("order.json");
+        fs::write(&path, "{not json").unwrap();
+        assert!(matches!(OrderStore::open(&path), Err(StoreError::Corrupt(_))));
+    }
+}
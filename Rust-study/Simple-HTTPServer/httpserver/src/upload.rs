@@ -0,0 +1,265 @@
+//文件上传接口 POST multipart/form-data 每个文件部分保存到上传目录 普通字段忽略
+//文件名取客户端文件名去掉路径后的部分 同名文件已存在时加上-1、-2后缀 不会覆盖已有文件
+//消息体不预先读进内存 直接从连接流式解析 文件部分边读边写到磁盘 整个消息体另有自己的上限
+//成功返回201和保存的文件列表 格式错误400 超过上限413 不是multipart请求415
+use super::handler::{json_response, ApiError, Handler};
+use super::log::LogLevel;
+use http::form::{FormError, MultipartLimits};
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+//客户端没有提供可用的文件名时使用
+const DEFAULT_FILE_NAME: &str = "upload";
+//整个上传请求的默认上限 单个文件的上限见MultipartLimits
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+pub struct UploadHandler {
+    dir: PathBuf,
+    limits: MultipartLimits,
+    max_upload_size: u64,
+}
+
+//保存好的文件 name是上传目录下的文件名
+#[derive(Serialize)]
+struct SavedFile {
+    field: String,
+    name: String,
+    size: u64,
+    content_type: Option<String>,
+}
+
+impl UploadHandler {
+    //目录不存在时在第一次上传时创建
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        UploadHandler {
+            dir: dir.into(),
+            limits: MultipartLimits::default(),
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+        }
+    }
+
+    pub fn limits(mut self, limits: MultipartLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    //整个请求消息体的上限 超过时回复413
+    pub fn max_upload_size(mut self, bytes: u64) -> Self {
+        self.max_upload_size = bytes;
+        self
+    }
+
+    fn upload(&self, req: &HttpRequest, body: impl Read) -> Result<HttpResponse, ApiError> {
+        fs::create_dir_all(&self.dir).map_err(internal)?;
+        //临时文件放在上传目录里 保存时只需要改名
+        let multipart = req.multipart_from(body, &self.dir, &self.limits).map_err(|e| match e {
+            FormError::UnsupportedContentType(_) => ApiError::new(StatusCode::UnsupportedMediaType, e.to_string()),
+            FormError::TooLarge(_) => ApiError::new(StatusCode::PayloadTooLarge, e.to_string()),
+            //客户端发送的消息体不完整或者chunked格式错误
+            FormError::Io(e) if is_client_error(&e) => ApiError::new(StatusCode::BadRequest, e.to_string()),
+            FormError::Io(e) => internal(e),
+            e => ApiError::new(StatusCode::BadRequest, e.to_string()),
+        })?;
+        if multipart.files.is_empty() {
+            return Err(ApiError::new(StatusCode::BadRequest, "no file in upload"));
+        }
+
+        let mut saved = Vec::new();
+        for file in multipart.files {
+            let name = file.safe_file_name().unwrap_or_else(|| DEFAULT_FILE_NAME.to_string());
+            let (name, dest) = reserve(&self.dir, &name).map_err(internal)?;
+            saved.push(SavedFile {
+                field: file.field.clone(),
+                name,
+                size: file.size,
+                content_type: file.content_type.clone(),
+            });
+            if let Err(e) = file.persist(&dest) {
+                let _ = fs::remove_file(&dest);
+                return Err(internal(e));
+            }
+            crate::log!(LogLevel::Info, "Saved upload {}", dest.display());
+        }
+        Ok(json_response(StatusCode::Created, &saved))
+    }
+}
+
+impl Handler for UploadHandler {
+    fn handle(&self, req: &HttpRequest) -> HttpResponse {
+        self.upload(req, req.body.as_slice()).unwrap_or_else(ApiError::into_response)
+    }
+
+    fn body_limit(&self, _req: &HttpRequest) -> Option<u64> {
+        Some(self.max_upload_size)
+    }
+
+    fn handle_stream(&self, req: &HttpRequest, body: &mut dyn Read) -> HttpResponse {
+        self.upload(req, body).unwrap_or_else(ApiError::into_response)
+    }
+}
+
+//创建一个还不存在的空文件占住名字 并发上传同名文件时也不会互相覆盖
+fn reserve(dir: &Path, name: &str) -> io::Result<(String, PathBuf)> {
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    let mut candidate = name.to_string();
+    for n in 1.. {
+        let path = dir.join(&candidate);
+        match File::options().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok((candidate, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => candidate = format!("{}-{}{}", stem, n, ext),
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+fn is_client_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+//文件系统出错属于服务器内部错误 细节只打印在服务器日志里
+fn internal(e: io::Error) -> ApiError {
+    crate::log!(LogLevel::Error, "Upload failed: {}", e);
+    ApiError::new(StatusCode::InternalServerError, "upload storage unavailable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Router;
+    use crate::server::Server;
+    use http::transfer::{self, MAX_BODY_SIZE};
+    use std::io::{BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::Ordering;
+    use std::thread;
+
+    fn post(handler: &UploadHandler, content_type: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let raw = format!(
+            "POST /api/uploads HTTP/1.1\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        );
+        let resp = handler.handle(&HttpRequest::parse(raw.as_bytes()).unwrap());
+        (resp.status_code(), serde_json::from_slice(resp.body()).unwrap())
+    }
+
+    fn upload_body(file_name: &str, content: &str) -> String {
+        format!(
+            "--b\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nignored\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: text/plain\r\n\r\n{}\r\n--b--\r\n",
+            file_name, content
+        )
+    }
+
+    #[test]
+    fn test_upload_saves_without_overwriting() {
+        let dir = tempfile::tempdir().unwrap();
+        let uploads = dir.path().join("uploads");
+        let handler = UploadHandler::new(&uploads);
+
+        let (status, json) = post(
+            &handler,
+            "multipart/form-data; boundary=b",
+            &upload_body("C:\\docs\\report.txt", "first"),
+        );
+        assert_eq!(status, StatusCode::Created);
+        assert_eq!(json[0]["name"], "report.txt");
+        assert_eq!(json[0]["size"], 5);
+        assert_eq!(json[0]["content_type"], "text/plain");
+
+        let (_, json) = post(
+            &handler,
+            "multipart/form-data; boundary=b",
+            &upload_body("report.txt", "second"),
+        );
+        assert_eq!(json[0]["name"], "report-1.txt");
+        assert_eq!(fs::read_to_string(uploads.join("report.txt")).unwrap(), "first");
+        assert_eq!(fs::read_to_string(uploads.join("report-1.txt")).unwrap(), "second");
+
+        //文件名只剩点号时使用默认名字 不会跳出上传目录
+        let (_, json) = post(&handler, "multipart/form-data; boundary=b", &upload_body("../..", "x"));
+        assert_eq!(json[0]["name"], "upload");
+        assert_eq!(fs::read_dir(&uploads).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_upload_is_streamed_from_connection() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = UploadHandler::new(dir.path())
+            .limits(MultipartLimits::default().max_file_size(2 * MAX_BODY_SIZE as u64))
+            .max_upload_size(2 * MAX_BODY_SIZE as u64);
+        let mut router = Router::new();
+        router.post("/upload", handler);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::new("127.0.0.1:0", router);
+        let shutdown = server.shutdown_handle();
+        let handle = thread::spawn(move || server.serve(listener));
+
+        //比MAX_BODY_SIZE还大的文件 不放进内存也能上传 连接之后还能继续使用
+        let body = upload_body("big.txt", &"x".repeat(MAX_BODY_SIZE + 1));
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        write!(
+            reader.get_mut(),
+            "POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let head = transfer::read_head(&mut reader).unwrap().unwrap();
+        assert!(head.starts_with("HTTP/1.1 201"), "{}", head);
+        assert_eq!(transfer::header_value(&head, "Connection"), Some("keep-alive"));
+        transfer::read_body(&mut reader, transfer::body_length(&head).unwrap()).unwrap();
+        assert_eq!(fs::metadata(dir.path().join("big.txt")).unwrap().len(), MAX_BODY_SIZE as u64 + 1);
+
+        //超过整个上传的上限 不读消息体直接回复413
+        write!(
+            reader.get_mut(),
+            "POST /upload HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b\r\nContent-Length: {}\r\n\r\n",
+            2 * MAX_BODY_SIZE + 1
+        )
+        .unwrap();
+        let head = transfer::read_head(&mut reader).unwrap().unwrap();
+        assert!(head.starts_with("HTTP/1.1 413"), "{}", head);
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_upload_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let handler = UploadHandler::new(dir.path()).limits(MultipartLimits::default().max_file_size(4));
+
+        let (status, _) = post(&handler, "application/json", "{}");
+        assert_eq!(status, StatusCode::UnsupportedMediaType);
+        let (status, json) = post(
+            &handler,
+            "multipart/form-data; boundary=b",
+            &upload_body("a.txt", "too long"),
+        );
+        assert_eq!(status, StatusCode::PayloadTooLarge);
+        assert!(json["error"].as_str().unwrap().contains("a.txt"));
+        let (status, _) = post(&handler, "multipart/form-data; boundary=b", "--b--\r\n");
+        assert_eq!(status, StatusCode::BadRequest);
+        let (status, _) = post(&handler, "multipart/form-data; boundary=b", "--b\r\nbroken");
+        assert_eq!(status, StatusCode::BadRequest);
+
+        //失败的上传不留下临时文件
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
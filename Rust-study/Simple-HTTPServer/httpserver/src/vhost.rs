@@ -0,0 +1,98 @@
+//虚拟主机 按请求的Host头部(去掉端口 不区分大小写)选择处理函数
+//没有Host头部或者没有匹配的主机名时交给默认处理函数
+use super::handler::{BoxFuture, Handler};
+use http::httprequest::HttpRequest;
+use http::httpresponse::HttpResponse;
+use std::collections::HashMap;
+use std::io::Read;
+
+pub struct VirtualHosts {
+    handlers: Vec<Box<dyn Handler>>,
+    names: HashMap<String, usize>, //主机名 -> handlers中的下标
+    default: Box<dyn Handler>,
+}
+
+impl VirtualHosts {
+    pub fn new(default: impl Handler + 'static) -> Self {
+        VirtualHosts {
+            handlers: Vec::new(),
+            names: HashMap::new(),
+            default: Box::new(default),
+        }
+    }
+
+    //一个处理函数可以对应多个主机名 同名的后注册的生效
+    pub fn host<S: AsRef<str>>(mut self, names: &[S], handler: impl Handler + 'static) -> Self {
+        for name in names {
+            self.names.insert(name.as_ref().to_ascii_lowercase(), self.handlers.len());
+        }
+        self.handlers.push(Box::new(handler));
+        self
+    }
+}
+
+impl VirtualHosts {
+    fn select(&self, req: &HttpRequest) -> &dyn Handler {
+        req.header("Host")
+            .map(|host| host_name(host).to_ascii_lowercase())
+            .and_then(|name| self.names.get(&name))
+            .map(|&i| self.handlers[i].as_ref())
+            .unwrap_or(self.default.as_ref())
+    }
+}
+
+//异步调用和流式读取消息体时也转发给选中的处理函数
+impl Handler for VirtualHosts {
+    fn handle(&self, req: &HttpRequest) -> HttpResponse {
+        self.select(req).handle(req)
+    }
+
+    fn handle_async<'a>(&'a self, req: &'a HttpRequest) -> BoxFuture<'a, HttpResponse> {
+        self.select(req).handle_async(req)
+    }
+
+    fn body_limit(&self, req: &HttpRequest) -> Option<u64> {
+        self.select(req).body_limit(req)
+    }
+
+    fn handle_stream(&self, req: &HttpRequest, body: &mut dyn Read) -> HttpResponse {
+        self.select(req).handle_stream(req, body)
+    }
+}
+
+//去掉端口 IPv6地址的形式是[::1]:3001
+fn host_name(host: &str) -> &str {
+    let host = host.trim();
+    if host.starts_with('[') {
+        return host.split_once(']').map_or(host, |(name, _)| &host[..name.len() + 1]);
+    }
+    host.split(':').next().unwrap_or(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(body: &'static str) -> impl Handler {
+        move |_: &HttpRequest| HttpResponse::builder().body(body).build()
+    }
+
+    #[test]
+    fn test_dispatch_on_host_header() {
+        let vhosts = VirtualHosts::new(reply("default"))
+            .host(&["docs.localhost", "docs.example.com"], reply("docs"))
+            .host(&["[::1]"], reply("ipv6"));
+        let cases = [
+            ("Host: docs.localhost:3001\r\n", "docs"),
+            ("Host: DOCS.Example.com\r\n", "docs"),
+            ("Host: [::1]:3001\r\n", "ipv6"),
+            ("Host: other.localhost\r\n", "default"),
+            ("", "default"),
+        ];
+        for (header, expected) in cases {
+            let raw = format!("GET / HTTP/1.1\r\n{}\r\n", header);
+            let req = HttpRequest::parse(raw.as_bytes()).unwrap();
+            assert_eq!(vhosts.handle(&req).body(), expected.as_bytes(), "{}", header);
+        }
+    }
+}
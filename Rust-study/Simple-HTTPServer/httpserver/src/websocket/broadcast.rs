@@ -0,0 +1,72 @@
+//广播频道 连接打开时加入 关闭时离开 broadcast把消息发给当前所有连接
+//复制出来的Broadcast共享同一个连接列表 一份注册到路由 一份交给产生事件的处理函数
+use super::{Message, WsHandler, WsSender};
+use http::httprequest::HttpRequest;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Clone, Default)]
+pub struct Broadcast {
+    clients: Arc<Mutex<Vec<WsSender>>>,
+}
+
+impl Broadcast {
+    //返回收到消息的连接数 顺便移除已经断开的连接
+    pub fn broadcast(&self, text: &str) -> usize {
+        let mut clients = self.lock();
+        clients.retain(|client| client.send(Message::Text(text.to_string())));
+        clients.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<WsSender>> {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+//只推送 客户端发来的消息忽略
+impl WsHandler for Broadcast {
+    fn on_open(&self, sender: &WsSender, _req: &HttpRequest) {
+        self.lock().push(sender.clone());
+    }
+
+    fn on_message(&self, _sender: &WsSender, _msg: Message) {}
+
+    fn on_close(&self, sender: &WsSender) {
+        self.lock().retain(|client| client.id() != sender.id());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::Outgoing;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_broadcast_skips_closed_connections() {
+        let channel = Broadcast::default();
+        let req = HttpRequest::parse(b"GET /ws HTTP/1.1\r\n\r\n").unwrap();
+        let (tx1, rx1) = mpsc::channel();
+        let (tx2, rx2) = mpsc::channel();
+        let first = WsSender { id: 1, tx: tx1 };
+        let second = WsSender { id: 2, tx: tx2 };
+        channel.on_open(&first, &req);
+        channel.on_open(&second, &req);
+
+        assert_eq!(channel.broadcast("one"), 2);
+        assert!(matches!(rx1.try_recv(), Ok(Outgoing::Message(Message::Text(t))) if t == "one"));
+
+        //接收端已经不存在的连接在下次广播时移除
+        drop(rx2);
+        assert_eq!(channel.broadcast("two"), 1);
+        channel.on_close(&first);
+        assert!(channel.is_empty());
+    }
+}
@@ -0,0 +1,10 @@
+//回显 收到什么消息就原样发回去 用于调试客户端
+use super::{Message, WsHandler, WsSender};
+
+pub struct Echo;
+
+impl WsHandler for Echo {
+    fn on_message(&self, sender: &WsSender, msg: Message) {
+        sender.send(msg);
+    }
+}
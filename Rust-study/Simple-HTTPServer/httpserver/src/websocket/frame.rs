@@ -0,0 +1,282 @@
+//WebSocket帧的编码和解码(RFC 6455 第5节)
+//  0                   1                   2                   3
+//  FIN RSV1-3 opcode(4) | MASK 长度(7) | 扩展长度(16/64) | 掩码(32) | 数据
+//客户端发送的帧必须带掩码 服务端发送的帧不带掩码
+use std::fmt;
+use std::io::{self, Write};
+
+//关闭帧中的状态码
+pub const NORMAL_CLOSURE: u16 = 1000;
+pub const GOING_AWAY: u16 = 1001;
+pub const PROTOCOL_ERROR: u16 = 1002;
+pub const INVALID_PAYLOAD: u16 = 1007;
+pub const MESSAGE_TOO_BIG: u16 = 1009;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_u8(value: u8) -> Option<Opcode> {
+        match value {
+            0x0 => Some(Opcode::Continuation),
+            0x1 => Some(Opcode::Text),
+            0x2 => Some(Opcode::Binary),
+            0x8 => Some(Opcode::Close),
+            0x9 => Some(Opcode::Ping),
+            0xA => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+        }
+    }
+
+    //控制帧不能分片 数据不超过125字节
+    pub fn is_control(&self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FrameError {
+    Protocol(&'static str),
+    TooLarge,
+}
+
+impl FrameError {
+    //出错时关闭连接使用的状态码
+    pub fn close_code(&self) -> u16 {
+        match self {
+            FrameError::Protocol(_) => PROTOCOL_ERROR,
+            FrameError::TooLarge => MESSAGE_TOO_BIG,
+        }
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Protocol(reason) => write!(f, "{}", reason),
+            FrameError::TooLarge => write!(f, "message too big"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    //不分片的完整帧
+    pub fn new(opcode: Opcode, payload: impl Into<Vec<u8>>) -> Frame {
+        Frame {
+            fin: true,
+            opcode,
+            payload: payload.into(),
+        }
+    }
+
+    //关闭帧的数据是2字节状态码加上可选的原因 原因截断到控制帧的长度上限以内
+    pub fn close(code: u16, reason: &str) -> Frame {
+        let mut payload = code.to_be_bytes().to_vec();
+        let mut end = reason.len().min(123);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        payload.extend_from_slice(&reason.as_bytes()[..end]);
+        Frame::new(Opcode::Close, payload)
+    }
+
+    //关闭帧中的状态码 没有状态码时返回None
+    pub fn close_code(&self) -> Option<u16> {
+        match self.payload.get(..2) {
+            Some(&[hi, lo]) => Some(u16::from_be_bytes([hi, lo])),
+            _ => None,
+        }
+    }
+
+    //mask为Some时按客户端的方式给数据加掩码
+    pub fn encode(&self, mask: Option<[u8; 4]>) -> Vec<u8> {
+        let len = self.payload.len();
+        let mut out = Vec::with_capacity(len + 14);
+        out.push(if self.fin { 0x80 } else { 0 } | self.opcode.as_u8());
+        let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+        if len < 126 {
+            out.push(mask_bit | len as u8);
+        } else if len <= u16::MAX as usize {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+        match mask {
+            Some(key) => {
+                out.extend_from_slice(&key);
+                out.extend(self.payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+            }
+            None => out.extend_from_slice(&self.payload),
+        }
+        out
+    }
+
+    //服务端写出的帧不加掩码
+    pub fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_all(&self.encode(None))
+    }
+
+    //从缓冲区开头解码一个帧 数据还不完整时返回Ok(None) 成功时返回帧和用掉的字节数
+    //masked表示对端是否必须加掩码 服务端解码客户端的帧时为true
+    //长度超过max_payload时 不用等数据到齐就返回错误
+    pub fn decode(buf: &[u8], masked: bool, max_payload: usize) -> Result<Option<(Frame, usize)>, FrameError> {
+        let (b0, b1) = match buf {
+            [b0, b1, ..] => (*b0, *b1),
+            _ => return Ok(None),
+        };
+        if b0 & 0x70 != 0 {
+            return Err(FrameError::Protocol("reserved bits must be zero"));
+        }
+        let opcode = Opcode::from_u8(b0 & 0x0F).ok_or(FrameError::Protocol("unknown opcode"))?;
+        let fin = b0 & 0x80 != 0;
+        if (b1 & 0x80 != 0) != masked {
+            return Err(FrameError::Protocol(if masked {
+                "client frames must be masked"
+            } else {
+                "server frames must not be masked"
+            }));
+        }
+
+        let (len, mut pos) = match b1 & 0x7F {
+            126 => match buf.get(2..4) {
+                Some(bytes) => (u16::from_be_bytes([bytes[0], bytes[1]]) as u64, 4),
+                None => return Ok(None),
+            },
+            127 => match buf.get(2..10) {
+                Some(bytes) => {
+                    let len = u64::from_be_bytes(bytes.try_into().unwrap());
+                    if len >> 63 != 0 {
+                        return Err(FrameError::Protocol("invalid payload length"));
+                    }
+                    (len, 10)
+                }
+                None => return Ok(None),
+            },
+            n => (n as u64, 2),
+        };
+        if opcode.is_control() && (!fin || len > 125) {
+            return Err(FrameError::Protocol("invalid control frame"));
+        }
+        if len > max_payload as u64 {
+            return Err(FrameError::TooLarge);
+        }
+        let len = len as usize;
+
+        let key = if masked {
+            let Some(key) = buf.get(pos..pos + 4) else {
+                return Ok(None);
+            };
+            pos += 4;
+            Some([key[0], key[1], key[2], key[3]])
+        } else {
+            None
+        };
+        let Some(data) = buf.get(pos..pos + len) else {
+            return Ok(None);
+        };
+        let payload = match key {
+            Some(key) => data.iter().enumerate().map(|(i, b)| b ^ key[i % 4]).collect(),
+            None => data.to_vec(),
+        };
+        Ok(Some((Frame { fin, opcode, payload }, pos + len)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    #[test]
+    fn test_rfc_examples() {
+        //RFC 6455 5.7节的例子
+        let unmasked = [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f];
+        let masked = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+        let hello = Frame::new(Opcode::Text, "Hello");
+        assert_eq!(hello.encode(None), unmasked);
+        assert_eq!(hello.encode(Some(MASK)), masked);
+        assert_eq!(Frame::decode(&unmasked, false, 1024).unwrap(), Some((hello.clone(), 7)));
+        assert_eq!(Frame::decode(&masked, true, 1024).unwrap(), Some((hello, 11)));
+
+        //分片的消息 第一片是Text 第二片是Continuation
+        let (first, _) = Frame::decode(&[0x01, 0x03, 0x48, 0x65, 0x6c], false, 1024)
+            .unwrap()
+            .unwrap();
+        assert!(!first.fin);
+        assert_eq!(first.opcode, Opcode::Text);
+    }
+
+    #[test]
+    fn test_extended_lengths_round_trip() {
+        for len in [125, 126, 65535, 65536] {
+            let frame = Frame::new(Opcode::Binary, vec![7u8; len]);
+            let bytes = frame.encode(Some(MASK));
+            //数据不完整时等待更多数据
+            assert_eq!(Frame::decode(&bytes[..bytes.len() - 1], true, 1 << 20).unwrap(), None);
+            assert_eq!(
+                Frame::decode(&bytes, true, 1 << 20).unwrap(),
+                Some((frame, bytes.len()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_frames() {
+        //客户端没有加掩码
+        let unmasked = Frame::new(Opcode::Text, "hi").encode(None);
+        assert!(matches!(
+            Frame::decode(&unmasked, true, 1024),
+            Err(FrameError::Protocol(_))
+        ));
+        //保留位和未知opcode
+        assert!(Frame::decode(&[0xC1, 0x80, 0, 0, 0, 0], true, 1024).is_err());
+        assert!(Frame::decode(&[0x83, 0x80, 0, 0, 0, 0], true, 1024).is_err());
+        //控制帧分片或者过长
+        assert!(Frame::decode(&[0x09, 0x80, 0, 0, 0, 0], true, 1024).is_err());
+        let long_ping = Frame::new(Opcode::Ping, vec![0u8; 126]).encode(Some(MASK));
+        assert!(Frame::decode(&long_ping, true, 1024).is_err());
+        //只收到长度字段就能判断消息过大
+        let big = Frame::new(Opcode::Binary, vec![0u8; 2048]).encode(Some(MASK));
+        assert_eq!(Frame::decode(&big[..4], true, 1024), Err(FrameError::TooLarge));
+    }
+
+    #[test]
+    fn test_close_frame() {
+        let frame = Frame::close(GOING_AWAY, "bye");
+        assert_eq!(frame.payload, [0x03, 0xE9, b'b', b'y', b'e']);
+        assert_eq!(frame.close_code(), Some(GOING_AWAY));
+        assert_eq!(Frame::new(Opcode::Close, Vec::new()).close_code(), None);
+        //原因过长时截断 不超过控制帧的125字节
+        assert_eq!(Frame::close(NORMAL_CLOSURE, &"é".repeat(100)).payload.len(), 124);
+    }
+}
@@ -0,0 +1,511 @@
+//WebSocket支持(RFC 6455)
+//路由表发现带"Upgrade: websocket"的请求匹配到WebSocket路由时 校验握手并回复101
+//服务器发出101后把连接交给serve 之后按帧收发消息 直到任意一方关闭连接
+//处理函数通过WsSender发送消息 WsSender可以复制到其他线程 用于主动推送
+pub mod broadcast;
+pub mod echo;
+pub mod frame;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use frame::{Frame, Opcode};
+use http::httprequest::{HttpRequest, Method};
+use http::httpresponse::HttpResponse;
+use http::statuscode::StatusCode;
+use sha1::{Digest, Sha1};
+use std::io::{self, BufReader, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+//握手时拼接在Sec-WebSocket-Key后面的固定GUID
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+//单条消息(分片合并后)的大小上限
+pub const MAX_MESSAGE_SIZE: usize = 1 << 20;
+//WebSocket连接的读超时 决定了推送消息和响应关闭信号的延迟
+pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+//空闲这么久发送一次ping 再过同样的时间还没有收到任何数据就断开
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Message {
+    fn into_frame(self) -> Frame {
+        match self {
+            Message::Text(text) => Frame::new(Opcode::Text, text),
+            Message::Binary(data) => Frame::new(Opcode::Binary, data),
+        }
+    }
+}
+
+//处理函数在多个会话线程之间共享 所以要求Send + Sync
+//每个连接有自己的WsSender 通过id区分不同的连接
+pub trait WsHandler: Send + Sync {
+    fn on_open(&self, _sender: &WsSender, _req: &HttpRequest) {}
+
+    fn on_message(&self, sender: &WsSender, msg: Message);
+
+    fn on_close(&self, _sender: &WsSender) {}
+}
+
+//发给连接的消息先进入队列 由运行这个会话的线程写出
+enum Outgoing {
+    Message(Message),
+    Close(u16, String),
+}
+
+#[derive(Clone)]
+pub struct WsSender {
+    id: usize,
+    tx: Sender<Outgoing>,
+}
+
+static NEXT_CONNECTION_ID: AtomicUsize = AtomicUsize::new(1);
+
+impl WsSender {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    //连接已经关闭时返回false
+    pub fn send(&self, msg: Message) -> bool {
+        self.tx.send(Outgoing::Message(msg)).is_ok()
+    }
+
+    pub fn text(&self, text: impl Into<String>) -> bool {
+        self.send(Message::Text(text.into()))
+    }
+
+    //发送关闭帧后结束连接
+    pub fn close(&self, code: u16, reason: &str) {
+        let _ = self.tx.send(Outgoing::Close(code, reason.to_string()));
+    }
+}
+
+//Upgrade头部包含websocket 并且Connection头部包含upgrade
+pub fn is_upgrade_request(req: &HttpRequest) -> bool {
+    has_token(req.header("Upgrade"), "websocket") && has_token(req.header("Connection"), "upgrade")
+}
+
+//Sec-WebSocket-Accept = base64(sha1(Sec-WebSocket-Key + GUID))
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    BASE64_STANDARD.encode(hasher.finalize())
+}
+
+//校验握手请求 成功时返回101 否则返回说明原因的错误响应
+pub fn handshake(req: &HttpRequest) -> HttpResponse {
+    if req.method != Method::Get || !is_upgrade_request(req) {
+        return HttpResponse::builder()
+            .status(StatusCode::UpgradeRequired)
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .header("Content-Type", "text/plain")
+            .body("WebSocket upgrade required")
+            .build();
+    }
+    if req.header("Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        return HttpResponse::builder()
+            .status(StatusCode::UpgradeRequired)
+            .header("Sec-WebSocket-Version", "13")
+            .header("Content-Type", "text/plain")
+            .body("unsupported WebSocket version")
+            .build();
+    }
+    //客户端的key是16个随机字节的base64编码
+    let key = match req.header("Sec-WebSocket-Key") {
+        Some(key) if BASE64_STANDARD.decode(key.trim()).is_ok_and(|k| k.len() == 16) => key,
+        _ => {
+            return HttpResponse::builder()
+                .status(StatusCode::BadRequest)
+                .header("Content-Type", "text/plain")
+                .body("invalid Sec-WebSocket-Key")
+                .build();
+        }
+    };
+    HttpResponse::builder()
+        .status(StatusCode::SwitchingProtocols)
+        .header("Upgrade", "websocket")
+        .header("Connection", "Upgrade")
+        .header("Sec-WebSocket-Accept", accept_key(key))
+        .build()
+}
+
+//在已经完成握手的连接上运行WebSocket会话 reader里可能已经缓存了客户端紧接着发来的帧
+//调用方需要先把读超时设为POLL_INTERVAL 服务器关闭时发送1001关闭帧后返回
+pub fn serve<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    req: &HttpRequest,
+    handler: &dyn WsHandler,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let sender = WsSender {
+        id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::SeqCst),
+        tx,
+    };
+    handler.on_open(&sender, req);
+    let result = run_session(reader, handler, &sender, &rx, shutdown);
+    handler.on_close(&sender);
+    result
+}
+
+fn run_session<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    handler: &dyn WsHandler,
+    sender: &WsSender,
+    rx: &Receiver<Outgoing>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    //正在接收的分片消息
+    let mut fragments: Option<(Opcode, Vec<u8>)> = None;
+    let mut last_seen = Instant::now();
+    let mut ping_sent = false;
+
+    loop {
+        //处理缓冲区里所有完整的帧
+        loop {
+            let (frame, used) = match Frame::decode(&buf, true, MAX_MESSAGE_SIZE) {
+                Ok(Some(decoded)) => decoded,
+                Ok(None) => break,
+                Err(e) => return close(reader.get_mut(), e.close_code(), &e.to_string()),
+            };
+            buf.drain(..used);
+            if !handle_frame(reader.get_mut(), frame, &mut fragments, handler, sender)? {
+                return Ok(());
+            }
+        }
+
+        //写出处理函数和其他线程排队的消息
+        while let Ok(outgoing) = rx.try_recv() {
+            match outgoing {
+                Outgoing::Message(msg) => msg.into_frame().write_to(reader.get_mut())?,
+                Outgoing::Close(code, reason) => return close(reader.get_mut(), code, &reason),
+            }
+        }
+        reader.get_mut().flush()?;
+
+        if shutdown.load(Ordering::SeqCst) {
+            return close(reader.get_mut(), frame::GOING_AWAY, "server shutting down");
+        }
+
+        match reader.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(n) => {
+                buf.extend_from_slice(&chunk[..n]);
+                last_seen = Instant::now();
+                ping_sent = false;
+            }
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                let idle = last_seen.elapsed();
+                if idle >= PING_INTERVAL * 2 {
+                    return Ok(());
+                }
+                if idle >= PING_INTERVAL && !ping_sent {
+                    Frame::new(Opcode::Ping, Vec::new()).write_to(reader.get_mut())?;
+                    ping_sent = true;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+//处理一个帧 返回false表示会话结束
+fn handle_frame(
+    stream: &mut impl Write,
+    frame: Frame,
+    fragments: &mut Option<(Opcode, Vec<u8>)>,
+    handler: &dyn WsHandler,
+    sender: &WsSender,
+) -> io::Result<bool> {
+    match frame.opcode {
+        Opcode::Ping => Frame::new(Opcode::Pong, frame.payload).write_to(stream)?,
+        Opcode::Pong => {}
+        //对端发起关闭 回复同样的状态码
+        Opcode::Close => {
+            let reply = match frame.close_code() {
+                Some(code) => Frame::close(code, ""),
+                None => Frame::new(Opcode::Close, Vec::new()),
+            };
+            reply.write_to(stream)?;
+            stream.flush()?;
+            return Ok(false);
+        }
+        Opcode::Text | Opcode::Binary => {
+            if fragments.is_some() {
+                close(stream, frame::PROTOCOL_ERROR, "expected continuation frame")?;
+                return Ok(false);
+            }
+            if frame.fin {
+                return deliver(stream, frame.opcode, frame.payload, handler, sender);
+            }
+            *fragments = Some((frame.opcode, frame.payload));
+        }
+        Opcode::Continuation => {
+            let Some((opcode, mut payload)) = fragments.take() else {
+                close(stream, frame::PROTOCOL_ERROR, "unexpected continuation frame")?;
+                return Ok(false);
+            };
+            payload.extend_from_slice(&frame.payload);
+            if payload.len() > MAX_MESSAGE_SIZE {
+                close(stream, frame::MESSAGE_TOO_BIG, "message too big")?;
+                return Ok(false);
+            }
+            if frame.fin {
+                return deliver(stream, opcode, payload, handler, sender);
+            }
+            *fragments = Some((opcode, payload));
+        }
+    }
+    Ok(true)
+}
+
+//把完整的消息交给处理函数 文本消息必须是合法的UTF-8
+fn deliver(
+    stream: &mut impl Write,
+    opcode: Opcode,
+    payload: Vec<u8>,
+    handler: &dyn WsHandler,
+    sender: &WsSender,
+) -> io::Result<bool> {
+    let msg = match opcode {
+        Opcode::Text => match String::from_utf8(payload) {
+            Ok(text) => Message::Text(text),
+            Err(_) => {
+                close(stream, frame::INVALID_PAYLOAD, "invalid utf-8")?;
+                return Ok(false);
+            }
+        },
+        _ => Message::Binary(payload),
+    };
+    handler.on_message(sender, msg);
+    Ok(true)
+}
+
+//服务端主动关闭 发送关闭帧后直接断开 不等待对端的关闭帧
+fn close(stream: &mut impl Write, code: u16, reason: &str) -> io::Result<()> {
+    Frame::close(code, reason).write_to(stream)?;
+    stream.flush()
+}
+
+fn has_token(value: Option<&str>, token: &str) -> bool {
+    value.is_some_and(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::echo::Echo;
+    use super::*;
+    use crate::router::Router;
+    use crate::server::Server;
+    use http::transfer;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    fn upgrade_request(key: &str) -> HttpRequest {
+        let raw = format!(
+            "GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: keep-alive, Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            key
+        );
+        HttpRequest::parse(raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_handshake() {
+        //RFC 6455 1.3节的例子
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        let resp = handshake(&upgrade_request("dGhlIHNhbXBsZSBub25jZQ=="));
+        assert_eq!(resp.status_code(), StatusCode::SwitchingProtocols);
+        assert_eq!(
+            resp.header("Sec-WebSocket-Accept"),
+            Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+        );
+
+        let resp = handshake(&upgrade_request("not-base64"));
+        assert_eq!(resp.status_code(), StatusCode::BadRequest);
+        let plain = HttpRequest::parse(b"GET /ws HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(handshake(&plain).status_code(), StatusCode::UpgradeRequired);
+    }
+
+    //客户端 完成握手后按帧收发
+    struct TestClient {
+        stream: TcpStream,
+        buf: Vec<u8>,
+    }
+
+    impl TestClient {
+        fn connect(addr: &str, path: &str) -> TestClient {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                 Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+                path, addr
+            )
+            .unwrap();
+            //逐字节读取响应头部 避免把后面的帧读进缓冲区
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                let mut byte = [0u8];
+                stream.read_exact(&mut byte).unwrap();
+                head.push(byte[0]);
+            }
+            let head = String::from_utf8(head).unwrap();
+            assert!(head.starts_with("HTTP/1.1 101"), "{}", head);
+            assert_eq!(
+                transfer::header_value(&head, "Sec-WebSocket-Accept"),
+                Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+            );
+            TestClient {
+                stream,
+                buf: Vec::new(),
+            }
+        }
+
+        fn send(&mut self, frame: Frame) {
+            self.stream.write_all(&frame.encode(Some([1, 2, 3, 4]))).unwrap();
+        }
+
+        fn recv(&mut self) -> Frame {
+            loop {
+                if let Some((frame, used)) = Frame::decode(&self.buf, false, MAX_MESSAGE_SIZE).unwrap() {
+                    self.buf.drain(..used);
+                    return frame;
+                }
+                let mut chunk = [0u8; 1024];
+                let n = self.stream.read(&mut chunk).unwrap();
+                assert!(n > 0, "connection closed");
+                self.buf.extend_from_slice(&chunk[..n]);
+            }
+        }
+    }
+
+    fn spawn_echo_server() -> (String, std::sync::Arc<AtomicBool>, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut router = Router::new();
+        router.websocket("/ws/echo", Echo);
+        let server = Server::new("127.0.0.1:0", router);
+        let shutdown = server.shutdown_handle();
+        let handle = thread::spawn(move || server.serve(listener));
+        (addr, shutdown, handle)
+    }
+
+    #[test]
+    fn test_echo_ping_and_close() {
+        let (addr, shutdown, handle) = spawn_echo_server();
+        let mut client = TestClient::connect(&addr, "/ws/echo");
+
+        client.send(Frame::new(Opcode::Text, "hello"));
+        assert_eq!(client.recv(), Frame::new(Opcode::Text, "hello"));
+
+        //分片的二进制消息合并后再交给处理函数 中间可以插入ping
+        client.send(Frame {
+            fin: false,
+            opcode: Opcode::Binary,
+            payload: vec![1, 2],
+        });
+        client.send(Frame::new(Opcode::Ping, "p"));
+        client.send(Frame::new(Opcode::Continuation, vec![3]));
+        assert_eq!(client.recv(), Frame::new(Opcode::Pong, "p"));
+        assert_eq!(client.recv(), Frame::new(Opcode::Binary, vec![1, 2, 3]));
+
+        client.send(Frame::close(frame::NORMAL_CLOSURE, "done"));
+        assert_eq!(client.recv().close_code(), Some(frame::NORMAL_CLOSURE));
+        assert_eq!(client.stream.read(&mut [0u8; 1]).unwrap(), 0);
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_protocol_errors_close_connection() {
+        let (addr, shutdown, handle) = spawn_echo_server();
+
+        //客户端帧没有掩码
+        let mut client = TestClient::connect(&addr, "/ws/echo");
+        client
+            .stream
+            .write_all(&Frame::new(Opcode::Text, "hi").encode(None))
+            .unwrap();
+        assert_eq!(client.recv().close_code(), Some(frame::PROTOCOL_ERROR));
+
+        //文本消息不是UTF-8
+        let mut client = TestClient::connect(&addr, "/ws/echo");
+        client.send(Frame::new(Opcode::Text, vec![0xff, 0xfe]));
+        assert_eq!(client.recv().close_code(), Some(frame::INVALID_PAYLOAD));
+
+        //服务器关闭时通知还连着的客户端
+        let mut client = TestClient::connect(&addr, "/ws/echo");
+        client.send(Frame::new(Opcode::Text, "still here"));
+        client.recv();
+        shutdown.store(true, Ordering::SeqCst);
+        assert_eq!(client.recv().close_code(), Some(frame::GOING_AWAY));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_websocket_does_not_hold_worker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut router = Router::new();
+        router.websocket("/ws/echo", Echo);
+        let server = Server::new("127.0.0.1:0", router).workers(1).max_websockets(1);
+        let shutdown = server.shutdown_handle();
+        let metrics = server.metrics();
+        let handle = thread::spawn(move || server.serve(listener));
+
+        //唯一的工作线程交出WebSocket会话后 普通请求仍然可以得到响应
+        let mut client = TestClient::connect(&addr, "/ws/echo");
+        let mut reader = BufReader::new(TcpStream::connect(&addr).unwrap());
+        reader.get_mut().write_all(b"GET /missing HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let head = transfer::read_head(&mut reader).unwrap().unwrap();
+        assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+
+        //会话数达到上限 新的握手回复503
+        let mut reader = BufReader::new(TcpStream::connect(&addr).unwrap());
+        write!(
+            reader.get_mut(),
+            "GET /ws/echo HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+        )
+        .unwrap();
+        let head = transfer::read_head(&mut reader).unwrap().unwrap();
+        assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+
+        client.send(Frame::new(Opcode::Text, "hello"));
+        assert_eq!(client.recv(), Frame::new(Opcode::Text, "hello"));
+        assert_eq!(metrics.websocket_connections.load(Ordering::SeqCst), 1);
+
+        //关闭时等待会话发送1001关闭帧后结束
+        shutdown.store(true, Ordering::SeqCst);
+        assert_eq!(client.recv().close_code(), Some(frame::GOING_AWAY));
+        handle.join().unwrap();
+        assert_eq!(metrics.websocket_connections.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_plain_get_on_websocket_route() {
+        let (addr, shutdown, handle) = spawn_echo_server();
+        let mut reader = BufReader::new(TcpStream::connect(&addr).unwrap());
+        reader
+            .get_mut()
+            .write_all(b"GET /ws/echo HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let head = transfer::read_head(&mut reader).unwrap().unwrap();
+        assert!(head.starts_with("HTTP/1.1 426"), "{}", head);
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+//集成测试: 在随机端口上启动完整的服务器 用http crate的Client访问
+use http::client::Client;
+use http::httprequest::Method;
+use http::statuscode::StatusCode;
+use httpserver::handler::WebServiceHandler;
+use httpserver::metrics::ServerMetrics;
+use httpserver::middleware::requestid::RequestId;
+use httpserver::router::Router;
+use httpserver::server::Server;
+use httpserver::staticfile::StaticPageHandler;
+use httpserver::store::OrderStore;
+use httpserver::websocket::broadcast::Broadcast;
+use httpserver::websocket::frame::{Frame, Opcode};
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+struct TestServer {
+    base: String,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<ServerMetrics>,
+    handle: Option<JoinHandle<()>>,
+    _dir: tempfile::TempDir,
+}
+
+impl TestServer {
+    //和main一样注册订单接口和静态文件 数据和页面放在临时目录里
+    fn start() -> TestServer {
+        let dir = tempfile::tempdir().unwrap();
+        let public = dir.path().join("public");
+        fs::create_dir(&public).unwrap();
+        fs::write(public.join("index.html"), "<h1>Welcome</h1>").unwrap();
+        fs::create_dir(public.join("docs")).unwrap();
+
+        let store = OrderStore::open(dir.path().join("order.json")).unwrap();
+        let events = Broadcast::default();
+        let orders = WebServiceHandler::new(Arc::new(store)).notify(events.clone());
+        let mut router = Router::new();
+        router
+            .get("/api/shipping/orders", orders.clone())
+            .post("/api/shipping/orders", orders.clone())
+            .get("/api/shipping/orders/:id", orders.clone())
+            .put("/api/shipping/orders/:id", orders.clone())
+            .delete("/api/shipping/orders/:id", orders)
+            .websocket("/ws/orders", events)
+            .get("/*path", StaticPageHandler::new(&public));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = Server::new("127.0.0.1:0", router).wrap(RequestId::default());
+        let shutdown = server.shutdown_handle();
+        let metrics = server.metrics();
+        let handle = thread::spawn(move || server.serve(listener));
+        TestServer {
+            base,
+            shutdown,
+            metrics,
+            handle: Some(handle),
+            _dir: dir,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[test]
+fn test_static_pages_and_redirect() {
+    let server = TestServer::start();
+    let client = Client::new();
+
+    let resp = client.get(&server.url("/")).unwrap();
+    assert_eq!(resp.status_code(), StatusCode::Ok);
+    assert_eq!(resp.body(), b"<h1>Welcome</h1>");
+    assert!(resp.header("X-Request-Id").is_some());
+
+    //目录缺少结尾的'/' 服务器返回301 客户端跟随后得到404(没有开启目录列表)
+    let resp = client.get(&server.url("/docs")).unwrap();
+    assert_eq!(resp.status_code(), StatusCode::NotFound);
+    let resp = Client::new().max_redirects(0).get(&server.url("/docs")).unwrap();
+    assert_eq!(resp.header("Location"), Some("/docs/"));
+}
+
+#[test]
+fn test_order_crud_over_http() {
+    let server = TestServer::start();
+    let client = Client::new();
+    let orders = server.url("/api/shipping/orders");
+
+    let resp = client
+        .post(&orders, "application/json", r#"{"order_date":"21 Jan 2020","order_status":"Pending"}"#)
+        .unwrap();
+    assert_eq!(resp.status_code(), StatusCode::Created);
+    let location = resp.header("Location").unwrap().to_string();
+
+    let resp = client
+        .send(Method::Put, &server.url(&location), &[], br#"{"order_status":"Shipped"}"#.to_vec())
+        .unwrap();
+    assert_eq!(resp.status_code(), StatusCode::Ok);
+
+    let resp = client.get(&format!("{}?status=shipped", orders)).unwrap();
+    let list: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+
+    let resp = client.send(Method::Delete, &server.url(&location), &[], Vec::new()).unwrap();
+    assert_eq!(resp.status_code(), StatusCode::NoContent);
+    let resp = client.get(&server.url(&location)).unwrap();
+    assert_eq!(resp.status_code(), StatusCode::NotFound);
+    let error: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert!(error["error"].is_string());
+}
+
+#[test]
+fn test_client_reuses_keep_alive_connection() {
+    let server = TestServer::start();
+    let client = Client::new();
+    for _ in 0..5 {
+        assert_eq!(client.get(&server.url("/")).unwrap().status_code(), StatusCode::Ok);
+    }
+    assert_eq!(server.metrics.total_connections.load(Ordering::SeqCst), 1);
+    assert_eq!(server.metrics.total_requests.load(Ordering::SeqCst), 5);
+}
+
+//完成WebSocket握手 测试里服务器只会发来完整的小帧
+fn ws_connect(base: &str, path: &str) -> TcpStream {
+    let addr = base.trim_start_matches("http://");
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        path, addr
+    )
+    .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0u8];
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+    }
+    assert!(head.starts_with(b"HTTP/1.1 101"));
+    stream
+}
+
+fn ws_recv(stream: &mut TcpStream) -> Frame {
+    let mut buf = Vec::new();
+    loop {
+        if let Some((frame, _)) = Frame::decode(&buf, false, 1 << 20).unwrap() {
+            return frame;
+        }
+        let mut chunk = [0u8; 1024];
+        let n = stream.read(&mut chunk).unwrap();
+        assert!(n > 0, "connection closed");
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+#[test]
+fn test_order_changes_pushed_over_websocket() {
+    let server = TestServer::start();
+    let mut ws = ws_connect(&server.base, "/ws/orders");
+    //收到pong说明连接已经加入广播频道
+    ws.write_all(&Frame::new(Opcode::Ping, "sync").encode(Some([9, 8, 7, 6]))).unwrap();
+    assert_eq!(ws_recv(&mut ws), Frame::new(Opcode::Pong, "sync"));
+
+    let client = Client::new();
+    let resp = client
+        .post(&server.url("/api/shipping/orders"), "application/json", r#"{"order_date":"1 Apr 2020","order_status":"Pending"}"#)
+        .unwrap();
+    assert_eq!(resp.status_code(), StatusCode::Created);
+    let event: serde_json::Value = serde_json::from_slice(&ws_recv(&mut ws).payload).unwrap();
+    assert_eq!(event["event"], "created");
+    assert_eq!(event["order"]["order_status"], "Pending");
+
+    let location = server.url(resp.header("Location").unwrap());
+    client.send(Method::Delete, &location, &[], Vec::new()).unwrap();
+    let event: serde_json::Value = serde_json::from_slice(&ws_recv(&mut ws).payload).unwrap();
+    assert_eq!(event["event"], "deleted");
+    assert_eq!(event["order_id"], 1);
+    assert!(event["order"].is_null());
+}
@@ -1,13 +1,243 @@
-use std::io::{Read, Write};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::net::TcpStream;
-use std::str;
+use std::process;
+#[cfg(feature = "tls")]
+use std::path::Path;
+use tcpproto::{Request, Response};
+
+#[cfg(feature = "tls")]
+use http::tls::rustls::{pki_types::ServerName, ClientConnection, StreamOwned};
+
+const COMMANDS: &str = "echo <message>, time, get <key>, set <key> <value>, quit";
+
+//用法: tcpclient [地址] [--script 文件]
+//不指定脚本时进入交互模式 从标准输入逐行读取命令
 fn main() {
-    let mut stream = TcpStream::connect("localhost:3001").unwrap(); //连接3001端口
-    stream.write("Hello, world!".as_bytes()).unwrap(); 
+    let mut addr = "localhost:3001".to_string();
+    let mut script = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--script" => script = args.next(),
+            _ => addr = arg,
+        }
+    }
+
+    let stream = TcpStream::connect(&addr).unwrap_or_else(|e| {
+        eprintln!("Failed to connect to {}: {}", addr, e);
+        process::exit(1);
+    });
+
+    //开启tls特性并且设置了TLS_CA时走TLS 自签名证书直接把服务端证书作为TLS_CA
+    #[cfg(feature = "tls")]
+    if let Ok(ca) = env::var("TLS_CA") {
+        let stream = connect_tls(stream, &addr, ca.as_ref()).unwrap_or_else(|e| {
+            eprintln!("Failed to set up TLS with {}: {}", ca, e);
+            process::exit(1);
+        });
+        exit_with(session(stream, script.as_deref()));
+    }
+    exit_with(session(stream, script.as_deref()));
+}
+
+//用ca验证服务端证书 证书里的名称要和地址中的主机名一致
+#[cfg(feature = "tls")]
+fn connect_tls(stream: TcpStream, addr: &str, ca: &Path) -> io::Result<StreamOwned<ClientConnection, TcpStream>> {
+    let config = http::tls::client_config(ca)?;
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    let name = ServerName::try_from(host.to_string()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let conn = ClientConnection::new(config, name).map_err(io::Error::other)?;
+    Ok(StreamOwned::new(conn, stream))
+}
+
+//脚本中有命令失败时以1退出 方便在测试脚本里检查
+fn exit_with(result: io::Result<usize>) -> ! {
+    match result {
+        Ok(0) => process::exit(0),
+        Ok(failures) => {
+            eprintln!("{} command(s) failed", failures);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Connection failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+//明文和TLS连接共用
+fn session<S: Read + Write>(stream: S, script: Option<&str>) -> io::Result<usize> {
+    match script {
+        Some(path) => run(stream, BufReader::new(File::open(path)?), io::stdout(), false),
+        None => run(stream, io::stdin().lock(), io::stdout(), true),
+    }
+}
+
+//逐行读取命令 发送请求并打印回复 返回失败的命令数
+//空行和#开头的注释行跳过 交互模式打印提示符 脚本模式把命令也打印出来 方便对照输出
+fn run<S: Read + Write>(mut stream: S, input: impl BufRead, mut out: impl Write, interactive: bool) -> io::Result<usize> {
+    let mut failures = 0;
+    let prompt = |out: &mut dyn Write| -> io::Result<()> {
+        if interactive {
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        Ok(())
+    };
+
+    prompt(&mut out)?;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            prompt(&mut out)?;
+            continue;
+        }
+        if !interactive {
+            writeln!(out, "> {}", line)?;
+        }
+        if line == "quit" || line == "exit" {
+            break;
+        }
+
+        match parse_command(line) {
+            Ok(req) => {
+                tcpproto::send(&mut stream, &req)?;
+                let resp: Response = tcpproto::recv(&mut stream)?
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection"))?;
+                if matches!(resp, Response::Error { .. }) {
+                    failures += 1;
+                }
+                writeln!(out, "{}", resp)?;
+            }
+            Err(e) => {
+                failures += 1;
+                writeln!(out, "ERROR: {}", e)?;
+            }
+        }
+        prompt(&mut out)?;
+    }
+    Ok(failures)
+}
+
+//命令名不区分大小写 echo和set的值是命令后面的剩余部分 可以包含空格
+fn parse_command(line: &str) -> Result<Request, String> {
+    let (cmd, rest) = match line.split_once(char::is_whitespace) {
+        Some((cmd, rest)) => (cmd, rest.trim()),
+        None => (line, ""),
+    };
+    match (cmd.to_ascii_lowercase().as_str(), rest) {
+        ("echo", message) => Ok(Request::Echo {
+            message: message.to_string(),
+        }),
+        ("time", "") => Ok(Request::Time),
+        ("get", key) if !key.is_empty() && !key.contains(char::is_whitespace) => Ok(Request::Get { key: key.to_string() }),
+        ("set", rest) => match rest.split_once(char::is_whitespace) {
+            Some((key, value)) => Ok(Request::Set {
+                key: key.to_string(),
+                value: value.trim_start().to_string(),
+            }),
+            None => Err("usage: set <key> <value>".to_string()),
+        },
+        _ => Err(format!("unknown command '{}' (commands: {})", line, COMMANDS)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command("ECHO  hello world"),
+            Ok(Request::Echo {
+                message: "hello world".to_string()
+            })
+        );
+        assert_eq!(parse_command("time"), Ok(Request::Time));
+        assert_eq!(
+            parse_command("set name  Simple HTTP"),
+            Ok(Request::Set {
+                key: "name".to_string(),
+                value: "Simple HTTP".to_string()
+            })
+        );
+        for bad in ["time now", "get", "get a b", "set name", "del name"] {
+            assert!(parse_command(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_script_mode() {
+        //假的服务器 echo原样返回 其他命令一律回复错误
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            while let Some(req) = tcpproto::recv::<Request>(&mut stream).unwrap() {
+                let resp = match req {
+                    Request::Echo { message } => Response::Echo { message },
+                    _ => Response::Error {
+                        message: "unsupported".to_string(),
+                    },
+                };
+                tcpproto::send(&mut stream, &resp).unwrap();
+            }
+        });
+
+        let script = "# comment\necho hi there\n\ntime\nbogus\nquit\necho never sent\n";
+        let mut out = Vec::new();
+        let failures = run(TcpStream::connect(addr).unwrap(), script.as_bytes(), &mut out, false).unwrap();
+        assert_eq!(failures, 2);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("> echo hi there\nhi there\n> time\nERROR: unsupported\n> bogus\nERROR: unknown command"));
+        assert!(out.ends_with("> quit\n"));
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_tls_script_mode() {
+        use http::tls::rustls::ServerConnection;
+
+        let dir = tempfile::tempdir().unwrap();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+        //TLS上的假服务器 echo原样返回
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = http::tls::server_config(&cert_path, &key_path).unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stream = StreamOwned::new(ServerConnection::new(config).unwrap(), stream);
+            while let Ok(Some(req)) = tcpproto::recv::<Request>(&mut stream) {
+                let resp = match req {
+                    Request::Echo { message } => Response::Echo { message },
+                    _ => Response::Ok,
+                };
+                tcpproto::send(&mut stream, &resp).unwrap();
+            }
+        });
 
-    //从服务器接受消息
-    let mut buffer = [0; 15];
-    stream.read(&mut buffer).unwrap(); 
+        let addr = format!("localhost:{}", port);
+        let stream = connect_tls(TcpStream::connect(("127.0.0.1", port)).unwrap(), &addr, &cert_path).unwrap();
+        let mut out = Vec::new();
+        let failures = run(stream, "echo over tls\nquit\n".as_bytes(), &mut out, false).unwrap();
+        assert_eq!(failures, 0);
+        assert_eq!(String::from_utf8(out).unwrap(), "> echo over tls\nover tls\n> quit\n");
 
-    println!("response from server{:?}", str::from_utf8(&buffer[..]).unwrap());
+        //CA文件不存在时返回错误 不会panic
+        let missing = dir.path().join("missing.pem");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert!(connect_tls(stream, &addr, &missing).is_err());
+    }
 }
@@ -0,0 +1,154 @@
+//tcpserver和tcpclient共用的消息协议
+//每条消息是一帧: 4字节大端序长度 + json编码的消息体
+//有了长度前缀 接收方就知道一条消息在哪里结束 不会截断或者把两条消息粘在一起
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+//单帧的大小上限 防止对端发来一个巨大的长度把内存耗尽
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Request {
+    Echo { message: String },
+    Time,
+    Get { key: String },
+    Set { key: String, value: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Response {
+    Echo { message: String },
+    Time { unix_secs: u64 },
+    Value { value: Option<String> }, //key不存在时为None
+    Ok,
+    Error { message: String },
+}
+
+//客户端打印回复时使用
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Response::Echo { message } => write!(f, "{}", message),
+            Response::Time { unix_secs } => write!(f, "{}", unix_secs),
+            Response::Value { value: Some(value) } => write!(f, "{}", value),
+            Response::Value { value: None } => write!(f, "(nil)"),
+            Response::Ok => write!(f, "OK"),
+            Response::Error { message } => write!(f, "ERROR: {}", message),
+        }
+    }
+}
+
+//写入一帧
+pub fn write_frame(stream: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame too large"));
+    }
+    stream.write_all(&(payload.len() as u32).to_be_bytes())?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
+
+//读取一帧 对端在两帧之间关闭连接时返回Ok(None) 帧读到一半断开是UnexpectedEof
+pub fn read_frame(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match stream.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+//把消息编码成json后作为一帧发送
+pub fn send<T: Serialize>(stream: &mut impl Write, msg: &T) -> io::Result<()> {
+    write_frame(stream, &serde_json::to_vec(msg)?)
+}
+
+//接收一帧并解码 消息格式不对时返回InvalidData
+pub fn recv<T: DeserializeOwned>(stream: &mut impl Read) -> io::Result<Option<T>> {
+    match read_frame(stream)? {
+        Some(payload) => Ok(Some(serde_json::from_slice(&payload)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_round_trip() {
+        let requests = vec![
+            Request::Echo {
+                message: "hello\nworld".to_string(),
+            },
+            Request::Time,
+            Request::Set {
+                key: "k".to_string(),
+                value: "v".to_string(),
+            },
+        ];
+        let mut buf = Vec::new();
+        for req in &requests {
+            send(&mut buf, req).unwrap();
+        }
+        //长度前缀是消息体的字节数
+        assert_eq!(&buf[..4], &(br#"{"cmd":"echo","message":"hello\nworld"}"#.len() as u32).to_be_bytes());
+
+        let mut reader = Cursor::new(buf);
+        for req in &requests {
+            assert_eq!(recv::<Request>(&mut reader).unwrap().as_ref(), Some(req));
+        }
+        assert_eq!(recv::<Request>(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_and_oversized_frames() {
+        let mut buf = Vec::new();
+        send(&mut buf, &Response::Ok).unwrap();
+        //长度或者消息体只收到一部分
+        for cut in [2, buf.len() - 1] {
+            let err = read_frame(&mut Cursor::new(&buf[..cut])).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        let huge = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes();
+        assert_eq!(read_frame(&mut Cursor::new(huge)).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let mut sink = Vec::new();
+        assert!(write_frame(&mut sink, &vec![0u8; MAX_FRAME_SIZE + 1]).is_err());
+
+        //帧完整但不是合法的消息
+        let mut buf = Vec::new();
+        write_frame(&mut buf, b"{\"cmd\":\"unknown\"}").unwrap();
+        let err = recv::<Request>(&mut Cursor::new(buf)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_display_response() {
+        assert_eq!(Response::Value { value: None }.to_string(), "(nil)");
+        assert_eq!(
+            Response::Error {
+                message: "bad".to_string()
+            }
+            .to_string(),
+            "ERROR: bad"
+        );
+    }
+}
@@ -1,19 +1,210 @@
-use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Read, Write};
 use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tcpproto::{Request, Response};
+
+#[cfg(feature = "tls")]
+use http::tls::rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+//所有连接共享的键值存储
+type Store = Arc<Mutex<HashMap<String, String>>>;
 
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:3001").unwrap(); //绑定本地端口
-    println!("Running on port 3001");
+    //第一个参数可以指定监听地址
+    let addr = env::args().nth(1).unwrap_or_else(|| "127.0.0.1:3001".to_string());
+    let listener = TcpListener::bind(&addr).unwrap(); //绑定本地端口
+    println!("Running on {}", addr);
 
-    // let result = listener.accept().unwrap(); //只接受一次
-    // 持续监听端口
+    //开启tls特性并且设置了TLS_CERT和TLS_KEY时 所有连接都走TLS
+    #[cfg(feature = "tls")]
+    let tls = tls_config();
+
+    let store = Store::default();
+    // 持续监听端口 每个连接一个线程 多个客户端可以同时连接
     for stream in listener.incoming() {
-        let mut stream = stream.unwrap(); //可变流
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Accept failed: {}", e);
+                continue;
+            }
+        };
         println!("Connecting established");
-        let mut buffer = [0; 1024]; //1024 初始化全为 0
+        let store = Arc::clone(&store);
+
+        #[cfg(feature = "tls")]
+        if let Some(config) = &tls {
+            let config = Arc::clone(config);
+            thread::spawn(move || {
+                //握手失败只影响这一个连接
+                if let Err(e) = serve_tls(config, stream, &store) {
+                    println!("TLS connection failed: {}", e);
+                }
+            });
+            continue;
+        }
+        thread::spawn(move || {
+            if let Err(e) = serve(stream, &store) {
+                println!("Connection failed: {}", e);
+            }
+        });
+    }
+}
+
+//逐帧读取请求并回复 直到客户端关闭连接 明文和TLS连接共用
+//帧完整但内容不是合法请求时回复错误 连接继续可用
+fn serve<S: Read + Write>(mut stream: S, store: &Store) -> io::Result<()> {
+    while let Some(payload) = tcpproto::read_frame(&mut stream)? {
+        let resp = match serde_json::from_slice(&payload) {
+            Ok(req) => dispatch(req, store),
+            Err(e) => Response::Error {
+                message: format!("invalid request: {}", e),
+            },
+        };
+        tcpproto::send(&mut stream, &resp)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+fn serve_tls(config: Arc<ServerConfig>, stream: std::net::TcpStream, store: &Store) -> io::Result<()> {
+    let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+    serve(StreamOwned::new(conn, stream), store)
+}
+
+fn dispatch(req: Request, store: &Store) -> Response {
+    match req {
+        Request::Echo { message } => Response::Echo { message },
+        Request::Time => {
+            //系统时间早于1970年时按0处理
+            let unix_secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            Response::Time { unix_secs }
+        }
+        Request::Get { key } => Response::Value {
+            value: lock(store).get(&key).cloned(),
+        },
+        Request::Set { key, value } => {
+            lock(store).insert(key, value);
+            Response::Ok
+        }
+    }
+}
+
+//持锁线程panic不会破坏存储 继续使用
+fn lock(store: &Store) -> std::sync::MutexGuard<'_, HashMap<String, String>> {
+    store.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(feature = "tls")]
+fn tls_config() -> Option<Arc<ServerConfig>> {
+    let (cert, key) = (env::var("TLS_CERT").ok()?, env::var("TLS_KEY").ok()?);
+    match http::tls::server_config(cert.as_ref(), key.as_ref()) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("Failed to load TLS certificate: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    fn request(stream: &mut TcpStream, req: Request) -> Response {
+        tcpproto::send(stream, &req).unwrap();
+        tcpproto::recv(stream).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_clients_share_store() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let store = Store::default();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let store = Arc::clone(&store);
+                thread::spawn(move || serve(stream.unwrap(), &store));
+            }
+        });
+
+        //两个客户端同时连着 一个写入的值另一个能读到
+        let mut first = TcpStream::connect(addr).unwrap();
+        let mut second = TcpStream::connect(addr).unwrap();
+        let key = "greeting".to_string();
+        assert_eq!(request(&mut second, Request::Get { key: key.clone() }), Response::Value { value: None });
+        let set = Request::Set {
+            key: key.clone(),
+            value: "hello".to_string(),
+        };
+        assert_eq!(request(&mut first, set), Response::Ok);
+        assert_eq!(
+            request(&mut second, Request::Get { key }),
+            Response::Value {
+                value: Some("hello".to_string())
+            }
+        );
+
+        //超过原来1024字节缓冲区的消息完整返回
+        let message = "x".repeat(5000);
+        assert_eq!(
+            request(&mut first, Request::Echo { message: message.clone() }),
+            Response::Echo { message }
+        );
+        assert!(matches!(request(&mut first, Request::Time), Response::Time { unix_secs } if unix_secs > 0));
+
+        //不合法的请求得到错误回复 连接仍然可用
+        tcpproto::write_frame(&mut second, b"not json").unwrap();
+        assert!(matches!(tcpproto::recv(&mut second).unwrap(), Some(Response::Error { .. })));
+        assert!(matches!(request(&mut second, Request::Time), Response::Time { .. }));
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_tls_round_trip() {
+        use http::tls::rustls::pki_types::ServerName;
+        use http::tls::rustls::ClientConnection;
+
+        //测试时为localhost生成自签名证书 客户端把它当作CA
+        let dir = tempfile::tempdir().unwrap();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
 
-        stream.read(&mut buffer).unwrap(); //读取数据 可变取地址
-        stream.write(&mut buffer).unwrap(); //直接返回
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = http::tls::server_config(&cert_path, &key_path).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_tls(config, stream, &Store::default())
+        });
 
+        let client = http::tls::client_config(&cert_path).unwrap();
+        let conn = ClientConnection::new(client, ServerName::try_from("localhost").unwrap()).unwrap();
+        let mut stream = StreamOwned::new(conn, TcpStream::connect(addr).unwrap());
+        let set = Request::Set {
+            key: "k".to_string(),
+            value: "v".to_string(),
+        };
+        tcpproto::send(&mut stream, &set).unwrap();
+        assert_eq!(tcpproto::recv::<Response>(&mut stream).unwrap(), Some(Response::Ok));
+        tcpproto::send(&mut stream, &Request::Get { key: "k".to_string() }).unwrap();
+        assert_eq!(
+            tcpproto::recv::<Response>(&mut stream).unwrap(),
+            Some(Response::Value {
+                value: Some("v".to_string())
+            })
+        );
+        stream.conn.send_close_notify();
+        stream.flush().unwrap();
+        drop(stream);
+        server.join().unwrap().unwrap();
     }
 }